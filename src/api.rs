@@ -0,0 +1,487 @@
+// src/api.rs
+use chrono::{DateTime, Utc};
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::error::Error;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use crate::backend;
+use crate::config::get_original_mac;
+use crate::error::MacError;
+use crate::filter::MacFilter;
+use crate::hooks::Trigger;
+use crate::logger::{HistoryFilter, MacLogger};
+use crate::mac::{self, MacAddress, RandomPolicy};
+use crate::metrics;
+use crate::platform::apply_mac;
+use crate::renew;
+use crate::rules::{AppRule, RuleManager};
+use crate::transaction::{ChangeSummary, Transaction};
+
+/// Request bodies are small JSON documents.
+const MAX_BODY: usize = 64 * 1024;
+
+/// Changes go one at a time, like they would from a shell.
+static CHANGES: Mutex<()> = Mutex::new(());
+
+pub struct ServeConfig {
+    pub listen: SocketAddr,
+    pub tls: Option<TlsConfig>,
+    pub insecure: bool, // Plain HTTP on a non-loopback address
+}
+
+/// HTTPS, and with `client_ca` mutual TLS: clients then authenticate with
+/// a certificate signed by it instead of a token.
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    pub client_ca: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenEntry {
+    pub hash: String, // SHA-256 of the token, which is only shown once
+    pub created: DateTime<Utc>,
+}
+
+/// Named API tokens; the name is the principal logged with each change.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ApiTokens {
+    tokens: BTreeMap<String, TokenEntry>,
+}
+
+impl ApiTokens {
+    fn path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_default()
+            .join("mac_changer")
+            .join("api_tokens.json")
+    }
+
+    pub fn load() -> Self {
+        fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        }
+        Ok(())
+    }
+
+    /// Create a token for `name`, replacing any it had, and return it.
+    pub fn add(&mut self, name: &str) -> Result<String, Box<dyn Error>> {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token = hex::encode(bytes);
+
+        self.tokens.insert(name.to_string(), TokenEntry { hash: hash(&token), created: Utc::now() });
+        self.save()?;
+        Ok(token)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<bool, Box<dyn Error>> {
+        let removed = self.tokens.remove(name).is_some();
+        if removed {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    pub fn list(&self) -> &BTreeMap<String, TokenEntry> {
+        &self.tokens
+    }
+
+    /// The name of the token, if it is one.
+    fn find(&self, token: &str) -> Option<&str> {
+        let hash = hash(token);
+        self.tokens
+            .iter()
+            .find(|(_, entry)| entry.hash == hash)
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+fn hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(target_os = "linux")]
+type Acceptor = tokio_rustls::TlsAcceptor;
+
+// Without rustls there is nothing to accept TLS with
+#[cfg(not(target_os = "linux"))]
+type Acceptor = Infallible;
+
+#[cfg(target_os = "linux")]
+fn acceptor(tls: &TlsConfig) -> Result<Acceptor, Box<dyn Error>> {
+    use std::io::BufReader;
+    use std::sync::Arc;
+    use tokio_rustls::rustls::pki_types::CertificateDer;
+    use tokio_rustls::rustls::server::WebPkiClientVerifier;
+    use tokio_rustls::rustls::{self, RootCertStore};
+
+    let read_certs = |path: &PathBuf| -> Result<Vec<CertificateDer<'static>>, Box<dyn Error>> {
+        let certs = rustls_pemfile::certs(&mut BufReader::new(fs::File::open(path)?)).collect::<Result<Vec<_>, _>>()?;
+        if certs.is_empty() {
+            return Err(Box::new(MacError::InvalidFormat(format!("No certificates in {}", path.display()))));
+        }
+        Ok(certs)
+    };
+
+    let certs = read_certs(&tls.cert)?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(fs::File::open(&tls.key)?))?
+        .ok_or_else(|| MacError::InvalidFormat(format!("No private key in {}", tls.key.display())))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = rustls::ServerConfig::builder_with_provider(provider.clone()).with_safe_default_protocol_versions()?;
+    let builder = match &tls.client_ca {
+        Some(client_ca) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(client_ca)? {
+                roots.add(cert)?;
+            }
+            builder.with_client_cert_verifier(WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider).build()?)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut config = builder.with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(tokio_rustls::TlsAcceptor::from(Arc::new(config)))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn acceptor(tls: &TlsConfig) -> Result<Acceptor, Box<dyn Error>> {
+    Err(Box::new(MacError::UnsupportedPlatform(format!(
+        "Serving HTTPS with {} and {} needs Linux; put a TLS proxy in front of chameleon instead",
+        tls.cert.display(), tls.key.display()
+    ))))
+}
+
+/// Serve the API until Ctrl-C. Every request needs a token from
+/// `api-token add`, or a client certificate when serving mutual TLS.
+pub async fn serve(config: ServeConfig) -> Result<(), Box<dyn Error>> {
+    // Fails right away where TLS is not supported
+    let acceptor = config.tls.as_ref().map(acceptor).transpose()?;
+    if acceptor.is_none() && !config.listen.ip().is_loopback() && !config.insecure {
+        return Err(Box::new(MacError::ValidationFailed(format!(
+            "Serving on {} over plain HTTP would send bearer tokens in the clear; serve HTTPS with --tls-cert and --tls-key, \
+             listen on a loopback address, or pass --insecure",
+            config.listen
+        ))));
+    }
+
+    let mutual_tls = config.tls.as_ref().is_some_and(|tls| tls.client_ca.is_some());
+    if !mutual_tls && ApiTokens::load().list().is_empty() {
+        return Err(Box::new(MacError::ValidationFailed(
+            "No API tokens; create one with `chameleon api-token add NAME` or serve mutual TLS with --client-ca".into()
+        )));
+    }
+
+    let listener = TcpListener::bind(config.listen).await?;
+    println!("[api] Serving on {}://{} ({}). Press Ctrl-C to stop.",
+             if acceptor.is_some() { "https" } else { "http" },
+             listener.local_addr()?,
+             if mutual_tls { "client certificates" } else { "bearer tokens" });
+
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = tokio::signal::ctrl_c() => {
+                println!("[api] Stopped");
+                return Ok(());
+            }
+        };
+
+        match &acceptor {
+            None => {
+                tokio::spawn(serve_connection(stream, peer, None));
+            }
+            #[cfg(target_os = "linux")]
+            Some(acceptor) => {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    match acceptor.accept(stream).await {
+                        Ok(stream) => {
+                            // Fingerprint of the client certificate, if one was required
+                            let certificate = stream
+                                .get_ref()
+                                .1
+                                .peer_certificates()
+                                .and_then(|certs| certs.first())
+                                .map(|cert| hex::encode(&Sha256::digest(cert.as_ref())[..8]));
+                            serve_connection(stream, peer, certificate).await;
+                        }
+                        Err(e) => eprintln!("[api] {} TLS handshake failed: {}", peer, e),
+                    }
+                });
+            }
+            #[cfg(not(target_os = "linux"))]
+            Some(never) => match *never {},
+        }
+    }
+}
+
+async fn serve_connection<S>(stream: S, peer: SocketAddr, certificate: Option<String>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = service_fn(move |request| {
+        let certificate = certificate.clone();
+        async move { Ok::<_, Infallible>(handle(request, peer, certificate).await) }
+    });
+
+    if let Err(e) = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await {
+        eprintln!("[api] {}: {}", peer, e);
+    }
+}
+
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+
+    fn not_found() -> Self {
+        Self::new(StatusCode::NOT_FOUND, "No such endpoint")
+    }
+}
+
+impl From<Box<dyn Error>> for ApiError {
+    fn from(e: Box<dyn Error>) -> Self {
+        let status = match e.downcast_ref::<MacError>() {
+            Some(MacError::ValidationFailed(_) | MacError::InvalidFormat(_) | MacError::ParseError(_) | MacError::VendorNotFound(_)) => {
+                StatusCode::BAD_REQUEST
+            }
+            Some(MacError::PermissionDenied(_)) => StatusCode::FORBIDDEN,
+            Some(MacError::OutsideChangeWindow(_) | MacError::Locked(_)) => StatusCode::CONFLICT,
+            Some(MacError::UnsupportedPlatform(_)) => StatusCode::NOT_IMPLEMENTED,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self::new(status, e.to_string().trim())
+    }
+}
+
+impl From<MacError> for ApiError {
+    fn from(e: MacError) -> Self {
+        Box::<dyn Error>::from(Box::new(e)).into()
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, format!("Invalid JSON: {}", e))
+    }
+}
+
+fn respond(status: StatusCode, body: &Value) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(body.to_string())));
+    *response.status_mut() = status;
+    response.headers_mut().insert(CONTENT_TYPE, "application/json".parse().expect("valid header value"));
+    response
+}
+
+async fn handle(request: Request<Incoming>, peer: SocketAddr, certificate: Option<String>) -> Response<Full<Bytes>> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+
+    let principal = match certificate {
+        Some(fingerprint) => Some(format!("cert:{}", fingerprint)),
+        None => request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .and_then(|token| ApiTokens::load().find(token.trim()).map(|name| format!("token:{}", name))),
+    };
+
+    let result = match principal.clone() {
+        None => Err(ApiError::new(StatusCode::UNAUTHORIZED, "Missing or unknown API token")),
+        // Scrapers expect the text format rather than JSON
+        Some(principal) if method == Method::GET && path == "/metrics" => {
+            let response = metrics::response().await;
+            println!("[api] {} {} {} {} -> {}", peer.ip(), principal, method, path, response.status().as_u16());
+            return response;
+        }
+        Some(principal) => {
+            let query = request.uri().query().unwrap_or_default().to_string();
+            match Limited::new(request.into_body(), MAX_BODY).collect().await {
+                Ok(body) => {
+                    let body = body.to_bytes();
+                    let method = method.clone();
+                    let path = path.clone();
+                    // Changes block for seconds while links go down and up
+                    tokio::task::spawn_blocking(move || route(&method, &path, &query, &body, &principal))
+                        .await
+                        .unwrap_or_else(|e| Err(ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())))
+                }
+                Err(e) => Err(ApiError::new(StatusCode::PAYLOAD_TOO_LARGE, e.to_string())),
+            }
+        }
+    };
+
+    let (status, body) = match result {
+        Ok(body) => (StatusCode::OK, body),
+        Err(e) => (e.status, json!({ "error": e.message })),
+    };
+    println!("[api] {} {} {} {} -> {}", peer.ip(), principal.as_deref().unwrap_or("-"), method, path, status.as_u16());
+    respond(status, &body)
+}
+
+/// Body of `POST /interfaces/{name}/mac`, and the arguments of the D-Bus
+/// `ChangeMac`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ChangeRequest {
+    pub mac: Option<String>,
+    pub random: bool,
+    pub permanent: bool,
+    pub force: bool, // Skip the whitelist and blacklist
+    pub renew: bool, // Renew DHCP and flush neighbors afterwards
+}
+
+fn route(method: &Method, path: &str, query: &str, body: &[u8], principal: &str) -> Result<Value, ApiError> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    match (method, segments.as_slice()) {
+        (&Method::GET, ["interfaces"]) => list_interfaces(),
+        (&Method::POST, ["interfaces", interface, "mac"]) => {
+            let request: ChangeRequest = if body.is_empty() { ChangeRequest::default() } else { serde_json::from_slice(body)? };
+            Ok(serde_json::to_value(change(interface, &request, Trigger::Api, principal)?)?)
+        }
+        (&Method::POST, ["interfaces", interface, "restore"]) => {
+            Ok(serde_json::to_value(restore(interface, false, Trigger::Api, principal)?)?)
+        }
+        (&Method::GET, ["history"]) => history(query),
+        (&Method::GET, ["rules"]) => {
+            let rules = RuleManager::new()?;
+            let named: BTreeMap<&str, &AppRule> = rules.named_rules().into_iter().collect();
+            Ok(serde_json::to_value(named)?)
+        }
+        (&Method::PUT, ["rules", name]) => {
+            let mut rule: AppRule = serde_json::from_slice(body)?;
+            rule.validate()?;
+            RuleManager::new()?.add_rule(name, rule)?;
+            Ok(json!({ "saved": name }))
+        }
+        (&Method::DELETE, ["rules", name]) => match RuleManager::new()?.remove_rule(name)? {
+            true => Ok(json!({ "removed": name })),
+            false => Err(ApiError::new(StatusCode::NOT_FOUND, format!("No rule named {}", name))),
+        },
+        (&Method::POST, ["rules", name, action @ ("enable" | "disable")]) => {
+            match RuleManager::new()?.set_enabled(name, *action == "enable")? {
+                true => Ok(json!({ "rule": name, "enabled": *action == "enable" })),
+                false => Err(ApiError::new(StatusCode::NOT_FOUND, format!("No rule named {}", name))),
+            }
+        }
+        _ => Err(ApiError::not_found()),
+    }
+}
+
+fn list_interfaces() -> Result<Value, ApiError> {
+    let backend = backend::current();
+    let interfaces: Vec<Value> = backend
+        .list_interfaces()?
+        .into_iter()
+        .map(|interface| {
+            let state = backend.interface_state(&interface);
+            json!({
+                "interface": interface,
+                "mac": backend.get_mac(&interface).ok(),
+                "original_mac": get_original_mac(&interface).ok().flatten(),
+                "up": state.is_some_and(|state| state.up),
+                "carrier": state.is_some_and(|state| state.carrier),
+            })
+        })
+        .collect();
+    Ok(Value::Array(interfaces))
+}
+
+/// Change `interface` for a remote client, through the whitelist and
+/// blacklist unless the request is forced.
+pub fn change(interface: &str, request: &ChangeRequest, trigger: Trigger, principal: &str) -> Result<ChangeSummary, Box<dyn Error>> {
+    backend::current().verify_interface(interface)?;
+
+    let generate = || -> Result<MacAddress, Box<dyn Error>> {
+        match (&request.mac, request.random) {
+            (Some(mac), false) => Ok(mac::normalize(mac)?),
+            (None, true) => Ok(mac::generate_random_mac(&RandomPolicy::LocallyAdministered)?),
+            _ => Err(Box::new(MacError::ValidationFailed("Give either a MAC or random".into()))),
+        }
+    };
+    let new_mac = if request.force { generate()? } else { MacFilter::new().pick_allowed(generate)? };
+
+    let _change = CHANGES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let transaction = apply_mac(interface, &new_mac, request.permanent, &MacLogger::new(), None, trigger, Some(principal))?;
+    Ok(summarize(transaction, request.renew))
+}
+
+/// Put back the original MAC of `interface` for a remote client.
+pub fn restore(interface: &str, renew: bool, trigger: Trigger, principal: &str) -> Result<ChangeSummary, Box<dyn Error>> {
+    backend::current().verify_interface(interface)?;
+    let original = get_original_mac(interface)?
+        .ok_or_else(|| MacError::ValidationFailed(format!("No original MAC address saved for {}", interface)))?;
+
+    let _change = CHANGES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let transaction = apply_mac(interface, &original, false, &MacLogger::new(), None, trigger, Some(principal))?;
+    Ok(summarize(transaction, renew))
+}
+
+fn summarize(mut transaction: Transaction, renew: bool) -> ChangeSummary {
+    if renew {
+        transaction.renewal = Some(renew::renew(&transaction.interface));
+    }
+    transaction.run_hooks();
+    ChangeSummary::from_transaction(&transaction)
+}
+
+/// `?interface=eth0&last=10`, percent-encoded
+fn history(query: &str) -> Result<Value, ApiError> {
+    let mut filter = HistoryFilter::default();
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        match &*key {
+            "interface" => filter.interface = Some(value.into_owned()),
+            "last" => filter.last = Some(value.parse().map_err(|_| MacError::ParseError(format!("Invalid last={}", value)))?),
+            "since" => filter.since = Some(parse_time(&value)?),
+            "until" => filter.until = Some(parse_time(&value)?),
+            _ => return Err(ApiError::new(StatusCode::BAD_REQUEST, format!("Unknown parameter {}", key))),
+        }
+    }
+    Ok(serde_json::to_value(MacLogger::new().query_history(&filter)?)?)
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>, MacError> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| MacError::ParseError(format!("Invalid time {}, expected RFC 3339", value)))
+}
@@ -0,0 +1,241 @@
+// src/apps.rs
+use std::collections::BTreeSet;
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"))]
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// A running process, as application rules see it.
+#[derive(Debug, Clone, Default)]
+pub struct RunningApp {
+    pub name: String,          // Process name, e.g. "firefox" or "chrome.exe"
+    pub path: Option<PathBuf>, // Executable, when the process can be inspected
+    pub title: Option<String>, // Title of its first visible window
+}
+
+impl RunningApp {
+    /// The process name and the executable's file name. They differ when a
+    /// process renames itself, or Linux cuts its name to 15 characters.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        let file_name = self.path.as_ref().and_then(|path| path.file_name()).and_then(|name| name.to_str());
+        std::iter::once(self.name.as_str()).chain(file_name.filter(|file_name| *file_name != self.name))
+    }
+}
+
+/// Every process on the host, with window titles where the platform can
+/// tell them.
+#[cfg(target_os = "linux")]
+pub fn running() -> Result<Vec<RunningApp>, Box<dyn Error>> {
+    use std::fs;
+
+    let mut titles = window_titles();
+    let mut apps = Vec::new();
+    for entry in fs::read_dir("/proc")? {
+        let entry = entry?;
+        let Ok(pid) = entry.file_name().to_string_lossy().parse::<u32>() else { continue };
+        let Ok(name) = fs::read_to_string(entry.path().join("comm")) else { continue };
+        apps.push(RunningApp {
+            name: name.trim().to_string(),
+            // Needs root for other users' processes
+            path: fs::read_link(entry.path().join("exe")).ok(),
+            title: titles.remove(&pid),
+        });
+    }
+    Ok(apps)
+}
+
+/// Titles of X11 windows by process, through wmctrl. Empty without it or
+/// without a display to ask.
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"))]
+fn window_titles() -> HashMap<u32, String> {
+    let Ok(output) = std::process::Command::new("wmctrl").arg("-lp").output() else {
+        return HashMap::new();
+    };
+
+    // "0x03a00003  0 4242   host Window title"
+    let mut titles = HashMap::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 5 {
+            continue;
+        }
+        if let Ok(pid) = fields[2].parse() {
+            titles.entry(pid).or_insert_with(|| fields[4..].join(" "));
+        }
+    }
+    titles
+}
+
+#[cfg(target_os = "macos")]
+pub fn running() -> Result<Vec<RunningApp>, Box<dyn Error>> {
+    // comm is the full executable path on macOS
+    let output = std::process::Command::new("ps").args(["-e", "-o", "comm="]).output()?;
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| {
+            let path = PathBuf::from(line.trim());
+            RunningApp {
+                name: path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+                path: Some(path),
+                title: None,
+            }
+        })
+        .collect())
+}
+
+/// BSDs have no /proc by default, so ask ps; comm is the bare name there.
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub fn running() -> Result<Vec<RunningApp>, Box<dyn Error>> {
+    let output = std::process::Command::new("ps").args(["-axo", "pid=,comm="]).output()?;
+
+    let mut titles = window_titles();
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (pid, name) = line.trim().split_once(char::is_whitespace)?;
+            let pid: u32 = pid.parse().ok()?;
+            Some(RunningApp { name: name.trim().to_string(), path: None, title: titles.remove(&pid) })
+        })
+        .collect())
+}
+
+#[cfg(target_os = "windows")]
+pub fn running() -> Result<Vec<RunningApp>, Box<dyn Error>> {
+    use crate::win32;
+
+    // Toolhelp instead of tasklist, which IoT and Nano Server SKUs lack
+    let mut titles = win32::window_titles();
+    Ok(win32::processes()?
+        .into_iter()
+        .map(|(pid, name)| RunningApp {
+            name,
+            path: win32::process_path(pid),
+            title: titles.remove(&pid),
+        })
+        .collect())
+}
+
+/// Wake `tx` when the set of running processes or their window titles
+/// changes, checking every `interval`. For hosts without process events.
+pub fn poll(tx: UnboundedSender<()>, interval: Duration) -> JoinHandle<()> {
+    let snapshot = || -> BTreeSet<(String, Option<String>)> {
+        running().unwrap_or_default().into_iter().map(|app| (app.name, app.title)).collect()
+    };
+
+    thread::spawn(move || {
+        let mut previous = snapshot();
+        loop {
+            thread::sleep(interval);
+            let current = snapshot();
+            if current != previous {
+                previous = current;
+                if tx.send(()).is_err() {
+                    break;
+                }
+            }
+        }
+    })
+}
+
+/// Process start and exit notifications from the kernel's proc connector.
+/// Needs CAP_NET_ADMIN and a kernel built with CONFIG_PROC_EVENTS.
+#[cfg(target_os = "linux")]
+pub fn subscribe(tx: UnboundedSender<()>) -> Result<JoinHandle<()>, Box<dyn Error>> {
+    use nix::libc;
+    use nix::sys::socket::{bind, recv, send, MsgFlags, NetlinkAddr};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    const NETLINK_CONNECTOR: i32 = 11;
+    const CN_IDX_PROC: u32 = 1;
+    const CN_VAL_PROC: u32 = 1;
+    const NLMSG_DONE: u16 = 3;
+    const PROC_CN_MCAST_LISTEN: u32 = 1;
+    const PROC_EVENT_EXEC: u32 = 0x0000_0002;
+    const PROC_EVENT_EXIT: u32 = 0x8000_0000;
+    // A netlink header (16 bytes) and a connector header (20 bytes) come
+    // before the listen request and before each event's type
+    const HEADERS: usize = 36;
+
+    let fd = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, NETLINK_CONNECTOR) };
+    if fd < 0 {
+        return Err(Box::new(std::io::Error::last_os_error()));
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    bind(fd.as_raw_fd(), &NetlinkAddr::new(0, CN_IDX_PROC))?;
+
+    let mut request = Vec::with_capacity(HEADERS + 4);
+    request.extend(((HEADERS + 4) as u32).to_ne_bytes());
+    request.extend(NLMSG_DONE.to_ne_bytes());
+    request.extend(0u16.to_ne_bytes()); // flags
+    request.extend(0u32.to_ne_bytes()); // sequence
+    request.extend(std::process::id().to_ne_bytes());
+    request.extend(CN_IDX_PROC.to_ne_bytes());
+    request.extend(CN_VAL_PROC.to_ne_bytes());
+    request.extend(0u32.to_ne_bytes()); // sequence
+    request.extend(0u32.to_ne_bytes()); // ack
+    request.extend(4u16.to_ne_bytes()); // payload length
+    request.extend(0u16.to_ne_bytes()); // flags
+    request.extend(PROC_CN_MCAST_LISTEN.to_ne_bytes());
+    send(fd.as_raw_fd(), &request, MsgFlags::empty())?;
+
+    // Forks and other events change nothing a rule can see
+    Ok(thread::spawn(move || {
+        let mut buffer = [0u8; 4096];
+        while let Ok(length) = recv(fd.as_raw_fd(), &mut buffer, MsgFlags::empty()) {
+            let Some(what) = buffer[..length].get(HEADERS..HEADERS + 4) else { continue };
+            let what = u32::from_ne_bytes([what[0], what[1], what[2], what[3]]);
+            if (what == PROC_EVENT_EXEC || what == PROC_EVENT_EXIT) && tx.send(()).is_err() {
+                break;
+            }
+        }
+    }))
+}
+
+/// Process start and stop traces from WMI. Needs Administrator.
+#[cfg(target_os = "windows")]
+pub fn subscribe(tx: UnboundedSender<()>) -> Result<JoinHandle<()>, Box<dyn Error>> {
+    use std::sync::mpsc;
+    use crate::error::MacError;
+
+    // COM objects stay on the thread that created them, so the query is set
+    // up there and only its outcome is sent back
+    let (ready_tx, ready_rx) = mpsc::channel();
+    let handle = thread::spawn(move || {
+        let events = match crate::win32::process_traces() {
+            Ok(events) => {
+                let _ = ready_tx.send(Ok(()));
+                events
+            }
+            Err(e) => {
+                let _ = ready_tx.send(Err(e.to_string()));
+                return;
+            }
+        };
+        while events.next() {
+            if tx.send(()).is_err() {
+                break;
+            }
+        }
+    });
+
+    match ready_rx.recv() {
+        Ok(Ok(())) => Ok(handle),
+        Ok(Err(e)) => Err(Box::new(MacError::SystemError(e))),
+        Err(_) => Err(Box::new(MacError::SystemError("WMI process events thread failed".into()))),
+    }
+}
+
+/// No event source here; the caller falls back to polling.
+#[cfg(target_os = "macos")]
+pub fn subscribe(_tx: UnboundedSender<()>) -> Result<JoinHandle<()>, Box<dyn Error>> {
+    Err("no process event source on macOS".into())
+}
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub fn subscribe(_tx: UnboundedSender<()>) -> Result<JoinHandle<()>, Box<dyn Error>> {
+    Err("no process event source on BSD".into())
+}
@@ -0,0 +1,246 @@
+// src/backend.rs
+#[cfg(debug_assertions)]
+use serde::{Deserialize, Serialize};
+#[cfg(debug_assertions)]
+use std::collections::BTreeMap;
+#[cfg(debug_assertions)]
+use std::fs;
+#[cfg(debug_assertions)]
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use crate::error::MacError;
+use crate::mac::MacAddress;
+use crate::network::LinkState;
+use crate::transaction::{Plan, Step, Transaction};
+
+/// Set to a state file to run every change against `MockBackend` instead
+/// of the host's interfaces. Only read by debug builds, so a release
+/// binary never writes where the environment tells it to.
+#[cfg(debug_assertions)]
+pub const MOCK_ENV: &str = "CHAMELEON_MOCK";
+
+/// Everything the change path does to an interface. Changes record their
+/// steps in the transaction so a failure later on can be rolled back
+/// through `undo`.
+pub trait MacBackend: Send + Sync {
+    fn get_mac(&self, interface: &str) -> Result<MacAddress, MacError>;
+
+    /// The address burned into the hardware, if the driver reports one.
+    fn hardware_mac(&self, interface: &str) -> Option<MacAddress> {
+        crate::network::get_permanent_mac(interface)
+    }
+
+    /// Change the MAC until the next reboot, and check that it took.
+    fn set_mac(&self, transaction: &mut Transaction, interface: &str, mac: &MacAddress) -> Result<(), MacError>;
+
+    /// Keep the MAC set by `set_mac` across reboots.
+    fn set_permanent(&self, transaction: &mut Transaction, interface: &str, mac: &MacAddress) -> Result<(), MacError>;
+
+    /// Every non-loopback interface with a MAC.
+    fn list_interfaces(&self) -> Result<Vec<String>, MacError>;
+
+    /// None if the interface does not exist.
+    fn interface_state(&self, interface: &str) -> Option<LinkState>;
+
+    /// Add what `set_mac` and `set_permanent` would do to a dry run.
+    fn plan(&self, plan: &mut Plan, interface: &str, mac: &MacAddress, permanent: bool) -> Result<(), MacError>;
+
+    /// Reverse one recorded step.
+    fn undo(&self, step: &Step) -> Result<(), MacError>;
+
+    fn check_permissions(&self) -> Result<(), MacError>;
+
+    fn verify_interface(&self, interface: &str) -> Result<(), MacError> {
+        match self.interface_state(interface) {
+            Some(_) => Ok(()),
+            None => Err(MacError::InterfaceNotFound(format!("Interface {} not found", interface))),
+        }
+    }
+
+    /// Whether changes only happen in memory or a file, so they need no
+    /// privileges and stay out of the system log directory.
+    fn simulated(&self) -> bool {
+        false
+    }
+}
+
+static BACKEND: OnceLock<Box<dyn MacBackend>> = OnceLock::new();
+
+/// The backend for this run: the mock when `CHAMELEON_MOCK` is set in a
+/// debug build, the host platform's otherwise.
+pub fn current() -> &'static dyn MacBackend {
+    BACKEND
+        .get_or_init(|| {
+            #[cfg(debug_assertions)]
+            if let Some(path) = std::env::var_os(MOCK_ENV) {
+                return Box::new(MockBackend::new(PathBuf::from(path)));
+            }
+            crate::platform::native()
+        })
+        .as_ref()
+}
+
+#[cfg(debug_assertions)]
+/// One interface of the mock.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MockInterface {
+    pub mac: MacAddress,
+    #[serde(default = "default_true")]
+    pub up: bool,
+    #[serde(default = "default_true")]
+    pub carrier: bool,
+    /// The MAC applied at boot, once made permanent.
+    #[serde(default)]
+    pub permanent_mac: Option<MacAddress>,
+    /// What the driver reports as burned in, like `ethtool -P`.
+    #[serde(default)]
+    pub hardware_mac: Option<MacAddress>,
+    /// Like a driver that accepts the request but keeps its address.
+    #[serde(default)]
+    pub ignores_changes: bool,
+    #[serde(default = "default_true")]
+    pub permanent_supported: bool,
+}
+
+#[cfg(debug_assertions)]
+fn default_true() -> bool {
+    true
+}
+
+#[cfg(debug_assertions)]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MockState {
+    pub interfaces: BTreeMap<String, MockInterface>,
+}
+
+#[cfg(debug_assertions)]
+/// Interfaces kept in a JSON state file, so a change made by one run is
+/// seen by the next. Lets the whole change path run without privileges or
+/// a real NIC, e.g. from the integration tests.
+pub struct MockBackend {
+    path: PathBuf,
+}
+
+#[cfg(debug_assertions)]
+impl MockBackend {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn load(&self) -> Result<MockState, MacError> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(MockState::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, state: &MockState) -> Result<(), MacError> {
+        fs::write(&self.path, serde_json::to_string_pretty(state)?)?;
+        Ok(())
+    }
+
+    fn update<T>(&self, interface: &str, f: impl FnOnce(&mut MockInterface) -> Result<T, MacError>) -> Result<T, MacError> {
+        let mut state = self.load()?;
+        let entry = state.interfaces.get_mut(interface).ok_or_else(|| {
+            MacError::InterfaceNotFound(format!("Interface {} not found", interface))
+        })?;
+        let result = f(entry)?;
+        self.save(&state)?;
+        Ok(result)
+    }
+}
+
+#[cfg(debug_assertions)]
+impl MacBackend for MockBackend {
+    fn get_mac(&self, interface: &str) -> Result<MacAddress, MacError> {
+        self.load()?
+            .interfaces
+            .get(interface)
+            .map(|entry| entry.mac.clone())
+            .ok_or_else(|| MacError::InterfaceNotFound(format!("Interface {} not found", interface)))
+    }
+
+    fn hardware_mac(&self, interface: &str) -> Option<MacAddress> {
+        self.load().ok()?.interfaces.get(interface)?.hardware_mac.clone()
+    }
+
+    fn set_mac(&self, transaction: &mut Transaction, interface: &str, mac: &MacAddress) -> Result<(), MacError> {
+        let old_mac = self.update(interface, |entry| {
+            let old_mac = entry.mac.clone();
+            if !entry.ignores_changes {
+                entry.mac = mac.clone();
+            }
+            Ok(old_mac)
+        })?;
+        transaction.record(Step::AddressSet {
+            interface: interface.to_string(),
+            old_mac: old_mac.to_string(),
+            new_mac: mac.to_string(),
+        });
+
+        let current_mac = self.get_mac(interface)?;
+        if current_mac != *mac {
+            return Err(MacError::VerificationFailed(format!(
+                "MAC address change verification failed. Expected {}, got {}", mac, current_mac
+            )));
+        }
+        Ok(())
+    }
+
+    fn set_permanent(&self, transaction: &mut Transaction, interface: &str, mac: &MacAddress) -> Result<(), MacError> {
+        let previous = fs::read_to_string(&self.path).ok();
+        self.update(interface, |entry| {
+            if !entry.permanent_supported {
+                return Err(MacError::UnsupportedPlatform(format!(
+                    "Permanent MAC address changes are not supported on {}", interface
+                )));
+            }
+            entry.permanent_mac = Some(mac.clone());
+            Ok(())
+        })?;
+        transaction.record(Step::FileWritten { path: self.path.clone(), previous });
+        Ok(())
+    }
+
+    fn list_interfaces(&self) -> Result<Vec<String>, MacError> {
+        Ok(self.load()?.interfaces.into_keys().collect())
+    }
+
+    fn interface_state(&self, interface: &str) -> Option<LinkState> {
+        let state = self.load().ok()?;
+        let entry = state.interfaces.get(interface)?;
+        Some(LinkState { up: entry.up, carrier: entry.carrier })
+    }
+
+    fn plan(&self, plan: &mut Plan, interface: &str, mac: &MacAddress, permanent: bool) -> Result<(), MacError> {
+        self.verify_interface(interface)?;
+        plan.add(
+            Step::AddressSet { interface: interface.to_string(), old_mac: plan.old_mac.clone(), new_mac: mac.to_string() },
+            Some(format!("mock: set {} to {} in {}", interface, mac, self.path.display())),
+        );
+        if permanent {
+            plan.add(Step::FileWritten { path: self.path.clone(), previous: None }, None);
+        }
+        Ok(())
+    }
+
+    fn undo(&self, step: &Step) -> Result<(), MacError> {
+        match step {
+            Step::AddressSet { interface, old_mac, .. } => self.update(interface, |entry| {
+                entry.mac = old_mac.parse()?;
+                Ok(())
+            }),
+            Step::FileWritten { path, previous: Some(content) } => Ok(fs::write(path, content)?),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_permissions(&self) -> Result<(), MacError> {
+        Ok(())
+    }
+
+    fn simulated(&self) -> bool {
+        true
+    }
+}
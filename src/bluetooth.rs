@@ -0,0 +1,386 @@
+// src/bluetooth.rs
+use chrono::Utc;
+use serde::Serialize;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use crate::capabilities::find_tool;
+use crate::config::MacConfig;
+use crate::error::MacError;
+use crate::hooks::Trigger;
+use crate::logger::{MacChange, MacLogger};
+use crate::mac::{MacAddress, MacFormat};
+use crate::output::progress;
+
+const SYSFS_BLUETOOTH: &str = "/sys/class/bluetooth";
+
+/// Bluetooth SIG company identifiers of chipsets chameleon knows about.
+const MANUFACTURERS: &[(u16, &str)] = &[
+    (0, "Ericsson"),
+    (2, "Intel"),
+    (10, "Cambridge Silicon Radio"),
+    (13, "Texas Instruments"),
+    (15, "Broadcom"),
+    (18, "Zeevo"),
+    (29, "Qualcomm"),
+    (48, "ST Microelectronics"),
+    (72, "Marvell"),
+    (93, "Realtek"),
+    (305, "Cypress"),
+];
+
+/// Vendor HCI commands (OGF 0x3f) that write the address, as BlueZ's
+/// `bdaddr` tool sends them: manufacturer, OCF and bytes before the address.
+const VENDOR_COMMANDS: &[(u16, u16, &[u8])] = &[
+    (0, 0x000d, &[]),           // Ericsson
+    (2, 0x0031, &[]),           // Intel
+    (13, 0x0006, &[]),          // Texas Instruments
+    (15, 0x0001, &[]),          // Broadcom
+    (18, 0x0001, &[]),          // Zeevo
+    (48, 0x0022, &[0xfe, 0x06]), // ST Microelectronics
+    (305, 0x0001, &[]),         // Cypress, Broadcom's former line
+];
+
+/// How a controller's address can be changed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Method {
+    /// The kernel driver sets it through the management interface
+    /// (`btmgmt public-addr`); it is applied again whenever the controller
+    /// is reset.
+    Mgmt,
+    /// A vendor HCI command followed by a reset. Lost when the controller
+    /// is power cycled.
+    VendorCommand { ocf: u16, prefix: Vec<u8> },
+    Unsupported(String),
+}
+
+impl Method {
+    pub fn describe(&self) -> String {
+        match self {
+            Method::Mgmt => "btmgmt public-addr".into(),
+            Method::VendorCommand { ocf, .. } => format!("vendor HCI command 0x3f/0x{:04x}", ocf),
+            Method::Unsupported(reason) => format!("unsupported: {}", reason),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Controller {
+    pub name: String, // hci0, hci1, ...
+    pub address: Option<MacAddress>,
+    pub manufacturer: Option<u16>,
+    pub manufacturer_name: Option<String>,
+    pub bus: Option<String>,
+    pub powered: bool,
+    pub method: Method,
+}
+
+impl Controller {
+    pub fn list() -> Result<Vec<Self>, Box<dyn Error>> {
+        let Ok(entries) = fs::read_dir(SYSFS_BLUETOOTH) else {
+            return Ok(Vec::new());
+        };
+
+        // Connections show up as hci0:12 next to their controller
+        let mut names: Vec<String> = entries
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("hci") && !name.contains(':'))
+            .collect();
+        names.sort();
+
+        names.iter().map(|name| Self::new(name)).collect()
+    }
+
+    pub fn new(name: &str) -> Result<Self, Box<dyn Error>> {
+        let sysfs = Path::new(SYSFS_BLUETOOTH).join(name);
+        if !sysfs.exists() {
+            return Err(Box::new(MacError::ValidationFailed(format!("Bluetooth controller {} does not exist", name))));
+        }
+
+        let info = read_info(name);
+        let bus = fs::read_link(sysfs.join("device").join("subsystem"))
+            .ok()
+            .and_then(|link| link.file_name().map(|bus| bus.to_string_lossy().into_owned()));
+        let method = detect_method(name, info.manufacturer);
+
+        Ok(Self {
+            name: name.to_string(),
+            address: info.address,
+            manufacturer: info.manufacturer,
+            manufacturer_name: info.manufacturer.and_then(manufacturer_name).map(str::to_string),
+            bus,
+            powered: info.powered,
+            method,
+        })
+    }
+
+    pub fn address(&self) -> Result<&MacAddress, MacError> {
+        self.address
+            .as_ref()
+            .ok_or_else(|| MacError::SystemError(format!("Could not read the address of {}; is btmgmt or hciconfig installed?", self.name)))
+    }
+}
+
+fn manufacturer_name(id: u16) -> Option<&'static str> {
+    MANUFACTURERS.iter().find(|(known, _)| *known == id).map(|(_, name)| *name)
+}
+
+#[derive(Debug, Default)]
+struct Info {
+    address: Option<MacAddress>,
+    manufacturer: Option<u16>,
+    powered: bool,
+}
+
+fn index(name: &str) -> &str {
+    name.trim_start_matches("hci")
+}
+
+/// btmgmt and hciconfig print addresses in upper case.
+fn parse_address(address: &str) -> Option<MacAddress> {
+    MacAddress::parse(address).ok().map(|mac| mac.with_format(MacFormat::Colon))
+}
+
+fn run(tool: &str, args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let path = find_tool(tool).ok_or_else(|| MacError::SystemError(format!("{} is not installed", tool)))?;
+    let output = Command::new(path).args(args).output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+
+    // btmgmt reports failures on stdout and still exits with 0
+    let failed = stdout.lines().any(|line| line.contains("failed with status"));
+    if !output.status.success() || failed {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = [stderr.trim(), stdout.trim()].into_iter().find(|text| !text.is_empty()).unwrap_or("no output");
+        return Err(Box::new(MacError::SystemError(format!("{} {}: {}", tool, args.join(" "), message))));
+    }
+    Ok(stdout)
+}
+
+/// Address, manufacturer and power state from `btmgmt info`, or from the
+/// older `hciconfig -a` on hosts without it.
+fn read_info(name: &str) -> Info {
+    if let Ok(output) = run("btmgmt", &["--index", index(name), "info"]) {
+        let mut info = Info::default();
+        for line in output.lines().map(str::trim) {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if let Some(position) = words.iter().position(|word| *word == "addr") {
+                info.address = words.get(position + 1).and_then(|addr| parse_address(addr));
+            }
+            if let Some(position) = words.iter().position(|word| *word == "manufacturer") {
+                info.manufacturer = words.get(position + 1).and_then(|id| id.parse().ok());
+            }
+            if let Some(settings) = line.strip_prefix("current settings:") {
+                info.powered = settings.split_whitespace().any(|setting| setting == "powered");
+            }
+        }
+        if info.address.is_some() {
+            return info;
+        }
+    }
+
+    let mut info = Info::default();
+    if let Ok(output) = run("hciconfig", &["-a", name]) {
+        for line in output.lines().map(str::trim) {
+            if let Some(rest) = line.strip_prefix("BD Address:") {
+                info.address = rest.split_whitespace().next().and_then(parse_address);
+            } else if let Some(rest) = line.strip_prefix("Manufacturer:") {
+                // "Manufacturer: Intel Corp. (2)"
+                info.manufacturer = rest
+                    .rsplit_once('(')
+                    .and_then(|(_, id)| id.trim_end_matches(')').parse().ok());
+            } else if line.split_whitespace().any(|flag| flag == "UP") {
+                info.powered = true;
+            }
+        }
+    }
+    info
+}
+
+/// The management interface is preferred: the kernel driver knows how to
+/// set the address on its chip and re-applies it after resets. Chips whose
+/// driver cannot get a vendor command where one is known.
+fn detect_method(name: &str, manufacturer: Option<u16>) -> Method {
+    let mgmt = run("btmgmt", &["--index", index(name), "config"])
+        .is_ok_and(|output| {
+            output
+                .lines()
+                .any(|line| line.trim().starts_with("supported options:") && line.contains("public-address"))
+        });
+    if mgmt {
+        return Method::Mgmt;
+    }
+
+    let Some(manufacturer) = manufacturer else {
+        return Method::Unsupported("unknown chipset".into());
+    };
+    if let Some((_, ocf, prefix)) = VENDOR_COMMANDS.iter().find(|(id, _, _)| *id == manufacturer) {
+        if find_tool("hcitool").is_none() {
+            return Method::Unsupported("hcitool is not installed".into());
+        }
+        return Method::VendorCommand { ocf: *ocf, prefix: prefix.to_vec() };
+    }
+
+    Method::Unsupported(match manufacturer {
+        10 => "CSR chips keep the address in their persistent store; set it with `bccmd psset -r bdaddr`".into(),
+        id => format!("no known way to set the address on {} chips", manufacturer_name(id).unwrap_or("these")),
+    })
+}
+
+/// The commands `set_address` runs, for dry runs.
+pub fn commands(controller: &Controller, address: &MacAddress) -> Result<Vec<String>, Box<dyn Error>> {
+    let name = &controller.name;
+    Ok(match &controller.method {
+        Method::Mgmt => vec![
+            format!("btmgmt --index {} power off", index(name)),
+            format!("btmgmt --index {} public-addr {}", index(name), address),
+            format!("btmgmt --index {} power on", index(name)),
+        ],
+        Method::VendorCommand { ocf, prefix } => vec![
+            format!("hcitool -i {} cmd 0x3f 0x{:04x} {}", name, ocf, vendor_payload(prefix, address).join(" ")),
+            format!("hciconfig {} reset", name),
+        ],
+        Method::Unsupported(reason) => {
+            return Err(Box::new(MacError::UnsupportedPlatform(format!("Changing the address of {}: {}", name, reason))));
+        }
+    })
+}
+
+/// HCI sends addresses least significant byte first.
+fn vendor_payload(prefix: &[u8], address: &MacAddress) -> Vec<String> {
+    let mut bytes = *address.get_bytes();
+    bytes.reverse();
+    prefix.iter().chain(bytes.iter()).map(|byte| format!("0x{:02x}", byte)).collect()
+}
+
+/// Write `address` to the controller and check that it took.
+pub fn set_address(controller: &Controller, address: &MacAddress) -> Result<(), Box<dyn Error>> {
+    let name = &controller.name;
+
+    match &controller.method {
+        Method::Mgmt => {
+            progress!("Setting the address of {} through the management interface...", name);
+            run("btmgmt", &["--index", index(name), "power", "off"])?;
+            run("btmgmt", &["--index", index(name), "public-addr", &address.to_string()])?;
+            if controller.powered {
+                run("btmgmt", &["--index", index(name), "power", "on"])?;
+            }
+        }
+        Method::VendorCommand { ocf, prefix } => {
+            progress!("Setting the address of {} with a vendor command...", name);
+            let mut args = vec!["-i".to_string(), name.clone(), "cmd".into(), "0x3f".into(), format!("0x{:04x}", ocf)];
+            args.extend(vendor_payload(prefix, address));
+            run("hcitool", &args.iter().map(String::as_str).collect::<Vec<_>>())?;
+            // The chip only uses the new address after a reset
+            run("hciconfig", &[name, "reset"])?;
+        }
+        Method::Unsupported(reason) => {
+            return Err(Box::new(MacError::UnsupportedPlatform(format!("Changing the address of {}: {}", name, reason))));
+        }
+    }
+
+    // The controller re-registers after a reset
+    std::thread::sleep(Duration::from_millis(500));
+    let current = read_info(name).address;
+    if current.as_ref() != Some(address) {
+        return Err(Box::new(MacError::SystemError(format!(
+            "{} still reports {} after the change; the chip may not accept a new address",
+            name,
+            current.map(|mac| mac.to_string()).unwrap_or_else(|| "no address".into())
+        ))));
+    }
+    Ok(())
+}
+
+/// Bluetooth originals live apart from interface ones, so `panic-restore`
+/// and `status` don't treat controllers as network interfaces.
+fn originals_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_default()
+        .join("mac_changer")
+        .join("bluetooth")
+}
+
+pub fn get_original_address(name: &str) -> Result<Option<MacAddress>, Box<dyn Error>> {
+    let path = originals_dir().join(format!("{}.json", name));
+    if !path.exists() {
+        return Ok(None);
+    }
+    let config: MacConfig = serde_json::from_str(&fs::read_to_string(path)?)?;
+    Ok(Some(config.original_mac))
+}
+
+pub fn save_original_address(name: &str, address: &MacAddress) -> Result<(), Box<dyn Error>> {
+    let config = MacConfig {
+        original_mac: address.clone(),
+        interface: name.to_string(),
+        vendor: None,
+        hardware_mac: None,
+        last_modified: Utc::now(),
+    };
+    fs::create_dir_all(originals_dir())?;
+    fs::write(originals_dir().join(format!("{}.json", name)), serde_json::to_string_pretty(&config)?)?;
+    Ok(())
+}
+
+/// What `bt set` and `bt restore` did.
+#[derive(Debug, Serialize)]
+pub struct BtChange {
+    pub controller: String,
+    pub old_address: MacAddress,
+    pub new_address: MacAddress,
+    pub method: String,
+    pub survives_power_cycle: bool,
+}
+
+impl BtChange {
+    pub fn print(&self) {
+        println!();
+        println!("Summary for {}", self.controller);
+        println!("  Address:      {} -> {}", self.old_address, self.new_address);
+        println!("  Method:       {}", self.method);
+        println!("  Persistent:   {}", if self.survives_power_cycle {
+            "until the controller is unplugged or the host reboots"
+        } else {
+            "until the controller is power cycled"
+        });
+        println!("  Next:         Run `chameleon bt restore -i {}` to return to the original address", self.controller);
+    }
+}
+
+/// Change the address of `controller`, saving its original the first time
+/// and logging the change like an interface's.
+pub fn change(controller: &Controller, address: &MacAddress, trigger: Trigger) -> Result<BtChange, Box<dyn Error>> {
+    let old_address = controller.address()?.clone();
+    if get_original_address(&controller.name)?.is_none() {
+        progress!("Saving original address: {}", old_address);
+        save_original_address(&controller.name, &old_address)?;
+    }
+
+    set_address(controller, address)?;
+
+    MacLogger::new().log_change(MacChange {
+        timestamp: Utc::now(),
+        interface: controller.name.clone(),
+        old_mac: old_address.clone(),
+        new_mac: address.clone(),
+        geo_location: None,
+        permanent: false,
+        profile: None,
+        trigger: Some(trigger),
+        hostname: None,
+        dhcp_client_id: None,
+        principal: None,
+    })?;
+
+    Ok(BtChange {
+        controller: controller.name.clone(),
+        old_address,
+        new_address: address.clone(),
+        method: controller.method.describe(),
+        survives_power_cycle: controller.method == Method::Mgmt,
+    })
+}
@@ -0,0 +1,402 @@
+// src/boot.rs
+use std::error::Error;
+use std::process::Command;
+use crate::error::MacError;
+use crate::settings::RandomPolicyName;
+
+/// The chameleon command run at every boot: a random MAC, without the lease
+/// renewal and collision scan, which need a network that is not up yet.
+fn arguments(interface: &str, policy: Option<RandomPolicyName>) -> Vec<String> {
+    let mut args = vec!["-i".to_string(), interface.to_string(), "-r".to_string()];
+    match policy {
+        Some(RandomPolicyName::PreserveOui) => args.push("--preserve-oui".into()),
+        Some(RandomPolicyName::BurnedInLike) => args.push("--burned-in-like".into()),
+        // -r's own policy, unless config.toml sets random_policy
+        Some(RandomPolicyName::LocallyAdministered) | None => {}
+    }
+    args.extend(["--no-renew".to_string(), "--no-collision-check".to_string()]);
+    args
+}
+
+#[cfg_attr(any(target_os = "freebsd", target_os = "openbsd"), allow(dead_code))] // BSDs only write files
+fn run(program: &str, args: &[&str]) -> Result<(), Box<dyn Error>> {
+    let output = Command::new(program).args(args).output()?;
+    if !output.status.success() {
+        return Err(Box::new(MacError::SystemError(format!(
+            "{} {} failed: {}", program, args.join(" "), String::from_utf8_lossy(&output.stderr).trim()
+        ))));
+    }
+    Ok(())
+}
+
+/// Randomize `interface` at every boot. With `dry_run`, only print what
+/// would be installed.
+pub fn install(interface: &str, policy: Option<RandomPolicyName>, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let exe = std::env::current_exe()?;
+    platform::install(interface, &exe.display().to_string(), &arguments(interface, policy), dry_run)
+}
+
+pub fn uninstall(interface: &str, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    platform::uninstall(interface, dry_run)
+}
+
+/// What randomizes `interface` at boot, if anything.
+pub fn installed(interface: &str) -> Option<String> {
+    platform::installed(interface)
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::error::Error;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use crate::output::progress;
+
+    const UNIT_DIR: &str = "/etc/systemd/system";
+    const UDEV_RULES_DIR: &str = "/etc/udev/rules.d";
+
+    fn unit_name(interface: &str) -> String {
+        format!("chameleon-randomize-{}.service", interface)
+    }
+
+    fn unit_path(interface: &str) -> PathBuf {
+        Path::new(UNIT_DIR).join(unit_name(interface))
+    }
+
+    fn udev_rule_path(interface: &str) -> PathBuf {
+        Path::new(UDEV_RULES_DIR).join(format!("99-chameleon-randomize-{}.rules", interface))
+    }
+
+    /// Hosts booted without systemd get a udev rule instead.
+    fn has_systemd() -> bool {
+        Path::new("/run/systemd/system").exists()
+    }
+
+    /// The device unit systemd creates for a network interface, named the
+    /// way `systemd-escape` would.
+    fn device_unit(interface: &str) -> String {
+        format!("sys-subsystem-net-devices-{}.device", interface.replace('-', "\\x2d"))
+    }
+
+    /// Runs once the device appears and before anything brings networks up.
+    fn unit(interface: &str, exe: &str, args: &[String]) -> String {
+        format!(
+            "# Written by chameleon; remove with `chameleon uninstall-boot-randomization -i {interface}`\n\
+             [Unit]\n\
+             Description=Randomize the MAC address of {interface}\n\
+             BindsTo={device}\n\
+             After={device}\n\
+             Wants=network-pre.target\n\
+             Before=network-pre.target\n\
+             \n\
+             [Service]\n\
+             Type=oneshot\n\
+             ExecStart=\"{exe}\" {args}\n\
+             \n\
+             [Install]\n\
+             WantedBy=multi-user.target\n",
+            interface = interface,
+            device = device_unit(interface),
+            exe = exe,
+            args = args.join(" "),
+        )
+    }
+
+    fn udev_rule(interface: &str, exe: &str, args: &[String]) -> String {
+        format!(
+            "# Written by chameleon; remove with `chameleon uninstall-boot-randomization -i {}`\n\
+             ACTION==\"add\", SUBSYSTEM==\"net\", KERNEL==\"{}\", RUN+=\"{} {}\"\n",
+            interface, interface, exe, args.join(" ")
+        )
+    }
+
+    pub fn install(interface: &str, exe: &str, args: &[String], dry_run: bool) -> Result<(), Box<dyn Error>> {
+        let systemd = has_systemd();
+        let (path, content) = match systemd {
+            true => (unit_path(interface), unit(interface, exe, args)),
+            false => (udev_rule_path(interface), udev_rule(interface, exe, args)),
+        };
+
+        if dry_run {
+            println!("Would write {}:\n{}", path.display(), content);
+            if systemd {
+                println!("Would run systemctl enable {}", unit_name(interface));
+            }
+            return Ok(());
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, content)?;
+        println!("Wrote {}", path.display());
+
+        if systemd {
+            super::run("systemctl", &["daemon-reload"])?;
+            super::run("systemctl", &["enable", &unit_name(interface)])?;
+            println!("Enabled {}; {} gets a new random MAC at every boot", unit_name(interface), interface);
+        } else {
+            println!("{} gets a new random MAC every time it appears, including at boot", interface);
+        }
+        Ok(())
+    }
+
+    pub fn uninstall(interface: &str, dry_run: bool) -> Result<(), Box<dyn Error>> {
+        let mut found = false;
+        for path in [unit_path(interface), udev_rule_path(interface)] {
+            if !path.exists() {
+                continue;
+            }
+            found = true;
+            let is_unit = path == unit_path(interface);
+            if dry_run {
+                println!("Would remove {}", path.display());
+                continue;
+            }
+
+            if is_unit && let Err(e) = super::run("systemctl", &["disable", &unit_name(interface)]) {
+                progress!("Warning: {}", e);
+            }
+            fs::remove_file(&path)?;
+            println!("Removed {}", path.display());
+            if is_unit && let Err(e) = super::run("systemctl", &["daemon-reload"]) {
+                progress!("Warning: {}", e);
+            }
+        }
+
+        if !found {
+            println!("Boot randomization is not installed for {}", interface);
+        }
+        Ok(())
+    }
+
+    pub fn installed(interface: &str) -> Option<String> {
+        if unit_path(interface).exists() {
+            return Some(format!("systemd service {}", unit_name(interface)));
+        }
+        let rule = udev_rule_path(interface);
+        rule.exists().then(|| format!("udev rule {}", rule.display()))
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::error::Error;
+    use std::process::Command;
+
+    /// Scheduled tasks live under their own folder in Task Scheduler.
+    fn task_name(interface: &str) -> String {
+        format!("\\Chameleon\\Randomize {}", interface)
+    }
+
+    /// Windows interface names often have spaces ("Wi-Fi 2").
+    fn quote(arg: &str) -> String {
+        if arg.contains(' ') { format!("\"{}\"", arg) } else { arg.to_string() }
+    }
+
+    pub fn install(interface: &str, exe: &str, args: &[String], dry_run: bool) -> Result<(), Box<dyn Error>> {
+        let command = std::iter::once(exe)
+            .chain(args.iter().map(String::as_str))
+            .map(quote)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let task = task_name(interface);
+        let create = ["/Create", "/F", "/TN", &task, "/SC", "ONSTART", "/RU", "SYSTEM", "/RL", "HIGHEST", "/TR", &command];
+
+        if dry_run {
+            println!("Would run schtasks {}", create.map(quote).join(" "));
+            return Ok(());
+        }
+
+        super::run("schtasks", &create)?;
+        println!("Created the scheduled task {}; {} gets a new random MAC at every startup", task, interface);
+        Ok(())
+    }
+
+    pub fn uninstall(interface: &str, dry_run: bool) -> Result<(), Box<dyn Error>> {
+        let task = task_name(interface);
+        if installed(interface).is_none() {
+            println!("Boot randomization is not installed for {}", interface);
+            return Ok(());
+        }
+        if dry_run {
+            println!("Would delete the scheduled task {}", task);
+            return Ok(());
+        }
+
+        super::run("schtasks", &["/Delete", "/F", "/TN", &task])?;
+        println!("Deleted the scheduled task {}", task);
+        Ok(())
+    }
+
+    pub fn installed(interface: &str) -> Option<String> {
+        let task = task_name(interface);
+        let output = Command::new("schtasks").args(["/Query", "/TN", &task]).output().ok()?;
+        output.status.success().then(|| format!("scheduled task {}", task))
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::error::Error;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    const LAUNCH_DAEMONS_DIR: &str = "/Library/LaunchDaemons";
+
+    fn label(interface: &str) -> String {
+        format!("dev.duocore.chameleon.randomize.{}", interface)
+    }
+
+    fn plist_path(interface: &str) -> PathBuf {
+        Path::new(LAUNCH_DAEMONS_DIR).join(format!("{}.plist", label(interface)))
+    }
+
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+    }
+
+    /// launchd starts it once at boot, while the network is still coming up.
+    fn plist(interface: &str, exe: &str, args: &[String]) -> String {
+        let arguments: String = std::iter::once(exe)
+            .chain(args.iter().map(String::as_str))
+            .map(|arg| format!("    <string>{}</string>\n", escape(arg)))
+            .collect();
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+             <plist version=\"1.0\">\n\
+             <dict>\n\
+             \x20 <key>Label</key>\n\
+             \x20 <string>{}</string>\n\
+             \x20 <key>ProgramArguments</key>\n\
+             \x20 <array>\n\
+             {}\
+             \x20 </array>\n\
+             \x20 <key>RunAtLoad</key>\n\
+             \x20 <true/>\n\
+             </dict>\n\
+             </plist>\n",
+            label(interface), arguments
+        )
+    }
+
+    pub fn install(interface: &str, exe: &str, args: &[String], dry_run: bool) -> Result<(), Box<dyn Error>> {
+        let path = plist_path(interface);
+        let content = plist(interface, exe, args);
+        if dry_run {
+            println!("Would write {}:\n{}", path.display(), content);
+            return Ok(());
+        }
+
+        fs::write(&path, content)?;
+        println!("Wrote {}; {} gets a new random MAC at every boot", path.display(), interface);
+        Ok(())
+    }
+
+    pub fn uninstall(interface: &str, dry_run: bool) -> Result<(), Box<dyn Error>> {
+        let path = plist_path(interface);
+        if !path.exists() {
+            println!("Boot randomization is not installed for {}", interface);
+            return Ok(());
+        }
+        if dry_run {
+            println!("Would remove {}", path.display());
+            return Ok(());
+        }
+
+        // Only loaded since the last boot; fine if it is not
+        let _ = super::run("launchctl", &["bootout", &format!("system/{}", label(interface))]);
+        fs::remove_file(&path)?;
+        println!("Removed {}", path.display());
+        Ok(())
+    }
+
+    pub fn installed(interface: &str) -> Option<String> {
+        let path = plist_path(interface);
+        path.exists().then(|| format!("LaunchDaemon {}", path.display()))
+    }
+}
+
+/// A line in a file the network scripts run before configuring the
+/// interface: /etc/start_if.<if>, which FreeBSD's rc sources, or a `!`
+/// command in OpenBSD's /etc/hostname.<if>, which netstart runs first.
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+mod platform {
+    use std::error::Error;
+    use std::fs;
+    use std::path::PathBuf;
+
+    const MARKER: &str = "# chameleon boot randomization";
+
+    #[cfg(target_os = "freebsd")]
+    fn hook_path(interface: &str) -> PathBuf {
+        PathBuf::from(format!("/etc/start_if.{}", interface))
+    }
+
+    #[cfg(target_os = "openbsd")]
+    fn hook_path(interface: &str) -> PathBuf {
+        PathBuf::from(format!("/etc/hostname.{}", interface))
+    }
+
+    #[cfg(target_os = "freebsd")]
+    fn hook_line(exe: &str, args: &[String]) -> String {
+        format!("\"{}\" {} {}", exe, args.join(" "), MARKER)
+    }
+
+    #[cfg(target_os = "openbsd")]
+    fn hook_line(exe: &str, args: &[String]) -> String {
+        format!("!\"{}\" {} {}", exe, args.join(" "), MARKER)
+    }
+
+    fn without_hook(content: &str) -> String {
+        content.lines().filter(|line| !line.ends_with(MARKER)).map(|line| format!("{}\n", line)).collect()
+    }
+
+    pub fn install(interface: &str, exe: &str, args: &[String], dry_run: bool) -> Result<(), Box<dyn Error>> {
+        let path = hook_path(interface);
+        let previous = fs::read_to_string(&path).unwrap_or_default();
+        let content = format!("{}\n{}", hook_line(exe, args), without_hook(&previous));
+
+        if dry_run {
+            println!("Would write {}:\n{}", path.display(), content);
+            return Ok(());
+        }
+
+        fs::write(&path, content)?;
+        println!("Wrote {}; {} gets a new random MAC at every boot", path.display(), interface);
+        Ok(())
+    }
+
+    pub fn uninstall(interface: &str, dry_run: bool) -> Result<(), Box<dyn Error>> {
+        let path = hook_path(interface);
+        if installed(interface).is_none() {
+            println!("Boot randomization is not installed for {}", interface);
+            return Ok(());
+        }
+
+        // Whatever else the file configures stays; a file left empty goes
+        let content = without_hook(&fs::read_to_string(&path)?);
+        let remove = content.trim().is_empty();
+        if dry_run {
+            match remove {
+                true => println!("Would remove {}", path.display()),
+                false => println!("Would remove the chameleon line from {}", path.display()),
+            }
+            return Ok(());
+        }
+
+        match remove {
+            true => fs::remove_file(&path)?,
+            false => fs::write(&path, content)?,
+        }
+        println!("Removed boot randomization from {}", path.display());
+        Ok(())
+    }
+
+    pub fn installed(interface: &str) -> Option<String> {
+        let path = hook_path(interface);
+        let content = fs::read_to_string(&path).ok()?;
+        content.lines().any(|line| line.ends_with(MARKER)).then(|| format!("{}", path.display()))
+    }
+}
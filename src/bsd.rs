@@ -0,0 +1,203 @@
+// src/bsd.rs
+use std::path::PathBuf;
+use std::process::Command;
+use crate::network::WirelessConnection;
+
+/// The ifconfig keyword for a link-layer address.
+#[cfg(target_os = "freebsd")]
+pub const LLADDR: &str = "ether";
+#[cfg(target_os = "openbsd")]
+pub const LLADDR: &str = "lladdr";
+
+/// USB Ethernet drivers, whose interfaces are named after them (ue is the
+/// name FreeBSD gives all of its USB NICs).
+const USB_DRIVERS: &[&str] = &[
+    "ue", "axe", "axen", "cdce", "cue", "kue", "mue", "rue", "smsc", "udav", "upl", "ure", "url", "urndis",
+];
+
+/// `ifconfig <interface>` output, or None if there is no such interface.
+pub fn ifconfig(interface: &str) -> Option<String> {
+    let output = Command::new("ifconfig").arg(interface).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The value after `key` on the first indented line that starts with it.
+fn field<'a>(output: &'a str, key: &str) -> Option<&'a str> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix(key)?.strip_prefix(' '))
+        .map(|rest| rest.split_whitespace().next().unwrap_or_default())
+        .next()
+}
+
+/// The address the interface uses now: "ether" on FreeBSD, "lladdr" on
+/// OpenBSD.
+pub fn current_mac(output: &str) -> Option<&str> {
+    field(output, "ether").or_else(|| field(output, "lladdr"))
+}
+
+/// FreeBSD lists the burned-in address as "hwaddr"; OpenBSD does not
+/// report it.
+pub fn hardware_mac(output: &str) -> Option<&str> {
+    field(output, "hwaddr")
+}
+
+pub fn is_up(output: &str) -> bool {
+    output.lines().next().is_some_and(|flags| flags.contains("<UP"))
+}
+
+pub fn is_wireless(output: &str) -> bool {
+    output
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("media:"))
+        .any(|media| media.contains("IEEE 802.11") || media.contains("IEEE802.11"))
+}
+
+/// The driver an interface belongs to, which BSDs name it after: em0 is
+/// em, ue1 is ue.
+pub fn driver(interface: &str) -> &str {
+    interface.trim_end_matches(|c: char| c.is_ascii_digit())
+}
+
+pub fn is_usb(interface: &str) -> bool {
+    USB_DRIVERS.contains(&driver(interface))
+}
+
+/// The network a Wi-Fi interface is associated with. FreeBSD prints
+/// `ssid NAME channel 6 (2437 MHz 11g) bssid XX:..`, OpenBSD
+/// `ieee80211: nwid NAME chan 6 bssid XX:.. -52dBm`, quoting names with
+/// spaces.
+pub fn wireless_connection(output: &str) -> Option<WirelessConnection> {
+    if !output.contains("status: associated") && !output.contains("status: active") {
+        return None;
+    }
+
+    let line = output.lines().map(str::trim).find(|line| {
+        line.starts_with("ssid ") || line.starts_with("ieee80211: nwid ")
+    })?;
+    let rest = line.strip_prefix("ieee80211: nwid ").or_else(|| line.strip_prefix("ssid "))?;
+    let ssid = match rest.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next()?,
+        None => rest.split_whitespace().next()?,
+    };
+
+    let bssid = line
+        .split_whitespace()
+        .skip_while(|word| *word != "bssid")
+        .nth(1)
+        .and_then(|bssid| bssid.parse().ok());
+    Some(WirelessConnection { ssid: (!ssid.is_empty()).then(|| ssid.to_string()), bssid })
+}
+
+/// The file a permanent address for `interface` is written to.
+#[cfg(target_os = "freebsd")]
+pub fn override_path(_interface: &str) -> PathBuf {
+    PathBuf::from("/etc/rc.conf")
+}
+
+#[cfg(target_os = "openbsd")]
+pub fn override_path(interface: &str) -> PathBuf {
+    PathBuf::from(format!("/etc/hostname.{}", interface))
+}
+
+/// rc.conf variables use underscores where interface names have dots or
+/// dashes (vlan0.5 is ifconfig_vlan0_5).
+#[cfg(target_os = "freebsd")]
+fn rc_variable(interface: &str) -> String {
+    let name: String = interface.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    format!("ifconfig_{}", name)
+}
+
+/// The `ifconfig_<interface>` line of rc.conf and its unquoted value.
+#[cfg(target_os = "freebsd")]
+fn rc_entry<'a>(content: &'a str, interface: &str) -> Option<(&'a str, &'a str)> {
+    let prefix = format!("{}=", rc_variable(interface));
+    content.lines().find_map(|line| {
+        let value = line.trim().strip_prefix(&prefix)?;
+        Some((line, value.trim_matches(|c| c == '"' || c == '\'')))
+    })
+}
+
+/// The words of an ifconfig_ value without any `ether XX` pair.
+#[cfg(target_os = "freebsd")]
+fn without_ether(value: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut iter = value.split_whitespace();
+    while let Some(word) = iter.next() {
+        if word == LLADDR {
+            iter.next();
+        } else {
+            words.push(word);
+        }
+    }
+    words
+}
+
+/// The address `content` sets on `interface` at boot, if any.
+#[cfg(target_os = "freebsd")]
+pub fn find_override(content: &str, interface: &str) -> Option<String> {
+    let (_, value) = rc_entry(content, interface)?;
+    let mut words = value.split_whitespace();
+    words.find(|word| *word == LLADDR)?;
+    words.next().map(str::to_string)
+}
+
+/// `content` with `ether MAC` first in the interface's ifconfig_ line,
+/// keeping whatever else it configures (DHCP, addresses, WPA).
+#[cfg(target_os = "freebsd")]
+pub fn with_override(content: &str, interface: &str, mac: &str) -> String {
+    let Some((line, value)) = rc_entry(content, interface) else {
+        let mut content = content.to_string();
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(&format!("{}=\"{} {}\"\n", rc_variable(interface), LLADDR, mac));
+        return content;
+    };
+
+    let mut words = vec![LLADDR, mac];
+    words.extend(without_ether(value));
+    content.replacen(line, &format!("{}=\"{}\"", rc_variable(interface), words.join(" ")), 1)
+}
+
+/// `content` without the address; a line left with nothing to configure
+/// is removed.
+#[cfg(target_os = "freebsd")]
+pub fn without_override(content: &str, interface: &str) -> String {
+    let Some((line, value)) = rc_entry(content, interface) else {
+        return content.to_string();
+    };
+
+    let words = without_ether(value);
+    if words.is_empty() {
+        content.lines().filter(|other| *other != line).map(|other| format!("{}\n", other)).collect()
+    } else {
+        content.replacen(line, &format!("{}=\"{}\"", rc_variable(interface), words.join(" ")), 1)
+    }
+}
+
+#[cfg(target_os = "openbsd")]
+pub fn find_override(content: &str, _interface: &str) -> Option<String> {
+    content
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("lladdr ")?.split_whitespace().next())
+        .map(str::to_string)
+}
+
+/// `content` with `lladdr MAC` as the first line, so the address is set
+/// before netstart configures anything else.
+#[cfg(target_os = "openbsd")]
+pub fn with_override(content: &str, interface: &str, mac: &str) -> String {
+    format!("{} {}\n{}", LLADDR, mac, without_override(content, interface))
+}
+
+/// `content` without any `lladdr` line. A file left empty would still make
+/// netstart bring the interface up, so callers remove it instead.
+#[cfg(target_os = "openbsd")]
+pub fn without_override(content: &str, _interface: &str) -> String {
+    content
+        .lines()
+        .filter(|line| !line.trim().starts_with("lladdr "))
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
@@ -0,0 +1,386 @@
+// src/bundle.rs
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use crate::capabilities::find_tool;
+use crate::config::{self, MacConfig};
+use crate::error::MacError;
+use crate::change_window::ChangeWindows;
+use crate::filter::MacFilter;
+use crate::hooks::{Hook, HookManager};
+use crate::oui::OUIDatabase;
+use crate::output::{self, progress};
+use crate::profiles::ProfileManager;
+use crate::rules::RuleManager;
+use crate::settings::Settings;
+
+/// Bumped when a bundle's layout changes in a way older versions cannot read.
+pub const BUNDLE_VERSION: u32 = 1;
+const BUNDLE_FORMAT: &str = "chameleon-bundle";
+
+/// The first line of every age file.
+const AGE_HEADER: &[u8] = b"age-encryption.org/";
+
+/// JSON settings files carried in a bundle, by the key they are stored
+/// under. Keys a newer version adds are skipped on import.
+const FILES: [(&str, &str); 7] = [
+    ("filters", "filters.json"),
+    ("rules", "app_rules.json"),
+    ("profiles", "profiles.json"),
+    ("hooks", "hooks.json"),
+    ("change_windows", "change_windows.json"),
+    ("sandbox", "sandbox.json"),
+    ("system_log", "system_log.json"),
+];
+
+/// Everything chameleon keeps in its config directory except secrets (API
+/// tokens, escrow keys) and the OUI database itself, which is rebuilt with
+/// `oui update`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Bundle {
+    pub format: String,
+    pub version: u32,
+    pub exported: DateTime<Utc>,
+    #[serde(default)]
+    pub host: Option<String>,
+    /// Saved original MACs, one per interface.
+    #[serde(default)]
+    pub originals: Vec<MacConfig>,
+    /// Contents of the JSON settings files, keyed as in FILES.
+    #[serde(default)]
+    pub files: BTreeMap<String, Value>,
+    /// config.toml, kept as written so its comments survive.
+    #[serde(default)]
+    pub settings: Option<String>,
+    #[serde(default)]
+    pub oui: Option<OuiMetadata>,
+}
+
+/// What the exporting machine's vendor database looked like.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OuiMetadata {
+    pub downloaded: bool,
+    pub vendors: usize,
+    #[serde(default)]
+    pub updated: Option<DateTime<Utc>>,
+}
+
+fn config_dir() -> Result<PathBuf, MacError> {
+    Ok(dirs::config_dir()
+        .ok_or_else(|| MacError::SystemError("Could not find config directory".into()))?
+        .join("mac_changer"))
+}
+
+impl Bundle {
+    /// Collect the current configuration.
+    pub fn collect() -> Result<Self, Box<dyn Error>> {
+        let dir = config_dir()?;
+
+        let mut files = BTreeMap::new();
+        for (key, file) in FILES {
+            let Ok(content) = fs::read_to_string(dir.join(file)) else { continue };
+            let value = serde_json::from_str(&content)
+                .map_err(|e| MacError::ValidationFailed(format!("{} is not valid JSON: {}", dir.join(file).display(), e)))?;
+            files.insert(key.to_string(), value);
+        }
+
+        let oui = OUIDatabase::new()?;
+        let oui_path = dir.join("oui.db");
+        let updated = fs::metadata(&oui_path).and_then(|metadata| metadata.modified()).ok().map(DateTime::<Utc>::from);
+
+        Ok(Self {
+            format: BUNDLE_FORMAT.to_string(),
+            version: BUNDLE_VERSION,
+            exported: Utc::now(),
+            host: hostname::get().ok().map(|host| host.to_string_lossy().into_owned()),
+            originals: config::list_original_macs()?,
+            files,
+            settings: fs::read_to_string(Settings::user_path()).ok(),
+            oui: Some(OuiMetadata { downloaded: oui.is_downloaded(), vendors: oui.len(), updated }),
+        })
+    }
+
+    /// Read a bundle written by `export`, decrypting it first if it is an
+    /// age file.
+    pub fn read(path: &Path, identity: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let mut content = fs::read(path)?;
+        if content.starts_with(AGE_HEADER) {
+            content = decrypt(path, identity)?;
+        }
+
+        let bundle: Self = serde_json::from_slice(&content)
+            .map_err(|e| MacError::ValidationFailed(format!("{} is not a chameleon bundle: {}", path.display(), e)))?;
+        if bundle.format != BUNDLE_FORMAT {
+            return Err(Box::new(MacError::ValidationFailed(format!("{} is not a chameleon bundle", path.display()))));
+        }
+        // Interface names become file names under the config and lock
+        // directories, so a crafted bundle must not be able to point outside
+        if let Some(original) = bundle.originals.iter().find(|original| !is_safe_interface_name(&original.interface)) {
+            return Err(Box::new(MacError::ValidationFailed(format!(
+                "{} has an original MAC for {:?}, which is not an interface name",
+                path.display(), original.interface
+            ))));
+        }
+        if bundle.version > BUNDLE_VERSION {
+            return Err(Box::new(MacError::ValidationFailed(format!(
+                "{} was written by a newer chameleon (bundle version {}, this one reads up to {})",
+                path.display(), bundle.version, BUNDLE_VERSION
+            ))));
+        }
+        Ok(bundle)
+    }
+
+    /// Write the bundle to `path`. Paths ending in `.age` are encrypted with
+    /// the age tool, for `recipients` or else with a passphrase it asks for.
+    pub fn write(&self, path: &Path, recipients: &[String]) -> Result<(), Box<dyn Error>> {
+        let content = serde_json::to_string_pretty(self)?;
+        if path.extension().is_some_and(|extension| extension == "age") {
+            return encrypt(path, content.as_bytes(), recipients);
+        }
+        if !recipients.is_empty() {
+            return Err(Box::new(MacError::ValidationFailed(
+                "--recipient needs an encrypted bundle; give a path ending in .age".into()
+            )));
+        }
+        // Original MACs and hook commands are nobody else's business
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            options.mode(0o600);
+            // An existing file keeps its mode when opened
+            if path.exists() {
+                fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+            }
+        }
+        options.open(path)?.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    /// One line per artifact, for listings.
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if !self.originals.is_empty() {
+            let interfaces: Vec<&str> = self.originals.iter().map(|config| config.interface.as_str()).collect();
+            lines.push(format!("original MACs: {}", interfaces.join(", ")));
+        }
+        for (key, value) in &self.files {
+            let entries = match value {
+                Value::Object(map) => format!(" ({} entries)", map.len()),
+                Value::Array(list) => format!(" ({} entries)", list.len()),
+                _ => String::new(),
+            };
+            lines.push(format!("{}{}", key.replace('_', " "), entries));
+        }
+        if self.settings.is_some() {
+            lines.push("config.toml".to_string());
+        }
+        lines
+    }
+
+    /// Hooks the bundle brings in `hooks.json` and config.toml. They run
+    /// commands and call URLs on every change, so import shows them first.
+    pub fn hooks(&self) -> Result<Vec<Hook>, Box<dyn Error>> {
+        let mut hooks: Vec<Hook> = match self.files.get("hooks") {
+            Some(value) => serde_json::from_value(value.clone())
+                .map_err(|e| MacError::ValidationFailed(format!("The bundle's hooks are not valid: {}", e)))?,
+            None => Vec::new(),
+        };
+        if let Some(settings) = &self.settings {
+            let settings: Settings = toml::from_str(settings)
+                .map_err(|e| MacError::ValidationFailed(format!("The bundle's config.toml is not valid: {}", e.to_string().trim())))?;
+            hooks.extend(settings.hooks);
+        }
+        Ok(hooks)
+    }
+
+    /// Write the bundle's settings into the config directory, replacing the
+    /// files it carries. Existing original MACs are kept: the first save is
+    /// the real original. If any file does not load afterwards, everything
+    /// is put back as it was.
+    pub fn apply(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let dir = config_dir()?;
+        fs::create_dir_all(&dir)?;
+
+        let mut writes: Vec<(PathBuf, String)> = Vec::new();
+        for (key, value) in &self.files {
+            match FILES.iter().find(|(name, _)| name == key) {
+                Some((_, file)) => writes.push((dir.join(file), serde_json::to_string_pretty(value)?)),
+                None => progress!("Skipping {}, which this version does not know", key),
+            }
+        }
+        if let Some(settings) = &self.settings {
+            writes.push((Settings::user_path(), settings.clone()));
+        }
+
+        let previous: Vec<(PathBuf, Option<String>)> = writes
+            .iter()
+            .map(|(path, _)| (path.clone(), fs::read_to_string(path).ok()))
+            .collect();
+        let mut report = Vec::new();
+        for (path, content) in &writes {
+            fs::write(path, content)?;
+            report.push(format!("Wrote {}", path.display()));
+        }
+
+        if let Err(e) = check_loads() {
+            for (path, content) in previous {
+                let _ = match content {
+                    Some(content) => fs::write(&path, content),
+                    None => fs::remove_file(&path),
+                };
+            }
+            return Err(Box::new(MacError::ValidationFailed(format!("Bundle not imported, its settings do not load: {}", e))));
+        }
+
+        for original in &self.originals {
+            // A backup is only good for the card it was taken from
+            if let Some(exported) = &original.hardware_mac
+                && let Some(local) = crate::backend::current().hardware_mac(&original.interface)
+                && *exported != local
+            {
+                report.push(format!(
+                    "Skipped the original MAC of {}: it was saved for hardware {}, this card is {}",
+                    original.interface, exported, local
+                ));
+                continue;
+            }
+            report.push(match config::import_original(original)? {
+                true => format!("Saved the original MAC of {} ({})", original.interface, original.original_mac),
+                false => format!("Kept the original MAC already saved for {}", original.interface),
+            });
+        }
+
+        if let Some(oui) = &self.oui
+            && oui.downloaded
+            && !OUIDatabase::new()?.is_downloaded()
+        {
+            report.push(format!(
+                "The exporting machine had a downloaded vendor database ({} vendors); run `chameleon oui update` to get one",
+                oui.vendors
+            ));
+        }
+        Ok(report)
+    }
+}
+
+/// `chameleon export`
+pub fn export(path: &Path, recipients: &[String]) -> Result<(), Box<dyn Error>> {
+    let bundle = Bundle::collect()?;
+    bundle.write(path, recipients)?;
+    println!("Exported to {}:", path.display());
+    for line in bundle.summary() {
+        println!("  {}", line);
+    }
+    Ok(())
+}
+
+/// `chameleon import`. Hooks are listed in full and, unless `yes`, only
+/// imported once the user agrees.
+pub fn import(path: &Path, identity: Option<&Path>, dry_run: bool, yes: bool) -> Result<(), Box<dyn Error>> {
+    let bundle = Bundle::read(path, identity)?;
+    println!(
+        "Bundle exported {}{}:",
+        bundle.exported.format("%Y-%m-%d %H:%M UTC"),
+        bundle.host.as_deref().map(|host| format!(" on {}", host)).unwrap_or_default()
+    );
+    for line in bundle.summary() {
+        println!("  {}", line);
+    }
+    let hooks = bundle.hooks()?;
+    if !hooks.is_empty() {
+        println!("Hooks, which run after every MAC change:");
+        for hook in &hooks {
+            println!("  {}: {}", hook.name, hook.describe());
+        }
+    }
+    if dry_run {
+        println!("Dry run, nothing was changed");
+        return Ok(());
+    }
+    if !hooks.is_empty() && !yes && !output::confirm("Import these hooks?")? {
+        println!("Import cancelled, nothing was changed");
+        return Ok(());
+    }
+
+    for line in bundle.apply()? {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Load every imported file the way chameleon will, so a bad bundle is
+/// caught now rather than by the next command.
+fn check_loads() -> Result<(), Box<dyn Error>> {
+    MacFilter::check_saved()?;
+    RuleManager::new()?;
+    ProfileManager::new()?;
+    HookManager::new()?;
+    ChangeWindows::new()?;
+    if let Some(settings) = Settings::read(&Settings::user_path())?
+        && let Some(problem) = settings.problems().into_iter().next()
+    {
+        return Err(problem.into());
+    }
+    Ok(())
+}
+
+/// Names as Linux, macOS, the BSDs and Windows give them ("wlan0",
+/// "Wi-Fi 2"), plus `@netns` for namespaced backups. Nothing that can
+/// leave a directory or is not allowed in a file name.
+fn is_safe_interface_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 256
+        && !name.contains("..")
+        && !name.chars().any(|c| c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|'))
+}
+
+fn age() -> Result<PathBuf, MacError> {
+    find_tool("age").or_else(|| find_tool("rage")).ok_or_else(|| MacError::ValidationFailed(
+        "Encrypted bundles need the age tool (https://age-encryption.org) or rage; install one, or leave out .age".into()
+    ))
+}
+
+/// age asks for the passphrase on the terminal itself.
+fn encrypt(path: &Path, content: &[u8], recipients: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut command = Command::new(age()?);
+    command.arg("--encrypt");
+    match recipients {
+        [] => {
+            command.arg("--passphrase");
+        }
+        recipients => {
+            for recipient in recipients {
+                command.args(["--recipient", recipient]);
+            }
+        }
+    }
+    let mut child = command.arg("--output").arg(path).stdin(Stdio::piped()).spawn()?;
+    child.stdin.take().ok_or("age has no stdin")?.write_all(content)?;
+
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(Box::new(MacError::SystemError(format!("age could not encrypt {} ({})", path.display(), status))));
+    }
+    Ok(())
+}
+
+fn decrypt(path: &Path, identity: Option<&Path>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut command = Command::new(age()?);
+    command.arg("--decrypt");
+    if let Some(identity) = identity {
+        command.arg("--identity").arg(identity);
+    }
+    let output = command.arg(path).stderr(Stdio::inherit()).output()?;
+    if !output.status.success() {
+        return Err(Box::new(MacError::SystemError(format!("age could not decrypt {} ({})", path.display(), output.status))));
+    }
+    Ok(output.stdout)
+}
@@ -0,0 +1,111 @@
+// src/capabilities.rs
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+/// Searched after PATH: cron jobs, services and sudo with a reset environment
+/// often run with a PATH that lacks the sbin directories.
+#[cfg(unix)]
+const EXTRA_DIRS: &[&str] = &["/sbin", "/usr/sbin", "/bin", "/usr/bin", "/usr/local/sbin", "/usr/local/bin"];
+#[cfg(windows)]
+const EXTRA_DIRS: &[&str] = &["C:\\Windows\\System32"];
+
+/// Optional tools per platform: name, what chameleon uses it for, and what
+/// happens on hosts without it.
+#[cfg(target_os = "linux")]
+const TOOLS: &[(&str, &str, &str)] = &[
+    ("ip", "bring links down/up, set addresses and flush neighbors", "ioctls on a socket"),
+    ("sudo", "elevate commands when not running as root", "commands run directly"),
+    ("systemctl", "reload systemd after migrating from macchanger", "reload it manually"),
+    ("udevadm", "reload udev rules after --permanent", "rules apply from the next boot"),
+    ("iw", "detect the Wi-Fi network and multi-link addresses", "nmcli"),
+    ("wpa_cli", "keep wpa_supplicant from reassociating during a change", "iw disconnects directly"),
+    ("nmcli", "change interfaces NetworkManager manages, detect the Wi-Fi network", "changes go through `ip link`; network conditions never match"),
+    ("networkctl", "renew leases systemd-networkd manages", "its leases expire on their own"),
+    ("dhcpcd", "renew leases dhcpcd manages", "its leases expire on their own"),
+    ("dhclient", "renew leases dhclient manages", "its leases expire on their own"),
+    ("btmgmt", "change Bluetooth addresses through the kernel", "hcitool vendor commands"),
+    ("hcitool", "change Bluetooth addresses with vendor commands", "Bluetooth addresses stay unchanged"),
+    ("hciconfig", "read and reset Bluetooth controllers", "btmgmt"),
+];
+
+#[cfg(target_os = "windows")]
+const TOOLS: &[(&str, &str, &str)] = &[
+    ("netsh", "detect the Wi-Fi network and flush the ARP cache", "network conditions never match"),
+    ("ipconfig", "renew the DHCP lease after a change", "the old lease is kept until it expires"),
+];
+
+#[cfg(target_os = "macos")]
+const TOOLS: &[(&str, &str, &str)] = &[
+    ("ifconfig", "set addresses and read interface state", "addresses cannot be read or changed"),
+    ("networksetup", "find hardware ports and rejoin networks", "Wi-Fi and dock detection are unavailable"),
+    ("sysctl", "identify the hardware model", "spoofing support is assumed"),
+    ("ipconfig", "renew the DHCP lease after a change", "the old lease is kept until it expires"),
+    ("arp", "flush the neighbor cache after a change", "stale entries expire on their own"),
+];
+
+#[cfg(target_os = "freebsd")]
+const TOOLS: &[(&str, &str, &str)] = &[
+    ("ifconfig", "set addresses and read interface state", "addresses cannot be read or changed"),
+    ("service", "restart dhclient after a change", "the old lease is kept until it expires"),
+    ("arp", "flush the neighbor cache after a change", "stale entries expire on their own"),
+];
+
+#[cfg(target_os = "openbsd")]
+const TOOLS: &[(&str, &str, &str)] = &[
+    ("ifconfig", "set addresses and read interface state", "addresses cannot be read or changed"),
+    ("dhcpleasectl", "renew the lease dhcpleased manages", "the old lease is kept until it expires"),
+    ("arp", "flush the neighbor cache after a change", "stale entries expire on their own"),
+];
+
+/// Full path of an external tool, or None if this host doesn't have it. The
+/// answer is cached for the life of the process.
+pub fn find_tool(name: &str) -> Option<PathBuf> {
+    static FOUND: OnceLock<Mutex<HashMap<String, Option<PathBuf>>>> = OnceLock::new();
+
+    let mut found = FOUND.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    found.entry(name.to_string()).or_insert_with(|| search(name)).clone()
+}
+
+/// `find_tool` for a tool the caller cannot do without.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn require_tool(name: &str) -> Result<PathBuf, crate::error::MacError> {
+    find_tool(name).ok_or_else(|| crate::error::MacError::SystemError(format!("{} is not installed", name)))
+}
+
+fn search(name: &str) -> Option<PathBuf> {
+    let file_name = if cfg!(windows) { format!("{}.exe", name) } else { name.to_string() };
+
+    std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .chain(EXTRA_DIRS.iter().map(PathBuf::from))
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+}
+
+#[derive(Debug)]
+pub struct Capability {
+    pub tool: &'static str,
+    pub purpose: &'static str,
+    pub fallback: &'static str,
+    pub path: Option<PathBuf>,
+}
+
+/// Which optional tools this host has.
+pub fn probe() -> Vec<Capability> {
+    TOOLS
+        .iter()
+        .map(|&(tool, purpose, fallback)| Capability { tool, purpose, fallback, path: find_tool(tool) })
+        .collect()
+}
+
+pub fn print(capabilities: &[Capability]) {
+    for capability in capabilities {
+        match &capability.path {
+            Some(path) => println!("{:<13} {} ({})", capability.tool, path.display(), capability.purpose),
+            None => println!("{:<13} missing, {}", capability.tool, capability.fallback),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+// src/change_window.rs
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use crate::error::MacError;
+use crate::rules::{Schedule, TIME_FORMAT};
+
+/// When each interface may have its MAC changed. Interfaces without a
+/// window may change at any time.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangeWindows {
+    windows: BTreeMap<String, Schedule>,
+    #[serde(skip)]
+    config_path: PathBuf,
+}
+
+impl ChangeWindows {
+    /// Fails if the file is there but does not parse: dropping the windows
+    /// would allow changes they were set up to prevent.
+    pub fn new() -> Result<Self, MacError> {
+        let config_path = dirs::config_dir()
+            .unwrap_or_default()
+            .join("mac_changer")
+            .join("change_windows.json");
+
+        let mut windows = Self {
+            windows: BTreeMap::new(),
+            config_path,
+        };
+
+        windows.load()?;
+        Ok(windows)
+    }
+
+    fn load(&mut self) -> Result<(), MacError> {
+        let content = match fs::read_to_string(&self.config_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let windows: ChangeWindows = serde_json::from_str(&content).map_err(|e| MacError::ParseError(format!(
+            "{} is not valid ({}); fix or remove it to change MACs again", self.config_path.display(), e
+        )))?;
+        self.windows = windows.windows;
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = self.config_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(&self.config_path, content)?;
+        Ok(())
+    }
+
+    /// Set the window for `interface`, replacing any previous one.
+    pub fn set(&mut self, interface: &str, schedule: Schedule) -> Result<(), Box<dyn Error>> {
+        self.windows.insert(interface.to_string(), schedule);
+        self.save()
+    }
+
+    pub fn remove(&mut self, interface: &str) -> Result<bool, Box<dyn Error>> {
+        let removed = self.windows.remove(interface).is_some();
+        self.save()?;
+        Ok(removed)
+    }
+
+    pub fn list(&self) -> Vec<(&String, &Schedule)> {
+        self.windows.iter().collect()
+    }
+
+    pub fn is_open(&self, interface: &str, at: DateTime<Local>) -> bool {
+        self.windows.get(interface).is_none_or(|schedule| schedule.is_active_at(at))
+    }
+
+    /// When `interface` may next change, or None if it may change now or its
+    /// window never opens again.
+    pub fn next_opening(&self, interface: &str, at: DateTime<Local>) -> Option<DateTime<Local>> {
+        if self.is_open(interface, at) {
+            return None;
+        }
+        self.windows.get(interface)?.next_activation(at)
+    }
+
+    /// The next time any window opens or closes.
+    pub fn next_transition(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        self.windows.values().filter_map(|schedule| schedule.next_transition(after)).min()
+    }
+
+    /// Fail with a hint on when to retry if `interface` may not change now.
+    pub fn check(&self, interface: &str, at: DateTime<Local>) -> Result<(), MacError> {
+        let Some(schedule) = self.windows.get(interface).filter(|schedule| !schedule.is_active_at(at)) else {
+            return Ok(());
+        };
+
+        let next = match schedule.next_activation(at) {
+            Some(opens) => format!("the next one opens {}", opens.format(TIME_FORMAT)),
+            None => "it does not open again within a year".into(),
+        };
+        Err(MacError::OutsideChangeWindow(format!(
+            "{} may only change during its change window ({}) and {}. Try again then, or lift the \
+             restriction with `chameleon window remove {}`",
+            interface, schedule, next, interface
+        )))
+    }
+}
@@ -0,0 +1,180 @@
+// src/chaos.rs
+use chrono::{Local, Utc};
+use rand::Rng;
+use std::error::Error;
+use std::io;
+use std::pin::Pin;
+use std::time::Duration;
+use crate::backend;
+use crate::change_window::ChangeWindows;
+use crate::config::{get_original_mac, save_original_mac};
+use crate::error::MacError;
+use crate::filter::MacFilter;
+use crate::hooks::Trigger;
+use crate::logger::{MacChange, MacLogger};
+use crate::mac::{generate_random_mac, MacAddress, RandomPolicy};
+use crate::platform::{change_mac, change_mac_anytime};
+use crate::rules::TIME_FORMAT;
+use crate::sandbox::Sandbox;
+
+/// Give up after this many failed changes in a row rather than hammering a
+/// broken interface.
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+
+#[derive(Debug)]
+pub struct ChaosConfig {
+    pub interface: String,
+    pub interval: Duration,
+    pub jitter: Duration,
+    pub iterations: Option<u64>,
+}
+
+impl ChaosConfig {
+    fn next_delay(&self) -> Duration {
+        if self.jitter.is_zero() {
+            return self.interval;
+        }
+
+        let jitter_ms = self.jitter.as_millis() as i64;
+        let offset = rand::thread_rng().gen_range(-jitter_ms..=jitter_ms);
+        let delay_ms = (self.interval.as_millis() as i64 + offset).max(0);
+        Duration::from_millis(delay_ms as u64)
+    }
+}
+
+/// Randomize the MAC of a sandboxed interface until interrupted or the
+/// iteration limit is reached, then put back the address it started with.
+pub async fn run(config: ChaosConfig, logger: &MacLogger) -> Result<(), Box<dyn Error>> {
+    let sandbox = Sandbox::new();
+    if !sandbox.contains(&config.interface) {
+        return Err(Box::new(MacError::PermissionDenied(format!(
+            "Interface {} is not in the sandbox list. Chaos mode only runs against lab \
+             interfaces; add it with `chameleon sandbox add {}`",
+            config.interface, config.interface
+        ))));
+    }
+
+    if config.jitter > config.interval {
+        return Err(Box::new(MacError::ValidationFailed(
+            "Jitter must not be larger than the interval".into()
+        )));
+    }
+
+    // Listening before the first change means Ctrl-C at any point stops the
+    // loop and restores the MAC instead of killing the process
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    if interrupted(&mut ctrl_c).await {
+        return Ok(());
+    }
+
+    let starting_mac = backend::current().get_mac(&config.interface)?;
+    if get_original_mac(&config.interface)?.is_none() {
+        save_original_mac(&config.interface, &starting_mac)?;
+    }
+
+    println!("Starting chaos mode on {} (interval {:?}, jitter {:?}). Press Ctrl-C to stop.",
+             config.interface, config.interval, config.jitter);
+
+    let mut completed = 0u64;
+    let mut consecutive_failures = 0u32;
+
+    while config.iterations.is_none_or(|max| completed < max) {
+        // A change runs to completion; Ctrl-C during one is seen here
+        if interrupted(&mut ctrl_c).await {
+            println!("[chaos] Interrupted");
+            break;
+        }
+
+        // Rotations outside the interface's change window wait for it to open
+        let windows = match ChangeWindows::new() {
+            Ok(windows) => windows,
+            Err(e) => {
+                println!("[chaos] {}, stopping", e);
+                break;
+            }
+        };
+        if !windows.is_open(&config.interface, Local::now()) {
+            let Some(opens) = windows.next_opening(&config.interface, Local::now()) else {
+                println!("[chaos] The change window for {} never opens, stopping", config.interface);
+                break;
+            };
+            println!("[chaos] Outside the change window, waiting until {}", opens.format(TIME_FORMAT));
+
+            tokio::select! {
+                _ = tokio::time::sleep((opens - Local::now()).to_std().unwrap_or_default()) => continue,
+                _ = &mut ctrl_c => {
+                    println!("[chaos] Interrupted");
+                    break;
+                }
+            }
+        }
+
+        let result = (|| -> Result<(MacAddress, MacAddress), Box<dyn Error>> {
+            let old_mac = backend::current().get_mac(&config.interface)?;
+            // Read on every rotation so filter edits apply to a running session
+            let new_mac = MacFilter::new().pick_allowed(|| Ok(generate_random_mac(&RandomPolicy::LocallyAdministered)?))?;
+            change_mac(&config.interface, &new_mac, false, Trigger::Chaos)?.run_hooks();
+            Ok((old_mac, new_mac))
+        })();
+
+        match result {
+            Ok((old_mac, new_mac)) => {
+                consecutive_failures = 0;
+                println!("[chaos] #{} {}: {} -> {}", completed + 1, config.interface, old_mac, new_mac);
+
+                logger.log_change(MacChange {
+                    timestamp: Utc::now(),
+                    interface: config.interface.clone(),
+                    old_mac,
+                    new_mac,
+                    geo_location: None,
+                    permanent: false,
+                    profile: None,
+                    trigger: Some(Trigger::Chaos),
+                    hostname: None,
+                    dhcp_client_id: None,
+                    principal: None,
+                })?;
+            }
+            Err(e) => {
+                consecutive_failures += 1;
+                println!("[chaos] #{} change failed: {}", completed + 1, e);
+
+                if consecutive_failures >= MAX_CONSECUTIVE_FAILURES {
+                    println!("[chaos] {} consecutive failures, stopping", consecutive_failures);
+                    break;
+                }
+            }
+        }
+
+        completed += 1;
+        if config.iterations.is_some_and(|max| completed >= max) {
+            break;
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(config.next_delay()) => {}
+            _ = &mut ctrl_c => {
+                println!("[chaos] Interrupted");
+                break;
+            }
+        }
+    }
+
+    println!("Restoring {} to {}...", config.interface, starting_mac);
+    // Undoing our own changes is allowed outside the window
+    change_mac_anytime(&config.interface, &starting_mac, false, Trigger::Chaos)?.run_hooks();
+
+    Ok(())
+}
+
+/// Whether Ctrl-C was pressed since `ctrl_c` was first polled, without
+/// waiting for it.
+async fn interrupted(ctrl_c: &mut Pin<&mut impl Future<Output = io::Result<()>>>) -> bool {
+    tokio::select! {
+        biased;
+        _ = ctrl_c => true,
+        _ = std::future::ready(()) => false,
+    }
+}
@@ -0,0 +1,246 @@
+// src/collision.rs
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::process::Command;
+use crate::backend;
+use crate::capabilities::find_tool;
+use crate::error::MacError;
+use crate::mac::{MacAddress, MacFormat};
+use crate::output::progress;
+
+/// How long to listen for ARP replies after the last request.
+#[cfg(not(target_os = "windows"))]
+const REPLY_WAIT: std::time::Duration = std::time::Duration::from_millis(1500);
+
+/// Larger subnets are not swept; the neighbor table still counts.
+#[cfg(not(target_os = "windows"))]
+const MAX_SWEEP_HOSTS: u32 = 1024;
+
+const MAX_ATTEMPTS: usize = 20;
+
+/// MACs already in use next to an interface, with where each was seen.
+#[derive(Debug, Default, Serialize)]
+pub struct InUse {
+    pub seen: HashMap<MacAddress, String>,
+    pub notes: Vec<String>,
+}
+
+impl InUse {
+    /// Where `mac` was seen, if it is in use.
+    pub fn find(&self, mac: &MacAddress) -> Option<&str> {
+        self.seen.get(mac).map(String::as_str)
+    }
+
+    /// Call `generate` until it produces a MAC nobody uses. A generator that
+    /// returns the same MAC twice is fixed, so its collision is final.
+    pub fn pick_unused(
+        &self,
+        mut generate: impl FnMut() -> Result<MacAddress, Box<dyn Error>>,
+    ) -> Result<MacAddress, Box<dyn Error>> {
+        let mut previous: Option<(MacAddress, String)> = None;
+
+        for _ in 0..MAX_ATTEMPTS {
+            let mac = generate()?;
+
+            if let Some((previous, seen)) = &previous {
+                if *previous == mac {
+                    return Err(Box::new(MacError::ValidationFailed(format!(
+                        "{} is already in use ({}); pick another MAC or pass --no-collision-check", mac, seen
+                    ))));
+                }
+                progress!("{} is already in use ({}), generated another", previous, seen);
+            }
+
+            match self.find(&mac) {
+                Some(seen) => previous = Some((mac, seen.to_string())),
+                None => return Ok(mac),
+            }
+        }
+
+        Err(Box::new(MacError::ValidationFailed(format!(
+            "No unused MAC found in {} attempts", MAX_ATTEMPTS
+        ))))
+    }
+
+    fn add(&mut self, mac: &MacAddress, seen: impl FnOnce() -> String) {
+        // Broadcast, multicast and incomplete entries are not stations
+        if mac.is_multicast() || mac.is_null() {
+            return;
+        }
+        self.seen.entry(mac.with_format(MacFormat::Colon)).or_insert_with(seen);
+    }
+}
+
+/// Collect the MACs in use around `interface`: the host's other interfaces,
+/// the neighbor table and, where raw sockets are available, whatever answers
+/// an ARP sweep of its IPv4 subnet. The interface's own MAC is left out, and
+/// nothing here fails; what could not be checked ends up in `notes`.
+pub fn scan(interface: &str) -> InUse {
+    let mut in_use = InUse::default();
+
+    #[cfg(not(target_os = "windows"))]
+    match arp_sweep(interface) {
+        Ok(replies) => {
+            for (ip, mac) in replies {
+                in_use.add(&mac, || format!("answered ARP for {} on {}", ip, interface));
+            }
+        }
+        Err(e) => in_use.notes.push(format!("Skipped the ARP sweep: {}", e.to_string().trim())),
+    }
+
+    match neighbors(interface) {
+        Ok(neighbors) => {
+            for (ip, mac) in neighbors {
+                in_use.add(&mac, || format!("neighbor {}", ip));
+            }
+        }
+        Err(e) => in_use.notes.push(format!("Could not read the neighbor table: {}", e.to_string().trim())),
+    }
+
+    let backend = backend::current();
+    for name in backend.list_interfaces().unwrap_or_default() {
+        if name != interface && let Ok(mac) = backend.get_mac(&name) {
+            in_use.add(&mac, || format!("local interface {}", name));
+        }
+    }
+
+    if let Ok(own) = backend.get_mac(interface) {
+        in_use.seen.remove(&own);
+    }
+
+    in_use
+}
+
+#[cfg(target_os = "linux")]
+fn neighbor_command(interface: &str) -> (&'static str, Vec<String>) {
+    ("ip", vec!["neigh".into(), "show".into(), "dev".into(), interface.into()])
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn neighbor_command(interface: &str) -> (&'static str, Vec<String>) {
+    ("arp", vec!["-an".into(), "-i".into(), interface.into()])
+}
+
+/// `arp -a` cannot be narrowed to an adapter by name, so every neighbor
+/// counts; a MAC seen on another segment is not worth the risk either.
+#[cfg(target_os = "windows")]
+fn neighbor_command(_interface: &str) -> (&'static str, Vec<String>) {
+    ("arp", vec!["-a".into()])
+}
+
+/// (address, MAC) pairs from the neighbor table. The tools print them in
+/// different layouts, so each line is searched for the first IP and MAC.
+fn neighbors(interface: &str) -> Result<Vec<(String, MacAddress)>, Box<dyn Error>> {
+    let (tool, args) = neighbor_command(interface);
+    let path = find_tool(tool).ok_or_else(|| MacError::SystemError(format!("{} is not installed", tool)))?;
+    let output = Command::new(path).args(&args).output()?;
+    if !output.status.success() {
+        return Err(Box::new(MacError::SystemError(String::from_utf8_lossy(&output.stderr).into_owned())));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let ip = tokens
+                .iter()
+                .map(|token| token.trim_matches(['(', ')']))
+                .find(|token| token.parse::<std::net::IpAddr>().is_ok())?;
+            let mac = tokens
+                .iter()
+                .filter(|token| token.len() >= 11 && token.contains([':', '-']))
+                .find_map(|token| MacAddress::parse(token).ok())?;
+            Some((ip.to_string(), mac))
+        })
+        .collect())
+}
+
+/// Ask every address of the interface's IPv4 subnet who has it and collect
+/// the replies. Needs a raw socket, so root or CAP_NET_RAW on Linux.
+#[cfg(not(target_os = "windows"))]
+fn arp_sweep(interface: &str) -> Result<Vec<(std::net::Ipv4Addr, MacAddress)>, Box<dyn Error>> {
+    use pnet::datalink::{self, Channel};
+    use pnet::ipnetwork::IpNetwork;
+    use pnet::packet::arp::{ArpHardwareTypes, ArpOperations, ArpPacket, MutableArpPacket};
+    use pnet::packet::ethernet::{EtherTypes, EthernetPacket, MutableEthernetPacket};
+    use pnet::packet::{MutablePacket, Packet};
+    use pnet::util::MacAddr;
+    use std::time::{Duration, Instant};
+
+    let iface = datalink::interfaces()
+        .into_iter()
+        .find(|iface| iface.name == interface)
+        .ok_or_else(|| MacError::SystemError(format!("{} not found", interface)))?;
+    if !iface.is_up() {
+        return Err(Box::new(MacError::SystemError(format!("{} is down", interface))));
+    }
+    let own_mac = iface.mac.ok_or_else(|| MacError::SystemError(format!("{} has no MAC", interface)))?;
+    let network = iface
+        .ips
+        .iter()
+        .find_map(|ip| match ip {
+            IpNetwork::V4(network) => Some(*network),
+            IpNetwork::V6(_) => None,
+        })
+        .ok_or_else(|| MacError::SystemError(format!("{} has no IPv4 address", interface)))?;
+    if network.size() > MAX_SWEEP_HOSTS {
+        return Err(Box::new(MacError::SystemError(format!(
+            "/{} is too large to sweep", network.prefix()
+        ))));
+    }
+
+    let config = datalink::Config {
+        read_timeout: Some(Duration::from_millis(100)),
+        ..Default::default()
+    };
+    let (mut tx, mut rx) = match datalink::channel(&iface, config)? {
+        Channel::Ethernet(tx, rx) => (tx, rx),
+        _ => return Err(Box::new(MacError::SystemError("Unsupported channel type".into()))),
+    };
+
+    let targets = network
+        .iter()
+        .filter(|ip| *ip != network.ip() && *ip != network.network() && *ip != network.broadcast());
+    for target in targets {
+        let mut frame = [0u8; 42];
+        let mut ethernet = MutableEthernetPacket::new(&mut frame).expect("frame fits an ARP request");
+        ethernet.set_destination(MacAddr::broadcast());
+        ethernet.set_source(own_mac);
+        ethernet.set_ethertype(EtherTypes::Arp);
+
+        let mut arp = MutableArpPacket::new(ethernet.payload_mut()).expect("frame fits an ARP request");
+        arp.set_hardware_type(ArpHardwareTypes::Ethernet);
+        arp.set_protocol_type(EtherTypes::Ipv4);
+        arp.set_hw_addr_len(6);
+        arp.set_proto_addr_len(4);
+        arp.set_operation(ArpOperations::Request);
+        arp.set_sender_hw_addr(own_mac);
+        arp.set_sender_proto_addr(network.ip());
+        arp.set_target_hw_addr(MacAddr::zero());
+        arp.set_target_proto_addr(target);
+
+        if let Some(Err(e)) = tx.send_to(&frame, None) {
+            return Err(Box::new(e));
+        }
+    }
+
+    let mut replies = Vec::new();
+    let deadline = Instant::now() + REPLY_WAIT;
+    while Instant::now() < deadline {
+        // Errors are mostly the read timeout
+        let Ok(frame) = rx.next() else { continue };
+        let Some(ethernet) = EthernetPacket::new(frame) else { continue };
+        if ethernet.get_ethertype() != EtherTypes::Arp {
+            continue;
+        }
+        if let Some(arp) = ArpPacket::new(ethernet.payload())
+            && arp.get_operation() == ArpOperations::Reply
+        {
+            let MacAddr(a, b, c, d, e, f) = arp.get_sender_hw_addr();
+            replies.push((arp.get_sender_proto_addr(), MacAddress::new([a, b, c, d, e, f], MacFormat::Colon)));
+        }
+    }
+
+    Ok(replies)
+}
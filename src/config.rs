@@ -1,22 +1,33 @@
 use serde::{Serialize, Deserialize};
-use std::error::Error;
 use std::fs;
 use crate::error::MacError;
+use crate::mac::MacAddress;
+use crate::output::progress;
 use std;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MacConfig {
-    pub original_mac: String,
+    pub original_mac: MacAddress,
     pub interface: String,
     pub vendor: Option<String>,
+    /// The burned-in address, which a wiped config directory cannot lose
+    /// to a spoofed one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hardware_mac: Option<MacAddress>,
     pub last_modified: chrono::DateTime<chrono::Utc>,
 }
 
-pub fn save_original_mac(interface: &str, mac: &str) -> Result<(), Box<dyn Error>> {
+/// Inside `--netns` the backup is kept under the qualified name, so it
+/// cannot be mistaken for the host interface of the same name.
+pub fn save_original_mac(interface: &str, mac: &MacAddress) -> Result<(), MacError> {
+    let _lock = crate::lock::acquire(interface)?;
+    let hardware_mac = crate::backend::current().hardware_mac(interface);
+    let interface = &crate::netns::qualify(interface);
     let config = MacConfig {
-        original_mac: mac.to_string(),
+        original_mac: mac.clone(),
         interface: interface.to_string(),
         vendor: None,
+        hardware_mac,
         last_modified: chrono::Utc::now(),
     };
 
@@ -26,14 +37,65 @@ pub fn save_original_mac(interface: &str, mac: &str) -> Result<(), Box<dyn Error
 
     fs::create_dir_all(&config_dir)?;
 
+    // Another process may have saved it since the caller looked; the first
+    // save is the real original
     let config_file = config_dir.join(format!("{}.json", interface));
+    if config_file.exists() {
+        return Ok(());
+    }
+    if let Some(warning) = spoofed_original_warning(mac, config.hardware_mac.as_ref()) {
+        progress!("Warning: {}", warning);
+    }
     let config_json = serde_json::to_string_pretty(&config)?;
     fs::write(config_file, config_json)?;
 
     Ok(())
 }
 
-pub fn get_original_mac(interface: &str) -> Result<Option<String>, Box<dyn Error>> {
+/// Save a backup taken on another machine or before a reinstall, unless
+/// one is already saved for its interface. Returns whether it was saved.
+pub fn import_original(config: &MacConfig) -> Result<bool, MacError> {
+    let _lock = crate::lock::acquire(&config.interface)?;
+    let config_dir = dirs::config_dir()
+        .ok_or_else(|| MacError::SystemError("Could not find config directory".into()))?
+        .join("mac_changer");
+    fs::create_dir_all(&config_dir)?;
+
+    let config_file = config_dir.join(format!("{}.json", config.interface));
+    if config_file.exists() {
+        return Ok(false);
+    }
+    fs::write(config_file, serde_json::to_string_pretty(config)?)?;
+    Ok(true)
+}
+
+/// Every original MAC saved so far.
+pub fn list_original_macs() -> Result<Vec<MacConfig>, MacError> {
+    let config_dir = dirs::config_dir()
+        .ok_or_else(|| MacError::SystemError("Could not find config directory".into()))?
+        .join("mac_changer");
+
+    let Ok(entries) = fs::read_dir(config_dir) else {
+        return Ok(Vec::new());
+    };
+
+    // Other settings share the directory; only backups parse as MacConfig
+    let mut configs: Vec<MacConfig> = entries
+        .filter_map(|entry| fs::read_to_string(entry.ok()?.path()).ok())
+        .filter_map(|content| serde_json::from_str(&content).ok())
+        .collect();
+    configs.sort_by(|a, b| a.interface.cmp(&b.interface));
+
+    Ok(configs)
+}
+
+pub fn get_original_mac(interface: &str) -> Result<Option<MacAddress>, MacError> {
+    Ok(get_original(interface)?.map(|config| config.original_mac))
+}
+
+/// The saved backup for `interface`, if any.
+pub fn get_original(interface: &str) -> Result<Option<MacConfig>, MacError> {
+    let interface = crate::netns::qualify(interface);
     let config_file = dirs::config_dir()
         .ok_or_else(|| MacError::SystemError("Could not find config directory".into()))?
         .join("mac_changer")
@@ -41,9 +103,33 @@ pub fn get_original_mac(interface: &str) -> Result<Option<String>, Box<dyn Error
 
     if config_file.exists() {
         let content = fs::read_to_string(config_file)?;
-        let config: MacConfig = serde_json::from_str(&content)?;
-        Ok(Some(config.original_mac))
+        Ok(Some(serde_json::from_str(&content)?))
     } else {
         Ok(None)
     }
 }
+
+/// The burned-in address of `interface`: what the driver reports now, or
+/// what was saved with the original MAC.
+pub fn get_factory_mac(interface: &str) -> Result<Option<MacAddress>, MacError> {
+    match crate::backend::current().hardware_mac(interface) {
+        Some(mac) => Ok(Some(mac)),
+        None => Ok(get_original(interface)?.and_then(|config| config.hardware_mac)),
+    }
+}
+
+/// Why `original` may not be the real original: a locally administered
+/// address was most likely set by chameleon or another tool, e.g. when the
+/// backup was lost while a spoofed MAC was active.
+pub fn spoofed_original_warning(original: &MacAddress, hardware_mac: Option<&MacAddress>) -> Option<String> {
+    if !original.is_locally_administered() {
+        return None;
+    }
+    Some(match hardware_mac {
+        Some(hardware_mac) if hardware_mac != original => format!(
+            "The original MAC {} is locally administered and likely spoofed; `--restore --factory` puts back the hardware address {}",
+            original, hardware_mac
+        ),
+        _ => format!("The original MAC {} is locally administered and may already be spoofed", original),
+    })
+}
@@ -0,0 +1,387 @@
+// src/daemon.rs
+use chrono::{DateTime, Local, Utc};
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use crate::apps;
+use crate::backend;
+use crate::change_window::ChangeWindows;
+use crate::error::MacError;
+use crate::filter::MacFilter;
+use crate::hooks::Trigger;
+use crate::journal::{DaemonEvent, Journal};
+use crate::logger::{LinkEvent, MacLogger};
+use crate::mac::MacAddress;
+use crate::metrics;
+use crate::network::{self, LinkState};
+use crate::platform::apply_mac;
+use crate::rules::{RuleManager, ANY_INTERFACE, TIME_FORMAT};
+use crate::watch;
+
+/// Launches often come in bursts, such as a browser starting its helpers;
+/// wait for them to settle before looking at the process list.
+const PROCESS_SETTLE_DELAY: Duration = Duration::from_millis(250);
+
+/// How often processes are listed where the OS offers no process events.
+const PROCESS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub struct DaemonConfig {
+    /// Interfaces to manage in addition to those named by rules.
+    pub interfaces: Vec<String>,
+    /// How often rules are evaluated.
+    pub interval: Duration,
+    /// Where to serve Prometheus metrics, if anywhere.
+    pub metrics_listen: Option<SocketAddr>,
+}
+
+/// Apply matching rules to managed interfaces on every interval and record
+/// their link state transitions until interrupted.
+pub async fn run(config: DaemonConfig, logger: &MacLogger) -> Result<(), Box<dyn Error>> {
+    let mut rule_manager = RuleManager::new()?;
+    let mut interfaces = managed_interfaces(&config, &rule_manager)?;
+
+    if interfaces.is_empty() {
+        return Err(Box::new(MacError::ValidationFailed(
+            "No interfaces to manage; pass -i or add a rule first".into()
+        )));
+    }
+
+    let _metrics = match config.metrics_listen {
+        Some(listen) => Some(metrics::serve(listen).await?),
+        None => None,
+    };
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let _events = match watch::subscribe("", tx) {
+        Ok(events) => Some(events),
+        Err(e) => {
+            println!("Link events unavailable ({}), sampling link state every {:?}", e, config.interval);
+            None
+        }
+    };
+
+    let (rules_tx, mut rules_rx) = mpsc::unbounded_channel();
+    let _rules_watcher = match rule_manager.watch(rules_tx) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            println!("Not watching rules for changes ({}), restart the daemon after editing them", e);
+            None
+        }
+    };
+
+    // App rules apply when their application starts rather than on the next tick
+    let (process_tx, mut process_rx) = mpsc::unbounded_channel();
+    let _processes = match apps::subscribe(process_tx.clone()) {
+        Ok(events) => events,
+        Err(e) => {
+            println!("Process events unavailable ({}), listing processes every {:?}", e, PROCESS_POLL_INTERVAL);
+            apps::poll(process_tx, PROCESS_POLL_INTERVAL)
+        }
+    };
+    let mut running_app_rules = Vec::new();
+
+    let mut link_states: HashMap<String, LinkState> = interfaces
+        .iter()
+        .map(|interface| (interface.clone(), current_link_state(interface)))
+        .collect();
+
+    let journal = Journal::new();
+    metrics::daemon_started(interfaces.len());
+    record(&journal, DaemonEvent::Started { interfaces: interfaces.iter().cloned().collect() });
+
+    println!("Managing {} (rules every {:?}). Press Ctrl-C to stop.",
+             interfaces.iter().cloned().collect::<Vec<_>>().join(", "), config.interval);
+
+    let mut ticker = tokio::time::interval(config.interval);
+    let mut applied = HashMap::new();
+    // Wake when a schedule or change window starts or ends rather than up to
+    // an interval later
+    let mut next_transition = next_wakeup(&rule_manager);
+
+    loop {
+        tokio::select! {
+            Some(()) = rx.recv() => {
+                record_link_changes(&mut link_states, logger, &journal);
+            }
+            Some(()) = process_rx.recv() => {
+                if !rule_manager.has_app_rules() {
+                    continue;
+                }
+                tokio::time::sleep(PROCESS_SETTLE_DELAY).await;
+                while process_rx.try_recv().is_ok() {}
+
+                // Most processes are nothing a rule looks for
+                let Ok(running) = apps::running() else { continue };
+                let matching: Vec<String> = rule_manager.running_app_rules(&running).into_iter().map(String::from).collect();
+                if matching != running_app_rules {
+                    running_app_rules = matching;
+                    apply_rules(&rule_manager, &interfaces, &mut applied, logger, &journal);
+                }
+            }
+            Some(()) = rules_rx.recv() => {
+                match rule_manager.reload() {
+                    Ok(false) => {}
+                    Ok(true) => {
+                        println!("[daemon] Rules changed, reloaded {} rule(s)", rule_manager.list_rules().len());
+                        record(&journal, DaemonEvent::RulesReloaded { rules: rule_manager.list_rules().len() });
+                        // Start over so edited rules are resolved afresh
+                        applied.clear();
+                        match managed_interfaces(&config, &rule_manager) {
+                            Ok(managed) => {
+                                interfaces = managed;
+                                link_states.retain(|interface, _| interfaces.contains(interface));
+                                for interface in &interfaces {
+                                    link_states.entry(interface.clone()).or_insert_with(|| current_link_state(interface));
+                                }
+                            }
+                            Err(e) => println!("[daemon] Could not list interfaces: {}", e),
+                        }
+                        apply_rules(&rule_manager, &interfaces, &mut applied, logger, &journal);
+                        next_transition = next_wakeup(&rule_manager);
+                    }
+                    // Likely caught mid-edit; the next write triggers another reload
+                    Err(e) => {
+                        println!("[daemon] Could not reload rules, keeping the previous ones: {}", e);
+                        record(&journal, DaemonEvent::Error {
+                            interface: None,
+                            message: format!("Could not reload rules: {}", e),
+                        });
+                    }
+                }
+            }
+            _ = ticker.tick() => {
+                record_link_changes(&mut link_states, logger, &journal);
+                apply_rules(&rule_manager, &interfaces, &mut applied, logger, &journal);
+            }
+            _ = tokio::time::sleep(time_until(next_transition)), if next_transition.is_some() => {
+                apply_rules(&rule_manager, &interfaces, &mut applied, logger, &journal);
+                next_transition = next_wakeup(&rule_manager);
+            }
+            _ = tokio::signal::ctrl_c() => {
+                println!("[daemon] Stopped");
+                record(&journal, DaemonEvent::Stopped);
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn next_wakeup(rule_manager: &RuleManager) -> Option<DateTime<Local>> {
+    let now = Local::now();
+    [rule_manager.next_transition(now), ChangeWindows::new().ok().and_then(|windows| windows.next_transition(now))].into_iter().flatten().min()
+}
+
+fn time_until(at: Option<DateTime<Local>>) -> Duration {
+    at.and_then(|at| (at - Local::now()).to_std().ok()).unwrap_or_default()
+}
+
+/// Interfaces passed with -i plus those named by rules. Wildcard rules pull
+/// in every interface on the machine.
+fn managed_interfaces(config: &DaemonConfig, rule_manager: &RuleManager) -> Result<BTreeSet<String>, Box<dyn Error>> {
+    let all_interfaces = if rule_manager.has_wildcard_rules() {
+        backend::current().list_interfaces()?
+    } else {
+        Vec::new()
+    };
+
+    Ok(config.interfaces
+        .iter()
+        .cloned()
+        .chain(rule_manager.list_rules().into_iter().map(|rule| rule.interface.clone()))
+        .filter(|interface| interface != ANY_INTERFACE)
+        .chain(all_interfaces)
+        .collect())
+}
+
+/// An interface that has disappeared counts as down.
+fn current_link_state(interface: &str) -> LinkState {
+    backend::current().interface_state(interface).unwrap_or(LinkState { up: false, carrier: false })
+}
+
+fn record_link_changes(link_states: &mut HashMap<String, LinkState>, logger: &MacLogger, journal: &Journal) {
+    for (interface, previous) in link_states.iter_mut() {
+        let state = current_link_state(interface);
+        if state == *previous {
+            continue;
+        }
+        *previous = state;
+
+        let event = LinkEvent {
+            timestamp: Utc::now(),
+            interface: interface.clone(),
+            up: state.up,
+            carrier: state.carrier,
+        };
+        if let Err(e) = logger.log_link_event(&event) {
+            println!("[daemon] Could not record link event: {}", e);
+        }
+        record(journal, DaemonEvent::Link { interface: event.interface, up: event.up, carrier: event.carrier });
+    }
+}
+
+/// The rule (by the name it is saved under) the daemon last acted on for
+/// an interface, and the MAC it applied or is holding until the change
+/// window opens.
+struct Applied {
+    rule: String,
+    mac: MacAddress,
+    done: bool, // False while held for the change window
+    // What an app rule put its MAC over, restored when the application exits
+    restore: Option<MacAddress>,
+}
+
+fn record(journal: &Journal, event: DaemonEvent) {
+    if let Err(e) = journal.record(event) {
+        println!("[daemon] Could not write to the event journal: {}", e);
+    }
+}
+
+fn apply_rules(
+    rule_manager: &RuleManager,
+    interfaces: &BTreeSet<String>,
+    applied: &mut HashMap<String, Applied>,
+    logger: &MacLogger,
+    journal: &Journal,
+) {
+    let running_apps = match apps::running() {
+        Ok(apps) => apps,
+        Err(e) => {
+            println!("[daemon] Could not list running applications: {}", e);
+            record(journal, DaemonEvent::Error {
+                interface: None,
+                message: format!("Could not list running applications: {}", e),
+            });
+            return;
+        }
+    };
+
+    // Read on every pass so filter and window edits apply without a restart
+    let filter = MacFilter::new();
+    let windows = match ChangeWindows::new() {
+        Ok(windows) => windows,
+        Err(e) => {
+            println!("[daemon] Not applying rules: {}", e);
+            record(journal, DaemonEvent::Error { interface: None, message: e.to_string() });
+            return;
+        }
+    };
+
+    for interface in interfaces {
+        let connection = network::get_wireless_connection(interface);
+        let class = network::get_interface_class(interface);
+        let Some((name, rule)) = rule_manager.find_matching_rule(interface, &running_apps, connection.as_ref(), &class) else {
+            if let Some(previous) = applied.remove(interface) {
+                record(journal, DaemonEvent::RuleEnded { rule: previous.rule.clone(), interface: interface.clone() });
+                if previous.done && let Some(mac) = &previous.restore {
+                    restore(interface, &previous.rule, mac, logger, journal);
+                }
+            }
+            continue;
+        };
+
+        let name = name.to_string();
+        // Carried over when one app rule follows another, so the address
+        // from before the first one comes back in the end
+        let earlier_restore = applied.get(interface).and_then(|earlier| earlier.restore.clone());
+        let is_app_rule = !rule.app_name.is_empty();
+
+        // While the same rule keeps matching, hold on to the MAC it got
+        // instead of resolving a random profile again on every tick
+        let previous = applied.get(interface).filter(|previous| previous.rule == name);
+        let mac = match previous {
+            Some(previous) => Ok(previous.mac.clone()),
+            None => logger.get_history().and_then(|history| filter.pick_allowed(|| rule.resolve_mac(&history))),
+        };
+
+        let result = mac.and_then(|mac| {
+            let current = backend::current().get_mac(interface)?;
+            let restore = match previous {
+                Some(previous) => previous.restore.clone(),
+                None if is_app_rule => earlier_restore.or_else(|| Some(current.clone())),
+                None => None,
+            };
+            if current == mac {
+                return Ok(Applied { rule: name.clone(), mac, done: true, restore });
+            }
+
+            if previous.is_some_and(|previous| previous.done) {
+                println!("[daemon] {} reverted to {}, re-applying rule {}", interface, current, name);
+                record(journal, DaemonEvent::Reverted {
+                    interface: interface.clone(),
+                    expected: mac.clone(),
+                    found: current.clone(),
+                });
+            }
+
+            if !windows.is_open(interface, Local::now()) {
+                // Announce once; the MAC is kept and applied when the window opens
+                if previous.is_none_or(|previous| previous.done) {
+                    let opens = windows.next_opening(interface, Local::now());
+                    println!("[daemon] Rule {} on {} deferred to the change window (opens {})", name, interface,
+                             opens.map(|opens| opens.format(TIME_FORMAT).to_string()).unwrap_or_else(|| "never".into()));
+                    record(journal, DaemonEvent::RuleDeferred {
+                        rule: name.clone(),
+                        interface: interface.clone(),
+                        until: opens.map(|opens| opens.with_timezone(&Utc)),
+                    });
+                }
+                return Ok(Applied { rule: name.clone(), mac, done: false, restore });
+            }
+
+            apply_mac(interface, &mac, false, logger, rule.profile.as_deref(), Trigger::Daemon, None)?.run_hooks();
+            println!("[daemon] Applied rule {} on {}: {}", name, interface, mac);
+            record(journal, DaemonEvent::RuleApplied {
+                rule: name.clone(),
+                interface: interface.clone(),
+                old_mac: current,
+                new_mac: mac.clone(),
+            });
+            Ok(Applied { rule: name.clone(), mac, done: true, restore })
+        });
+
+        match result {
+            Ok(state) => {
+                applied.insert(interface.clone(), state);
+            }
+            Err(e) => {
+                println!("[daemon] Rule {} failed: {}", name, e);
+                record(journal, DaemonEvent::Error {
+                    interface: Some(interface.clone()),
+                    message: format!("Rule {} failed: {}", name, e.to_string().trim()),
+                });
+            }
+        }
+    }
+
+    metrics::daemon_evaluated(interfaces.len());
+}
+
+/// Put back the MAC an app rule replaced, once its application has exited.
+fn restore(interface: &str, rule: &str, mac: &MacAddress, logger: &MacLogger, journal: &Journal) {
+    let current = match backend::current().get_mac(interface) {
+        Ok(current) if current != *mac => current,
+        _ => return,
+    };
+
+    match apply_mac(interface, mac, false, logger, None, Trigger::Daemon, None).map(|mut transaction| transaction.run_hooks()) {
+        Ok(_) => {
+            println!("[daemon] Rule {} ended, restored {} on {}", rule, mac, interface);
+            record(journal, DaemonEvent::Restored {
+                rule: rule.to_string(),
+                interface: interface.to_string(),
+                old_mac: current,
+                new_mac: mac.clone(),
+            });
+        }
+        Err(e) => {
+            println!("[daemon] Could not restore {} on {}: {}", mac, interface, e);
+            record(journal, DaemonEvent::Error {
+                interface: Some(interface.to_string()),
+                message: format!("Could not restore {} after rule {}: {}", mac, rule, e.to_string().trim()),
+            });
+        }
+    }
+}
@@ -0,0 +1,289 @@
+// src/dbus.rs
+use nix::unistd::{Uid, User};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use zbus::message::Header;
+use zbus::zvariant::Value;
+use zbus::{fdo, Connection};
+use crate::api::{self, ChangeRequest};
+use crate::error::MacError;
+use crate::hooks::Trigger;
+use crate::oui::OUIDatabase;
+use crate::output::progress;
+use crate::status::collect_status;
+use crate::transaction::ChangeSummary;
+
+pub const BUS_NAME: &str = "dev.duocore.Chameleon";
+const OBJECT_PATH: &str = "/dev/duocore/Chameleon";
+
+/// polkit actions, declared in the policy `install` writes.
+const CHANGE_ACTION: &str = "dev.duocore.Chameleon.change-mac";
+const RESTORE_ACTION: &str = "dev.duocore.Chameleon.restore";
+
+/// Lets polkit show the authentication dialog.
+const ALLOW_USER_INTERACTION: u32 = 1;
+
+const BUS_POLICY: &str = "/usr/share/dbus-1/system.d/dev.duocore.Chameleon.conf";
+const ACTIVATION_FILE: &str = "/usr/share/dbus-1/system-services/dev.duocore.Chameleon.service";
+const POLKIT_POLICY: &str = "/usr/share/polkit-1/actions/dev.duocore.Chameleon.policy";
+
+#[zbus::proxy(
+    interface = "org.freedesktop.PolicyKit1.Authority",
+    default_service = "org.freedesktop.PolicyKit1",
+    default_path = "/org/freedesktop/PolicyKit1/Authority"
+)]
+trait Authority {
+    fn check_authorization(
+        &self,
+        subject: &(&str, HashMap<&str, Value<'_>>),
+        action_id: &str,
+        details: &HashMap<&str, &str>,
+        flags: u32,
+        cancellation_id: &str,
+    ) -> zbus::Result<(bool, bool, HashMap<String, String>)>;
+}
+
+#[zbus::proxy(
+    interface = "dev.duocore.Chameleon",
+    default_service = "dev.duocore.Chameleon",
+    default_path = "/dev/duocore/Chameleon"
+)]
+trait Chameleon {
+    fn change_mac(&self, interface: &str, mac: &str, permanent: bool, force: bool, renew: bool) -> zbus::Result<String>;
+
+    fn restore(&self, interface: &str, renew: bool) -> zbus::Result<String>;
+
+    fn get_status(&self, interface: &str) -> zbus::Result<String>;
+}
+
+/// The system bus service. Changes need polkit's approval for the caller;
+/// results are returned as the same JSON `--output json` prints.
+struct Service;
+
+#[zbus::interface(name = "dev.duocore.Chameleon")]
+impl Service {
+    /// `mac` is an address or "random".
+    #[allow(clippy::too_many_arguments)]
+    async fn change_mac(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        interface: String,
+        mac: String,
+        permanent: bool,
+        force: bool,
+        renew: bool,
+    ) -> fdo::Result<String> {
+        let principal = authorize(connection, &header, CHANGE_ACTION).await?;
+        let request = ChangeRequest { random: mac == "random", mac: (mac != "random").then_some(mac), permanent, force, renew };
+        logged("ChangeMac", &interface, &principal, blocking({
+            let (interface, principal) = (interface.clone(), principal.clone());
+            move || api::change(&interface, &request, Trigger::Dbus, &principal)
+        }).await)
+    }
+
+    async fn restore(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        interface: String,
+        renew: bool,
+    ) -> fdo::Result<String> {
+        let principal = authorize(connection, &header, RESTORE_ACTION).await?;
+        logged("Restore", &interface, &principal, blocking({
+            let (interface, principal) = (interface.clone(), principal.clone());
+            move || api::restore(&interface, renew, Trigger::Dbus, &principal)
+        }).await)
+    }
+
+    /// Status of one interface, or of all of them for "".
+    async fn get_status(&self, interface: String) -> fdo::Result<String> {
+        blocking(move || collect_status(Some(interface.as_str()).filter(|name| !name.is_empty()), &OUIDatabase::new()?)).await
+    }
+}
+
+/// Ask polkit whether the caller may do `action`, and name them for the
+/// history if so.
+async fn authorize(connection: &Connection, header: &Header<'_>, action: &str) -> fdo::Result<String> {
+    let sender = header.sender().ok_or_else(|| fdo::Error::AccessDenied("Caller has no bus name".into()))?;
+
+    let subject = ("system-bus-name", HashMap::from([("name", Value::from(sender.as_str()))]));
+    let (authorized, _, _) = AuthorityProxy::new(connection)
+        .await?
+        .check_authorization(&subject, action, &HashMap::new(), ALLOW_USER_INTERACTION, "")
+        .await
+        .map_err(|e| fdo::Error::AccessDenied(format!("Could not ask polkit: {}", e)))?;
+    if !authorized {
+        return Err(fdo::Error::AccessDenied(format!("Not authorized for {}", action)));
+    }
+
+    let uid = fdo::DBusProxy::new(connection).await?.get_connection_unix_user(sender.clone().into()).await?;
+    let user = User::from_uid(Uid::from_raw(uid)).ok().flatten().map(|user| user.name);
+    Ok(format!("dbus:{}", user.unwrap_or_else(|| uid.to_string())))
+}
+
+/// Run blocking change code off the bus thread, returning its result as JSON.
+async fn blocking<T: Serialize>(
+    f: impl FnOnce() -> Result<T, Box<dyn Error>> + Send + 'static,
+) -> fdo::Result<String> {
+    tokio::task::spawn_blocking(move || {
+        let value = f().map_err(to_fdo)?;
+        serde_json::to_string(&value).map_err(|e| fdo::Error::Failed(e.to_string()))
+    })
+    .await
+    .map_err(|e| fdo::Error::Failed(e.to_string()))?
+}
+
+fn logged(method: &str, interface: &str, principal: &str, result: fdo::Result<String>) -> fdo::Result<String> {
+    match &result {
+        Ok(_) => println!("[dbus] {} {} {} -> ok", principal, method, interface),
+        Err(e) => println!("[dbus] {} {} {} -> {}", principal, method, interface, e),
+    }
+    result
+}
+
+fn to_fdo(e: Box<dyn Error>) -> fdo::Error {
+    let message = e.to_string().trim().to_string();
+    match e.downcast_ref::<MacError>() {
+        Some(MacError::PermissionDenied(_)) => fdo::Error::AccessDenied(message),
+        Some(MacError::ValidationFailed(_) | MacError::InvalidFormat(_) | MacError::ParseError(_)) => fdo::Error::InvalidArgs(message),
+        Some(MacError::UnsupportedPlatform(_)) => fdo::Error::NotSupported(message),
+        _ => fdo::Error::Failed(message),
+    }
+}
+
+/// Own the bus name and serve until stopped. D-Bus activation starts this
+/// on the first call.
+pub async fn serve() -> Result<(), Box<dyn Error>> {
+    let _connection = zbus::connection::Builder::system()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, Service)?
+        .build()
+        .await?;
+    println!("[dbus] Serving {} on the system bus", BUS_NAME);
+
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
+
+/// Change a MAC through the service, as the calling user.
+pub async fn change(interface: &str, request: &ChangeRequest) -> Result<ChangeSummary, Box<dyn Error>> {
+    let mac = if request.random { "random" } else { request.mac.as_deref().unwrap_or_default() };
+    let proxy = proxy().await?;
+    let reply = proxy.change_mac(interface, mac, request.permanent, request.force, request.renew).await;
+    Ok(serde_json::from_str(&reply.map_err(from_zbus)?)?)
+}
+
+pub async fn restore(interface: &str, renew: bool) -> Result<ChangeSummary, Box<dyn Error>> {
+    let reply = proxy().await?.restore(interface, renew).await;
+    Ok(serde_json::from_str(&reply.map_err(from_zbus)?)?)
+}
+
+async fn proxy() -> Result<ChameleonProxy<'static>, Box<dyn Error>> {
+    let connection = Connection::system().await?;
+    Ok(ChameleonProxy::new(&connection).await?)
+}
+
+/// Errors from the service, as the matching `MacError`.
+fn from_zbus(e: zbus::Error) -> Box<dyn Error> {
+    Box::new(match fdo::Error::from(e) {
+        fdo::Error::AccessDenied(message) => MacError::PermissionDenied(message),
+        fdo::Error::InvalidArgs(message) => MacError::ValidationFailed(message),
+        fdo::Error::NotSupported(message) => MacError::UnsupportedPlatform(message),
+        fdo::Error::ServiceUnknown(_) | fdo::Error::NameHasNoOwner(_) => MacError::SystemError(format!(
+            "The {} service is not installed; run `sudo chameleon dbus install`", BUS_NAME
+        )),
+        e => MacError::SystemError(e.to_string()),
+    })
+}
+
+/// The bus policy, activation file and polkit actions, pointing at this
+/// binary.
+fn files() -> Result<Vec<(PathBuf, String)>, Box<dyn Error>> {
+    let exe = std::env::current_exe()?;
+    Ok(vec![
+        (PathBuf::from(BUS_POLICY), format!(r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-BUS Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <policy user="root">
+    <allow own="{name}"/>
+  </policy>
+  <policy context="default">
+    <allow send_destination="{name}"/>
+  </policy>
+</busconfig>
+"#, name = BUS_NAME)),
+        (PathBuf::from(ACTIVATION_FILE), format!(
+            "[D-BUS Service]\nName={}\nExec={} dbus serve\nUser=root\n", BUS_NAME, exe.display()
+        )),
+        (PathBuf::from(POLKIT_POLICY), format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE policyconfig PUBLIC "-//freedesktop//DTD PolicyKit Policy Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/PolicyKit/1/policyconfig.dtd">
+<policyconfig>
+  <vendor>Chameleon</vendor>
+  <vendor_url>https://github.com/N0tT1m/chameleon</vendor_url>
+  <action id="{change}">
+    <description>Change the MAC address of a network interface</description>
+    <message>Authentication is required to change the MAC address of a network interface</message>
+    <defaults>
+      <allow_any>auth_admin</allow_any>
+      <allow_inactive>auth_admin</allow_inactive>
+      <allow_active>auth_admin_keep</allow_active>
+    </defaults>
+  </action>
+  <action id="{restore}">
+    <description>Restore the original MAC address of a network interface</description>
+    <message>Authentication is required to restore the MAC address of a network interface</message>
+    <defaults>
+      <allow_any>auth_admin</allow_any>
+      <allow_inactive>auth_admin</allow_inactive>
+      <allow_active>auth_admin_keep</allow_active>
+    </defaults>
+  </action>
+</policyconfig>
+"#, change = CHANGE_ACTION, restore = RESTORE_ACTION)),
+    ])
+}
+
+/// Install the service files and have the bus pick them up.
+pub async fn install(dry_run: bool) -> Result<(), Box<dyn Error>> {
+    for (path, content) in files()? {
+        if dry_run {
+            println!("Would write {}:\n{}", path.display(), content);
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, content)?;
+        println!("Wrote {}", path.display());
+    }
+    if !dry_run {
+        reload_bus().await;
+    }
+    Ok(())
+}
+
+pub async fn uninstall() -> Result<(), Box<dyn Error>> {
+    for (path, _) in files()? {
+        match fs::remove_file(&path) {
+            Ok(()) => println!("Removed {}", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(Box::new(e)),
+        }
+    }
+    reload_bus().await;
+    Ok(())
+}
+
+/// The bus reads policies at startup and on request; polkit watches its
+/// directory by itself.
+async fn reload_bus() {
+    let reloaded = async { fdo::DBusProxy::new(&Connection::system().await?).await?.reload_config().await };
+    if let Err(e) = reloaded.await {
+        progress!("Warning: Could not reload the D-Bus configuration: {}", e);
+    }
+}
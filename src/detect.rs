@@ -0,0 +1,146 @@
+// src/detect.rs
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::error::Error;
+use std::io::Read;
+use std::path::Path;
+use crate::inspect::{self, SpoofSignal};
+use crate::mac::MacAddress;
+use crate::oui::OUIDatabase;
+
+/// One address that was checked, from a local interface or a CAM table line.
+#[derive(Debug, Serialize)]
+pub struct Finding {
+    pub mac: MacAddress,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub permanent_mac: Option<MacAddress>,
+    pub vendor: Option<String>,
+    pub signals: Vec<SpoofSignal>,
+    pub flagged: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>, // The rest of the CAM table line: VLAN, type, port
+}
+
+#[derive(Debug, Serialize)]
+pub struct DetectReport {
+    pub generated_at: DateTime<Utc>,
+    pub source: String,
+    /// Whether unknown prefixes were flagged; needs a downloaded OUI registry.
+    pub vendor_check: bool,
+    pub scanned: usize,
+    pub flagged: usize,
+    /// Multicast and broadcast entries, which belong to no station.
+    pub skipped_group_addresses: usize,
+    pub findings: Vec<Finding>,
+}
+
+impl DetectReport {
+    fn new(source: String, vendor_check: bool, findings: Vec<Finding>, skipped_group_addresses: usize) -> Self {
+        Self {
+            generated_at: Utc::now(),
+            source,
+            vendor_check,
+            scanned: findings.len(),
+            flagged: findings.iter().filter(|finding| finding.flagged).count(),
+            skipped_group_addresses,
+            findings,
+        }
+    }
+
+    /// Drop addresses that raised no signal.
+    pub fn retain_flagged(&mut self) {
+        self.findings.retain(|finding| finding.flagged);
+    }
+}
+
+/// The OUI database, if it is complete enough for unknown prefixes to be
+/// worth flagging.
+fn vendor_database(oui_db: &OUIDatabase) -> Option<&OUIDatabase> {
+    oui_db.is_downloaded().then_some(oui_db)
+}
+
+/// Vendors are still named from the built-in list when the check is off.
+fn vendor_name(oui_db: &OUIDatabase, mac: &MacAddress) -> Option<String> {
+    oui_db.get_vendor(&mac.as_string()).map(|vendor| vendor.name.clone())
+}
+
+/// Check every local interface that has a hardware address.
+pub fn scan_local(oui_db: &OUIDatabase) -> Result<DetectReport, Box<dyn Error>> {
+    let vendor_db = vendor_database(oui_db);
+    let mut inspections = inspect::inspect_all(vendor_db)?;
+    inspections.sort_by(|a, b| a.interface.cmp(&b.interface));
+
+    let findings = inspections
+        .into_iter()
+        .map(|inspection| Finding {
+            flagged: !inspection.signals.is_empty(),
+            vendor: inspection.vendor.or_else(|| vendor_name(oui_db, &inspection.current_mac)),
+            mac: inspection.current_mac,
+            interface: Some(inspection.interface),
+            permanent_mac: inspection.permanent_mac,
+            signals: inspection.signals,
+            line: None,
+            context: None,
+        })
+        .collect();
+
+    Ok(DetectReport::new("local interfaces".into(), vendor_db.is_some(), findings, 0))
+}
+
+/// Check the addresses in a MAC list or switch CAM table export ("-" reads
+/// stdin). Each line contributes the first token that parses as a MAC, in
+/// any notation including Cisco's dotted one; lines without one, such as
+/// headers, are ignored.
+pub fn scan_cam_table(path: &Path, oui_db: &OUIDatabase) -> Result<DetectReport, Box<dyn Error>> {
+    let content = if path == Path::new("-") {
+        let mut content = String::new();
+        std::io::stdin().read_to_string(&mut content)?;
+        content
+    } else {
+        std::fs::read_to_string(path)?
+    };
+
+    let vendor_db = vendor_database(oui_db);
+    let mut findings = Vec::new();
+    let mut skipped = 0;
+
+    for (index, line) in content.lines().enumerate() {
+        let tokens: Vec<&str> = line
+            .split(|c: char| c.is_whitespace() || c == ',' || c == ';' || c == '|')
+            .filter(|token| !token.is_empty())
+            .collect();
+        let Some((position, mac)) = tokens
+            .iter()
+            .enumerate()
+            .find_map(|(position, token)| MacAddress::parse(token).ok().map(|mac| (position, mac)))
+        else {
+            continue;
+        };
+
+        if mac.is_multicast() {
+            skipped += 1;
+            continue;
+        }
+
+        let (vendor, signals) = inspect::classify(&mac, vendor_db);
+        let vendor = vendor.or_else(|| vendor_name(oui_db, &mac));
+        let context: Vec<&str> = tokens.iter().enumerate().filter(|(i, _)| *i != position).map(|(_, token)| *token).collect();
+
+        findings.push(Finding {
+            mac,
+            interface: None,
+            permanent_mac: None,
+            vendor,
+            flagged: !signals.is_empty(),
+            signals,
+            line: Some(index + 1),
+            context: (!context.is_empty()).then(|| context.join(" ")),
+        });
+    }
+
+    Ok(DetectReport::new(path.display().to_string(), vendor_db.is_some(), findings, skipped))
+}
@@ -16,6 +16,11 @@ pub enum MacError {
     SerdeError(serde_json::Error),
     ParseError(String),
     UnsupportedPlatform(String),  // Added this variant
+    RollbackFailed(String),
+    OutsideChangeWindow(String),
+    Locked(String),
+    InterfaceNotFound(String),
+    VerificationFailed(String),
 }
 
 impl fmt::Display for MacError {
@@ -32,12 +37,77 @@ impl fmt::Display for MacError {
             MacError::SerdeError(e) => write!(f, "Serialization error: {}", e),
             MacError::ParseError(msg) => write!(f, "Parse error: {}", msg),
             MacError::UnsupportedPlatform(msg) => write!(f, "Unsupported platform: {}", msg),
+            MacError::RollbackFailed(msg) => write!(f, "Rollback failed: {}", msg),
+            MacError::OutsideChangeWindow(msg) => write!(f, "Outside change window: {}", msg),
+            MacError::Locked(msg) => write!(f, "Interface locked: {}", msg),
+            // Both already read as whole sentences
+            MacError::InterfaceNotFound(msg) => write!(f, "{}", msg),
+            MacError::VerificationFailed(msg) => write!(f, "{}", msg),
         }
     }
 }
 
 impl Error for MacError {}
 
+impl MacError {
+    /// Stable name for scripts, printed as `code` in JSON error output.
+    pub fn code(&self) -> &'static str {
+        match self {
+            MacError::ValidationFailed(_) => "validation_failed",
+            MacError::PermissionDenied(_) => "permission_denied",
+            MacError::SystemError(_) => "system_error",
+            MacError::InvalidFormat(_) => "invalid_format",
+            MacError::NetworkError(_) => "network_error",
+            MacError::DatabaseError(_) => "database_error",
+            MacError::VendorNotFound(_) => "vendor_not_found",
+            MacError::IoError(_) => "io_error",
+            MacError::SerdeError(_) => "serialization_error",
+            MacError::ParseError(_) => "parse_error",
+            MacError::UnsupportedPlatform(_) => "unsupported_platform",
+            MacError::RollbackFailed(_) => "rollback_failed",
+            MacError::OutsideChangeWindow(_) => "outside_change_window",
+            MacError::Locked(_) => "locked",
+            MacError::InterfaceNotFound(_) => "interface_not_found",
+            MacError::VerificationFailed(_) => "verification_failed",
+        }
+    }
+
+    /// The process exit code, documented in the README. 1 is left for
+    /// errors without a type and 2 for usage errors, which clap reports.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            MacError::SystemError(_) => 1,
+            MacError::PermissionDenied(_) => 3,
+            MacError::InterfaceNotFound(_) => 4,
+            MacError::VerificationFailed(_) => 5,
+            MacError::ValidationFailed(_) => 6,
+            MacError::InvalidFormat(_) => 7,
+            MacError::ParseError(_) => 8,
+            MacError::UnsupportedPlatform(_) => 9,
+            MacError::NetworkError(_) => 10,
+            MacError::DatabaseError(_) => 11,
+            MacError::VendorNotFound(_) => 12,
+            MacError::IoError(_) => 13,
+            MacError::SerdeError(_) => 14,
+            MacError::RollbackFailed(_) => 15,
+            MacError::OutsideChangeWindow(_) => 16,
+            MacError::Locked(_) => 17,
+        }
+    }
+}
+
+/// The code and exit code for any error. Errors that are not a MacError
+/// exit with 1.
+pub fn classify(err: &(dyn Error + 'static)) -> (&'static str, i32) {
+    if let Some(err) = err.downcast_ref::<MacError>() {
+        (err.code(), err.exit_code())
+    } else if err.is::<std::io::Error>() {
+        ("io_error", 13)
+    } else {
+        ("error", 1)
+    }
+}
+
 impl From<ParseIntError> for MacError {
     fn from(err: ParseIntError) -> Self {
         MacError::ParseError(err.to_string())
@@ -56,20 +126,32 @@ impl From<serde_json::Error> for MacError {
     }
 }
 
-impl From<&str> for MacError {
-    fn from(s: &str) -> Self {
-        MacError::ValidationFailed(s.to_string())
+#[cfg(unix)]
+impl From<nix::errno::Errno> for MacError {
+    fn from(err: nix::errno::Errno) -> Self {
+        MacError::IoError(err.into())
     }
 }
 
-impl From<String> for MacError {
-    fn from(s: String) -> Self {
-        MacError::ValidationFailed(s)
+#[cfg(windows)]
+impl From<windows::core::Error> for MacError {
+    fn from(err: windows::core::Error) -> Self {
+        if err.code() == windows::Win32::Foundation::E_ACCESSDENIED {
+            return MacError::PermissionDenied(err.message());
+        }
+        MacError::SystemError(err.to_string())
     }
 }
 
+// Keeps the variant when the boxed error was a MacError all along
 impl From<Box<dyn Error>> for MacError {
     fn from(err: Box<dyn Error>) -> Self {
-        MacError::SystemError(err.to_string())
+        match err.downcast::<MacError>() {
+            Ok(err) => *err,
+            Err(err) => match err.downcast::<std::io::Error>() {
+                Ok(err) => MacError::IoError(*err),
+                Err(err) => MacError::SystemError(err.to_string()),
+            },
+        }
     }
 }
\ No newline at end of file
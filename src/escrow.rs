@@ -0,0 +1,169 @@
+// src/escrow.rs
+use chrono::{DateTime, Utc};
+use ed25519_dalek::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::config::get_original_mac;
+use crate::error::MacError;
+use crate::network::{self, NetworkCard};
+
+const ESCROW_VERSION: u32 = 1;
+
+/// Hardware and assigned MAC of a single interface at export time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterfaceEscrow {
+    pub interface: String,
+    pub original_mac: String,
+    pub current_mac: String,
+    pub spoofed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EscrowRecord {
+    pub version: u32,
+    pub hostname: String,
+    pub generated_at: DateTime<Utc>,
+    pub interfaces: Vec<InterfaceEscrow>,
+}
+
+/// An escrow record plus an optional Ed25519 signature over its JSON encoding.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignedEscrow {
+    pub record: EscrowRecord,
+    pub signature: Option<String>,
+    pub public_key: Option<String>,
+}
+
+impl EscrowRecord {
+    /// Snapshot every interface with a hardware address on this machine.
+    pub fn collect() -> Result<Self, Box<dyn Error>> {
+        let hostname = hostname::get()?.to_string_lossy().to_string();
+        let mut interfaces = Vec::new();
+
+        for card in NetworkCard::list()? {
+            // Interfaces without a hardware address, such as tunnels, have nothing to escrow
+            let Ok(current_mac) = network::get_current_mac(&card.interface) else {
+                continue;
+            };
+
+            // Without a saved original the interface has never been touched,
+            // so the current address is the hardware one
+            let original_mac = get_original_mac(&card.interface)?
+                .unwrap_or_else(|| current_mac.clone());
+            let spoofed = original_mac != current_mac;
+
+            interfaces.push(InterfaceEscrow {
+                interface: card.interface,
+                original_mac: original_mac.to_string(),
+                current_mac: current_mac.to_string(),
+                spoofed,
+            });
+        }
+
+        interfaces.sort_by(|a, b| a.interface.cmp(&b.interface));
+
+        Ok(Self {
+            version: ESCROW_VERSION,
+            hostname,
+            generated_at: Utc::now(),
+            interfaces,
+        })
+    }
+
+    fn signing_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+}
+
+impl SignedEscrow {
+    pub fn unsigned(record: EscrowRecord) -> Self {
+        Self { record, signature: None, public_key: None }
+    }
+
+    /// Sign the record with an Ed25519 private key in PKCS#8 PEM format.
+    pub fn sign(record: EscrowRecord, key_path: &Path) -> Result<Self, Box<dyn Error>> {
+        let pem = fs::read_to_string(key_path)?;
+        let key = SigningKey::from_pkcs8_pem(&pem).map_err(|e| MacError::InvalidFormat(
+            format!("Could not read signing key {}: {}", key_path.display(), e)
+        ))?;
+
+        let signature = key.sign(&record.signing_bytes()?);
+
+        Ok(Self {
+            record,
+            signature: Some(hex::encode(signature.to_bytes())),
+            public_key: Some(hex::encode(key.verifying_key().to_bytes())),
+        })
+    }
+
+    /// Check the signature against a trusted Ed25519 public key in PEM format.
+    /// The embedded `public_key` is informational only and never trusted.
+    pub fn verify(&self, key_path: &Path) -> Result<(), Box<dyn Error>> {
+        let pem = fs::read_to_string(key_path)?;
+        let key = VerifyingKey::from_public_key_pem(&pem).map_err(|e| MacError::InvalidFormat(
+            format!("Could not read public key {}: {}", key_path.display(), e)
+        ))?;
+
+        let signature_hex = self.signature.as_ref()
+            .ok_or_else(|| MacError::ValidationFailed("Escrow record is not signed".into()))?;
+        let signature_bytes: [u8; 64] = hex::decode(signature_hex)
+            .map_err(|e| MacError::InvalidFormat(format!("Invalid signature encoding: {}", e)))?
+            .try_into()
+            .map_err(|_| MacError::InvalidFormat("Signature must be 64 bytes".into()))?;
+
+        key.verify(&self.record.signing_bytes()?, &Signature::from_bytes(&signature_bytes))
+            .map_err(|_| MacError::ValidationFailed(
+                format!("Signature verification failed for escrow record from {}", self.record.hostname)
+            ))?;
+
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+        let escrow: SignedEscrow = serde_json::from_str(&content)?;
+
+        if escrow.record.version > ESCROW_VERSION {
+            return Err(Box::new(MacError::InvalidFormat(
+                format!("Unsupported escrow record version {}", escrow.record.version)
+            )));
+        }
+
+        Ok(escrow)
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Store the record in the local escrow directory, replacing any earlier
+    /// record for the same host.
+    pub fn import(&self) -> Result<PathBuf, Box<dyn Error>> {
+        let escrow_dir = dirs::config_dir()
+            .ok_or_else(|| MacError::SystemError("Could not find config directory".into()))?
+            .join("mac_changer")
+            .join("escrow");
+
+        fs::create_dir_all(&escrow_dir)?;
+
+        let file_name: String = self.record.hostname
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+            .collect();
+        let path = escrow_dir.join(format!("{}.json", file_name));
+
+        if let Ok(existing) = Self::load(&path)
+            && existing.record.generated_at > self.record.generated_at
+        {
+            return Err(Box::new(MacError::ValidationFailed(
+                format!("A newer escrow record for {} is already stored", self.record.hostname)
+            )));
+        }
+
+        fs::write(&path, self.to_json()?)?;
+        Ok(path)
+    }
+}
@@ -1,26 +1,119 @@
 use std::collections::HashSet;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
 use std::fs;
+use crate::error::MacError;
+use crate::mac::{normalize_prefix, MacAddress, MacFormat};
+use crate::output::progress;
+use crate::settings::Settings;
+
+/// How many MACs a generator may produce before giving up on finding one the
+/// filter allows.
+const MAX_ATTEMPTS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum FilterList {
+    Whitelist,
+    Blacklist,
+}
+
+impl fmt::Display for FilterList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FilterList::Whitelist => write!(f, "whitelist"),
+            FilterList::Blacklist => write!(f, "blacklist"),
+        }
+    }
+}
+
+/// What happens to a MAC the lists reject, from `filter_mode` in config.toml.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterMode {
+    #[default]
+    Enforce,
+    Warn, // Apply it anyway, with a warning
+    Off,
+}
+
+/// File formats for `filter import` and `filter export`.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum FilterFormat {
+    /// One prefix or MAC per line; blank lines and `#` comments are ignored
+    Lines,
+    /// {"whitelist": [...], "blacklist": [...]}
+    Json,
+}
+
+impl FilterFormat {
+    /// JSON for `.json` files, one entry per line otherwise.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => FilterFormat::Json,
+            _ => FilterFormat::Lines,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FilterFile {
+    #[serde(default)]
+    whitelist: Vec<String>,
+    #[serde(default)]
+    blacklist: Vec<String>,
+}
+
+/// Canonical form of a filter entry: "XX:XX:XX" for a vendor prefix or
+/// "XX:XX:XX:XX:XX:XX" for a single address.
+pub fn parse_entry(entry: &str) -> Result<String, MacError> {
+    if let Ok(mac) = entry.parse::<MacAddress>() {
+        return Ok(mac.with_format(MacFormat::Colon).to_string().to_uppercase());
+    }
+
+    let entry = entry.trim();
+    let groups: Vec<&str> = entry.split([':', '-', '.']).collect();
+    let valid = match groups.len() {
+        3 => groups.iter().all(|group| (1..=2).contains(&group.len())),
+        1 => entry.len() == 6,
+        _ => false,
+    } && groups.iter().all(|group| group.chars().all(|c| c.is_ascii_hexdigit()));
+
+    if !valid {
+        return Err(MacError::InvalidFormat(format!(
+            "Invalid filter entry '{}'. Use a prefix (XX:XX:XX) or a full MAC (XX:XX:XX:XX:XX:XX)", entry
+        )));
+    }
+
+    Ok(normalize_prefix(entry))
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MacFilter {
     whitelist: HashSet<String>,
     blacklist: HashSet<String>,
     config_path: PathBuf,
+    #[serde(skip)]
+    mode: FilterMode,
 }
 
 impl MacFilter {
+    fn config_path() -> PathBuf {
+        dirs::config_dir().unwrap_or_default().join("mac_changer").join("filters.json")
+    }
+
     pub fn new() -> Self {
-        let config_path = dirs::config_dir()
-            .unwrap_or_default()
-            .join("mac_changer")
-            .join("filters.json");
+        let config_path = Self::config_path();
+
+        // A broken config.toml falls back to enforcing
+        let mode = Settings::load().ok().and_then(|settings| settings.filter_mode).unwrap_or_default();
 
         let mut filter = Self {
             whitelist: HashSet::new(),
             blacklist: HashSet::new(),
             config_path,
+            mode,
         };
 
         filter.load_filters();
@@ -36,6 +129,23 @@ impl MacFilter {
         }
     }
 
+    /// Fails if `filters.json` does not parse or holds an entry `filter add`
+    /// would refuse, both of which `new` passes over.
+    pub fn check_saved() -> Result<(), MacError> {
+        let path = Self::config_path();
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let file: FilterFile = serde_json::from_str(&content)
+            .map_err(|e| MacError::ParseError(format!("{} is not valid: {}", path.display(), e)))?;
+        for entry in file.whitelist.iter().chain(&file.blacklist) {
+            parse_entry(entry)?;
+        }
+        Ok(())
+    }
+
     pub fn save_filters(&self) -> Result<(), Box<dyn std::error::Error>> {
         if let Some(parent) = self.config_path.parent() {
             fs::create_dir_all(parent)?;
@@ -46,29 +156,206 @@ impl MacFilter {
         Ok(())
     }
 
+    fn list_mut(&mut self, list: FilterList) -> &mut HashSet<String> {
+        match list {
+            FilterList::Whitelist => &mut self.whitelist,
+            FilterList::Blacklist => &mut self.blacklist,
+        }
+    }
+
     pub fn add_to_whitelist(&mut self, mac_prefix: &str) -> Result<(), Box<dyn std::error::Error>> {
-        self.whitelist.insert(mac_prefix.to_uppercase());
+        self.whitelist.insert(parse_entry(mac_prefix)?);
         self.save_filters()?;
         Ok(())
     }
 
     pub fn add_to_blacklist(&mut self, mac_prefix: &str) -> Result<(), Box<dyn std::error::Error>> {
-        self.blacklist.insert(mac_prefix.to_uppercase());
+        self.blacklist.insert(parse_entry(mac_prefix)?);
         self.save_filters()?;
         Ok(())
     }
 
-    pub fn is_allowed(&self, mac: &str) -> bool {
-        let prefix = &mac[0..8].to_uppercase();
+    /// Sorted entries of one list.
+    pub fn entries(&self, list: FilterList) -> Vec<String> {
+        let set = match list {
+            FilterList::Whitelist => &self.whitelist,
+            FilterList::Blacklist => &self.blacklist,
+        };
+        let mut entries: Vec<String> = set.iter().cloned().collect();
+        entries.sort();
+        entries
+    }
+
+    /// Remove an entry from the given list, or from both. Returns the lists
+    /// it was removed from.
+    pub fn remove(&mut self, entry: &str, list: Option<FilterList>) -> Result<Vec<FilterList>, Box<dyn Error>> {
+        let entry = parse_entry(entry)?;
+        let lists = match list {
+            Some(list) => vec![list],
+            None => vec![FilterList::Whitelist, FilterList::Blacklist],
+        };
+
+        let removed: Vec<FilterList> = lists.into_iter()
+            .filter(|&list| self.list_mut(list).remove(&entry))
+            .collect();
+
+        if !removed.is_empty() {
+            self.save_filters()?;
+        }
+        Ok(removed)
+    }
+
+    /// Empty the given list, or both. Returns how many entries were dropped.
+    pub fn clear(&mut self, list: Option<FilterList>) -> Result<usize, Box<dyn Error>> {
+        let lists = match list {
+            Some(list) => vec![list],
+            None => vec![FilterList::Whitelist, FilterList::Blacklist],
+        };
+
+        let mut cleared = 0;
+        for list in lists {
+            let set = self.list_mut(list);
+            cleared += set.len();
+            set.clear();
+        }
+
+        self.save_filters()?;
+        Ok(cleared)
+    }
+
+    /// Add every entry in `path`. Nothing is imported unless every entry is
+    /// valid. Line files need `list`; JSON files name their own lists.
+    /// Returns how many entries were new.
+    pub fn import(&mut self, path: &Path, format: FilterFormat, list: Option<FilterList>) -> Result<usize, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+
+        let file = match format {
+            FilterFormat::Json => serde_json::from_str::<FilterFile>(&content)
+                .map_err(|e| MacError::InvalidFormat(format!("Invalid filter file {}: {}", path.display(), e)))?,
+            FilterFormat::Lines => {
+                let list = list.ok_or_else(|| MacError::ValidationFailed(
+                    "Choose the list to import into with --list whitelist|blacklist".into()
+                ))?;
+                let entries: Vec<String> = content.lines()
+                    .map(|line| line.split('#').next().unwrap_or_default().trim())
+                    .filter(|line| !line.is_empty())
+                    .map(String::from)
+                    .collect();
+
+                match list {
+                    FilterList::Whitelist => FilterFile { whitelist: entries, ..Default::default() },
+                    FilterList::Blacklist => FilterFile { blacklist: entries, ..Default::default() },
+                }
+            }
+        };
+
+        let mut invalid = Vec::new();
+        let mut parsed = Vec::new();
+        for (list, entries) in [(FilterList::Whitelist, &file.whitelist), (FilterList::Blacklist, &file.blacklist)] {
+            for entry in entries {
+                match parse_entry(entry) {
+                    Ok(entry) => parsed.push((list, entry)),
+                    Err(_) => invalid.push(entry.as_str()),
+                }
+            }
+        }
+
+        if !invalid.is_empty() {
+            return Err(Box::new(MacError::InvalidFormat(format!(
+                "{} invalid entr{} in {}: {}",
+                invalid.len(), if invalid.len() == 1 { "y" } else { "ies" }, path.display(), invalid.join(", ")
+            ))));
+        }
+
+        let added = parsed.into_iter()
+            .filter(|(list, entry)| self.list_mut(*list).insert(entry.clone()))
+            .count();
+
+        self.save_filters()?;
+        Ok(added)
+    }
+
+    /// Write one list as lines, or both lists as JSON. Returns how many
+    /// entries were written.
+    pub fn export(&self, path: &Path, format: FilterFormat, list: Option<FilterList>) -> Result<usize, Box<dyn Error>> {
+        let (content, count) = match format {
+            FilterFormat::Json => {
+                let file = FilterFile {
+                    whitelist: if list != Some(FilterList::Blacklist) { self.entries(FilterList::Whitelist) } else { Vec::new() },
+                    blacklist: if list != Some(FilterList::Whitelist) { self.entries(FilterList::Blacklist) } else { Vec::new() },
+                };
+                (serde_json::to_string_pretty(&file)?, file.whitelist.len() + file.blacklist.len())
+            }
+            FilterFormat::Lines => {
+                let list = list.ok_or_else(|| MacError::ValidationFailed(
+                    "Choose the list to export with --list whitelist|blacklist, or export JSON".into()
+                ))?;
+                let entries = self.entries(list);
+                (entries.iter().map(|entry| format!("{}\n", entry)).collect(), entries.len())
+            }
+        };
 
-        if !self.whitelist.is_empty() {
-            return self.whitelist.contains(prefix);
+        fs::write(path, content)?;
+        Ok(count)
+    }
+
+    /// Explain why `mac` may not be used, if it may not and `filter_mode`
+    /// enforces the lists.
+    pub fn check(&self, mac: &MacAddress) -> Result<(), MacError> {
+        match (self.mode, self.check_lists(mac)) {
+            (FilterMode::Warn, Err(e)) => {
+                progress!("Warning: {} (filter_mode is warn)", e);
+                Ok(())
+            }
+            (FilterMode::Off, _) => Ok(()),
+            (_, result) => result,
         }
+    }
 
-        if !self.blacklist.is_empty() {
-            return !self.blacklist.contains(prefix);
+    /// Entries match either the vendor prefix or the whole address.
+    fn check_lists(&self, mac: &MacAddress) -> Result<(), MacError> {
+        let prefix = mac.oui();
+        let full = mac.with_format(MacFormat::Colon).to_string().to_uppercase();
+        let listed = |set: &HashSet<String>| set.contains(&prefix) || set.contains(&full);
+
+        if !self.whitelist.is_empty() && !listed(&self.whitelist) {
+            return Err(MacError::ValidationFailed(format!(
+                "{} is not allowed: neither it nor prefix {} is on the whitelist (use --force to apply it anyway)", mac, prefix
+            )));
         }
 
-        true
+        if listed(&self.blacklist) {
+            return Err(MacError::ValidationFailed(format!(
+                "{} is not allowed: it is on the blacklist (use --force to apply it anyway)", mac
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Call `generate` until it produces a MAC the filter allows. A generator
+    /// that returns the same MAC twice is fixed, so its rejection is final.
+    pub fn pick_allowed(
+        &self,
+        mut generate: impl FnMut() -> Result<MacAddress, Box<dyn Error>>,
+    ) -> Result<MacAddress, Box<dyn Error>> {
+        let mut previous: Option<MacAddress> = None;
+
+        for _ in 0..MAX_ATTEMPTS {
+            let mac = generate()?;
+            let rejection = match self.check(&mac) {
+                Ok(()) => return Ok(mac),
+                Err(e) => e,
+            };
+
+            if previous.as_ref() == Some(&mac) {
+                return Err(Box::new(rejection));
+            }
+            previous = Some(mac);
+        }
+
+        Err(Box::new(MacError::ValidationFailed(format!(
+            "No allowed MAC found in {} attempts; check the whitelist and blacklist", MAX_ATTEMPTS
+        ))))
     }
-}
\ No newline at end of file
+}
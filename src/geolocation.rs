@@ -3,7 +3,9 @@ use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::collections::HashMap;
 use crate::error::MacError;
-use crate::oui::OUIDatabase;
+use rand::seq::SliceRandom;
+use crate::mac::MacAddress;
+use crate::oui::{OUIDatabase, VendorInfo};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GeoLocation {
@@ -35,7 +37,7 @@ impl GeoLocationService {
 
         // Look up vendor info from OUI database
         let vendor_info = oui_db.get_vendor(prefix)
-            .ok_or_else(|| MacError::ValidationFailed(
+            .ok_or_else(|| MacError::VendorNotFound(
                 format!("No vendor found for prefix {}", prefix)
             ))?;
 
@@ -50,22 +52,24 @@ impl GeoLocationService {
         Ok(location)
     }
 
-    pub fn suggest_mac_for_location(&self, country: &str, oui_db: &OUIDatabase) -> Option<String> {
-        // Find vendors for the specified country
-        let vendors = oui_db.vendors_by_country(country);
-
-        if vendors.is_empty() {
-            return None;
+    /// A random MAC under a randomly chosen prefix registered in `country`,
+    /// along with the vendor it impersonates.
+    pub fn suggest_mac_for_location<'a>(&self, country: &str, oui_db: &'a OUIDatabase) -> Result<(MacAddress, &'a VendorInfo), MacError> {
+        if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(MacError::InvalidFormat(
+                format!("Invalid country code '{}'. Use a two-letter code such as US", country)
+            ));
         }
 
-        // Use the first vendor found
-        let vendor = vendors[0];
+        let vendors = oui_db.vendors_by_country(country);
+        let vendor = vendors.choose(&mut rand::thread_rng()).ok_or_else(|| MacError::VendorNotFound(
+            format!("No vendors registered in {}; run `chameleon oui update` for the full registry", country.to_uppercase())
+        ))?;
 
-        // Generate random suffix
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-        let random_suffix: u32 = rng.gen_range(0..0xFFFFFF);
+        let mac = vendor.random_mac().ok_or_else(|| MacError::VendorNotFound(
+            format!("Invalid prefix {} for {}", vendor.prefix, vendor.name)
+        ))?;
 
-        Some(format!("{}:{:06X}", vendor.prefix, random_suffix))
+        Ok((mac, vendor))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,209 @@
+// src/history.rs
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use crate::error::MacError;
+use crate::logger::MacChange;
+use crate::mac::MacAddress;
+use crate::oui::OUIDatabase;
+
+/// How many vendors `history stats` lists.
+const TOP_VENDORS: usize = 10;
+
+/// File formats for `history export`.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+pub fn export(history: &[MacChange], format: ExportFormat) -> Result<String, Box<dyn Error>> {
+    Ok(match format {
+        ExportFormat::Json => serde_json::to_string_pretty(history)? + "\n",
+        ExportFormat::Csv => to_csv(history),
+    })
+}
+
+/// Which earlier MAC `rollback` goes back to.
+#[derive(Debug, Clone, Copy)]
+pub enum RollbackTarget {
+    Steps(usize),          // The MAC in place this many changes ago
+    At(DateTime<Utc>),     // The MAC in place at this time
+}
+
+/// The change that set the MAC to roll back to, from one interface's
+/// history in the order it was logged.
+pub fn rollback_target(changes: &[MacChange], target: RollbackTarget) -> Result<&MacChange, MacError> {
+    let found = match target {
+        RollbackTarget::Steps(0) => {
+            return Err(MacError::ValidationFailed("--steps must be at least 1".into()));
+        }
+        RollbackTarget::Steps(steps) => changes.len().checked_sub(steps + 1).map(|index| &changes[index]),
+        RollbackTarget::At(time) => changes.iter().rfind(|change| change.timestamp <= time),
+    };
+
+    found.ok_or_else(|| MacError::ValidationFailed(match target {
+        RollbackTarget::Steps(_) => format!(
+            "Only {} earlier change(s) recorded; use --restore to go back to the original MAC",
+            changes.len().saturating_sub(1)
+        ),
+        RollbackTarget::At(time) => format!(
+            "No change recorded before {}; use --restore to go back to the original MAC",
+            time.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+        ),
+    }))
+}
+
+fn to_csv(history: &[MacChange]) -> String {
+    let mut csv = String::from("timestamp,interface,old_mac,new_mac,geo_location,permanent,profile,trigger,hostname,dhcp_client_id,principal\n");
+
+    for change in history {
+        let fields = [
+            change.timestamp.to_rfc3339(),
+            change.interface.clone(),
+            change.old_mac.to_string(),
+            change.new_mac.to_string(),
+            change.geo_location.clone().unwrap_or_default(),
+            change.permanent.to_string(),
+            change.profile.clone().unwrap_or_default(),
+            change.trigger.map(|trigger| trigger.to_string()).unwrap_or_default(),
+            change.hostname.clone().unwrap_or_default(),
+            change.dhcp_client_id.clone().unwrap_or_default(),
+            change.principal.clone().unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+/// Quoted only when it holds a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct InterfaceStats {
+    pub interface: String,
+    pub changes: usize,
+    pub average_interval_secs: Option<i64>, // None with fewer than two changes
+}
+
+#[derive(Debug, Serialize)]
+pub struct VendorCount {
+    pub vendor: String,
+    pub changes: usize,
+}
+
+/// Summary of a stretch of history for `history stats`.
+#[derive(Debug, Serialize)]
+pub struct HistoryStats {
+    pub changes: usize,
+    pub first: Option<DateTime<Utc>>,
+    pub last: Option<DateTime<Utc>>,
+    pub average_interval_secs: Option<i64>,
+    pub interfaces: Vec<InterfaceStats>,
+    pub vendors: Vec<VendorCount>, // Vendors of the new MACs, most used first
+}
+
+impl HistoryStats {
+    /// `history` is expected oldest first, as `MacLogger` returns it.
+    pub fn collect(history: &[MacChange], oui_db: &OUIDatabase) -> Self {
+        let mut by_interface: BTreeMap<&str, Vec<DateTime<Utc>>> = BTreeMap::new();
+        let mut vendor_counts: HashMap<String, usize> = HashMap::new();
+
+        for change in history {
+            by_interface.entry(&change.interface).or_default().push(change.timestamp);
+            *vendor_counts.entry(vendor_name(&change.new_mac, oui_db)).or_default() += 1;
+        }
+
+        let interfaces = by_interface
+            .into_iter()
+            .map(|(interface, timestamps)| InterfaceStats {
+                interface: interface.to_string(),
+                changes: timestamps.len(),
+                average_interval_secs: average_interval(&timestamps),
+            })
+            .collect();
+
+        let mut vendors: Vec<VendorCount> = vendor_counts
+            .into_iter()
+            .map(|(vendor, changes)| VendorCount { vendor, changes })
+            .collect();
+        vendors.sort_by(|a, b| b.changes.cmp(&a.changes).then_with(|| a.vendor.cmp(&b.vendor)));
+        vendors.truncate(TOP_VENDORS);
+
+        let timestamps: Vec<DateTime<Utc>> = history.iter().map(|change| change.timestamp).collect();
+
+        Self {
+            changes: history.len(),
+            first: timestamps.first().copied(),
+            last: timestamps.last().copied(),
+            average_interval_secs: average_interval(&timestamps),
+            interfaces,
+            vendors,
+        }
+    }
+
+    pub fn print(&self) {
+        let (Some(first), Some(last)) = (self.first, self.last) else {
+            println!("No history recorded");
+            return;
+        };
+
+        println!("{} change(s) from {} to {}", self.changes,
+                 first.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                 last.with_timezone(&Local).format("%Y-%m-%d %H:%M"));
+        if let Some(secs) = self.average_interval_secs {
+            println!("Average time between changes: {}", format_interval(secs));
+        }
+
+        println!();
+        println!("{:<16} {:<8} AVERAGE INTERVAL", "INTERFACE", "CHANGES");
+        for interface in &self.interfaces {
+            println!("{:<16} {:<8} {}", interface.interface, interface.changes,
+                     interface.average_interval_secs.map(format_interval).unwrap_or_else(|| "-".into()));
+        }
+
+        println!();
+        println!("{:<40} CHANGES", "VENDOR");
+        for vendor in &self.vendors {
+            println!("{:<40} {}", vendor.vendor, vendor.changes);
+        }
+    }
+}
+
+/// Randomized addresses have no registered vendor, so they are counted
+/// together rather than as unknown.
+fn vendor_name(mac: &MacAddress, oui_db: &OUIDatabase) -> String {
+    if let Some(vendor) = oui_db.get_vendor(&mac.oui()) {
+        return vendor.name.clone();
+    }
+    if mac.is_locally_administered() {
+        "(locally administered)".into()
+    } else {
+        "(unknown)".into()
+    }
+}
+
+fn average_interval(timestamps: &[DateTime<Utc>]) -> Option<i64> {
+    let (first, last) = (timestamps.first()?, timestamps.last()?);
+    let gaps = timestamps.len() as i64 - 1;
+    (gaps > 0).then(|| (*last - *first).num_seconds() / gaps)
+}
+
+pub fn format_interval(secs: i64) -> String {
+    match secs {
+        s if s >= 86400 => format!("{}d {}h", s / 86400, s % 86400 / 3600),
+        s if s >= 3600 => format!("{}h {}m", s / 3600, s % 3600 / 60),
+        s if s >= 60 => format!("{}m {}s", s / 60, s % 60),
+        s => format!("{}s", s),
+    }
+}
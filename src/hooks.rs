@@ -0,0 +1,311 @@
+// src/hooks.rs
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use crate::error::MacError;
+use crate::output::progress;
+use crate::settings::Settings;
+
+/// How long a hook may run before it is killed or abandoned.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What caused a MAC change.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trigger {
+    Manual,  // A command run by the user
+    Rule,    // A matching rule applied by a command
+    Daemon,  // A rule applied by the daemon
+    Watch,   // --watch putting back a reverted MAC
+    Chaos,   // Chaos mode rotation
+    Rollback, // `rollback` re-applying a MAC from the history
+    Api,     // A request to `serve`
+    Dbus,    // A call to the D-Bus service
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Trigger::Manual => "manual",
+            Trigger::Rule => "rule",
+            Trigger::Daemon => "daemon",
+            Trigger::Watch => "watch",
+            Trigger::Chaos => "chaos",
+            Trigger::Rollback => "rollback",
+            Trigger::Api => "api",
+            Trigger::Dbus => "dbus",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Which outcomes a hook runs after.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum HookOn {
+    #[default]
+    Success,
+    Failure,
+    Any,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookAction {
+    /// Run through the shell with the event in CHAMELEON_* variables
+    Command(String),
+    /// POST the event as JSON
+    Webhook(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hook {
+    pub name: String,
+    #[serde(default)]
+    pub on: HookOn,
+    #[serde(default)]
+    pub interface: Option<String>, // Only for changes to this interface
+    #[serde(flatten)]
+    pub action: HookAction,
+}
+
+/// A finished MAC change attempt, as passed to hooks.
+#[derive(Debug, Clone, Serialize)]
+pub struct HookEvent {
+    pub timestamp: DateTime<Utc>,
+    pub interface: String,
+    pub old_mac: String,
+    pub new_mac: String,
+    pub trigger: Trigger,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+impl HookEvent {
+    pub fn new(interface: &str, old_mac: &str, new_mac: &str, trigger: Trigger, error: Option<String>) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            interface: interface.to_string(),
+            old_mac: old_mac.to_string(),
+            new_mac: new_mac.to_string(),
+            trigger,
+            success: error.is_none(),
+            error,
+        }
+    }
+
+    fn env(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("CHAMELEON_INTERFACE", self.interface.clone()),
+            ("CHAMELEON_OLD_MAC", self.old_mac.clone()),
+            ("CHAMELEON_NEW_MAC", self.new_mac.clone()),
+            ("CHAMELEON_TRIGGER", self.trigger.to_string()),
+            ("CHAMELEON_RESULT", if self.success { "success" } else { "failure" }.to_string()),
+            ("CHAMELEON_ERROR", self.error.clone().unwrap_or_default()),
+        ]
+    }
+}
+
+impl Hook {
+    fn matches(&self, event: &HookEvent) -> bool {
+        let outcome = match self.on {
+            HookOn::Success => event.success,
+            HookOn::Failure => !event.success,
+            HookOn::Any => true,
+        };
+        outcome && self.interface.as_deref().is_none_or(|interface| interface == event.interface)
+    }
+
+    pub fn describe(&self) -> String {
+        match &self.action {
+            HookAction::Command(command) => format!("command {}", command),
+            HookAction::Webhook(url) => format!("webhook {}", url),
+        }
+    }
+
+    /// Run the hook for `event`, waiting at most HOOK_TIMEOUT.
+    pub fn run(&self, event: &HookEvent) -> Result<(), Box<dyn Error>> {
+        match &self.action {
+            HookAction::Command(command) => run_command(command, event),
+            HookAction::Webhook(url) => post_webhook(url, event),
+        }
+    }
+}
+
+fn run_command(command: &str, event: &HookEvent) -> Result<(), Box<dyn Error>> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+
+    // stdout carries our own output, which may be JSON for a script
+    let mut child = shell.arg(command).envs(event.env()).stdin(Stdio::null()).stdout(std::io::stderr()).spawn()?;
+    let started = Instant::now();
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return if status.success() {
+                Ok(())
+            } else {
+                Err(Box::new(MacError::SystemError(format!("exited with {}", status))))
+            };
+        }
+        if started.elapsed() > HOOK_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(Box::new(MacError::SystemError(format!("killed after {:?}", HOOK_TIMEOUT))));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Changes happen in synchronous code, often inside the async runtime, so
+/// the request gets a runtime of its own on a separate thread.
+fn post_webhook(url: &str, event: &HookEvent) -> Result<(), Box<dyn Error>> {
+    let url = url.to_string();
+    let body = serde_json::to_vec(event)?;
+
+    std::thread::spawn(move || -> Result<(), String> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        runtime.block_on(async {
+            let response = reqwest::Client::builder()
+                .timeout(HOOK_TIMEOUT)
+                .build()
+                .map_err(|e| e.to_string())?
+                .post(&url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            if response.status().is_success() {
+                Ok(())
+            } else {
+                Err(format!("server answered {}", response.status()))
+            }
+        })
+    })
+    .join()
+    .map_err(|_| MacError::SystemError("webhook thread panicked".into()))?
+    .map_err(|e| Box::new(MacError::NetworkError(e)) as Box<dyn Error>)
+}
+
+/// Hooks registered in `hooks.json`, run in order after MAC changes,
+/// followed by those defined in config.toml.
+#[derive(Debug)]
+pub struct HookManager {
+    hooks: Vec<Hook>,
+    configured: Vec<Hook>, // From config.toml, which `hooks` commands leave alone
+    config_path: PathBuf,
+}
+
+impl HookManager {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        let config_path = dirs::config_dir()
+            .ok_or("Could not find config directory")?
+            .join("mac_changer")
+            .join("hooks.json");
+
+        let hooks = match fs::read_to_string(&config_path) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self { hooks, configured: Settings::load()?.hooks, config_path })
+    }
+
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = self.config_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.config_path, serde_json::to_string_pretty(&self.hooks)?)?;
+        Ok(())
+    }
+
+    /// Register a hook, replacing any hook with the same name in place.
+    /// Returns whether one was replaced.
+    pub fn add(&mut self, hook: Hook) -> Result<bool, Box<dyn Error>> {
+        let replaced = match self.hooks.iter_mut().find(|existing| existing.name == hook.name) {
+            Some(existing) => {
+                *existing = hook;
+                true
+            }
+            None => {
+                self.hooks.push(hook);
+                false
+            }
+        };
+        self.save()?;
+        Ok(replaced)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<bool, Box<dyn Error>> {
+        let count = self.hooks.len();
+        self.hooks.retain(|hook| hook.name != name);
+        if self.hooks.len() == count {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Hook> {
+        self.active().find(|hook| hook.name == name)
+    }
+
+    pub fn list(&self) -> &[Hook] {
+        &self.hooks
+    }
+
+    pub fn configured(&self) -> &[Hook] {
+        &self.configured
+    }
+
+    /// Every hook that runs; `hooks.json` wins over config.toml on a name.
+    fn active(&self) -> impl Iterator<Item = &Hook> {
+        let configured = self.configured.iter().filter(|hook| !self.hooks.iter().any(|other| other.name == hook.name));
+        self.hooks.iter().chain(configured)
+    }
+}
+
+/// Hooks that would run after a successful change to `interface`.
+pub fn would_run(interface: &str) -> Vec<Hook> {
+    let event = HookEvent::new(interface, "", "", Trigger::Manual, None);
+    HookManager::new()
+        .map(|manager| manager.active().filter(|hook| hook.matches(&event)).cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Run every hook that matches `event`. Hooks never fail the change that
+/// triggered them; their errors are only reported.
+pub fn fire(event: &HookEvent) {
+    let manager = match HookManager::new() {
+        Ok(manager) => manager,
+        Err(e) => {
+            progress!("Warning: could not load hooks: {}", e);
+            return;
+        }
+    };
+
+    for hook in manager.active().filter(|hook| hook.matches(event)) {
+        if let Err(e) = hook.run(event) {
+            progress!("Warning: hook {} failed: {}", hook.name, e);
+        }
+    }
+}
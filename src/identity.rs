@@ -0,0 +1,302 @@
+// src/identity.rs
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use crate::error::MacError;
+use crate::mac::MacAddress;
+use crate::output::progress;
+use crate::transaction::{Step, Transaction};
+
+/// Used when `--spoof-hostname` is given without a template, shaped like
+/// the names the platform picks itself.
+#[cfg(target_os = "windows")]
+const DEFAULT_TEMPLATE: &str = "DESKTOP-*******";
+#[cfg(target_os = "macos")]
+const DEFAULT_TEMPLATE: &str = "MacBook-Pro-####";
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const DEFAULT_TEMPLATE: &str = "localhost-******";
+
+/// Windows names machines in capitals, the others mostly in lowercase.
+#[cfg(target_os = "windows")]
+const LETTERS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+#[cfg(not(target_os = "windows"))]
+const LETTERS: &str = "abcdefghijklmnopqrstuvwxyz";
+const DIGITS: &str = "0123456789";
+
+/// A hostname from `template`, where `#` becomes a digit, `?` a letter and
+/// `*` either. An empty template means the platform default.
+pub fn random_hostname(template: &str) -> Result<String, MacError> {
+    let template = if template.is_empty() { DEFAULT_TEMPLATE } else { template };
+    let either = format!("{}{}", LETTERS, DIGITS);
+    let mut rng = rand::thread_rng();
+    let mut pick = |set: &str| set.as_bytes()[rng.gen_range(0..set.len())] as char;
+
+    let hostname: String = template
+        .chars()
+        .map(|c| match c {
+            '#' => pick(DIGITS),
+            '?' => pick(LETTERS),
+            '*' => pick(&either),
+            c => c,
+        })
+        .collect();
+
+    // One DNS label, which every platform accepts as a host name
+    let valid = !hostname.is_empty()
+        && hostname.len() <= 63
+        && hostname.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && !hostname.starts_with('-')
+        && !hostname.ends_with('-');
+    if !valid {
+        return Err(MacError::InvalidFormat(format!(
+            "'{}' is not a valid hostname: use letters, digits and inner hyphens, at most 63", hostname
+        )));
+    }
+
+    Ok(hostname)
+}
+
+/// The DHCP client identifier that goes with `mac`. It is derived from the
+/// MAC, so it changes whenever the MAC does and says nothing about the old
+/// one: hardware type 1 and the address on Linux and macOS, a DUID-LL for
+/// Windows' DHCPv6 client (DHCPv4 there always identifies by MAC).
+pub fn client_id_for(mac: &MacAddress) -> String {
+    let bytes = mac.get_bytes().map(|byte| format!("{:02x}", byte)).join(":");
+    if cfg!(target_os = "windows") {
+        format!("00:03:00:01:{}", bytes)
+    } else {
+        format!("01:{}", bytes)
+    }
+}
+
+/// Hostname and DHCP client identifiers as they were before chameleon
+/// first changed them, for `--restore`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Originals {
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub dhcp_client_ids: BTreeMap<String, Option<String>>, // By interface; None when unset
+}
+
+impl Originals {
+    fn path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_default()
+            .join("mac_changer")
+            .join("identity.json")
+    }
+
+    pub fn load() -> Self {
+        fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = Self::path();
+        if self.hostname.is_none() && self.dhcp_client_ids.is_empty() {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+pub fn current_hostname() -> Result<String, Box<dyn Error>> {
+    Ok(hostname::get()?.to_string_lossy().into_owned())
+}
+
+/// Set the hostname, remembering the original the first time.
+pub fn spoof_hostname(transaction: &mut Transaction, hostname: &str) -> Result<(), Box<dyn Error>> {
+    let previous = current_hostname()?;
+    let mut originals = Originals::load();
+    originals.hostname.get_or_insert_with(|| previous.clone());
+    originals.save()?;
+
+    progress!("Setting hostname to {}...", hostname);
+    set_hostname(transaction, hostname, previous)
+}
+
+/// Point the DHCP client of `interface` at the identifier for `mac`,
+/// remembering the original the first time. The new identifier is sent
+/// from the next lease, which renewing after the change requests.
+pub fn spoof_dhcp_id(transaction: &mut Transaction, interface: &str, mac: &MacAddress) -> Result<String, Box<dyn Error>> {
+    let client_id = client_id_for(mac);
+    let previous = dhcp::get(interface)?;
+    let mut originals = Originals::load();
+    originals.dhcp_client_ids.entry(interface.to_string()).or_insert_with(|| previous.clone());
+    originals.save()?;
+
+    progress!("Setting DHCP client ID of {} to {}...", interface, client_id);
+    dhcp::set(interface, Some(&client_id), !transaction.permanent)?;
+    transaction.record(Step::DhcpClientIdSet {
+        interface: interface.to_string(),
+        client_id: client_id.clone(),
+        previous,
+    });
+    Ok(client_id)
+}
+
+/// Put back the hostname and the DHCP client ID of `interface` if they were
+/// spoofed. Returns whether anything was restored.
+pub fn restore(transaction: &mut Transaction, interface: &str) -> Result<bool, Box<dyn Error>> {
+    let mut originals = Originals::load();
+    let mut restored = false;
+
+    if let Some(hostname) = originals.hostname.take() {
+        progress!("Restoring hostname {}...", hostname);
+        set_hostname(transaction, &hostname, current_hostname()?)?;
+        restored = true;
+    }
+
+    if let Some(client_id) = originals.dhcp_client_ids.remove(interface) {
+        progress!("Restoring DHCP client ID of {}...", interface);
+        let previous = dhcp::get(interface)?;
+        dhcp::set(interface, client_id.as_deref(), false)?;
+        transaction.record(Step::DhcpClientIdSet {
+            interface: interface.to_string(),
+            client_id: client_id.unwrap_or_else(|| "(default)".into()),
+            previous,
+        });
+        restored = true;
+    }
+
+    originals.save()?;
+    Ok(restored)
+}
+
+fn set_hostname(transaction: &mut Transaction, hostname: &str, previous: String) -> Result<(), Box<dyn Error>> {
+    hostname::set(hostname)?;
+    transaction.record(Step::HostnameSet { hostname: hostname.to_string(), previous });
+
+    // The kernel name goes away with a reboot elsewhere; here it only
+    // takes effect with one
+    if cfg!(target_os = "windows") {
+        transaction.suggest("Restart Windows for the new hostname to take effect");
+    }
+    Ok(())
+}
+
+/// Through the interface's active NetworkManager connection, the only
+/// client whose identifier can be set per interface without rewriting its
+/// configuration.
+#[cfg(target_os = "linux")]
+mod dhcp {
+    use std::error::Error;
+    use crate::error::MacError;
+    use crate::networkmanager;
+
+    const SETTING: &str = "ipv4.dhcp-client-id";
+
+    fn connection(interface: &str) -> Result<networkmanager::Connection, MacError> {
+        networkmanager::device(interface)
+            .filter(|device| device.is_active())
+            .and_then(|device| device.connection)
+            .ok_or_else(|| MacError::SystemError(format!(
+                "The DHCP client ID can only be set through NetworkManager, which has no active connection on {}", interface
+            )))
+    }
+
+    pub fn get(interface: &str) -> Result<Option<String>, Box<dyn Error>> {
+        networkmanager::get_setting(&connection(interface)?.uuid, SETTING)
+    }
+
+    pub fn set(interface: &str, client_id: Option<&str>, temporary: bool) -> Result<(), Box<dyn Error>> {
+        networkmanager::set_setting(&connection(interface)?.uuid, SETTING, client_id, temporary)
+    }
+}
+
+/// Through the network service of the interface. The setting is saved with
+/// the service either way.
+#[cfg(target_os = "macos")]
+mod dhcp {
+    use std::error::Error;
+    use std::process::Command;
+    use crate::error::MacError;
+
+    fn service(interface: &str) -> Result<String, MacError> {
+        crate::network::hardware_port(interface)
+            .ok_or_else(|| MacError::SystemError(format!("No network service found for {}", interface)))
+    }
+
+    pub fn get(interface: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let output = Command::new("networksetup").args(["-getinfo", &service(interface)?]).output()?;
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| line.strip_prefix("Client ID:"))
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty()))
+    }
+
+    pub fn set(interface: &str, client_id: Option<&str>, _temporary: bool) -> Result<(), Box<dyn Error>> {
+        let service = service(interface)?;
+        let mut args = vec!["-setdhcp", service.as_str()];
+        args.extend(client_id);
+
+        let output = Command::new("networksetup").args(&args).output()?;
+        if !output.status.success() {
+            return Err(Box::new(MacError::SystemError(String::from_utf8_lossy(&output.stderr).into_owned())));
+        }
+        Ok(())
+    }
+}
+
+/// The DHCPv6 DUID, which Windows shares between all adapters.
+#[cfg(target_os = "windows")]
+mod dhcp {
+    use std::error::Error;
+    use winreg::enums::REG_BINARY;
+    use winreg::RegValue;
+    use crate::error::MacError;
+
+    const KEY: &str = "HKLM\\SYSTEM\\CurrentControlSet\\Services\\Tcpip6\\Parameters";
+    const VALUE: &str = "Dhcpv6DUID";
+
+    pub fn get(_interface: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let key = crate::win32::open_registry_path(KEY)?;
+        Ok(key.get_raw_value(VALUE).ok().map(|value| {
+            value.bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(":")
+        }))
+    }
+
+    pub fn set(_interface: &str, client_id: Option<&str>, _temporary: bool) -> Result<(), Box<dyn Error>> {
+        let key = crate::win32::open_registry_path(KEY)?;
+        let Some(client_id) = client_id else {
+            // Windows generates a new one when it is missing
+            key.delete_value(VALUE)?;
+            return Ok(());
+        };
+
+        let bytes = client_id
+            .split(':')
+            .map(|byte| u8::from_str_radix(byte, 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| MacError::InvalidFormat(format!("Invalid DUID {}", client_id)))?;
+        key.set_raw_value(VALUE, &RegValue { bytes, vtype: REG_BINARY })?;
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+mod dhcp {
+    use std::error::Error;
+    use crate::error::MacError;
+
+    pub fn get(_interface: &str) -> Result<Option<String>, Box<dyn Error>> {
+        Err(Box::new(MacError::UnsupportedPlatform("Setting the DHCP client ID".into())))
+    }
+
+    pub fn set(_interface: &str, _client_id: Option<&str>, _temporary: bool) -> Result<(), Box<dyn Error>> {
+        Err(Box::new(MacError::UnsupportedPlatform("Setting the DHCP client ID".into())))
+    }
+}
@@ -0,0 +1,74 @@
+// src/inspect.rs
+use serde::Serialize;
+use std::error::Error;
+use crate::mac::MacAddress;
+use crate::network::{get_current_mac, get_permanent_mac, NetworkCard};
+use crate::oui::OUIDatabase;
+
+/// A hint that an interface is not using its hardware address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SpoofSignal {
+    /// The current MAC differs from the address burned into the hardware.
+    DiffersFromPermanent,
+    /// The locally administered bit is set, as on generated and OS-randomized MACs.
+    LocallyAdministered,
+    /// No vendor in the database owns the prefix. Only meaningful with a full
+    /// database (`chameleon oui update`).
+    UnknownVendor,
+}
+
+/// What can be learned about an interface's address without privileges.
+#[derive(Debug, Clone, Serialize)]
+pub struct Inspection {
+    pub interface: String,
+    pub current_mac: MacAddress,
+    pub permanent_mac: Option<MacAddress>,
+    pub vendor: Option<String>,
+    pub signals: Vec<SpoofSignal>,
+}
+
+impl Inspection {
+    /// Whether the current MAC is probably not the hardware one. A known
+    /// permanent address settles it; without one the LAA bit decides.
+    pub fn is_spoofed(&self) -> bool {
+        match self.permanent_mac {
+            Some(_) => self.signals.contains(&SpoofSignal::DiffersFromPermanent),
+            None => self.signals.contains(&SpoofSignal::LocallyAdministered),
+        }
+    }
+}
+
+/// Inspect one interface. Vendor lookups are skipped without a database.
+pub fn inspect(interface: &str, oui_db: Option<&OUIDatabase>) -> Result<Inspection, Box<dyn Error>> {
+    let current_mac = get_current_mac(interface)?;
+    let permanent_mac = get_permanent_mac(interface);
+    let (vendor, mut signals) = classify(&current_mac, oui_db);
+    if permanent_mac.as_ref().is_some_and(|permanent| *permanent != current_mac) {
+        signals.insert(0, SpoofSignal::DiffersFromPermanent);
+    }
+
+    Ok(Inspection { interface: interface.to_string(), current_mac, permanent_mac, vendor, signals })
+}
+
+/// Vendor and spoof signals visible from an address alone, for MACs whose
+/// hardware address is unknown, such as those in a switch's CAM table.
+pub fn classify(mac: &MacAddress, oui_db: Option<&OUIDatabase>) -> (Option<String>, Vec<SpoofSignal>) {
+    let vendor = oui_db.and_then(|db| db.get_vendor(&mac.as_string())).map(|vendor| vendor.name.clone());
+
+    let mut signals = Vec::new();
+    if mac.is_locally_administered() {
+        signals.push(SpoofSignal::LocallyAdministered);
+    } else if oui_db.is_some() && vendor.is_none() {
+        signals.push(SpoofSignal::UnknownVendor);
+    }
+    (vendor, signals)
+}
+
+/// Inspect every interface that has a hardware address.
+pub fn inspect_all(oui_db: Option<&OUIDatabase>) -> Result<Vec<Inspection>, Box<dyn Error>> {
+    Ok(NetworkCard::list()?
+        .into_iter()
+        .filter_map(|card| inspect(&card.interface, oui_db).ok())
+        .collect())
+}
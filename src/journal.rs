@@ -0,0 +1,119 @@
+// src/journal.rs
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use crate::mac::MacAddress;
+
+/// Something the daemon did or saw.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum DaemonEvent {
+    Started { interfaces: Vec<String> },
+    Stopped,
+    RulesReloaded { rules: usize },
+    /// A rule matched and its MAC was applied.
+    RuleApplied { rule: String, interface: String, old_mac: MacAddress, new_mac: MacAddress },
+    /// A rule matched outside the interface's change window.
+    RuleDeferred { rule: String, interface: String, until: Option<DateTime<Utc>> },
+    /// A rule that had matched no longer does.
+    RuleEnded { rule: String, interface: String },
+    /// An app rule's application exited and the MAC from before it came back.
+    Restored { rule: String, interface: String, old_mac: MacAddress, new_mac: MacAddress },
+    /// Something else put another MAC on an interface the daemon manages.
+    Reverted { interface: String, expected: MacAddress, found: MacAddress },
+    Link { interface: String, up: bool, carrier: bool },
+    Error { interface: Option<String>, message: String },
+}
+
+impl DaemonEvent {
+    pub fn interface(&self) -> Option<&str> {
+        match self {
+            DaemonEvent::RuleApplied { interface, .. }
+            | DaemonEvent::RuleDeferred { interface, .. }
+            | DaemonEvent::RuleEnded { interface, .. }
+            | DaemonEvent::Restored { interface, .. }
+            | DaemonEvent::Reverted { interface, .. }
+            | DaemonEvent::Link { interface, .. } => Some(interface),
+            DaemonEvent::Error { interface, .. } => interface.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            DaemonEvent::Started { interfaces } => format!("Daemon started, managing {}", interfaces.join(", ")),
+            DaemonEvent::Stopped => "Daemon stopped".into(),
+            DaemonEvent::RulesReloaded { rules } => format!("Rules changed, reloaded {} rule(s)", rules),
+            DaemonEvent::RuleApplied { rule, old_mac, new_mac, .. } => format!("Rule {} applied: {} -> {}", rule, old_mac, new_mac),
+            DaemonEvent::RuleDeferred { rule, until, .. } => match until {
+                Some(until) => format!("Rule {} deferred until {}", rule, until.with_timezone(&Local).format("%Y-%m-%d %H:%M")),
+                None => format!("Rule {} deferred, change window never opens", rule),
+            },
+            DaemonEvent::RuleEnded { rule, .. } => format!("Rule {} no longer matches", rule),
+            DaemonEvent::Restored { rule, old_mac, new_mac, .. } => format!("Rule {} ended, restored {} -> {}", rule, old_mac, new_mac),
+            DaemonEvent::Reverted { expected, found, .. } => format!("MAC reverted to {}, expected {}", found, expected),
+            DaemonEvent::Link { up, carrier, .. } => match (up, carrier) {
+                (true, true) => "Link up".into(),
+                (true, false) => "Link up, no carrier".into(),
+                (false, _) => "Link down".into(),
+            },
+            DaemonEvent::Error { message, .. } => format!("Error: {}", message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: DaemonEvent,
+}
+
+/// Append-only record of what the daemon did and why, kept apart from the
+/// MAC change history so it can be replayed on its own.
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        let path = dirs::data_dir()
+            .unwrap_or_default()
+            .join("mac_changer")
+            .join("logs")
+            .join("daemon_events.log");
+
+        Self { path }
+    }
+
+    pub fn record(&self, event: DaemonEvent) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        let entry = JournalEntry { timestamp: Utc::now(), event };
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+
+    /// Entries in the order they were recorded, optionally only those from
+    /// `since` on. Lines that don't parse, such as one cut short by a crash,
+    /// are skipped.
+    pub fn replay(&self, since: Option<DateTime<Utc>>) -> Result<Vec<JournalEntry>, Box<dyn Error>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(content
+            .lines()
+            .filter_map(|line| serde_json::from_str::<JournalEntry>(line).ok())
+            .filter(|entry| since.is_none_or(|since| entry.timestamp >= since))
+            .collect())
+    }
+}
@@ -0,0 +1,31 @@
+// src/lib.rs
+// Read-only API for monitoring agents, enabled by the `inspect` feature:
+// interface enumeration, current and permanent MACs, vendor lookup and
+// spoof detection. Nothing here changes an address or needs privileges.
+#![cfg(feature = "inspect")]
+
+// Finds the tools network.rs runs
+#[allow(dead_code)]
+mod capabilities;
+pub mod error;
+pub mod inspect;
+pub mod mac;
+pub mod network;
+pub mod oui;
+
+// Platform helpers shared with the binary, which uses more of them
+#[cfg(target_os = "windows")]
+#[allow(dead_code)]
+mod win32;
+#[cfg(target_os = "macos")]
+#[allow(dead_code)]
+mod macos;
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+#[allow(dead_code)]
+mod bsd;
+
+pub use error::MacError;
+pub use inspect::{classify, inspect, inspect_all, Inspection, SpoofSignal};
+pub use mac::MacAddress;
+pub use network::{get_current_mac, get_permanent_mac, NetworkCard};
+pub use oui::{OUIDatabase, VendorInfo};
@@ -0,0 +1,197 @@
+// src/lock.rs
+use std::error::Error;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, ThreadId};
+use crate::error::MacError;
+use crate::output::progress;
+
+#[cfg(unix)]
+const LOCK_DIR: &str = "/var/run/chameleon";
+
+static WAIT: AtomicBool = AtomicBool::new(true);
+
+/// Interfaces locked by this process, and the thread holding each.
+static HELD: Mutex<Vec<(String, ThreadId)>> = Mutex::new(Vec::new());
+
+/// Whether to wait for another process's lock (`--wait`, the default) or
+/// fail right away (`--no-wait`). Set once at startup.
+pub fn set_wait(wait: bool) {
+    WAIT.store(wait, Ordering::Relaxed);
+}
+
+/// Held while an interface is being changed or its original MAC saved, so
+/// two chameleon processes cannot interleave. Released on drop.
+pub struct InterfaceLock {
+    name: String,
+    held: Option<Held>, // None when this thread already holds the lock
+}
+
+impl Drop for InterfaceLock {
+    fn drop(&mut self) {
+        if self.held.take().is_some() {
+            let mut held = HELD.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            held.retain(|(name, _)| name != &self.name);
+        }
+    }
+}
+
+/// Lock `interface` for this thread, waiting for other processes unless
+/// `--no-wait` was given. Taking a lock this thread already holds is free.
+pub fn acquire(interface: &str) -> Result<InterfaceLock, Box<dyn Error>> {
+    let name = crate::netns::qualify(interface);
+    let this_thread = thread::current().id();
+
+    let already_held = HELD
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .iter()
+        .any(|(held, thread)| held == &name && *thread == this_thread);
+    if already_held {
+        return Ok(InterfaceLock { name, held: None });
+    }
+
+    let held = Held::acquire(&name, WAIT.load(Ordering::Relaxed))?;
+    HELD.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push((name.clone(), this_thread));
+    Ok(InterfaceLock { name, held: Some(held) })
+}
+
+fn busy(name: &str, holder: &str) -> Box<dyn Error> {
+    Box::new(MacError::Locked(format!(
+        "{} is being changed by another chameleon process{}; try again or leave out --no-wait", name, holder
+    )))
+}
+
+/// An flock on `/var/run/chameleon/{interface}.lock`, which holds the pid
+/// of the process that has it. Closing the file releases it.
+#[cfg(unix)]
+struct Held {
+    _file: std::fs::File,
+}
+
+#[cfg(unix)]
+impl Held {
+    fn dir() -> Result<std::path::PathBuf, Box<dyn Error>> {
+        use std::fs::{self, DirBuilder};
+        use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+        use std::path::PathBuf;
+        use crate::backend;
+
+        // The mock's locks stay with its other files
+        if backend::current().simulated() {
+            return Ok(dirs::config_dir().unwrap_or_default().join("mac_changer").join("locks"));
+        }
+        let dir = PathBuf::from(LOCK_DIR);
+        if fs::create_dir_all(&dir).is_ok() {
+            return Ok(dir);
+        }
+
+        // Anyone can create files in the temporary directory, so only a
+        // directory there that belongs to this user alone will do
+        let dir = std::env::temp_dir().join("chameleon-locks");
+        match DirBuilder::new().mode(0o700).create(&dir) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(Box::new(e)),
+        }
+        let metadata = fs::symlink_metadata(&dir)?;
+        if !metadata.is_dir() || metadata.uid() != nix::unistd::Uid::effective().as_raw() || metadata.mode() & 0o022 != 0 {
+            return Err(Box::new(MacError::PermissionDenied(format!(
+                "{} is not a directory only this user can write to; remove it so interfaces can be locked",
+                dir.display()
+            ))));
+        }
+        Ok(dir)
+    }
+
+    fn acquire(name: &str, wait: bool) -> Result<Self, Box<dyn Error>> {
+        use std::fs::{self, OpenOptions, TryLockError};
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let dir = Self::dir()?;
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.lock", name));
+        // Never follow a link planted in place of the lock file: it is
+        // truncated below, with root's rights
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .custom_flags(nix::libc::O_NOFOLLOW)
+            .open(&path)?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let holder = fs::read_to_string(&path)
+                    .ok()
+                    .map(|pid| pid.trim().to_string())
+                    .filter(|pid| !pid.is_empty())
+                    .map(|pid| format!(" (pid {})", pid))
+                    .unwrap_or_default();
+                if !wait {
+                    return Err(busy(name, &holder));
+                }
+                progress!("Waiting for another chameleon process{} to finish with {}...", holder, name);
+                file.lock()?;
+            }
+            Err(TryLockError::Error(e)) => return Err(Box::new(e)),
+        }
+
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+        Ok(Self { _file: file })
+    }
+}
+
+/// A named mutex, `Global\chameleon-{interface}`, shared by every session.
+#[cfg(windows)]
+struct Held(windows::Win32::Foundation::HANDLE);
+
+#[cfg(windows)]
+impl Held {
+    fn acquire(name: &str, wait: bool) -> Result<Self, Box<dyn Error>> {
+        use windows::core::PCWSTR;
+        use windows::Win32::Foundation::{CloseHandle, WAIT_ABANDONED, WAIT_OBJECT_0, WAIT_TIMEOUT};
+        use windows::Win32::System::Threading::{CreateMutexW, WaitForSingleObject, INFINITE};
+
+        // Backslashes would read as a namespace
+        let mutex: Vec<u16> = format!("Global\\chameleon-{}", name.replace('\\', "_"))
+            .encode_utf16()
+            .chain(Some(0))
+            .collect();
+        let handle = unsafe { CreateMutexW(None, false, PCWSTR(mutex.as_ptr())) }?;
+
+        let mut status = unsafe { WaitForSingleObject(handle, 0) };
+        if status == WAIT_TIMEOUT {
+            if !wait {
+                let _ = unsafe { CloseHandle(handle) };
+                return Err(busy(name, ""));
+            }
+            progress!("Waiting for another chameleon process to finish with {}...", name);
+            status = unsafe { WaitForSingleObject(handle, INFINITE) };
+        }
+
+        // An abandoned mutex is ours now; its owner exited without releasing it
+        if status != WAIT_OBJECT_0 && status != WAIT_ABANDONED {
+            let _ = unsafe { CloseHandle(handle) };
+            return Err(Box::new(MacError::SystemError(format!("Could not lock {}", name))));
+        }
+        Ok(Self(handle))
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Held {
+    fn drop(&mut self) {
+        use windows::Win32::Foundation::CloseHandle;
+        use windows::Win32::System::Threading::ReleaseMutex;
+
+        unsafe {
+            let _ = ReleaseMutex(self.0);
+            let _ = CloseHandle(self.0);
+        }
+    }
+}
@@ -1,75 +1,210 @@
 use chrono::{DateTime, Utc};
+use ed25519_dalek::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::fs::{self, File};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use crate::error::MacError;
+use crate::hooks::Trigger;
+use crate::mac::MacAddress;
+use crate::output::progress;
+use crate::system_log::{Severity, SystemLogConfig};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MacChange {
     pub timestamp: DateTime<Utc>,
     pub interface: String,
-    pub old_mac: String,
-    pub new_mac: String,
+    #[serde(deserialize_with = "unknown_as_null")]
+    pub old_mac: MacAddress,
+    pub new_mac: MacAddress,
     pub geo_location: Option<String>,
     pub permanent: bool,
+    #[serde(default)]
+    pub profile: Option<String>, // Profile the MAC came from, if any
+    #[serde(default)]
+    pub trigger: Option<Trigger>, // Not recorded by older versions
+    #[serde(default)]
+    pub hostname: Option<String>, // Set with --spoof-hostname
+    #[serde(default)]
+    pub dhcp_client_id: Option<String>, // Set with --spoof-dhcp-id
+    #[serde(default)]
+    pub principal: Option<String>, // Who asked for it through `serve`
+}
+
+/// Older versions logged "" when the old MAC could not be read; it reads
+/// as 00:00:00:00:00:00, what drivers report without an address.
+fn unknown_as_null<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<MacAddress, D::Error> {
+    let mac = String::deserialize(deserializer)?;
+    if mac.is_empty() {
+        return Ok(MacAddress::new([0; 6], crate::mac::MacFormat::Colon));
+    }
+    crate::mac::normalize(&mac).map_err(serde::de::Error::custom)
+}
+
+/// A link state transition seen by the daemon.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkEvent {
+    pub timestamp: DateTime<Utc>,
+    pub interface: String,
+    pub up: bool,
+    pub carrier: bool,
 }
 
 pub struct MacLogger {
     log_dir: PathBuf,
     max_log_size: u64,
     max_log_files: usize,
+    system_log: Option<Severity>, // Also send changes to journald/syslog/Event Log
+}
+
+/// Where privileged runs keep the log, writable only by administrators.
+fn system_log_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    return std::env::var_os("ProgramData").map(|dir| PathBuf::from(dir).join("chameleon").join("logs"));
+
+    #[cfg(target_os = "macos")]
+    return Some(PathBuf::from("/Library/Logs/chameleon"));
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    return Some(PathBuf::from("/var/log/chameleon"));
+}
+
+fn is_privileged() -> bool {
+    #[cfg(unix)]
+    return nix::unistd::Uid::effective().is_root();
+
+    #[cfg(windows)]
+    return is_elevated::is_elevated();
+}
+
+/// Changes are only made privileged, so their history goes to the system
+/// directory where an unprivileged user can read but not rewrite it. Once
+/// that exists everyone reads it; the per-user directory is left for
+/// hosts where chameleon has never run privileged.
+fn log_dir() -> PathBuf {
+    let user_dir = dirs::data_dir()
+        .unwrap_or_default()
+        .join("mac_changer")
+        .join("logs");
+
+    // A mock run has changed nothing worth auditing
+    if crate::backend::current().simulated() {
+        return user_dir;
+    }
+
+    match system_log_dir() {
+        Some(system_dir) if system_dir.exists() => system_dir,
+        Some(system_dir) if is_privileged() && create_system_log_dir(&system_dir, &user_dir).is_ok() => system_dir,
+        _ => user_dir,
+    }
+}
+
+/// Create the system log directory, moving over the history kept so far.
+fn create_system_log_dir(system_dir: &Path, user_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(system_dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(system_dir, fs::Permissions::from_mode(0o755))?;
+    }
+
+    for entry in fs::read_dir(user_dir).into_iter().flatten().flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with("mac_changes") || name.starts_with("link_events") {
+            fs::copy(entry.path(), system_dir.join(&*name))?;
+            fs::remove_file(entry.path())?;
+        }
+    }
+
+    Ok(())
 }
 
 impl MacLogger {
     pub fn new() -> Self {
-        let log_dir = dirs::data_dir()
-            .unwrap_or_default()
-            .join("mac_changer")
-            .join("logs");
-
+        let log_dir = log_dir();
         fs::create_dir_all(&log_dir).unwrap_or_default();
 
+        let system_log = SystemLogConfig::load();
+
         Self {
             log_dir,
             max_log_size: 10 * 1024 * 1024, // 10MB
             max_log_files: 5,
+            system_log: system_log.enabled.then_some(system_log.severity),
         }
     }
 
-    pub fn log_change(&self, change: MacChange) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn log_dir(&self) -> &Path {
+        &self.log_dir
+    }
+
+    pub fn log_change(&self, mut change: MacChange) -> Result<(), Box<dyn std::error::Error>> {
+        change.interface = crate::netns::qualify(&change.interface);
         let log_file = self.log_dir.join("mac_changes.log");
 
+        // Another process appending between reading the last hash and
+        // writing would fork the chain
+        let _lock = self.lock_chain()?;
+
         // Check if rotation needed
         if let Ok(metadata) = fs::metadata(&log_file) {
             if metadata.len() > self.max_log_size {
-                self.rotate_logs()?;
+                self.rotate_logs("mac_changes")?;
             }
         }
 
+        let prev_hash = self.last_entry_hash()?;
+
         // Append to log file
         let mut file = fs::OpenOptions::new()
             .create(true)
             .append(true)
             .open(log_file)?;
 
-        let log_entry = serde_json::to_string(&change)?;
+        let log_entry = serde_json::to_string(&ChainedEntry { change: &change, prev_hash })?;
         writeln!(file, "{}", log_entry)?;
 
+        // The file log is the record; a missing system logger doesn't fail the change
+        if let Some(severity) = self.system_log
+            && let Err(e) = crate::system_log::send(&change, severity)
+        {
+            progress!("Warning: Could not write to the system log: {}", e);
+        }
+
         Ok(())
     }
 
-    fn rotate_logs(&self) -> Result<(), Box<dyn std::error::Error>> {
+    /// An exclusive lock on `mac_changes.lock`, held while the chain's end
+    /// is read and extended. A separate file because rotation renames the
+    /// log itself. Closing the file releases it.
+    fn lock_chain(&self) -> Result<File, Box<dyn std::error::Error>> {
+        let path = self.log_dir.join("mac_changes.lock");
+        // Readers of a system log may not create files there, but can still lock one
+        let file = match fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => File::open(&path)?,
+            Err(e) => return Err(e.into()),
+        };
+        file.lock()?;
+        Ok(file)
+    }
+
+    /// Move `{name}.log` to `{name}.1.log`, shifting older files up.
+    fn rotate_logs(&self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
         for i in (1..self.max_log_files).rev() {
-            let old_path = self.log_dir.join(format!("mac_changes.{}.log", i));
-            let new_path = self.log_dir.join(format!("mac_changes.{}.log", i + 1));
+            let old_path = self.log_dir.join(format!("{}.{}.log", name, i));
+            let new_path = self.log_dir.join(format!("{}.{}.log", name, i + 1));
 
             if old_path.exists() {
                 fs::rename(old_path, new_path)?;
             }
         }
 
-        let current = self.log_dir.join("mac_changes.log");
-        let backup = self.log_dir.join("mac_changes.1.log");
+        let current = self.log_dir.join(format!("{}.log", name));
+        let backup = self.log_dir.join(format!("{}.1.log", name));
 
         if current.exists() {
             fs::rename(current, backup)?;
@@ -78,19 +213,286 @@ impl MacLogger {
         Ok(())
     }
 
+    pub fn log_link_event(&self, event: &LinkEvent) -> Result<(), Box<dyn std::error::Error>> {
+        let log_file = self.log_dir.join("link_events.log");
+        if fs::metadata(&log_file).is_ok_and(|metadata| metadata.len() > self.max_log_size) {
+            self.rotate_logs("link_events")?;
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_file)?;
+
+        writeln!(file, "{}", serde_json::to_string(event)?)?;
+        Ok(())
+    }
+
+    /// Link events, oldest first, including those rotated out to
+    /// `link_events.N.log`.
+    pub fn get_link_events(&self) -> Result<Vec<LinkEvent>, Box<dyn std::error::Error>> {
+        let mut events = Vec::new();
+        for (_, content) in self.read_log_files("link_events")? {
+            events.extend(content.lines().filter_map(|line| serde_json::from_str::<LinkEvent>(line).ok()));
+        }
+        Ok(events)
+    }
+
     pub fn get_history(&self) -> Result<Vec<MacChange>, Box<dyn std::error::Error>> {
+        self.query_history(&HistoryFilter::default())
+    }
+
+    /// Changes matching `filter`, oldest first, including those already
+    /// rotated out to `mac_changes.N.log`.
+    pub fn query_history(&self, filter: &HistoryFilter) -> Result<Vec<MacChange>, Box<dyn std::error::Error>> {
         let mut history = Vec::new();
-        let log_file = self.log_dir.join("mac_changes.log");
+        for (_, content) in self.read_log_files("mac_changes")? {
+            history.extend(
+                content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str::<MacChange>(line).ok())
+                    .filter(|change| filter.matches(change)),
+            );
+        }
 
-        if log_file.exists() {
-            let content = fs::read_to_string(log_file)?;
-            for line in content.lines() {
-                if let Ok(change) = serde_json::from_str(line) {
-                    history.push(change);
+        if let Some(last) = filter.last {
+            history.drain(..history.len().saturating_sub(last));
+        }
+
+        Ok(history)
+    }
+
+    /// The `{name}` log files that exist, oldest first, with their content.
+    /// The highest rotation number holds the oldest entries.
+    fn read_log_files(&self, name: &str) -> Result<Vec<(PathBuf, String)>, Box<dyn std::error::Error>> {
+        let mut log_files: Vec<PathBuf> = (1..=self.max_log_files)
+            .rev()
+            .map(|i| self.log_dir.join(format!("{}.{}.log", name, i)))
+            .collect();
+        log_files.push(self.log_dir.join(format!("{}.log", name)));
+
+        let mut files = Vec::new();
+        for log_file in log_files {
+            match fs::read_to_string(&log_file) {
+                Ok(content) => files.push((log_file, content)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(files)
+    }
+
+    /// Hash of the newest entry, which the next one links to. Only the
+    /// current file and the one rotated last can hold it.
+    fn last_entry_hash(&self) -> Result<String, Box<dyn std::error::Error>> {
+        for name in ["mac_changes.log", "mac_changes.1.log"] {
+            match fs::read_to_string(self.log_dir.join(name)) {
+                Ok(content) => {
+                    if let Some(line) = content.lines().rfind(|line| !line.trim().is_empty()) {
+                        return Ok(entry_hash(line));
+                    }
                 }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
             }
         }
+        Ok(GENESIS_HASH.to_string())
+    }
 
-        Ok(history)
+    /// Walk the hash chain through every history file. A modified entry
+    /// breaks the link from the entry after it, and so do deleted entries.
+    /// Changes to the newest entry, or entries cut off the end, only show
+    /// against a signed head.
+    pub fn verify_chain(&self, head: Option<(&Path, &Path)>) -> Result<ChainReport, Box<dyn std::error::Error>> {
+        let mut report = ChainReport::default();
+        let mut previous: Option<(String, String)> = None; // (location, hash)
+        let mut hashes = Vec::new();
+
+        for (path, content) in self.read_log_files("mac_changes")? {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+
+            for (number, line) in content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+                let location = format!("{}:{}", file_name, number + 1);
+                report.entries += 1;
+
+                match serde_json::from_str::<ChainLink>(line) {
+                    Err(_) => report.problems.push(format!("{} is not a valid entry", location)),
+                    Ok(ChainLink { prev_hash: None }) if report.chained > 0 => {
+                        report.problems.push(format!("{} has no hash although earlier entries do", location));
+                    }
+                    Ok(ChainLink { prev_hash: None }) => report.unchained += 1,
+                    Ok(ChainLink { prev_hash: Some(prev_hash) }) => {
+                        report.chained += 1;
+                        match &previous {
+                            None => report.rotated_out = prev_hash != GENESIS_HASH,
+                            Some((_, hash)) if *hash == prev_hash => {}
+                            Some((previous_location, _)) => report.problems.push(format!(
+                                "{} does not follow {}: that entry was modified or entries between them were deleted",
+                                location, previous_location
+                            )),
+                        }
+                    }
+                }
+
+                let hash = entry_hash(line);
+                hashes.push(hash.clone());
+                previous = Some((location, hash));
+            }
+        }
+
+        report.head = previous.map(|(_, hash)| hash);
+
+        if let Some((head_path, key_path)) = head {
+            let head = SignedHead::load(head_path)?;
+            head.verify(key_path)?;
+
+            match hashes.iter().rposition(|hash| *hash == head.hash) {
+                Some(position) => report.after_signed_head = Some(hashes.len() - position - 1),
+                None => report.problems.push(format!(
+                    "The head signed at {} is not in the log: it was rewritten or entries were removed from its end",
+                    head.signed_at.to_rfc3339()
+                )),
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Sign the newest entry so later edits anywhere up to it can be proven.
+    pub fn sign_head(&self, key_path: &Path) -> Result<SignedHead, Box<dyn std::error::Error>> {
+        let _lock = self.lock_chain()?;
+        let hash = self.last_entry_hash()?;
+        if hash == GENESIS_HASH {
+            return Err(Box::new(MacError::ValidationFailed("No history to sign".into())));
+        }
+        SignedHead::sign(hash, key_path)
+    }
+}
+
+/// What the first entry links to when nothing came before it.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// SHA-256 of an entry exactly as written, without the line break.
+fn entry_hash(line: &str) -> String {
+    hex::encode(Sha256::digest(line.trim_end_matches('\r').as_bytes()))
+}
+
+/// A history line: the change plus the hash of the line before it.
+#[derive(Serialize)]
+struct ChainedEntry<'a> {
+    #[serde(flatten)]
+    change: &'a MacChange,
+    prev_hash: String,
+}
+
+#[derive(Deserialize)]
+struct ChainLink {
+    prev_hash: Option<String>,
+}
+
+/// Result of `history verify`.
+#[derive(Debug, Default, Serialize)]
+pub struct ChainReport {
+    pub entries: usize,
+    pub chained: usize,
+    pub unchained: usize,  // Written before the log was chained
+    pub rotated_out: bool, // The oldest entries the chain links to are gone
+    pub head: Option<String>,
+    pub after_signed_head: Option<usize>, // Entries newer than the signed head
+    pub problems: Vec<String>,
+}
+
+impl ChainReport {
+    pub fn print(&self) {
+        println!("Entries:       {} ({} chained, {} from before chaining)", self.entries, self.chained, self.unchained);
+        if self.rotated_out {
+            println!("Start:         older entries have been rotated out");
+        }
+        if let Some(head) = &self.head {
+            println!("Head:          {}", head);
+        }
+        match self.after_signed_head {
+            Some(0) => println!("Signed head:   matches the newest entry"),
+            Some(newer) => println!("Signed head:   found, {} newer entries are only chained", newer),
+            None => {}
+        }
+        for problem in &self.problems {
+            println!("Problem:       {}", problem);
+        }
+        if self.problems.is_empty() {
+            println!("The chain is intact");
+        }
+    }
+}
+
+/// The newest entry's hash, signed with an Ed25519 key.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignedHead {
+    pub hash: String,
+    pub signed_at: DateTime<Utc>,
+    pub signature: String,
+    pub public_key: String, // Informational; verification uses a trusted key
+}
+
+impl SignedHead {
+    fn signing_bytes(hash: &str, signed_at: &DateTime<Utc>) -> Vec<u8> {
+        format!("{}\n{}", hash, signed_at.to_rfc3339()).into_bytes()
+    }
+
+    /// Sign with an Ed25519 private key in PKCS#8 PEM format.
+    fn sign(hash: String, key_path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let pem = fs::read_to_string(key_path)?;
+        let key = SigningKey::from_pkcs8_pem(&pem).map_err(|e| MacError::InvalidFormat(
+            format!("Could not read signing key {}: {}", key_path.display(), e)
+        ))?;
+
+        let signed_at = Utc::now();
+        let signature = key.sign(&Self::signing_bytes(&hash, &signed_at));
+
+        Ok(Self {
+            hash,
+            signed_at,
+            signature: hex::encode(signature.to_bytes()),
+            public_key: hex::encode(key.verifying_key().to_bytes()),
+        })
+    }
+
+    fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| MacError::InvalidFormat(format!("Invalid signed head {}: {}", path.display(), e)).into())
     }
-}
\ No newline at end of file
+
+    /// Check the signature against a trusted Ed25519 public key in PEM format.
+    fn verify(&self, key_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let pem = fs::read_to_string(key_path)?;
+        let key = VerifyingKey::from_public_key_pem(&pem).map_err(|e| MacError::InvalidFormat(
+            format!("Could not read public key {}: {}", key_path.display(), e)
+        ))?;
+
+        let signature: [u8; 64] = hex::decode(&self.signature)
+            .map_err(|e| MacError::InvalidFormat(format!("Invalid signature encoding: {}", e)))?
+            .try_into()
+            .map_err(|_| MacError::InvalidFormat("Signature must be 64 bytes".into()))?;
+
+        key.verify(&Self::signing_bytes(&self.hash, &self.signed_at), &Signature::from_bytes(&signature))
+            .map_err(|_| MacError::ValidationFailed("Signature verification failed for the signed head".into()).into())
+    }
+}
+
+/// Which history entries to return; the default returns all of them.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    pub interface: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub last: Option<usize>, // Only the newest N of those that match
+}
+
+impl HistoryFilter {
+    fn matches(&self, change: &MacChange) -> bool {
+        self.interface.as_ref().is_none_or(|interface| &change.interface == interface)
+            && self.since.is_none_or(|since| change.timestamp >= since)
+            && self.until.is_none_or(|until| change.timestamp <= until)
+    }
+}
@@ -1,10 +1,11 @@
 // src/mac.rs
 use std::fmt;
 use rand::Rng;
+use rand::seq::SliceRandom;
 use std::num::ParseIntError;
 use crate::error::MacError;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MacFormat {
     Colon,      // XX:XX:XX:XX:XX:XX
     Hyphen,     // XX-XX-XX-XX-XX-XX
@@ -26,40 +27,53 @@ impl MacAddress {
     pub fn with_format(&self, format: MacFormat) -> Self {
         Self {
             bytes: self.bytes,
-            format: format,
+            format,
         }
     }
 
+    /// Parse colon, hyphen or dot separated octets (leading zeros optional, as
+    /// some tools print them), Cisco-style "xxxx.xxxx.xxxx", or 12 bare hex
+    /// digits. Case and surrounding whitespace are ignored.
     pub fn parse(mac_str: &str) -> Result<Self, MacError> {
-        let clean_mac = mac_str.replace([':', '-', '.'], "");
-        if clean_mac.len() != 12 {
-            return Err(MacError::InvalidFormat("MAC address must be 12 hexadecimal characters".into()));
+        let mac_str = mac_str.trim();
+        let invalid = || MacError::InvalidFormat(format!("Invalid MAC address '{}'", mac_str));
+
+        let format = if mac_str.contains(':') {
+            MacFormat::Colon
+        } else if mac_str.contains('-') {
+            MacFormat::Hyphen
+        } else if mac_str.contains('.') {
+            MacFormat::Dot
+        } else {
+            MacFormat::Raw
+        };
+
+        let groups: Vec<&str> = mac_str.split([':', '-', '.']).collect();
+        if groups.iter().any(|group| group.is_empty() || !group.chars().all(|c| c.is_ascii_hexdigit())) {
+            return Err(invalid());
         }
 
+        // Pad short groups so all forms read as 12 digits
+        let hex: String = match groups.len() {
+            6 if groups.iter().all(|group| group.len() <= 2) => groups.iter().map(|group| format!("{:0>2}", group)).collect(),
+            // Cisco style; "00:11:22" is a vendor prefix, not a short MAC
+            3 if format == MacFormat::Dot && groups.iter().all(|group| group.len() == 4) => groups.concat(),
+            1 if mac_str.len() == 12 => mac_str.to_string(),
+            _ => return Err(invalid()),
+        };
+
         let bytes: Result<Vec<u8>, ParseIntError> = (0..6)
-            .map(|i| u8::from_str_radix(&clean_mac[i * 2..(i + 1) * 2], 16))
+            .map(|i| u8::from_str_radix(&hex[i * 2..(i + 1) * 2], 16))
             .collect();
 
-        match bytes {
-            Ok(b) => {
-                let mut array = [0u8; 6];
-                array.copy_from_slice(&b);
-
-                // Determine format from original string
-                let format = if mac_str.contains(':') {
-                    MacFormat::Colon
-                } else if mac_str.contains('-') {
-                    MacFormat::Hyphen
-                } else if mac_str.contains('.') {
-                    MacFormat::Dot
-                } else {
-                    MacFormat::Raw
-                };
-
-                Ok(Self { bytes: array, format })
-            }
-            Err(e) => Err(MacError::from(e))
-        }
+        let mut array = [0u8; 6];
+        array.copy_from_slice(&bytes?);
+        Ok(Self { bytes: array, format })
+    }
+
+    /// The vendor prefix as "XX:XX:XX".
+    pub fn oui(&self) -> String {
+        format!("{:02X}:{:02X}:{:02X}", self.bytes[0], self.bytes[1], self.bytes[2])
     }
 
     pub fn as_string(&self) -> String {
@@ -90,41 +104,200 @@ impl MacAddress {
     pub fn get_bytes(&self) -> &[u8; 6] {
         &self.bytes
     }
+
+    /// Whether the U/L bit is set, i.e. the address was not assigned by the
+    /// manufacturer. Randomized addresses are always locally administered.
+    pub fn is_locally_administered(&self) -> bool {
+        self.bytes[0] & 0x02 != 0
+    }
+
+    /// Whether the I/G bit is set: a group address, never a station's own.
+    pub fn is_multicast(&self) -> bool {
+        self.bytes[0] & 0x01 != 0
+    }
+
+    /// ff:ff:ff:ff:ff:ff, which is also multicast.
+    pub fn is_broadcast(&self) -> bool {
+        self.bytes == [0xFF; 6]
+    }
+
+    /// 00:00:00:00:00:00, what drivers report when they have no address.
+    pub fn is_null(&self) -> bool {
+        self.bytes == [0; 6]
+    }
+
+    pub fn oui_bytes(&self) -> [u8; 3] {
+        [self.bytes[0], self.bytes[1], self.bytes[2]]
+    }
+}
+
+impl std::str::FromStr for MacAddress {
+    type Err = MacError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// Written in the canonical form, read in any form `parse` takes and kept
+/// in the canonical form.
+impl serde::Serialize for MacAddress {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.with_format(MacFormat::Colon))
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for MacAddress {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Self::parse(&s).map(|mac| mac.with_format(MacFormat::Colon)).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Addresses are equal when their bytes are, however they were written.
+impl PartialEq for MacAddress {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl Eq for MacAddress {}
+
+impl std::hash::Hash for MacAddress {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.bytes.hash(state);
+    }
+}
+
+/// `mac` in the canonical form: lowercase, colon separated, zero padded.
+pub fn normalize(mac: &str) -> Result<MacAddress, MacError> {
+    Ok(MacAddress::parse(mac)?.with_format(MacFormat::Colon))
+}
+
+/// The canonical form of a vendor prefix, or of the prefix of a full MAC:
+/// the first three octets as "XX:XX:XX".
+pub fn normalize_prefix(prefix: &str) -> String {
+    if let Ok(mac) = MacAddress::parse(prefix) {
+        return mac.oui();
+    }
+
+    let groups: Vec<&str> = prefix.trim().split([':', '-', '.']).collect();
+    let hex: String = if groups.len() >= 3 && groups[..3].iter().all(|group| (1..=2).contains(&group.len())) {
+        groups[..3].iter().map(|group| format!("{:0>2}", group)).collect()
+    } else {
+        prefix.chars().filter(|c| c.is_ascii_hexdigit()).take(6).collect()
+    };
+
+    hex.to_uppercase()
+        .as_bytes()
+        .chunks(2)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect::<Vec<_>>()
+        .join(":")
 }
 
 // Remove the ToString implementation since it's automatically
 // derived from Display
 impl fmt::Display for MacAddress {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.as_string())
+        f.pad(&self.as_string())
     }
 }
 
-pub fn generate_random_mac(vendor_prefix: Option<&str>) -> Result<MacAddress, MacError> {
-    let mut rng = rand::thread_rng();
-    let mut bytes = [0u8; 6];
-
-    if let Some(prefix) = vendor_prefix {
-        let prefix_bytes = prefix.split(|c| c == ':' || c == '-')
-            .take(3)
-            .map(|b| u8::from_str_radix(b, 16))
-            .collect::<Result<Vec<_>, _>>()?;
+/// How a random MAC is built.
+#[derive(Debug, Clone)]
+pub enum RandomPolicy {
+    /// Fully random locally administered unicast address.
+    LocallyAdministered,
+    /// Random address under a 3-byte vendor prefix.
+    Vendor(String),
+    /// Keep the OUI of this (current) address and randomize the lower 3 bytes.
+    PreserveOui(MacAddress),
+    /// Universally administered address under one of these real OUIs, so it
+    /// reads like a burned-in address rather than a randomized one.
+    BurnedInLike(Vec<String>),
+}
 
-        if prefix_bytes.len() != 3 {
-            return Err(MacError::VendorNotFound("Vendor prefix must be 3 bytes".into()));
+impl RandomPolicy {
+    /// `Vendor` if a prefix is given, `LocallyAdministered` otherwise.
+    pub fn from_vendor(vendor_prefix: Option<&str>) -> Self {
+        match vendor_prefix {
+            Some(prefix) => RandomPolicy::Vendor(prefix.to_string()),
+            None => RandomPolicy::LocallyAdministered,
         }
+    }
+}
 
-        bytes[0..3].copy_from_slice(&prefix_bytes);
-    } else {
-        // Generate random locally administered unicast address
-        bytes[0] = rng.r#gen::<u8>() & 0xFE | 0x02;
-        bytes[1] = rng.r#gen();
-        bytes[2] = rng.r#gen();
+/// OUI the IANA holds for protocol use (VRRP, multicast mappings).
+const IANA_OUI: [u8; 3] = [0x00, 0x00, 0x5E];
+
+/// Addresses no generated MAC should ever be: group addresses (multicast and
+/// broadcast), all zeros and IANA-reserved ranges.
+pub fn is_excluded(bytes: &[u8; 6]) -> bool {
+    let mac = MacAddress::new(*bytes, MacFormat::Colon);
+    mac.is_multicast() || mac.is_null() || mac.oui_bytes() == IANA_OUI
+}
+
+fn parse_prefix(prefix: &str) -> Result<[u8; 3], MacError> {
+    let prefix_bytes = prefix.split([':', '-'])
+        .take(3)
+        .map(|b| u8::from_str_radix(b, 16))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if prefix_bytes.len() != 3 {
+        return Err(MacError::VendorNotFound("Vendor prefix must be 3 bytes".into()));
+    }
+
+    let prefix = [prefix_bytes[0], prefix_bytes[1], prefix_bytes[2]];
+    if prefix[0] & 0x01 != 0 || prefix == IANA_OUI {
+        return Err(MacError::VendorNotFound(
+            format!("{} is a multicast or reserved prefix", prefix_bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(":"))
+        ));
     }
 
-    bytes[3] = rng.r#gen();
-    bytes[4] = rng.r#gen();
-    bytes[5] = rng.r#gen();
+    Ok(prefix)
+}
+
+pub fn generate_random_mac(policy: &RandomPolicy) -> Result<MacAddress, MacError> {
+    let mut rng = rand::thread_rng();
+
+    let prefix = match policy {
+        RandomPolicy::LocallyAdministered => None,
+        RandomPolicy::Vendor(prefix) => Some(parse_prefix(prefix)?),
+        RandomPolicy::PreserveOui(current) => Some(parse_prefix(&current.oui())?),
+        RandomPolicy::BurnedInLike(prefixes) => {
+            // Only manufacturer-assigned OUIs look burned in
+            let universal: Vec<[u8; 3]> = prefixes
+                .iter()
+                .filter_map(|prefix| parse_prefix(prefix).ok())
+                .filter(|prefix| prefix[0] & 0x02 == 0)
+                .collect();
+            Some(*universal.choose(&mut rng).ok_or_else(|| {
+                MacError::VendorNotFound("No manufacturer OUIs to choose from; run `chameleon oui update`".into())
+            })?)
+        }
+    };
+
+    // A random lower half can still land on an excluded address (all zeros
+    // under a zero prefix), so draw again until it doesn't
+    loop {
+        let mut bytes = [0u8; 6];
+
+        if let Some(prefix) = prefix {
+            bytes[0..3].copy_from_slice(&prefix);
+        } else {
+            // Generate random locally administered unicast address
+            bytes[0] = rng.r#gen::<u8>() & 0xFE | 0x02;
+            bytes[1] = rng.r#gen();
+            bytes[2] = rng.r#gen();
+        }
 
-    Ok(MacAddress::new(bytes, MacFormat::Colon))
-}
\ No newline at end of file
+        bytes[3] = rng.r#gen();
+        bytes[4] = rng.r#gen();
+        bytes[5] = rng.r#gen();
+
+        if !is_excluded(&bytes) {
+            return Ok(MacAddress::new(bytes, MacFormat::Colon));
+        }
+    }
+}
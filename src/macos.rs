@@ -0,0 +1,127 @@
+// src/macos.rs
+use std::error::Error;
+use std::process::Command;
+use objc2_core_wlan::{CWInterface, CWInterfaceMode, CWWiFiClient};
+use objc2_foundation::NSString;
+use objc2::rc::Retained;
+use crate::error::MacError;
+
+/// Interfaces created and owned by the system (AirDrop, VPN tunnels, bridges,
+/// Apple internal networking) that never accept a new link-layer address.
+const SYSTEM_INTERFACE_PREFIXES: &[&str] = &[
+    "lo", "awdl", "llw", "utun", "ipsec", "bridge", "anpi", "ap", "gif", "stf",
+];
+
+/// What macOS allows us to do with an interface's MAC address.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpoofCapability {
+    /// `ifconfig ether` works directly (wired and most USB adapters).
+    Supported,
+    /// Wi-Fi only accepts a new address while not associated to a network.
+    WhileDisassociated,
+    /// The address cannot be changed on this interface, with the reason.
+    Unsupported(String),
+}
+
+fn wifi_interface(interface: &str) -> Option<Retained<CWInterface>> {
+    unsafe {
+        let client = CWWiFiClient::sharedWiFiClient();
+        client.interfaceWithName(Some(&NSString::from_str(interface)))
+    }
+}
+
+fn hardware_model() -> String {
+    Command::new("sysctl")
+        .args(["-n", "hw.model"])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default()
+}
+
+fn is_apple_silicon() -> bool {
+    Command::new("sysctl")
+        .args(["-n", "hw.optional.arm64"])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim() == "1")
+        .unwrap_or(false)
+}
+
+pub fn spoof_capability(interface: &str) -> SpoofCapability {
+    if SYSTEM_INTERFACE_PREFIXES.iter().any(|prefix| {
+        interface.strip_prefix(prefix)
+            .is_some_and(|rest| rest.chars().all(|c| c.is_ascii_digit()))
+    }) {
+        return SpoofCapability::Unsupported(
+            format!("{} is a system-managed virtual interface", interface)
+        );
+    }
+
+    if let Some(wifi) = wifi_interface(interface) {
+        if !unsafe { wifi.powerOn() } {
+            return SpoofCapability::Unsupported(
+                format!("Wi-Fi on {} is powered off; turn it on before changing the address", interface)
+            );
+        }
+        return SpoofCapability::WhileDisassociated;
+    }
+
+    SpoofCapability::Supported
+}
+
+/// Drop the current Wi-Fi association through CoreWLAN. Returns whether the
+/// interface was associated before.
+pub fn disassociate(interface: &str) -> Result<bool, Box<dyn Error>> {
+    let wifi = wifi_interface(interface).ok_or_else(|| MacError::ValidationFailed(
+        format!("{} is not a Wi-Fi interface", interface)
+    ))?;
+
+    unsafe {
+        if wifi.interfaceMode() == CWInterfaceMode::None {
+            return Ok(false);
+        }
+        wifi.disassociate();
+    }
+
+    Ok(true)
+}
+
+/// SSID and BSSID of the current association. macOS only reveals these to
+/// processes with Location Services access, so either may be missing.
+pub fn wifi_connection(interface: &str) -> Option<crate::network::WirelessConnection> {
+    let wifi = wifi_interface(interface)?;
+
+    let (ssid, bssid) = unsafe {
+        (
+            wifi.ssid().map(|ssid| ssid.to_string()),
+            wifi.bssid().and_then(|bssid| bssid.to_string().parse().ok()),
+        )
+    };
+
+    if ssid.is_none() && bssid.is_none() {
+        return None;
+    }
+
+    Some(crate::network::WirelessConnection { ssid, bssid })
+}
+
+/// Explain why a change that ifconfig accepted (or rejected) did not stick.
+pub fn unsupported_reason(interface: &str, capability: &SpoofCapability) -> String {
+    let model = hardware_model();
+    match capability {
+        SpoofCapability::WhileDisassociated if is_apple_silicon() => format!(
+            "The Wi-Fi driver on this Apple Silicon Mac ({}) rejected the new address for {} \
+             even while disassociated. This hardware and macOS release do not permit Wi-Fi \
+             MAC spoofing; use the per-network Private Wi-Fi Address setting instead",
+            model, interface
+        ),
+        SpoofCapability::WhileDisassociated => format!(
+            "The Wi-Fi driver on this Mac ({}) rejected the new address for {} while disassociated",
+            model, interface
+        ),
+        SpoofCapability::Supported => format!(
+            "The driver for {} does not support changing its MAC address",
+            interface
+        ),
+        SpoofCapability::Unsupported(reason) => reason.clone(),
+    }
+}
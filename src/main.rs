@@ -3,27 +3,83 @@ mod error;
 mod mac;
 mod network;
 mod platform;
+mod backend;
 mod config;
 mod geolocation;
 mod filter;
 mod logger;
 mod rules;
 mod oui;
+mod escrow;
+mod status;
+mod sandbox;
+mod chaos;
+mod profiles;
+mod transaction;
+mod watch;
+mod daemon;
+mod timeline;
+mod recovery;
+mod personas;
+mod capabilities;
+mod migrate;
+mod inspect;
+mod detect;
+mod change_window;
+mod hooks;
+mod journal;
+mod renew;
+mod output;
+mod history;
+mod system_log;
+mod collision;
+mod identity;
+mod netns;
+mod api;
+mod settings;
+mod lock;
+mod boot;
+mod apps;
+mod bundle;
+mod metrics;
+#[cfg(target_os = "linux")]
+mod bluetooth;
+#[cfg(target_os = "linux")]
+mod dbus;
+#[cfg(target_os = "linux")]
+mod networkmanager;
+#[cfg(target_os = "linux")]
+mod permanence;
+#[cfg(target_os = "windows")]
+mod win32;
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+mod bsd;
 
 use crate::geolocation::GeoLocationService;
-use crate::filter::MacFilter;
-use crate::logger::{MacLogger, MacChange};
+use crate::filter::{FilterFormat, FilterList, MacFilter};
+use crate::logger::{HistoryFilter, MacLogger, MacChange};
 
-use clap::{Parser, ArgGroup};
+use clap::{Parser, Subcommand, ArgGroup};
 use error::MacError;
-use mac::{MacAddress, MacFormat};
-use network::NetworkCard;
-use platform::change_mac;
-use config::{save_original_mac, get_original_mac};
+use mac::{MacAddress, MacFormat, RandomPolicy};
+use network::{NetworkCard, RfkillState};
+use platform::{apply_mac, change_mac};
+use config::{save_original_mac, get_original_mac, get_factory_mac};
 use std::error::Error;
-use chrono::Utc;
-use crate::platform::get_running_applications;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use crate::rules::{AppRule, RuleManager, Schedule};
+use crate::escrow::{EscrowRecord, SignedEscrow};
+use crate::transaction::{ChangeSummary, Plan, Transaction};
+use crate::output::{OutputFormat, progress};
+use serde::Serialize;
+use crate::hooks::{Hook, HookAction, HookManager, Trigger};
+use crate::settings::{RandomPolicyName, Settings};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -31,22 +87,30 @@ use crate::rules::{AppRule, RuleManager, Schedule};
     about = "A tool to change MAC addresses across different platforms",
     version = "1.0.0",
     author = "Nathan Moritz <nathan.moritz@duocore.dev>",
-    long_about = None
+    long_about = None,
+    subcommand_negates_reqs = true,
+    args_conflicts_with_subcommands = true
 )]
 #[command(group(
     ArgGroup::new("mac_source")
-        .args(["random", "mac", "restore"])
+        .args(["random", "mac", "restore", "random_from_vendor", "persona"])
         .required(true)
 ))]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
     /// Network interface to modify
     #[arg(
         short = 'i',
         long = "interface",
-        required = true,
-        help = "Network interface (e.g., eth0, wlan0)"
+        help = "Network interface (e.g., eth0, wlan0); defaults to `interface` in config.toml"
     )]
-    interface: String,
+    interface: Option<String>,
+
+    /// Change an interface inside this Linux network namespace: a name from `ip netns` or a PID in it
+    #[arg(long, value_name = "NAME|PID", conflicts_with_all = ["permanent", "spoof_hostname"])]
+    netns: Option<String>,
 
     /// Generate a random MAC address
     #[arg(
@@ -76,6 +140,10 @@ struct Cli {
     )]
     permanent: bool,
 
+    /// Keep this change temporary even if config.toml makes changes permanent
+    #[arg(long, conflicts_with = "permanent")]
+    no_permanent: bool,
+
     /// Use a specific vendor prefix
     #[arg(
         short = 'v',
@@ -87,17 +155,122 @@ struct Cli {
     )]
     vendor: Option<String>,
 
+    /// Generate a random MAC under a prefix owned by a vendor
+    #[arg(
+        long = "random-from-vendor",
+        value_name = "COMPANY",
+        help = "Generate a random MAC under a randomly chosen prefix registered to COMPANY",
+        conflicts_with_all = ["random", "mac", "restore", "vendor"]
+    )]
+    random_from_vendor: Option<String>,
+
+    /// Generate a MAC that looks like a common device
+    #[arg(
+        long = "persona",
+        value_name = "DEVICE",
+        help = "Generate a MAC that looks like a common device (e.g., iphone, galaxy, dell-xps, raspberry-pi, random)",
+        conflicts_with_all = ["random", "mac", "restore", "vendor", "random_from_vendor"]
+    )]
+    persona: Option<String>,
+
+    /// Keep the current OUI and randomize the lower 3 bytes
+    #[arg(
+        long = "preserve-oui",
+        help = "Keep the current vendor prefix and randomize only the lower 3 bytes",
+        requires = "random",
+        conflicts_with_all = ["vendor", "spoof_location"]
+    )]
+    preserve_oui: bool,
+
+    /// Use a real manufacturer OUI
+    #[arg(
+        long = "burned-in-like",
+        help = "Use a real manufacturer prefix so the MAC doesn't look randomized",
+        requires = "random",
+        conflicts_with_all = ["vendor", "preserve_oui", "spoof_location"]
+    )]
+    burned_in_like: bool,
+
+    /// Apply the MAC even if the whitelist/blacklist rejects it
+    #[arg(long, conflicts_with = "restore")]
+    force: bool,
+
     /// Restore original MAC
     #[arg(
         short = 'o',
         long = "restore",
         help = "Restore the original MAC address",
-        conflicts_with_all = ["random", "mac", "permanent", "vendor"]
+        conflicts_with_all = ["random", "mac", "permanent", "vendor", "random_from_vendor", "persona"]
     )]
     restore: bool,
 
+    /// With --restore, put back the address burned into the hardware instead of the saved original
+    #[arg(long, requires = "restore")]
+    factory: bool,
+
+    /// Also set a random hostname, from TEMPLATE if given (# digit, ? letter, * either)
+    #[arg(long, value_name = "TEMPLATE", num_args = 0..=1, default_missing_value = "", conflicts_with = "restore")]
+    spoof_hostname: Option<String>,
+
+    /// Also set a DHCP client ID derived from the new MAC
+    #[arg(long, conflicts_with = "restore")]
+    spoof_dhcp_id: bool,
+
+    /// Print the change summary as JSON (same as --output json)
+    #[arg(long)]
+    json: bool,
+
+    /// Print results as text or JSON; with JSON, progress goes to stderr
+    #[arg(long, value_enum, global = true, default_value = "text")]
+    output: OutputFormat,
+
+    /// Wait for another chameleon process changing the same interface (the default)
+    #[arg(long, global = true)]
+    wait: bool,
+
+    /// Fail instead of waiting when another chameleon process is changing the interface
+    #[arg(long, global = true, conflicts_with = "wait")]
+    no_wait: bool,
+
+    /// Leave Wi-Fi interfaces disconnected after the change instead of reassociating
+    #[arg(long, global = true)]
+    no_reconnect: bool,
+
+    /// Skip renewing DHCP and flushing neighbors after the change
+    #[arg(long)]
+    no_renew: bool,
+
+    /// Skip checking the network for another device already using the new MAC
+    #[arg(long, conflicts_with = "restore")]
+    no_collision_check: bool,
+
+    /// Validate and show what would change, without changing anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Ask the chameleon D-Bus service to make the change, authenticating through polkit instead of needing root (Linux)
+    #[arg(long, conflicts_with_all = [
+        "netns", "vendor", "random_from_vendor", "persona", "preserve_oui", "burned_in_like", "spoof_location",
+        "spoof_hostname", "spoof_dhcp_id", "dry_run", "watch", "whitelist", "blacklist", "history", "factory",
+    ])]
+    via_dbus: bool,
+
+    /// Keep running and re-apply the MAC whenever the driver reverts it
+    #[arg(long, conflicts_with = "restore")]
+    watch: bool,
+
+    /// How often --watch checks the interface when no link event arrives
+    #[arg(long, value_parser = parse_duration, default_value = "5s", requires = "watch")]
+    watch_interval: Duration,
+
     /// Spoof location to specific country
-    #[arg(long, value_name = "COUNTRY")]
+    #[arg(
+        long,
+        value_name = "COUNTRY",
+        help = "Use a random prefix registered in COUNTRY (two-letter code, e.g., US)",
+        requires = "random",
+        conflicts_with = "vendor"
+    )]
     spoof_location: Option<String>,
 
     /// Add MAC prefix to whitelist
@@ -112,222 +285,2497 @@ struct Cli {
     #[arg(long)]
     history: bool,
 
-    /// Add application-specific MAC rule
+    /// Interleave link up/down events recorded by the daemon with the history
+    #[arg(long, requires = "history")]
+    detailed: bool,
+
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Export, verify or import original-MAC escrow records
+    Escrow {
+        #[command(subcommand)]
+        action: EscrowAction,
+    },
+
+    /// List network interfaces chameleon can operate on
+    List {
+        /// Only show wireless interfaces
+        #[arg(long)]
+        wireless_only: bool,
+
+        /// Only show interfaces that are up
+        #[arg(long)]
+        up_only: bool,
+
+        /// List the interfaces of this Linux network namespace: a name from `ip netns` or a PID in it
+        #[arg(long, value_name = "NAME|PID")]
+        netns: Option<String>,
+    },
+
+    /// Randomize a lab interface's MAC on an aggressive schedule
+    Chaos {
+        /// Sandboxed interface to randomize
+        #[arg(short = 'i', long = "interface")]
+        interface: String,
+
+        /// Time between changes (e.g. 30s, 5m)
+        #[arg(long, value_parser = parse_duration, default_value = "30s")]
+        interval: Duration,
+
+        /// Random deviation applied to each interval
+        #[arg(long, value_parser = parse_duration, default_value = "0s")]
+        jitter: Duration,
+
+        /// Stop after this many changes
+        #[arg(long)]
+        iterations: Option<u64>,
+    },
+
+    /// Manage the list of lab interfaces chaos mode may touch
+    Sandbox {
+        #[command(subcommand)]
+        action: SandboxAction,
+    },
+
+    /// Restrict when interfaces may have their MAC changed
+    Window {
+        #[command(subcommand)]
+        action: WindowAction,
+    },
+
+    /// Inspect or undo MAC changes made with --permanent
+    Permanent {
+        #[command(subcommand)]
+        action: PermanentAction,
+    },
+
+    /// Give an interface a new random MAC at every boot, before the network comes up
+    InstallBootRandomization {
+        #[arg(short, long)]
+        interface: String,
+
+        /// How the MAC is built; without it, config.toml's random_policy or a locally administered MAC
+        #[arg(long, value_enum)]
+        policy: Option<RandomPolicyName>,
+
+        /// Show what would be installed without installing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Stop randomizing an interface at boot
+    UninstallBootRandomization {
+        #[arg(short, long)]
+        interface: String,
+
+        /// Show what would be removed without removing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Run commands or webhooks after MAC changes
+    Hooks {
+        #[command(subcommand)]
+        action: HookCommand,
+    },
+
+    /// Show, edit or check config.toml
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Save and apply named MAC profiles
+    Profile {
+        #[command(subcommand)]
+        action: ProfileAction,
+    },
+
+    /// Run in the background, applying rules and recording link state
+    Daemon {
+        /// Also manage these interfaces (interfaces with rules are always managed)
+        #[arg(short = 'i', long = "interface")]
+        interfaces: Vec<String>,
+
+        /// How often rules are evaluated [default: daemon.interval in config.toml, or 30s]
+        #[arg(long, value_parser = parse_duration)]
+        interval: Option<Duration>,
+
+        /// Serve Prometheus metrics at http://ADDR/metrics, without authentication
+        #[arg(long, value_name = "ADDR")]
+        metrics_listen: Option<SocketAddr>,
+    },
+
+    /// Changes, failures and re-applies per interface and rule activations, for alerting
+    Stats {
+        /// Print the Prometheus text format that `/metrics` serves
+        #[arg(long)]
+        prometheus: bool,
+    },
+
+    /// Show, export or summarize the MAC change history, including rotated logs
+    History {
+        #[command(flatten)]
+        filter: HistoryArgs,
+
+        #[command(subcommand)]
+        action: Option<HistoryAction>,
+    },
+
+    /// Re-apply an earlier MAC from the history
+    Rollback {
+        #[arg(short, long)]
+        interface: String,
+
+        /// Go back this many changes
+        #[arg(long, default_value_t = 1, conflicts_with = "to")]
+        steps: usize,
+
+        /// Go back to the MAC in place at this time: a duration ago (2h, 7d), a date or "YYYY-MM-DD HH:MM"
+        #[arg(long, value_parser = parse_since)]
+        to: Option<DateTime<Utc>>,
+
+        /// Make the MAC change permanent
+        #[arg(short, long)]
+        permanent: bool,
+
+        /// Apply without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Apply the MAC even if the whitelist/blacklist rejects it
+        #[arg(long)]
+        force: bool,
+
+        /// Skip renewing DHCP and flushing neighbors after the change
+        #[arg(long)]
+        no_renew: bool,
+
+        /// Show what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Also record MAC changes in journald/syslog or the Windows Event Log
+    SystemLog {
+        #[command(subcommand)]
+        action: SystemLogAction,
+    },
+
+    /// Show MAC changes and link up/down events in time order
+    Timeline {
+        /// Only show this interface
+        #[arg(short = 'i', long = "interface")]
+        interface: Option<String>,
+    },
+
+    /// Replay what the daemon did: rule firings, deferrals, reversions, link changes and errors
+    Events {
+        /// Only show events from this point on: a duration ago (30m, 2h, 7d), a date or "YYYY-MM-DD HH:MM"
+        #[arg(long, value_parser = parse_since)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only show this interface
+        #[arg(short = 'i', long = "interface")]
+        interface: Option<String>,
+
+        /// Print one JSON object per line
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Manage and query the OUI vendor database
+    Oui {
+        #[command(subcommand)]
+        action: OuiAction,
+    },
+
+    /// Manage rules that change MACs based on applications, networks and schedules
+    Rules {
+        #[command(subcommand)]
+        action: RuleAction,
+    },
+
+    /// Manage the MAC whitelist and blacklist
+    Filter {
+        #[command(subcommand)]
+        action: FilterAction,
+    },
+
+    /// Import spoofing configuration from macchanger or TMAC
+    Migrate {
+        /// Tool to migrate from
+        #[arg(long, value_enum)]
+        from: migrate::Source,
+
+        /// Remove the old tool's configuration after importing it
+        #[arg(long)]
+        remove_old: bool,
+
+        /// Show what would be imported and removed without doing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Restore every original MAC, remove persistence and disable all rules
+    PanicRestore {
+        /// Show what would be restored and removed without doing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Write original MACs, filters, rules, profiles, hooks and settings to one file
+    Export {
+        /// Bundle to write; a .age path is encrypted with the age tool
+        path: PathBuf,
+
+        /// Encrypt for this age recipient instead of a passphrase (repeatable)
+        #[arg(long)]
+        recipient: Vec<String>,
+    },
+
+    /// Restore configuration from a bundle written by export
+    Import {
+        path: PathBuf,
+
+        /// age identity file for a bundle encrypted to a recipient
+        #[arg(long)]
+        identity: Option<PathBuf>,
+
+        /// Show what the bundle holds without importing it
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Import hooks without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Show which optional system tools were found and what is used without them
+    Capabilities,
+
+    /// Flag likely spoofed MACs on local interfaces or in a switch CAM table export
+    Detect {
+        /// Check the MACs in this file (a MAC list or CAM table export, "-" for stdin) instead of local interfaces
+        #[arg(long, value_name = "FILE")]
+        cam_table: Option<PathBuf>,
+
+        /// Write the JSON report to a file instead of stdout
+        #[arg(long, value_name = "FILE")]
+        out: Option<PathBuf>,
+
+        /// Only report flagged addresses
+        #[arg(long)]
+        flagged_only: bool,
+    },
+
+    /// Read and change Bluetooth controller addresses (Linux)
+    Bt {
+        #[command(subcommand)]
+        action: BtAction,
+    },
+
+    /// Serve an authenticated HTTP API for listing interfaces, changing MACs, history and rules
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        listen: SocketAddr,
+
+        /// Serve HTTPS with this certificate chain (PEM, Linux)
+        #[arg(long, value_name = "FILE", requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+
+        /// Private key for --tls-cert (PEM, Linux)
+        #[arg(long, value_name = "FILE", requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+
+        /// Require client certificates signed by this CA instead of tokens (Linux)
+        #[arg(long, value_name = "FILE", requires = "tls_cert")]
+        client_ca: Option<PathBuf>,
+
+        /// Accept bearer tokens over plain HTTP on a non-loopback address
+        #[arg(long, conflicts_with = "tls_cert")]
+        insecure: bool,
+    },
+
+    /// Run or install the system D-Bus service desktop tools and --via-dbus use (Linux)
+    Dbus {
+        #[command(subcommand)]
+        action: DbusAction,
+    },
+
+    /// Manage the bearer tokens `serve` accepts
+    ApiToken {
+        #[command(subcommand)]
+        action: ApiTokenAction,
+    },
+
+    /// Show current, original and permanent MAC addresses
+    Status {
+        /// Only show this interface
+        #[arg(short = 'i', long = "interface")]
+        interface: Option<String>,
+
+        /// Show the interfaces of this Linux network namespace: a name from `ip netns` or a PID in it
+        #[arg(long, value_name = "NAME|PID")]
+        netns: Option<String>,
+
+        /// Print machine-readable JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ProfileAction {
+    /// Save a named profile
+    Save {
+        /// Profile name
+        name: String,
+
+        /// Interface the profile applies to
+        #[arg(short = 'i', long = "interface")]
+        interface: String,
+
+        /// Fixed MAC address; without it a random MAC is generated on apply
+        #[arg(short = 'm', long = "mac", value_name = "MAC")]
+        mac: Option<String>,
+
+        /// Vendor prefix for generated MACs
+        #[arg(short = 'v', long = "vendor", value_name = "VENDOR", conflicts_with = "mac")]
+        vendor: Option<String>,
+
+        /// Hostname to set when the profile is applied
+        #[arg(long)]
+        hostname: Option<String>,
+
+        /// How often a random MAC is regenerated
+        #[arg(long, value_enum, default_value = "per-connect", conflicts_with = "mac")]
+        regenerate: profiles::Regenerate,
+    },
+
+    /// Apply a saved profile
+    Apply {
+        name: String,
+
+        /// Make the MAC address change permanent
+        #[arg(short = 'p', long = "permanent")]
+        permanent: bool,
+
+        /// Print the change summary as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Apply the MAC even if the whitelist/blacklist rejects it
+        #[arg(long)]
+        force: bool,
+
+        /// Skip renewing DHCP and flushing neighbors after the change
+        #[arg(long)]
+        no_renew: bool,
+
+        /// Show what applying the profile would change without changing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// List saved profiles
+    List,
+
+    /// Delete a profile
+    Remove { name: String },
+
+    /// Export one or all profiles as JSON
+    Export {
+        name: Option<String>,
+
+        /// Write to a file instead of stdout
+        #[arg(long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum OuiAction {
+    /// Download (or read a local copy of) the vendor registry
+    Update {
+        /// Registry to build the database from
+        #[arg(long, default_value = "ieee", value_parser = ["ieee", "manuf"])]
+        source: String,
+
+        /// Try this mirror when a file cannot be downloaded, before oui.mirrors in config.toml
+        #[arg(long, value_name = "URL")]
+        mirror: Vec<String>,
+
+        /// Build the database from these copies of the registry files instead of downloading
+        #[arg(long, value_name = "FILE", num_args = 1.., conflicts_with = "mirror")]
+        offline: Vec<PathBuf>,
+    },
+
+    /// Show the vendor that owns a MAC address or prefix
+    Lookup { mac: String },
+
+    /// List prefixes registered to vendors matching a name
+    Search { query: String },
+}
+
+/// Boxed in `RuleAction::Add`, which would otherwise dwarf the other
+/// actions.
+#[derive(clap::Args, Debug)]
+#[command(group(ArgGroup::new("rule_action").args(["mac", "profile", "exempt"]).required(true)))]
+struct RuleAddArgs {
+    /// Interface to manage, or "*" for every interface matching the class options
+    #[arg(short = 'i', long = "interface")]
+    interface: String,
+
+    /// Only match while this application is running
+    #[arg(long, value_name = "APP")]
+    app: Option<String>,
+
+    /// Look for --app in the process name (default), the executable's path or a window title
+    #[arg(long, value_enum, requires = "app", default_value = "name")]
+    app_field: rules::AppField,
+
+    /// Compare --app exactly (default), as a case-insensitive substring or as a regular expression
+    #[arg(long, value_enum, requires = "app", default_value = "exact")]
+    app_match: rules::AppMatch,
+
+    /// MAC address to apply
+    #[arg(short = 'm', long = "mac", value_name = "MAC")]
+    mac: Option<String>,
+
+    /// Apply this profile instead of a fixed MAC
     #[arg(long)]
-    add_rule: bool,
+    profile: Option<String>,
 
-    /// Application name for rule
+    /// Leave matching interfaces alone, overriding other rules
     #[arg(long)]
-    app_name: Option<String>,
+    exempt: bool,
+
+    /// Only match while joined to this Wi-Fi network
+    #[arg(long, conflicts_with = "bssid")]
+    ssid: Option<String>,
 
-    /// Service name for rule (optional)
+    /// Only match while joined to this access point
     #[arg(long)]
-    service_name: Option<String>,
+    bssid: Option<MacAddress>,
 
-    /// Schedule for rule (days:start-end), e.g., "mon,tue,wed:09:00-17:00"
+    /// Only match wired or wireless interfaces
+    #[arg(long, value_enum)]
+    medium: Option<rules::Medium>,
+
+    /// Only match interfaces at least this fast
+    #[arg(long, value_name = "MBPS")]
+    min_speed: Option<u64>,
+
+    /// Only match interfaces that are (true) or are not (false) attached through a dock
     #[arg(long)]
-    schedule: Option<String>,
+    dock: Option<bool>,
+
+    /// When the rule applies, e.g. "mon-fri:09:00-12:00,13:00-17:00" or "fri,sat:22:00-06:00"
+    #[arg(long, value_parser = Schedule::parse, conflicts_with = "cron")]
+    schedule: Option<Schedule>,
+
+    /// Apply during minutes matching a cron expression, e.g. "* 22-23,0-5 * * *"
+    #[arg(long, value_parser = Schedule::from_cron, value_name = "EXPR")]
+    cron: Option<Schedule>,
 
-    /// List all application rules
+    /// Name to save the rule under (default: APP:INTERFACE, or INTERFACE without --app)
     #[arg(long)]
-    list_rules: bool,
+    name: Option<String>,
 
-    /// Remove application rule
+    /// Save the rule switched off
     #[arg(long)]
-    remove_rule: bool,
+    disabled: bool,
 }
 
-impl Cli {
-    fn validate(&self) -> Result<(), MacError> {
-        // Validate interface
-        if self.interface.is_empty() {
-            return Err(MacError::ValidationFailed("Interface name cannot be empty".into()));
-        }
+#[derive(Subcommand, Debug)]
+enum RuleAction {
+    /// Add a rule, or replace the rule with the same name
+    Add(Box<RuleAddArgs>),
 
-        // Validate MAC if provided
-        if let Some(mac) = &self.mac {
-            if !is_valid_mac_format(mac) {
-                return Err(MacError::InvalidFormat(
-                    "Invalid MAC address format. Use XX:XX:XX:XX:XX:XX".into()
-                ));
-            }
-        }
+    /// Show all rules
+    List,
 
-        // Validate vendor if provided
-        if let Some(vendor) = &self.vendor {
-            if !is_valid_vendor_format(vendor) {
-                return Err(MacError::InvalidFormat(
-                    "Invalid vendor prefix format. Use XX:XX:XX".into()
-                ));
-            }
-        }
+    /// Delete a rule
+    Remove { name: String },
 
-        Ok(())
-    }
-}
+    /// Switch a rule on
+    Enable { name: String },
 
-fn is_valid_mac_format(mac: &str) -> bool {
-    let re = regex::Regex::new(r"^([0-9A-Fa-f]{2}[:-]){5}([0-9A-Fa-f]{2})$").unwrap();
-    re.is_match(mac)
+    /// Switch a rule off without deleting it
+    Disable { name: String },
 }
 
-fn is_valid_vendor_format(vendor: &str) -> bool {
-    let re = regex::Regex::new(r"^([0-9A-Fa-f]{2}[:-]){2}([0-9A-Fa-f]{2})$").unwrap();
-    re.is_match(vendor)
-}
+#[derive(Subcommand, Debug)]
+enum FilterAction {
+    /// Add a prefix (XX:XX:XX) or full MAC to a list
+    Add {
+        entry: String,
 
-fn check_privileges() -> Result<(), MacError> {
-    #[cfg(unix)]
-    {
-        if !nix::unistd::Uid::effective().is_root() {
-            return Err(MacError::PermissionDenied("Must run with root privileges".into()));
-        }
-    }
+        #[arg(long, value_enum)]
+        list: FilterList,
+    },
 
-    #[cfg(windows)]
-    {
-        if !is_elevated::is_elevated() {
-            return Err(MacError::PermissionDenied("Must run with administrator privileges".into()));
-        }
-    }
+    /// Show both lists
+    List,
 
-    Ok(())
+    /// Remove a prefix or MAC from the lists
+    Remove {
+        entry: String,
+
+        /// Only remove it from this list
+        #[arg(long, value_enum)]
+        list: Option<FilterList>,
+    },
+
+    /// Empty the lists
+    Clear {
+        /// Only empty this list
+        #[arg(long, value_enum)]
+        list: Option<FilterList>,
+    },
+
+    /// Add entries from a file
+    Import {
+        file: PathBuf,
+
+        /// List to add a line-based file to
+        #[arg(long, value_enum)]
+        list: Option<FilterList>,
+
+        /// File format (default: json for .json files, lines otherwise)
+        #[arg(long, value_enum)]
+        format: Option<FilterFormat>,
+    },
+
+    /// Write entries to a file
+    Export {
+        file: PathBuf,
+
+        /// Only export this list (required for the lines format)
+        #[arg(long, value_enum)]
+        list: Option<FilterList>,
+
+        /// File format (default: json for .json files, lines otherwise)
+        #[arg(long, value_enum)]
+        format: Option<FilterFormat>,
+    },
 }
 
-// Inside src/main.rs
+#[derive(Subcommand, Debug)]
+enum SandboxAction {
+    /// Mark an interface as a sandbox interface
+    Add { interface: String },
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {  // Change return type to use dyn Error
-    let cli = Cli::parse();
+    /// Remove an interface from the sandbox list
+    Remove { interface: String },
 
-    // Validate arguments
-    cli.validate()?;  // MacError will automatically convert to Box<dyn Error>
+    /// Show all sandbox interfaces
+    List,
+}
 
-    // Check privileges
-    check_privileges()?;
+#[derive(Subcommand, Debug)]
+enum WindowAction {
+    /// Only allow changes to an interface during a window, replacing any previous one
+    #[command(group(ArgGroup::new("window").args(["schedule", "cron"]).required(true)))]
+    Set {
+        interface: String,
 
-    // Initialize services
-    let mut geo_service = GeoLocationService::new();
-    let mut oui_db = oui::OUIDatabase::new()?;
-    let mut mac_filter = MacFilter::new();
-    let mac_logger = MacLogger::new();
-    let mut rule_manager = RuleManager::new()?;
+        /// Allowed times, e.g. "mon-fri:00:00-05:00" or "sat,sun:22:00-06:00"
+        #[arg(long, value_parser = Schedule::parse)]
+        schedule: Option<Schedule>,
 
-    let provided_mac = cli.mac.clone();
+        /// Allow changes during minutes matching a cron expression
+        #[arg(long, value_parser = Schedule::from_cron, value_name = "EXPR")]
+        cron: Option<Schedule>,
+    },
 
-    // Verify interface
-    let card = NetworkCard::verify_interface(&cli.interface)?;
-    println!("Detected network card: {:?}", card);
+    /// Allow changes to an interface at any time again
+    Remove { interface: String },
 
-    if cli.restore {
-        match get_original_mac(&cli.interface)? {
-            Some(original_mac) => {
-                println!("Restoring original MAC address: {}", original_mac);
-                change_mac(&cli.interface, &original_mac, false)?;
-                println!("Successfully restored original MAC address");
-            }
-            None => {
-                return Err(MacError::ValidationFailed(
-                    "No original MAC address saved".into()
-                ).into());  // Use .into() to convert to Box<dyn Error>
-            }
-        }
-        return Ok(());
-    }
+    /// Show all change windows and whether they are open
+    List,
+}
 
-    // Get new MAC address
-    let new_mac = if cli.random {
-        println!("Generating random MAC address{}...",
-                 if cli.vendor.is_some() { " with vendor prefix" } else { "" });
-        mac::generate_random_mac(cli.vendor.as_deref())?.to_string()
-    } else if let Some(mac) = cli.mac {
-        mac
-    } else {
-        return Err(MacError::ValidationFailed(
-            "No MAC address specified".into()
-        ).into());
-    };
+#[derive(Subcommand, Debug)]
+enum PermanentAction {
+    /// Show every permanent MAC installed for an interface and where it lives
+    Show {
+        #[arg(short, long)]
+        interface: String,
+    },
 
-    // Save original MAC if first time
-    if get_original_mac(&cli.interface)?.is_none() {
-        match network::get_current_mac(&cli.interface) {
-            Ok(current_mac) => {
-                println!("Saving original MAC address: {}", current_mac);
-                save_original_mac(&cli.interface, &current_mac)?;
-            },
-            Err(e) => {
-                println!("Warning: Could not save original MAC address: {}", e);
-            }
-        }
-    }
+    /// Remove every permanent MAC for an interface; the current MAC stays until the next boot or --restore
+    Remove {
+        #[arg(short, long)]
+        interface: String,
 
-    // Platform-specific permanent flag handling
-    #[cfg(not(target_os = "macos"))]
-    let permanent = cli.permanent;
+        /// Show what would be removed without removing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
 
-    #[cfg(target_os = "macos")]
-    let permanent = {
-        if cli.permanent {
-            println!("Warning: Permanent MAC address changes are not supported on macOS.");
-            println!("Continuing with temporary change...");
-        }
-        false
-    };
+/// Which changes `history` works on; accepted before or after its subcommand.
+#[derive(clap::Args, Debug)]
+struct HistoryArgs {
+    /// Only changes of this interface
+    #[arg(short = 'i', long = "interface", global = true)]
+    interface: Option<String>,
 
-    // Handle filter commands
-    if let Some(prefix) = cli.whitelist {
-        mac_filter.add_to_whitelist(&prefix)?;
-        println!("Added {} to whitelist", prefix);
-        return Ok(());
-    }
+    /// Only changes from this point on: a duration ago (30m, 2h, 7d), a date or "YYYY-MM-DD HH:MM"
+    #[arg(long, value_parser = parse_since, global = true)]
+    since: Option<DateTime<Utc>>,
 
-    if let Some(prefix) = cli.blacklist {
-        mac_filter.add_to_blacklist(&prefix)?;
-        println!("Added {} to blacklist", prefix);
-        return Ok(());
-    }
+    /// Only changes up to this point, in the same formats as --since
+    #[arg(long, value_parser = parse_since, global = true)]
+    until: Option<DateTime<Utc>>,
 
-    if cli.history {
-        let history = mac_logger.get_history()?;
-        for change in history {
-            println!("{}: {} -> {} ({})",
-                     change.timestamp,
-                     change.old_mac,
-                     change.new_mac,
-                     change.interface
-            );
-        }
-        return Ok(());
-    }
+    /// Only the newest N matching changes
+    #[arg(long, value_name = "N", global = true)]
+    last: Option<usize>,
+}
 
-    // Check application rules
-    let running_apps = get_running_applications()?;
-    for rule in rule_manager.list_rules() {
-        if rule.interface == cli.interface &&
-            running_apps.contains(&rule.app_name) &&
-            rule_manager.is_rule_active(&rule) {
-            println!("Found active rule for running application: {}", rule.app_name);
-            println!("Using rule-specified MAC address: {}", rule.mac_address);
-            return change_mac(&cli.interface, &rule.mac_address, permanent);
-        }
+impl From<HistoryArgs> for HistoryFilter {
+    fn from(args: HistoryArgs) -> Self {
+        HistoryFilter { interface: args.interface, since: args.since, until: args.until, last: args.last }
     }
+}
 
-    // Get current MAC for logging
-    let old_mac = network::get_current_mac(&cli.interface)?;
+#[derive(Subcommand, Debug)]
+enum HistoryAction {
+    /// Write the history as CSV or JSON
+    Export {
+        #[arg(long, value_enum, default_value = "json")]
+        format: history::ExportFormat,
+
+        /// Write to a file instead of stdout
+        #[arg(long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+
+    /// Changes per interface, most used vendors and average time between changes
+    Stats,
+
+    /// Check the hash chain for modified or deleted entries across all history files
+    Verify {
+        /// Also check a head saved by `history sign`, proving entries up to it weren't rewritten
+        #[arg(long, value_name = "FILE", requires = "key")]
+        head: Option<PathBuf>,
+
+        /// Trusted Ed25519 public key (PEM) for the signed head
+        #[arg(long, value_name = "KEY", requires = "head")]
+        key: Option<PathBuf>,
+    },
+
+    /// Sign the newest entry so the history up to it can be proven later
+    Sign {
+        /// Ed25519 private key (PKCS#8 PEM)
+        #[arg(long, value_name = "KEY")]
+        key: PathBuf,
+
+        /// Where to save the signed head; keep a copy off this machine
+        #[arg(long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ApiTokenAction {
+    /// Create a token; it is printed once and only its hash is kept
+    Add {
+        /// Logged as the principal of every change made with the token
+        name: String,
+    },
+
+    List,
+
+    /// Revoke a token
+    Remove {
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DbusAction {
+    /// Serve dev.duocore.Chameleon on the system bus; D-Bus activation runs this
+    Serve,
+
+    /// Install the bus policy, activation file and polkit actions
+    Install {
+        /// Print the files instead of writing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Remove the files `install` wrote
+    Uninstall,
+}
+
+#[derive(Subcommand, Debug)]
+enum BtAction {
+    /// List controllers with their address and how it can be changed
+    List,
+
+    /// Change a controller's address
+    #[command(group(ArgGroup::new("bt_source").args(["mac", "random"]).required(true)))]
+    Set {
+        #[arg(short = 'i', long, default_value = "hci0")]
+        controller: String,
+
+        /// The new address
+        #[arg(short = 'm', long)]
+        mac: Option<String>,
+
+        /// A random address under the controller's own manufacturer prefix
+        #[arg(short = 'r', long)]
+        random: bool,
+
+        /// Show the commands that would run without running them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Put back the address the controller had before the first change
+    Restore {
+        #[arg(short = 'i', long, default_value = "hci0")]
+        controller: String,
+
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SystemLogAction {
+    /// Send every MAC change to the system log as well as the file log
+    Enable {
+        #[arg(long, value_enum, default_value = "notice")]
+        severity: system_log::Severity,
+    },
+
+    /// Only write the file log
+    Disable,
+
+    /// Show where MAC changes are logged
+    Show,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Print the settings in effect, with the user's file merged over the system one
+    Show,
+
+    /// Open the config file in $VISUAL or $EDITOR, then check it
+    Edit {
+        /// Edit /etc/chameleon/config.toml instead of the user's file
+        #[arg(long)]
+        system: bool,
+    },
+
+    /// Check both config files for errors
+    Validate,
+}
+
+#[derive(Subcommand, Debug)]
+enum HookCommand {
+    /// Register a hook, or replace the hook with the same name
+    #[command(group(ArgGroup::new("hook_action").args(["command", "webhook"]).required(true)))]
+    Add {
+        name: String,
+
+        /// Shell command to run, with the change in CHAMELEON_* environment variables
+        #[arg(long)]
+        command: Option<String>,
+
+        /// URL to POST the change to as JSON
+        #[arg(long, value_name = "URL")]
+        webhook: Option<String>,
+
+        /// Run after successful changes, failed ones, or both
+        #[arg(long, value_enum, default_value_t = hooks::HookOn::Success)]
+        on: hooks::HookOn,
+
+        /// Only run for changes to this interface
+        #[arg(short = 'i', long = "interface")]
+        interface: Option<String>,
+    },
+
+    /// Show all hooks in the order they run
+    List,
+
+    /// Delete a hook
+    Remove { name: String },
+
+    /// Run a hook once with a sample change
+    Test { name: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum EscrowAction {
+    /// Export this machine's hardware and current MAC addresses
+    Export {
+        /// Ed25519 private key (PKCS#8 PEM) used to sign the record
+        #[arg(long, value_name = "KEY")]
+        sign: Option<PathBuf>,
+
+        /// Write the record to a file instead of stdout
+        #[arg(long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+
+    /// Verify the signature of an escrow record
+    Verify {
+        /// Escrow record to verify
+        file: PathBuf,
+
+        /// Trusted Ed25519 public key (PEM)
+        #[arg(long, value_name = "KEY")]
+        key: PathBuf,
+    },
+
+    /// Verify and store an escrow record in the local escrow directory
+    Import {
+        /// Escrow record to import
+        file: PathBuf,
+
+        /// Trusted Ed25519 public key (PEM); unsigned records require --allow-unsigned
+        #[arg(long, value_name = "KEY")]
+        key: Option<PathBuf>,
+
+        /// Import the record without verifying a signature
+        #[arg(long, conflicts_with = "key")]
+        allow_unsigned: bool,
+    },
+}
+
+impl Cli {
+    /// The network namespace to work in, from whichever command takes one.
+    fn netns(&self) -> Option<&str> {
+        match &self.command {
+            Some(Commands::List { netns, .. } | Commands::Status { netns, .. }) => netns.as_deref(),
+            Some(_) => None,
+            None => self.netns.as_deref(),
+        }
+    }
+
+    /// Fill in the defaults config.toml sets for whatever no flag chose.
+    fn apply_settings(&mut self, settings: &Settings) {
+        if self.interface.is_none() {
+            self.interface = settings.interface.clone();
+        }
+        let Some(interface) = &self.interface else {
+            return;
+        };
+        let defaults = settings.for_interface(interface);
+
+        let policy_chosen = self.preserve_oui || self.burned_in_like || self.vendor.is_some() || self.spoof_location.is_some();
+        if self.random && !policy_chosen {
+            match defaults.random_policy {
+                Some(RandomPolicyName::PreserveOui) => self.preserve_oui = true,
+                Some(RandomPolicyName::BurnedInLike) => self.burned_in_like = true,
+                _ => self.vendor = defaults.vendor,
+            }
+        }
+
+        // --netns and --restore cannot be permanent
+        if defaults.permanent == Some(true) && !self.no_permanent && !self.restore && self.netns.is_none() {
+            self.permanent = true;
+        }
+    }
+
+    fn validate(&self) -> Result<(), MacError> {
+        // Validate interface
+        match self.interface.as_deref() {
+            None => return Err(MacError::ValidationFailed(
+                "No interface given; use -i or set `interface` in config.toml".into()
+            )),
+            Some("") => return Err(MacError::ValidationFailed("Interface name cannot be empty".into())),
+            Some(_) => {}
+        }
+
+        // Validate MAC if provided
+        if let Some(mac) = &self.mac {
+            check_assignable(mac)?;
+        }
+
+        // Validate vendor if provided
+        if let Some(vendor) = &self.vendor {
+            if !is_valid_vendor_format(vendor) {
+                return Err(MacError::InvalidFormat(
+                    "Invalid vendor prefix format. Use XX:XX:XX".into()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A MAC an interface can take, in any notation `MacAddress` reads, such as
+/// Cisco's xxxx.xxxx.xxxx. Group and all-zero addresses are refused.
+fn check_assignable(mac: &str) -> Result<MacAddress, MacError> {
+    let parsed: MacAddress = mac.parse().map_err(|_| {
+        MacError::InvalidFormat("Invalid MAC address format. Use XX:XX:XX:XX:XX:XX".into())
+    })?;
+    let problem = if parsed.is_broadcast() {
+        "the broadcast address"
+    } else if parsed.is_multicast() {
+        "a multicast address"
+    } else if parsed.is_null() {
+        "all zeros"
+    } else {
+        return Ok(parsed.with_format(MacFormat::Colon));
+    };
+    Err(MacError::ValidationFailed(format!("{} is {} and cannot be assigned to an interface", mac, problem)))
+}
+
+fn is_valid_vendor_format(vendor: &str) -> bool {
+    let re = regex::Regex::new(r"^([0-9A-Fa-f]{2}[:-]){2}([0-9A-Fa-f]{2})$").unwrap();
+    re.is_match(vendor)
+}
+
+/// Parse durations like "500ms", "30s", "5m" or "1h". A bare number is seconds.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: u64 = number.parse()
+        .map_err(|_| format!("Invalid duration '{}'", value))?;
+
+    let seconds_per_unit = match unit {
+        "ms" => return Ok(Duration::from_millis(number)),
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return Err(format!("Invalid duration unit '{}', use ms, s, m, h or d", unit)),
+    };
+    number.checked_mul(seconds_per_unit)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("Duration '{}' is too long", value))
+}
+
+/// A point in time: a duration ago, an RFC 3339 timestamp, or a local date
+/// with an optional time.
+fn parse_since(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(ago) = parse_duration(value) {
+        return chrono::Duration::from_std(ago)
+            .ok()
+            .and_then(|ago| Utc::now().checked_sub_signed(ago))
+            .ok_or_else(|| format!("'{}' is too long ago", value));
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    let value = value.trim();
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M"))
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|date| date.and_time(NaiveTime::MIN)))
+        .map_err(|_| format!("Invalid time '{}', use a duration such as 2h, a date or \"YYYY-MM-DD HH:MM\"", value))?
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| format!("{} does not exist in the local time zone", value))
+}
+
+fn check_privileges() -> Result<(), MacError> {
+    // Mock changes only touch its state file
+    if backend::current().simulated() {
+        return Ok(());
+    }
+
+    #[cfg(unix)]
+    {
+        if !nix::unistd::Uid::effective().is_root() {
+            return Err(MacError::PermissionDenied("Must run with root privileges".into()));
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        if !is_elevated::is_elevated() {
+            return Err(MacError::PermissionDenied("Must run with administrator privileges".into()));
+        }
+    }
+
+    Ok(())
+}
+
+fn run_escrow(action: EscrowAction) -> Result<(), Box<dyn Error>> {
+    match action {
+        EscrowAction::Export { sign, out } => {
+            let record = EscrowRecord::collect()?;
+            let escrow = match sign {
+                Some(key) => SignedEscrow::sign(record, &key)?,
+                None => SignedEscrow::unsigned(record),
+            };
+
+            match out {
+                Some(path) => {
+                    std::fs::write(&path, escrow.to_json()?)?;
+                    println!("Exported {} interface(s) to {}", escrow.record.interfaces.len(), path.display());
+                }
+                None => println!("{}", escrow.to_json()?),
+            }
+        }
+        EscrowAction::Verify { file, key } => {
+            let escrow = SignedEscrow::load(&file)?;
+            escrow.verify(&key)?;
+            println!("Signature OK: record from {} generated at {}",
+                     escrow.record.hostname, escrow.record.generated_at);
+        }
+        EscrowAction::Import { file, key, allow_unsigned } => {
+            let escrow = SignedEscrow::load(&file)?;
+            match key {
+                Some(key) => escrow.verify(&key)?,
+                None if allow_unsigned => {
+                    println!("Warning: importing escrow record without signature verification");
+                }
+                None => {
+                    return Err(MacError::ValidationFailed(
+                        "A public key is required to import escrow records (or pass --allow-unsigned)".into()
+                    ).into());
+                }
+            }
+
+            let path = escrow.import()?;
+            println!("Imported escrow record for {} to {}", escrow.record.hostname, path.display());
+            for iface in &escrow.record.interfaces {
+                println!("  {}: original {} current {}{}",
+                         iface.interface, iface.original_mac, iface.current_mac,
+                         if iface.spoofed { " (spoofed)" } else { "" });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn run_list(wireless_only: bool, up_only: bool) -> Result<(), Box<dyn Error>> {
+    let cards: Vec<NetworkCard> = NetworkCard::list()?
+        .into_iter()
+        .filter(|card| !wireless_only || card.is_wireless)
+        .filter(|card| !up_only || card.is_up)
+        .collect();
+
+    let entries: Vec<ListEntry> = cards
+        .iter()
+        .map(|card| ListEntry { mac: network::get_current_mac(&card.interface).ok(), card })
+        .collect();
+
+    output::emit(&entries, |entries| print_list(entries))
+}
+
+#[derive(Serialize)]
+struct ListEntry<'a> {
+    #[serde(flatten)]
+    card: &'a NetworkCard,
+    mac: Option<MacAddress>,
+}
+
+fn print_list(entries: &[ListEntry]) {
+    if entries.is_empty() {
+        println!("No matching interfaces found");
+        return;
+    }
+
+    println!("{:<16} {:<18} {:<5} {:<8} {:<12} {:<20} MAC CHANGE",
+             "INTERFACE", "MAC", "STATE", "TYPE", "DRIVER", "VENDOR");
+
+    for ListEntry { card, mac } in entries {
+        let driver = if card.driver.is_empty() { "-" } else { card.driver.as_str() };
+
+        println!("{:<16} {:<18} {:<5} {:<8} {:<12} {:<20} {}",
+                 card.interface,
+                 mac.as_ref().map_or("-".into(), MacAddress::to_string),
+                 if card.is_up { "up" } else { "down" },
+                 if card.is_wireless { "wireless" } else { "wired" },
+                 driver,
+                 card.vendor.as_deref().unwrap_or("-"),
+                 if card.supports_mac_change { "yes" } else { "no" });
+
+        for link in &card.mlo_links {
+            println!("{:<16} link {} {}", "", link.link_id, link.address);
+        }
+        if card.mlo_capable && card.mlo_links.is_empty() {
+            println!("{:<16} multi-link capable (not associated)", "");
+        }
+        if card.monitor_mode {
+            println!("{:<16} monitor mode", "");
+        }
+        match card.rfkill {
+            Some(RfkillState::HardBlocked) => println!("{:<16} radio hard-blocked by rfkill", ""),
+            Some(RfkillState::SoftBlocked) => println!("{:<16} radio soft-blocked by rfkill", ""),
+            _ => {}
+        }
+        if let Some(connection) = &card.connection {
+            let network = connection.ssid.clone().unwrap_or_else(|| "hidden network".to_string());
+            match &connection.bssid {
+                Some(bssid) => println!("{:<16} associated with {} ({})", "", network, bssid),
+                None => println!("{:<16} associated with {}", "", network),
+            }
+        } else if card.is_wireless && !card.monitor_mode && card.is_up {
+            println!("{:<16} not associated", "");
+        }
+    }
+}
+
+/// Get the interface back on the network under its new MAC, unless the
+/// user opted out, then run the change's hooks.
+fn finish_change(transaction: &mut Transaction, no_renew: bool) {
+    if !no_renew {
+        progress!("Renewing network configuration on {}...", transaction.interface);
+        transaction.renewal = Some(renew::renew(&transaction.interface));
+    }
+    transaction.run_hooks();
+}
+
+/// Set the hostname and DHCP client ID that go with a new MAC, returning
+/// what was set. The MAC has already changed, so failures are only warnings.
+fn spoof_identity(
+    transaction: &mut Transaction,
+    interface: &str,
+    mac: &MacAddress,
+    hostname: Option<&str>,
+    dhcp_id: bool,
+) -> (Option<String>, Option<String>) {
+    let hostname = hostname.filter(|hostname| match identity::spoof_hostname(transaction, hostname) {
+        Ok(()) => true,
+        Err(e) => {
+            progress!("Warning: Could not set the hostname: {}", e);
+            false
+        }
+    });
+
+    let client_id = dhcp_id.then(|| identity::spoof_dhcp_id(transaction, interface, mac)).and_then(|result| {
+        result.map_err(|e| progress!("Warning: Could not set the DHCP client ID: {}", e)).ok()
+    });
+
+    (hostname.map(str::to_string), client_id)
+}
+
+fn note_identity(plan: &mut Plan, mac: &MacAddress, hostname: Option<&str>, dhcp_id: bool) {
+    if let Some(hostname) = hostname {
+        plan.note(format!("Would set the hostname to {}", hostname));
+    }
+    if dhcp_id {
+        plan.note(format!("Would set the DHCP client ID to {}", identity::client_id_for(mac)));
+    }
+}
+
+fn note_collision_check(plan: &mut Plan, in_use: Option<&collision::InUse>) {
+    match in_use {
+        Some(in_use) => plan.note(format!("Checked for collisions against {} MAC(s) in use nearby", in_use.seen.len())),
+        None => plan.note("Skipped the collision check"),
+    }
+}
+
+/// Print what a change would do, for people or for scripts.
+fn report_plan(mut plan: Plan, no_renew: bool) -> Result<(), Box<dyn Error>> {
+    if get_original_mac(&plan.interface)?.is_none() && !plan.old_mac.is_empty() {
+        plan.note(format!("Would save {} as the original MAC", plan.old_mac));
+    }
+    if !no_renew {
+        plan.note("Would then renew the DHCP lease and flush neighbors (skip with --no-renew)");
+    }
+
+    output::emit(&plan, Plan::print)
+}
+
+/// Print what a change did, for people or for scripts.
+fn report(transaction: &Transaction) -> Result<(), Box<dyn Error>> {
+    let mut summary = transaction.summary();
+    summary.vendor = vendor_of(summary.new_mac.as_deref());
+    output::emit(&summary, ChangeSummary::print)
+}
+
+/// Run a change. Scripts reading JSON get a summary with the error when it
+/// fails, not only the message on stderr.
+fn attempt_change<E: Into<Box<dyn Error>>>(
+    interface: &str,
+    new_mac: &MacAddress,
+    permanent: bool,
+    change: impl FnOnce() -> Result<Transaction, E>,
+) -> Result<Transaction, Box<dyn Error>> {
+    let started = Instant::now();
+    let old_mac = backend::current().get_mac(interface).ok().map(|mac| mac.to_string());
+    let new_mac = new_mac.to_string();
+
+    change().map_err(Into::into).inspect_err(|e| {
+        if output::is_json() {
+            let mut summary = ChangeSummary::failed(interface, old_mac, &new_mac, permanent, started, e.to_string().trim().to_string());
+            summary.error_code = Some(error::classify(e.as_ref()).0.to_string());
+            summary.vendor = vendor_of(Some(&new_mac));
+            let _ = output::emit(&summary, |_| {});
+        }
+    })
+}
+
+fn vendor_of(mac: Option<&str>) -> Option<String> {
+    let oui_db = oui::OUIDatabase::new().ok()?;
+    oui_db.get_vendor(mac?).map(|vendor| vendor.name.clone())
+}
+
+fn run_profile(action: ProfileAction) -> Result<(), Box<dyn Error>> {
+    let mut profiles = profiles::ProfileManager::new()?;
+
+    match action {
+        ProfileAction::Save { name, interface, mac, vendor, hostname, regenerate } => {
+            let mac = mac.as_deref().map(check_assignable).transpose()?;
+            if let Some(vendor) = &vendor
+                && !is_valid_vendor_format(vendor)
+            {
+                return Err(MacError::InvalidFormat(
+                    "Invalid vendor prefix format. Use XX:XX:XX".into()
+                ).into());
+            }
+
+            profiles.save_profile(profiles::Profile {
+                name: name.clone(),
+                interface,
+                mac_address: mac,
+                vendor,
+                hostname,
+                regenerate,
+                created: Utc::now(),
+            })?;
+            println!("Saved profile {}", name);
+        }
+        ProfileAction::Apply { name, permanent, json: _, force, no_renew, dry_run } => {
+            if !dry_run {
+                check_privileges()?;
+            }
+
+            let profile = profiles.get_profile(&name)?;
+            backend::current().verify_interface(&profile.interface)?;
+
+            let logger = MacLogger::new();
+            let history = logger.get_history()?;
+            if let Some(mac) = profile.reusable_mac(&history) {
+                progress!("Reusing {} (regenerate: {})", mac, profile.regenerate);
+            }
+
+            let new_mac = if force {
+                profile.resolve_mac(&history)?
+            } else {
+                MacFilter::new().pick_allowed(|| Ok(profile.resolve_mac(&history)?))?
+            };
+            if dry_run {
+                let mut plan = platform::plan_mac_change(&profile.interface, &new_mac, permanent)?;
+                if let Some(hostname) = &profile.hostname {
+                    plan.note(format!("Would set the hostname to {}", hostname));
+                }
+                return report_plan(plan, no_renew);
+            }
+
+            progress!("Applying profile {} to {}: {}", profile.name, profile.interface, new_mac);
+            let mut transaction = attempt_change(&profile.interface, &new_mac, permanent, || {
+                apply_mac(&profile.interface, &new_mac, permanent, &logger, Some(&profile.name), Trigger::Manual, None)
+            })?;
+
+            if let Some(hostname) = &profile.hostname {
+                hostname::set(hostname)?;
+                progress!("Hostname set to {}", hostname);
+                transaction.suggest(format!("Hostname is now {}; restore it manually if needed", hostname));
+            }
+
+            finish_change(&mut transaction, no_renew);
+            report(&transaction)?;
+        }
+        ProfileAction::List => {
+            for profile in profiles.list_profiles() {
+                let target = match (&profile.mac_address, &profile.vendor) {
+                    (Some(mac), _) => mac.to_string(),
+                    (None, Some(vendor)) => format!("random ({}, {})", vendor, profile.regenerate),
+                    (None, None) => format!("random ({})", profile.regenerate),
+                };
+                println!("{}: {} -> {}{}", profile.name, profile.interface, target,
+                         profile.hostname.as_ref().map(|h| format!(" (hostname {})", h)).unwrap_or_default());
+            }
+        }
+        ProfileAction::Remove { name } => {
+            profiles.remove_profile(&name)?;
+            println!("Removed profile {}", name);
+        }
+        ProfileAction::Export { name, out } => {
+            let json = profiles.export(name.as_deref())?;
+            match out {
+                Some(path) => {
+                    std::fs::write(&path, json)?;
+                    println!("Exported profiles to {}", path.display());
+                }
+                None => println!("{}", json),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_oui(action: OuiAction) -> Result<(), Box<dyn Error>> {
+    let mut oui_db = oui::OUIDatabase::new()?;
+
+    match action {
+        OuiAction::Update { source, mut mirror, offline } => {
+            let source = oui::source_by_name(&source)
+                .ok_or_else(|| MacError::ValidationFailed(format!("Unknown OUI source {}", source)))?;
+            if offline.is_empty() {
+                mirror.extend(Settings::load()?.oui.mirrors);
+                oui_db.update(source.as_ref(), &mirror).await?;
+            } else {
+                oui_db.import(source.as_ref(), &offline)?;
+            }
+        }
+        OuiAction::Lookup { mac } => match oui_db.get_vendor(&mac) {
+            Some(vendor) => println!("{}/{}  {}{}", vendor.prefix, vendor.bits, vendor.name,
+                                     if vendor.country.is_empty() { String::new() } else { format!(" ({})", vendor.country) }),
+            None => println!("No vendor found for {} ({} entries in database)", mac, oui_db.len()),
+        },
+        OuiAction::Search { query } => {
+            let vendors = oui_db.search(&query);
+            if vendors.is_empty() {
+                println!("No vendors match '{}' ({} entries in database)", query, oui_db.len());
+            }
+            for vendor in vendors {
+                println!("{:<16} {:<3} {}", format!("{}/{}", vendor.prefix, vendor.bits), vendor.country, vendor.name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn run_sandbox(action: SandboxAction) -> Result<(), Box<dyn Error>> {
+    let mut sandbox = sandbox::Sandbox::new();
+
+    match action {
+        SandboxAction::Add { interface } => {
+            sandbox.add(&interface)?;
+            println!("Added {} to sandbox list", interface);
+        }
+        SandboxAction::Remove { interface } => {
+            if sandbox.remove(&interface)? {
+                println!("Removed {} from sandbox list", interface);
+            } else {
+                println!("{} is not in the sandbox list", interface);
+            }
+        }
+        SandboxAction::List => {
+            for interface in sandbox.list() {
+                println!("{}", interface);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn run_window(action: WindowAction) -> Result<(), Box<dyn Error>> {
+    let mut windows = change_window::ChangeWindows::new()?;
+
+    match action {
+        WindowAction::Set { interface, schedule, cron } => {
+            let schedule = schedule.or(cron).ok_or("A --schedule or --cron is required")?;
+            println!("{} may now only change during {}", interface, schedule);
+            windows.set(&interface, schedule)?;
+        }
+        WindowAction::Remove { interface } => {
+            if windows.remove(&interface)? {
+                println!("{} may change at any time again", interface);
+            } else {
+                println!("{} has no change window", interface);
+            }
+        }
+        WindowAction::List => {
+            let now = Local::now();
+            if windows.list().is_empty() {
+                println!("No change windows defined; every interface may change at any time");
+            }
+            for (interface, schedule) in windows.list() {
+                let state = match windows.next_opening(interface, now) {
+                    _ if windows.is_open(interface, now) => "open".to_string(),
+                    Some(opens) => format!("closed until {}", opens.format(rules::TIME_FORMAT)),
+                    None => "closed".to_string(),
+                };
+                println!("{:<10} {:<32} {}", interface, schedule.to_string(), state);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn run_permanent(action: PermanentAction) -> Result<(), Box<dyn Error>> {
+    match action {
+        PermanentAction::Show { interface } => {
+            let overrides = platform::list_permanent_overrides(&interface);
+            if overrides.is_empty() {
+                println!("{} has no permanent MAC", interface);
+            }
+            for (location, mac) in overrides {
+                println!("{}  {}", mac, location);
+            }
+        }
+        PermanentAction::Remove { interface, dry_run: true } => {
+            let overrides = platform::list_permanent_overrides(&interface);
+            if overrides.is_empty() {
+                println!("{} has no permanent MAC", interface);
+            }
+            for (location, mac) in overrides {
+                println!("Would remove {}  {}", mac, location);
+            }
+        }
+        PermanentAction::Remove { interface, dry_run: false } => {
+            check_privileges()?;
+            match platform::remove_permanent_override(&interface)? {
+                Some(removed) => println!("Removed {}", removed),
+                None => println!("{} has no permanent MAC", interface),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn run_hooks(action: HookCommand) -> Result<(), Box<dyn Error>> {
+    let mut manager = HookManager::new()?;
+
+    match action {
+        HookCommand::Add { name, command, webhook, on, interface } => {
+            let action = match (command, webhook) {
+                (Some(command), _) => HookAction::Command(command),
+                (None, Some(url)) => {
+                    reqwest::Url::parse(&url).map_err(|e| MacError::ValidationFailed(format!("Invalid URL {}: {}", url, e)))?;
+                    HookAction::Webhook(url)
+                }
+                (None, None) => return Err("A --command or --webhook is required".into()),
+            };
+            let replaced = manager.add(Hook { name: name.clone(), on, interface, action })?;
+            println!("{} hook {}", if replaced { "Replaced" } else { "Added" }, name);
+        }
+        HookCommand::List => {
+            if manager.list().is_empty() && manager.configured().is_empty() {
+                println!("No hooks defined");
+            }
+            let configured = manager.configured().iter().map(|hook| (hook, " (config.toml)"));
+            for (hook, source) in manager.list().iter().map(|hook| (hook, "")).chain(configured) {
+                println!("{:<16} {:<8} {:<10} {}{}",
+                         hook.name,
+                         format!("{:?}", hook.on).to_lowercase(),
+                         hook.interface.as_deref().unwrap_or("any"),
+                         hook.describe(),
+                         source);
+            }
+        }
+        HookCommand::Remove { name } => {
+            if !manager.remove(&name)? {
+                if manager.configured().iter().any(|hook| hook.name == name) {
+                    return Err(MacError::ValidationFailed(format!(
+                        "Hook {} is defined in config.toml; remove it with `chameleon config edit`", name
+                    )).into());
+                }
+                return Err(MacError::ValidationFailed(format!("No hook named {}", name)).into());
+            }
+            println!("Removed hook {}", name);
+        }
+        HookCommand::Test { name } => {
+            let hook = manager.get(&name).ok_or_else(|| MacError::ValidationFailed(format!("No hook named {}", name)))?;
+            let error = (hook.on == hooks::HookOn::Failure).then(|| "sample failure".to_string());
+            let event = hooks::HookEvent::new(
+                hook.interface.as_deref().unwrap_or("eth0"), "02:00:00:00:00:01", "02:00:00:00:00:02", Trigger::Manual, error,
+            );
+            hook.run(&event)?;
+            println!("Hook {} ran successfully", name);
+        }
+    }
+
+    Ok(())
+}
+
+fn run_config(action: ConfigAction) -> Result<(), Box<dyn Error>> {
+    match action {
+        ConfigAction::Show => {
+            let settings = Settings::load()?;
+            output::emit(&settings, |settings| {
+                for path in Settings::paths().iter().filter(|path| path.exists()) {
+                    println!("# From {}", path.display());
+                }
+                print!("{}", toml::to_string_pretty(settings).unwrap_or_default());
+            })
+        }
+        ConfigAction::Edit { system } => {
+            let path = if system { PathBuf::from(settings::SYSTEM_PATH) } else { Settings::user_path() };
+            if system {
+                check_privileges()?;
+            }
+            if !path.exists() {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&path, settings::TEMPLATE)?;
+            }
+
+            let editor = std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")).unwrap_or_else(|_| "vi".into());
+            let mut words = editor.split_whitespace();
+            let program = words.next().unwrap_or("vi");
+            let status = std::process::Command::new(program).args(words).arg(&path).status()?;
+            if !status.success() {
+                return Err(MacError::SystemError(format!("{} exited with {}", program, status)).into());
+            }
+
+            let problems = Settings::read(&path)?.map(|settings| settings.problems()).unwrap_or_default();
+            if !problems.is_empty() {
+                for problem in &problems {
+                    eprintln!("{}: {}", path.display(), problem);
+                }
+                return Err(MacError::ValidationFailed(format!("{} has {} problem(s)", path.display(), problems.len())).into());
+            }
+            println!("Saved {}", path.display());
+            Ok(())
+        }
+        ConfigAction::Validate => {
+            let mut failed = false;
+            for path in Settings::paths() {
+                match Settings::read(&path) {
+                    Ok(None) => println!("{}: not present", path.display()),
+                    Ok(Some(settings)) => {
+                        let problems = settings.problems();
+                        if problems.is_empty() {
+                            println!("{}: OK", path.display());
+                        }
+                        for problem in &problems {
+                            println!("{}: {}", path.display(), problem);
+                        }
+                        failed |= !problems.is_empty();
+                    }
+                    Err(e) => {
+                        println!("{}", e);
+                        failed = true;
+                    }
+                }
+            }
+            if failed {
+                return Err(MacError::ValidationFailed("The configuration has errors".into()).into());
+            }
+            Ok(())
+        }
+    }
+}
+
+fn run_rules(action: RuleAction) -> Result<(), Box<dyn Error>> {
+    let mut rule_manager = RuleManager::new()?;
+
+    match action {
+        RuleAction::Add(args) => {
+            let RuleAddArgs {
+                interface, app, app_field, app_match, mac, profile, exempt, ssid, bssid, medium, min_speed, dock, schedule, cron,
+                name, disabled,
+            } = *args;
+            let class = (medium.is_some() || min_speed.is_some() || dock.is_some()).then_some(rules::ClassCondition {
+                medium,
+                min_speed_mbps: min_speed,
+                dock,
+            });
+
+            let mut rule = AppRule {
+                app_name: app.unwrap_or_default(),
+                app_field,
+                app_match,
+                service_name: None,
+                mac_address: mac.as_deref().map(check_assignable).transpose()?,
+                interface,
+                profile,
+                network: ssid.map(rules::NetworkCondition::Ssid).or(bssid.map(rules::NetworkCondition::Bssid)),
+                class,
+                exempt,
+                schedule: schedule.or(cron),
+                last_applied: None,
+                enabled: !disabled,
+            };
+            rule.validate()?;
+
+            let name = name.unwrap_or_else(|| rule.default_name());
+            let replaced = rule_manager.get_rule(&name).is_some();
+            rule_manager.add_rule(&name, rule)?;
+            println!("{} rule {}", if replaced { "Replaced" } else { "Added" }, name);
+        }
+        RuleAction::List => {
+            let entries: Vec<RuleEntry> = rule_manager
+                .named_rules()
+                .into_iter()
+                .map(|(name, rule)| {
+                    let active = rule_manager.is_rule_active(rule);
+                    let next_activation = if rule.enabled && !active {
+                        rule.schedule.as_ref().and_then(|schedule| schedule.next_activation(Local::now()))
+                    } else {
+                        None
+                    };
+                    RuleEntry { name, rule, active, next_activation }
+                })
+                .collect();
+
+            output::emit(&entries, |entries| print_rules(entries))?;
+        }
+        RuleAction::Remove { name } => {
+            if !rule_manager.remove_rule(&name)? {
+                return Err(MacError::ValidationFailed(format!("No rule named {}", name)).into());
+            }
+            println!("Removed rule {}", name);
+        }
+        RuleAction::Enable { name } => set_rule_enabled(&mut rule_manager, &name, true)?,
+        RuleAction::Disable { name } => set_rule_enabled(&mut rule_manager, &name, false)?,
+    }
+
+    Ok(())
+}
+
+fn set_rule_enabled(rule_manager: &mut RuleManager, name: &str, enabled: bool) -> Result<(), Box<dyn Error>> {
+    if !rule_manager.set_enabled(name, enabled)? {
+        return Err(MacError::ValidationFailed(format!("No rule named {}", name)).into());
+    }
+    println!("{} rule {}", if enabled { "Enabled" } else { "Disabled" }, name);
+    Ok(())
+}
+
+fn run_filter(action: FilterAction) -> Result<(), Box<dyn Error>> {
+    let mut filter = MacFilter::new();
+
+    match action {
+        FilterAction::Add { entry, list } => {
+            match list {
+                FilterList::Whitelist => filter.add_to_whitelist(&entry)?,
+                FilterList::Blacklist => filter.add_to_blacklist(&entry)?,
+            }
+            println!("Added {} to {}", filter::parse_entry(&entry)?, list);
+        }
+        FilterAction::List => {
+            for list in [FilterList::Whitelist, FilterList::Blacklist] {
+                let entries = filter.entries(list);
+                println!("{}:", list);
+                if entries.is_empty() {
+                    println!("  (empty)");
+                }
+                for entry in entries {
+                    println!("  {}", entry);
+                }
+            }
+        }
+        FilterAction::Remove { entry, list } => {
+            let removed = filter.remove(&entry, list)?;
+            if removed.is_empty() {
+                println!("{} is not in the filter lists", entry);
+            }
+            for list in removed {
+                println!("Removed {} from {}", entry, list);
+            }
+        }
+        FilterAction::Clear { list } => {
+            let cleared = filter.clear(list)?;
+            println!("Removed {} filter entr{}", cleared, if cleared == 1 { "y" } else { "ies" });
+        }
+        FilterAction::Import { file, list, format } => {
+            let format = format.unwrap_or_else(|| FilterFormat::from_path(&file));
+            let added = filter.import(&file, format, list)?;
+            println!("Imported {} new entr{} from {}", added, if added == 1 { "y" } else { "ies" }, file.display());
+        }
+        FilterAction::Export { file, list, format } => {
+            let format = format.unwrap_or_else(|| FilterFormat::from_path(&file));
+            let count = filter.export(&file, format, list)?;
+            println!("Exported {} entr{} to {}", count, if count == 1 { "y" } else { "ies" }, file.display());
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct RuleEntry<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    rule: &'a AppRule,
+    active: bool,                                // Enabled and in its schedule
+    next_activation: Option<DateTime<Local>>,    // When an off-schedule rule starts again
+}
+
+fn print_rules(entries: &[RuleEntry]) {
+    if entries.is_empty() {
+        println!("No rules defined");
+        return;
+    }
+
+    println!("{:<20} {:<10} {:<28} {:<28} {:<32} STATE", "NAME", "INTERFACE", "ACTION", "WHEN", "SCHEDULE");
+    for RuleEntry { name, rule, active, next_activation } in entries {
+        let state = if !rule.enabled {
+            "disabled".to_string()
+        } else if *active {
+            "enabled".to_string()
+        } else {
+            match next_activation {
+                Some(next) => format!("enabled, off schedule until {}", next.format(rules::TIME_FORMAT)),
+                None => "enabled, off schedule".to_string(),
+            }
+        };
+
+        println!("{:<20} {:<10} {:<28} {:<28} {:<32} {}",
+                 name,
+                 rule.interface,
+                 rule.describe_action(),
+                 rule.describe_conditions(),
+                 rule.schedule.as_ref().map(|schedule| schedule.to_string()).unwrap_or_else(|| "any time".into()),
+                 state);
+    }
+}
+
+fn run_status(interface: Option<String>) -> Result<(), Box<dyn Error>> {
+    let oui_db = oui::OUIDatabase::new()?;
+    let statuses = status::collect_status(interface.as_deref(), &oui_db)?;
+
+    output::emit(&statuses, |statuses| statuses.iter().for_each(|status| status.print()))
+}
+
+fn run_detect(cam_table: Option<PathBuf>, out: Option<PathBuf>, flagged_only: bool) -> Result<(), Box<dyn Error>> {
+    let oui_db = oui::OUIDatabase::new()?;
+    let mut report = match &cam_table {
+        Some(path) => detect::scan_cam_table(path, &oui_db)?,
+        None => detect::scan_local(&oui_db)?,
+    };
+    if flagged_only {
+        report.retain_flagged();
+    }
+
+    let json = serde_json::to_string_pretty(&report)?;
+    match out {
+        Some(path) => {
+            std::fs::write(&path, json)?;
+            println!("Checked {} address(es), {} flagged; report written to {}", report.scanned, report.flagged, path.display());
+        }
+        None => println!("{}", json),
+    }
+    if !report.vendor_check {
+        eprintln!("Unknown vendors were not flagged; run `chameleon oui update` to enable that check");
+    }
+
+    Ok(())
+}
+
+fn run_history(filter: HistoryFilter, action: Option<HistoryAction>) -> Result<(), Box<dyn Error>> {
+    let logger = MacLogger::new();
+
+    match action {
+        None => output::emit(&logger.query_history(&filter)?, |changes| {
+            if changes.is_empty() {
+                println!("No history recorded");
+            }
+            for change in changes {
+                println!("{}  {:<12} {} -> {}{}{}",
+                         change.timestamp.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+                         change.interface,
+                         change.old_mac,
+                         change.new_mac,
+                         if change.permanent { " (permanent)" } else { "" },
+                         change.profile.as_ref().map(|profile| format!(" profile {}", profile)).unwrap_or_default());
+            }
+        }),
+        Some(HistoryAction::Export { format, out }) => {
+            let changes = logger.query_history(&filter)?;
+            let exported = history::export(&changes, format)?;
+            match out {
+                Some(path) => {
+                    std::fs::write(&path, exported)?;
+                    progress!("Exported {} change(s) to {}", changes.len(), path.display());
+                }
+                None => print!("{}", exported),
+            }
+            Ok(())
+        }
+        Some(HistoryAction::Stats) => {
+            let oui_db = oui::OUIDatabase::new()?;
+            let stats = history::HistoryStats::collect(&logger.query_history(&filter)?, &oui_db);
+            output::emit(&stats, history::HistoryStats::print)
+        }
+        Some(HistoryAction::Verify { head, key }) => {
+            let report = logger.verify_chain(head.as_deref().zip(key.as_deref()))?;
+            output::emit(&report, logger::ChainReport::print)?;
+            if !report.problems.is_empty() {
+                return Err(MacError::ValidationFailed("The change history has been tampered with".into()).into());
+            }
+            Ok(())
+        }
+        Some(HistoryAction::Sign { key, out }) => {
+            let head = logger.sign_head(&key)?;
+            let path = out.unwrap_or_else(|| logger.log_dir().join("mac_changes.head"));
+            std::fs::write(&path, serde_json::to_string_pretty(&head)?)?;
+            println!("Signed head {} written to {}", head.hash, path.display());
+            Ok(())
+        }
+    }
+}
+
+fn run_rollback(
+    interface: String,
+    target: history::RollbackTarget,
+    permanent: bool,
+    yes: bool,
+    force: bool,
+    no_renew: bool,
+    dry_run: bool,
+) -> Result<(), Box<dyn Error>> {
+    if !dry_run {
+        check_privileges()?;
+    }
+    backend::current().verify_interface(&interface)?;
+
+    let logger = MacLogger::new();
+    let changes = logger.query_history(&HistoryFilter { interface: Some(interface.clone()), ..Default::default() })?;
+    let candidate = history::rollback_target(&changes, target)?;
+
+    let current = backend::current().get_mac(&interface)?;
+    if current == candidate.new_mac {
+        progress!("{} already uses {}", interface, current);
+        return Ok(());
+    }
+
+    progress!("Rolling back {} from {} to {}, set {}{}{}",
+              interface,
+              current,
+              candidate.new_mac,
+              candidate.timestamp.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+              candidate.trigger.map(|trigger| format!(" ({})", trigger)).unwrap_or_default(),
+              candidate.profile.as_ref().map(|profile| format!(" from profile {}", profile)).unwrap_or_default());
+
+    // The lists may have changed since the MAC was in use
+    if !force {
+        MacFilter::new().check(&candidate.new_mac)?;
+    }
+    if dry_run {
+        let plan = platform::plan_mac_change(&interface, &candidate.new_mac, permanent)?;
+        return report_plan(plan, no_renew);
+    }
+    if !yes && !output::confirm("Apply this MAC?")? {
+        progress!("Rollback cancelled");
+        return Ok(());
+    }
+
+    let mut transaction = attempt_change(&interface, &candidate.new_mac, permanent, || {
+        apply_mac(&interface, &candidate.new_mac, permanent, &logger, candidate.profile.as_deref(), Trigger::Rollback, None)
+    })?;
+    finish_change(&mut transaction, no_renew);
+    report(&transaction)
+}
+
+#[cfg(target_os = "linux")]
+async fn run_dbus(action: DbusAction) -> Result<(), Box<dyn Error>> {
+    match action {
+        DbusAction::Serve => {
+            check_privileges()?;
+            dbus::serve().await
+        }
+        DbusAction::Install { dry_run } => {
+            if !dry_run {
+                check_privileges()?;
+            }
+            dbus::install(dry_run).await
+        }
+        DbusAction::Uninstall => {
+            check_privileges()?;
+            dbus::uninstall().await
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn run_dbus(_action: DbusAction) -> Result<(), Box<dyn Error>> {
+    Err(Box::new(MacError::UnsupportedPlatform("The D-Bus service is only available on Linux".into())))
+}
+
+#[cfg(target_os = "linux")]
+async fn change_via_dbus(cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let interface = cli.interface.as_deref().unwrap_or_default();
+    let mut summary = if cli.restore {
+        dbus::restore(interface, !cli.no_renew).await?
+    } else {
+        let request = api::ChangeRequest {
+            mac: cli.mac.clone(),
+            random: cli.random,
+            permanent: cli.permanent,
+            force: cli.force,
+            renew: !cli.no_renew,
+        };
+        dbus::change(interface, &request).await?
+    };
+    summary.vendor = vendor_of(summary.new_mac.as_deref());
+    output::emit(&summary, ChangeSummary::print)
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn change_via_dbus(_cli: &Cli) -> Result<(), Box<dyn Error>> {
+    Err(Box::new(MacError::UnsupportedPlatform("--via-dbus is only available on Linux".into())))
+}
+
+fn run_api_token(action: ApiTokenAction) -> Result<(), Box<dyn Error>> {
+    let mut tokens = api::ApiTokens::load();
+    match action {
+        ApiTokenAction::Add { name } => {
+            let token = tokens.add(&name)?;
+            println!("Token for {} (shown only once):", name);
+            println!("{}", token);
+        }
+        ApiTokenAction::List => {
+            if tokens.list().is_empty() {
+                println!("No API tokens");
+            }
+            for (name, entry) in tokens.list() {
+                println!("{:<24} created {}", name, entry.created.with_timezone(&Local).format("%Y-%m-%d %H:%M"));
+            }
+        }
+        ApiTokenAction::Remove { name } => {
+            if tokens.remove(&name)? {
+                println!("Removed token {}", name);
+            } else {
+                println!("No token named {}", name);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn run_bt(action: BtAction) -> Result<(), Box<dyn Error>> {
+    use bluetooth::Controller;
+
+    let (controller, target, dry_run) = match action {
+        BtAction::List => {
+            let controllers = Controller::list()?;
+            return output::emit(&controllers, |controllers| {
+                if controllers.is_empty() {
+                    println!("No Bluetooth controllers found");
+                    return;
+                }
+                println!("{:<6} {:<18} {:<24} {:<6} {:<8} METHOD", "NAME", "ADDRESS", "MANUFACTURER", "BUS", "POWERED");
+                for controller in controllers {
+                    println!("{:<6} {:<18} {:<24} {:<6} {:<8} {}",
+                             controller.name,
+                             controller.address.as_ref().map(|address| address.to_string()).unwrap_or_else(|| "-".into()),
+                             controller.manufacturer_name.as_deref().unwrap_or("-"),
+                             controller.bus.as_deref().unwrap_or("-"),
+                             if controller.powered { "yes" } else { "no" },
+                             controller.method.describe());
+                }
+            });
+        }
+        BtAction::Set { controller, mac, random, dry_run } => {
+            let controller = Controller::new(&controller)?;
+            let address = if random {
+                mac::generate_random_mac(&RandomPolicy::PreserveOui(controller.address()?.clone()))?
+            } else {
+                MacAddress::parse(&mac.unwrap_or_default())?
+            };
+            (controller, address.with_format(MacFormat::Colon), dry_run)
+        }
+        BtAction::Restore { controller, dry_run } => {
+            let controller = Controller::new(&controller)?;
+            let original = bluetooth::get_original_address(&controller.name)?.ok_or_else(|| {
+                MacError::ValidationFailed(format!("No original address saved for {}", controller.name))
+            })?;
+            (controller, original, dry_run)
+        }
+    };
+
+    if dry_run {
+        println!("Would change {} from {} to {} with {}:", controller.name, controller.address()?, target, controller.method.describe());
+        for command in bluetooth::commands(&controller, &target)? {
+            println!("  $ {}", command);
+        }
+        return Ok(());
+    }
+
+    check_privileges()?;
+    let change = bluetooth::change(&controller, &target, Trigger::Manual)?;
+    output::emit(&change, bluetooth::BtChange::print)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn run_bt(_action: BtAction) -> Result<(), Box<dyn Error>> {
+    Err(Box::new(MacError::UnsupportedPlatform("Bluetooth addresses can only be changed on Linux".into())))
+}
+
+fn run_system_log(action: SystemLogAction) -> Result<(), Box<dyn Error>> {
+    let mut config = system_log::SystemLogConfig::load();
+
+    match action {
+        SystemLogAction::Enable { severity } => {
+            config.enabled = true;
+            config.severity = severity;
+            config.save()?;
+            println!("MAC changes will also be sent to the system log at severity {}", severity);
+        }
+        SystemLogAction::Disable => {
+            config.enabled = false;
+            config.save()?;
+            println!("MAC changes will only be written to the file log");
+        }
+        SystemLogAction::Show => {
+            println!("File log:     {}", MacLogger::new().log_dir().join("mac_changes.log").display());
+            match config.enabled {
+                true => println!("System log:   enabled, severity {}", config.severity),
+                false => println!("System log:   disabled"),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn run_events(since: Option<DateTime<Utc>>, interface: Option<String>) -> Result<(), Box<dyn Error>> {
+    let entries: Vec<journal::JournalEntry> = journal::Journal::new()
+        .replay(since)?
+        .into_iter()
+        .filter(|entry| interface.is_none() || entry.event.interface() == interface.as_deref())
+        .collect();
+
+    if output::is_json() {
+        for entry in &entries {
+            println!("{}", serde_json::to_string(entry)?);
+        }
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("No daemon events recorded");
+    }
+    for entry in &entries {
+        println!("{}  {:<12} {}",
+                 entry.timestamp.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+                 entry.event.interface().unwrap_or("-"),
+                 entry.event.describe());
+    }
+
+    Ok(())
+}
+
+// Inside src/main.rs
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = (|| -> Result<(), Box<dyn Error>> {
+        // Namespaces belong to threads, so enter it before the runtime starts
+        // its workers; they inherit it
+        if let Some(target) = cli.netns() {
+            netns::enter(target)?;
+        }
+        tokio::runtime::Runtime::new()?.block_on(run(cli))
+    })();
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => exit_with(e.as_ref()),
+    }
+}
+
+/// Report a failed run and turn its error into the documented exit code.
+/// Under `--output json` the error is one JSON object on stderr, after any
+/// progress messages.
+fn exit_with(error: &(dyn Error + 'static)) -> ExitCode {
+    let (code, exit_code) = error::classify(error);
+    if output::is_json() {
+        let report = serde_json::json!({
+            "error": { "code": code, "exit_code": exit_code, "message": error.to_string() }
+        });
+        eprintln!("{}", report);
+    } else {
+        eprintln!("Error: {}", error);
+    }
+    ExitCode::from(exit_code as u8)
+}
+
+async fn run(mut cli: Cli) -> Result<(), Box<dyn Error>> {
+    lock::set_wait(cli.wait || !cli.no_wait);
+    platform::set_reconnect(!cli.no_reconnect);
+
+    // The older per-command --json flags mean the same as --output json
+    let json = cli.json || matches!(&cli.command, Some(
+        Commands::Status { json: true, .. }
+        | Commands::Events { json: true, .. }
+        | Commands::Profile { action: ProfileAction::Apply { json: true, .. } }
+    ));
+    output::set_format(if json { OutputFormat::Json } else { cli.output });
+
+    if let Some(command) = cli.command {
+        return match command {
+            Commands::Escrow { action } => run_escrow(action),
+            Commands::List { wireless_only, up_only, .. } => run_list(wireless_only, up_only),
+            Commands::Status { interface, .. } => run_status(interface),
+            Commands::Detect { cam_table, out, flagged_only } => run_detect(cam_table, out, flagged_only),
+            Commands::Oui { action } => run_oui(action).await,
+            Commands::Events { since, interface, .. } => run_events(since, interface),
+            Commands::History { filter, action } => run_history(filter.into(), action),
+            Commands::Rollback { interface, steps, to, permanent, yes, force, no_renew, dry_run } => {
+                let target = match to {
+                    Some(time) => history::RollbackTarget::At(time),
+                    None => history::RollbackTarget::Steps(steps),
+                };
+                run_rollback(interface, target, permanent, yes, force, no_renew, dry_run)
+            }
+            Commands::SystemLog { action } => run_system_log(action),
+            Commands::Bt { action } => run_bt(action),
+            Commands::Serve { listen, tls_cert, tls_key, client_ca, insecure } => {
+                check_privileges()?;
+                let tls = tls_cert.zip(tls_key).map(|(cert, key)| api::TlsConfig { cert, key, client_ca });
+                api::serve(api::ServeConfig { listen, tls, insecure }).await
+            }
+            Commands::Dbus { action } => run_dbus(action).await,
+            Commands::ApiToken { action } => run_api_token(action),
+            Commands::Timeline { interface } => {
+                let logger = MacLogger::new();
+                timeline::print(&logger.get_history()?, &logger.get_link_events()?, interface.as_deref());
+                Ok(())
+            }
+            Commands::Daemon { interfaces, interval, metrics_listen } => {
+                check_privileges()?;
+                let interval = match interval {
+                    Some(interval) => interval,
+                    None => Settings::load()?.daemon_interval()?.unwrap_or(Duration::from_secs(30)),
+                };
+                daemon::run(daemon::DaemonConfig { interfaces, interval, metrics_listen }, &MacLogger::new()).await
+            }
+            Commands::Stats { prometheus: true } => {
+                print!("{}", metrics::Stats::collect()?.render());
+                Ok(())
+            }
+            Commands::Stats { prometheus: false } => {
+                let stats = metrics::Stats::collect()?;
+                output::emit(&stats, metrics::Stats::print)
+            }
+            Commands::Export { path, recipient } => bundle::export(&path, &recipient),
+            Commands::Import { path, identity, dry_run, yes } => bundle::import(&path, identity.as_deref(), dry_run, yes),
+            Commands::Capabilities => {
+                capabilities::print(&capabilities::probe());
+                Ok(())
+            }
+            Commands::Migrate { from, remove_old, dry_run } => {
+                if remove_old && !dry_run {
+                    check_privileges()?;
+                }
+                migrate::run(from, remove_old, dry_run)
+            }
+            Commands::PanicRestore { dry_run: true } => {
+                recovery::plan_panic_restore()?.print();
+                Ok(())
+            }
+            Commands::PanicRestore { dry_run: false } => {
+                check_privileges()?;
+                let report = recovery::panic_restore(&MacLogger::new())?;
+                report.print();
+                Ok(recovery::check(&report)?)
+            }
+            Commands::Rules { action } => run_rules(action),
+            Commands::Filter { action } => run_filter(action),
+            Commands::Sandbox { action } => run_sandbox(action),
+            Commands::Window { action } => run_window(action),
+            Commands::Permanent { action } => run_permanent(action),
+            Commands::InstallBootRandomization { interface, policy, dry_run } => {
+                if !dry_run {
+                    check_privileges()?;
+                }
+                boot::install(&interface, policy, dry_run)
+            }
+            Commands::UninstallBootRandomization { interface, dry_run } => {
+                if !dry_run {
+                    check_privileges()?;
+                }
+                boot::uninstall(&interface, dry_run)
+            }
+            Commands::Hooks { action } => run_hooks(action),
+            Commands::Config { action } => run_config(action),
+            Commands::Profile { action } => run_profile(action),
+            Commands::Chaos { interface, interval, jitter, iterations } => {
+                check_privileges()?;
+                let config = chaos::ChaosConfig { interface, interval, jitter, iterations };
+                chaos::run(config, &MacLogger::new()).await
+            }
+        };
+    }
+
+    // config.toml fills in what the flags left out
+    cli.apply_settings(&Settings::load()?);
+
+    // Validate arguments
+    cli.validate()?;  // MacError will automatically convert to Box<dyn Error>
+
+    // The service checks with polkit and does the rest as root
+    if cli.via_dbus {
+        return change_via_dbus(&cli).await;
+    }
+
+    // Check privileges; a dry run only notes that they are missing
+    if !cli.dry_run {
+        check_privileges()?;
+    }
+
+    // Initialize services
+    let geo_service = GeoLocationService::new();
+    let oui_db = oui::OUIDatabase::new()?;
+    let mut mac_filter = MacFilter::new();
+    let mac_logger = MacLogger::new();
+    let rule_manager = RuleManager::new()?;
+
+    let provided_mac = cli.mac.clone();
+    let interface = cli.interface.clone().unwrap_or_default();
+
+    // Verify interface
+    backend::current().verify_interface(&interface)?;
+
+    // Handle filter commands
+    if cli.dry_run && let Some(prefix) = cli.whitelist.as_ref().or(cli.blacklist.as_ref()) {
+        let list = if cli.whitelist.is_some() { "whitelist" } else { "blacklist" };
+        println!("Would add {} to the {} (dry run, nothing was changed)", filter::parse_entry(prefix)?, list);
+        return Ok(());
+    }
+
+    if let Some(prefix) = cli.whitelist {
+        mac_filter.add_to_whitelist(&prefix)?;
+        println!("Added {} to whitelist", prefix);
+        return Ok(());
+    }
+
+    if let Some(prefix) = cli.blacklist {
+        mac_filter.add_to_blacklist(&prefix)?;
+        println!("Added {} to blacklist", prefix);
+        return Ok(());
+    }
+
+    if cli.history {
+        let history = mac_logger.get_history()?;
+        if cli.detailed {
+            timeline::print(&history, &mac_logger.get_link_events()?, Some(&interface));
+            return Ok(());
+        }
+        return output::emit(&history, |history| {
+            for change in history {
+                println!("{}: {} -> {} ({})",
+                         change.timestamp,
+                         change.old_mac,
+                         change.new_mac,
+                         change.interface
+                );
+            }
+        });
+    }
+
+    if cli.restore {
+        let target = if cli.factory {
+            let factory_mac = get_factory_mac(&interface)?.ok_or_else(|| MacError::ValidationFailed(format!(
+                "The driver of {} does not report a hardware address", interface
+            )))?;
+            Some(factory_mac)
+        } else {
+            let original = get_original_mac(&interface)?;
+            if let Some(original) = &original
+                && let Some(warning) = config::spoofed_original_warning(original, backend::current().hardware_mac(&interface).as_ref())
+            {
+                progress!("Warning: {}", warning);
+            }
+            original
+        };
+        match target {
+            Some(original_mac) if cli.dry_run => {
+                let plan = platform::plan_mac_change(&interface, &original_mac, false)?;
+                return report_plan(plan, cli.no_renew);
+            }
+            Some(original_mac) => {
+                let which = if cli.factory { "hardware" } else { "original" };
+                progress!("Restoring {} MAC address: {}", which, original_mac);
+                let mut transaction = attempt_change(&interface, &original_mac, false, || {
+                    change_mac(&interface, &original_mac, false, Trigger::Manual)
+                })?;
+                progress!("Successfully restored {} MAC address", which);
+                if let Err(e) = identity::restore(&mut transaction, &interface) {
+                    progress!("Warning: Could not restore the hostname or DHCP client ID: {}", e);
+                }
+                finish_change(&mut transaction, cli.no_renew);
+                report(&transaction)?;
+            }
+            None => {
+                return Err(MacError::ValidationFailed(
+                    "No original MAC address saved".into()
+                ).into());  // Use .into() to convert to Box<dyn Error>
+            }
+        }
+        return Ok(());
+    }
+
+    // Look for MACs already in use next to the interface, so the new one doesn't clash
+    let in_use = (!cli.no_collision_check).then(|| {
+        progress!("Checking {} for MACs already in use...", interface);
+        let in_use = collision::scan(&interface);
+        for note in &in_use.notes {
+            progress!("Warning: {}", note);
+        }
+        in_use
+    });
+    let pick_unused = |generate: &dyn Fn() -> Result<MacAddress, Box<dyn Error>>| match &in_use {
+        Some(in_use) => in_use.pick_unused(generate),
+        None => generate(),
+    };
+
+    // An active application rule decides the MAC; -m and the generators
+    // only apply without one
+    let running_apps = apps::running()?;
+    let connection = network::get_wireless_connection(&interface);
+    let class = network::get_interface_class(&interface);
+    let rule = rule_manager
+        .find_matching_rule(&interface, &running_apps, connection.as_ref(), &class)
+        .map(|(_, rule)| rule);
+    if let Some(rule) = rule {
+        match &rule.network {
+            Some(network) => progress!("Found active rule for network: {:?}", network),
+            None => progress!("Found active rule for running application: {}", rule.app_name),
+        }
+
+        if let Some(profile) = &rule.profile {
+            progress!("Using profile {} from rule", profile);
+        }
+    }
+    let history = if rule.is_some() { mac_logger.get_history()? } else { Vec::new() };
+
+    // Get new MAC address, generating again while it collides or the filter rejects it
+    let generate = || -> Result<MacAddress, Box<dyn Error>> {
+        if let Some(rule) = rule {
+            return rule.resolve_mac(&history);
+        }
+        Ok(if let Some(country) = &cli.spoof_location {
+            let (mac, vendor) = geo_service.suggest_mac_for_location(country, &oui_db)?;
+            progress!("Using {} persona: {} ({}/{})", vendor.country, vendor.name, vendor.prefix, vendor.bits);
+            mac
+        } else if cli.random {
+            let policy = if cli.preserve_oui {
+                RandomPolicy::PreserveOui(backend::current().get_mac(&interface)?)
+            } else if cli.burned_in_like {
+                RandomPolicy::BurnedInLike(oui_db.ma_l_prefixes())
+            } else {
+                RandomPolicy::from_vendor(cli.vendor.as_deref())
+            };
+
+            progress!("Generating random MAC address{}...", match policy {
+                RandomPolicy::LocallyAdministered => "",
+                RandomPolicy::Vendor(_) => " with vendor prefix",
+                RandomPolicy::PreserveOui(_) => " keeping the current OUI",
+                RandomPolicy::BurnedInLike(_) => " under a manufacturer OUI",
+            });
+            mac::generate_random_mac(&policy)?
+        } else if let Some(company) = &cli.random_from_vendor {
+            let mac = oui_db.random_mac_from_vendor(company)?;
+            if let Some(vendor) = oui_db.get_vendor(&mac.to_string()) {
+                progress!("Generating random MAC from {} ({}/{})...", vendor.name, vendor.prefix, vendor.bits);
+            }
+            mac
+        } else if let Some(name) = &cli.persona {
+            let persona = personas::find(name)?;
+            let mac = persona.generate_mac()?;
+            progress!("Generating MAC for persona {}{}...", persona.name,
+                     oui_db.get_vendor(&mac.to_string()).map(|vendor| format!(" ({})", vendor.name)).unwrap_or_default());
+            mac
+        } else if let Some(mac) = &cli.mac {
+            check_assignable(mac)?
+        } else {
+            return Err(MacError::ValidationFailed(
+                "No MAC address specified".into()
+            ).into());
+        })
+    };
+    let new_mac = if cli.force {
+        pick_unused(&generate)?
+    } else {
+        mac_filter.pick_allowed(|| pick_unused(&generate))?
+    }
+    .with_format(MacFormat::Colon);
+    if rule.is_some() {
+        progress!("Using rule-specified MAC address: {}", new_mac);
+    }
+    let trigger = if rule.is_some() { Trigger::Rule } else { Trigger::Manual };
+
+    let spoofed_hostname = cli.spoof_hostname.as_deref().map(identity::random_hostname).transpose()?;
+
+    // Save original MAC if first time
+    if !cli.dry_run && get_original_mac(&interface)?.is_none() {
+        match backend::current().get_mac(&interface) {
+            Ok(current_mac) => {
+                progress!("Saving original MAC address: {}", current_mac);
+                save_original_mac(&interface, &current_mac)?;
+            },
+            Err(e) => {
+                progress!("Warning: Could not save original MAC address: {}", e);
+            }
+        }
+    }
+
+    // Platform-specific permanent flag handling
+    #[cfg(not(target_os = "macos"))]
+    let permanent = cli.permanent;
+
+    #[cfg(target_os = "macos")]
+    let permanent = {
+        if cli.permanent {
+            progress!("Warning: Permanent MAC address changes are not supported on macOS.");
+            progress!("Continuing with temporary change...");
+        }
+        false
+    };
+
+    if cli.dry_run {
+        let mut plan = platform::plan_mac_change(&interface, &new_mac, permanent)?;
+        note_collision_check(&mut plan, in_use.as_ref());
+        note_identity(&mut plan, &new_mac, spoofed_hostname.as_deref(), cli.spoof_dhcp_id);
+        if cli.watch {
+            plan.note("Would then keep watching for the MAC to revert");
+        }
+        return report_plan(plan, cli.no_renew);
+    }
+
+    // Get current MAC for logging
+    let old_mac = backend::current().get_mac(&interface)?;
 
     // Change MAC
-    change_mac(&cli.interface, &new_mac, permanent)?;
+    let mut transaction = attempt_change(&interface, &new_mac, permanent, || {
+        change_mac(&interface, &new_mac, permanent, trigger)
+    })?;
+    let (hostname, dhcp_client_id) =
+        spoof_identity(&mut transaction, &interface, &new_mac, spoofed_hostname.as_deref(), cli.spoof_dhcp_id);
 
     // Log the change
     let change = MacChange {
         timestamp: Utc::now(),
-        interface: cli.interface,
+        interface: interface.clone(),
         old_mac,
-        new_mac,
-        geo_location: cli.spoof_location,
+        new_mac: new_mac.clone(),
+        geo_location: cli.spoof_location.filter(|_| rule.is_none()).map(|country| country.to_uppercase()),
         permanent,
+        profile: rule.and_then(|rule| rule.profile.clone()),
+        trigger: Some(trigger),
+        hostname,
+        dhcp_client_id,
+        principal: None,
     };
     mac_logger.log_change(change)?;
 
+    finish_change(&mut transaction, cli.no_renew);
+    report(&transaction)?;
+
+    if cli.watch {
+        let config = watch::WatchConfig {
+            interface,
+            mac: new_mac,
+            permanent,
+            poll_interval: cli.watch_interval,
+        };
+        watch::run(config, &mac_logger).await?;
+    }
+
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,241 @@
+// src/metrics.rs
+use chrono::{DateTime, Local, Utc};
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::header::CONTENT_TYPE;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use tokio::net::TcpListener;
+use crate::history::format_interval;
+use crate::hooks::Trigger;
+use crate::journal::{DaemonEvent, Journal};
+use crate::logger::MacLogger;
+
+/// The Prometheus text exposition format.
+const CONTENT_TYPE_TEXT: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Set while this process runs the daemon; a scrape of any other process
+/// has no daemon to report on.
+static DAEMON: Mutex<Option<DaemonState>> = Mutex::new(None);
+
+/// What the running daemon knows that the logs don't: whether it is still
+/// evaluating rules at all.
+#[derive(Debug, Clone, Serialize)]
+pub struct DaemonState {
+    pub started: DateTime<Utc>,
+    pub interfaces: usize,
+    pub last_evaluation: Option<DateTime<Utc>>,
+}
+
+/// Counters for one interface, over all of its recorded history.
+#[derive(Debug, Default, Serialize)]
+pub struct InterfaceStats {
+    pub changes: u64,
+    pub by_trigger: BTreeMap<String, u64>,
+    /// Daemon rules that could not be applied or undone.
+    pub failures: u64,
+    /// A reverted MAC put back by `--watch` or the daemon.
+    pub reapplies: u64,
+    pub last_change: Option<DateTime<Utc>>,
+}
+
+/// Result of `stats`, and what `/metrics` exposes.
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub generated: DateTime<Utc>,
+    pub interfaces: BTreeMap<String, InterfaceStats>,
+    /// How often each daemon rule put its MAC on an interface.
+    pub rule_activations: BTreeMap<String, u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub daemon: Option<DaemonState>,
+}
+
+pub fn daemon_started(interfaces: usize) {
+    *DAEMON.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) =
+        Some(DaemonState { started: Utc::now(), interfaces, last_evaluation: None });
+}
+
+/// Called after every pass over the rules.
+pub fn daemon_evaluated(interfaces: usize) {
+    if let Some(state) = DAEMON.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).as_mut() {
+        state.interfaces = interfaces;
+        state.last_evaluation = Some(Utc::now());
+    }
+}
+
+impl Stats {
+    /// Count changes from the history and daemon activity from its journal.
+    pub fn collect() -> Result<Self, Box<dyn Error>> {
+        let mut interfaces: BTreeMap<String, InterfaceStats> = BTreeMap::new();
+
+        for change in MacLogger::new().get_history()? {
+            let stats = interfaces.entry(change.interface.clone()).or_default();
+            stats.changes += 1;
+            // Older entries don't say what caused them
+            let trigger = change.trigger.map(|trigger| trigger.to_string()).unwrap_or_else(|| "unknown".into());
+            *stats.by_trigger.entry(trigger).or_default() += 1;
+            if change.trigger == Some(Trigger::Watch) {
+                stats.reapplies += 1;
+            }
+            stats.last_change = stats.last_change.max(Some(change.timestamp));
+        }
+
+        let mut rule_activations: BTreeMap<String, u64> = BTreeMap::new();
+        for entry in Journal::new().replay(None)? {
+            match entry.event {
+                DaemonEvent::RuleApplied { rule, .. } => *rule_activations.entry(rule).or_default() += 1,
+                DaemonEvent::Reverted { interface, .. } => interfaces.entry(interface).or_default().reapplies += 1,
+                DaemonEvent::Error { interface: Some(interface), .. } => interfaces.entry(interface).or_default().failures += 1,
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            generated: Utc::now(),
+            interfaces,
+            rule_activations,
+            daemon: DAEMON.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone(),
+        })
+    }
+
+    pub fn print(&self) {
+        if self.interfaces.is_empty() {
+            println!("No changes recorded");
+        } else {
+            println!("{:<16} {:<8} {:<9} {:<9} LAST CHANGE", "INTERFACE", "CHANGES", "FAILURES", "REAPPLIED");
+            for (interface, stats) in &self.interfaces {
+                let last_change = match stats.last_change {
+                    Some(last) => format!("{} ago", format_interval((self.generated - last).num_seconds().max(0))),
+                    None => "-".into(),
+                };
+                println!("{:<16} {:<8} {:<9} {:<9} {}", interface, stats.changes, stats.failures, stats.reapplies, last_change);
+            }
+        }
+
+        if !self.rule_activations.is_empty() {
+            println!();
+            println!("{:<40} ACTIVATIONS", "RULE");
+            for (rule, activations) in &self.rule_activations {
+                println!("{:<40} {}", rule, activations);
+            }
+        }
+
+        if let Some(daemon) = &self.daemon {
+            println!();
+            println!("Daemon running since {}, managing {} interface(s)",
+                     daemon.started.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"), daemon.interfaces);
+        }
+    }
+
+    /// The Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let now = self.generated.timestamp() as f64;
+
+        family(&mut out, "chameleon_mac_changes_total", "counter", "MAC changes recorded in the history",
+               self.interfaces.iter().flat_map(|(interface, stats)| {
+                   stats.by_trigger.iter().map(move |(trigger, count)| {
+                       (labels(&[("interface", interface), ("trigger", trigger)]), *count as f64)
+                   })
+               }));
+        family(&mut out, "chameleon_rotation_failures_total", "counter", "Daemon rules that failed on an interface",
+               self.interfaces.iter().map(|(interface, stats)| (labels(&[("interface", interface)]), stats.failures as f64)));
+        family(&mut out, "chameleon_reapplies_total", "counter", "Reverted MACs put back by the watchdog or daemon",
+               self.interfaces.iter().map(|(interface, stats)| (labels(&[("interface", interface)]), stats.reapplies as f64)));
+        family(&mut out, "chameleon_last_change_timestamp_seconds", "gauge", "When the interface last got a new MAC",
+               self.interfaces.iter().filter_map(|(interface, stats)| {
+                   Some((labels(&[("interface", interface)]), stats.last_change?.timestamp() as f64))
+               }));
+        family(&mut out, "chameleon_seconds_since_last_change", "gauge", "Seconds since the interface last got a new MAC",
+               self.interfaces.iter().filter_map(|(interface, stats)| {
+                   Some((labels(&[("interface", interface)]), now - stats.last_change?.timestamp() as f64))
+               }));
+        family(&mut out, "chameleon_rule_activations_total", "counter", "Times a daemon rule applied its MAC",
+               self.rule_activations.iter().map(|(rule, count)| (labels(&[("rule", rule)]), *count as f64)));
+
+        family(&mut out, "chameleon_daemon_up", "gauge", "Whether this process is running the daemon",
+               [(String::new(), if self.daemon.is_some() { 1.0 } else { 0.0 })]);
+        if let Some(daemon) = &self.daemon {
+            family(&mut out, "chameleon_daemon_start_time_seconds", "gauge", "When the daemon started",
+                   [(String::new(), daemon.started.timestamp() as f64)]);
+            family(&mut out, "chameleon_daemon_managed_interfaces", "gauge", "Interfaces the daemon manages",
+                   [(String::new(), daemon.interfaces as f64)]);
+            family(&mut out, "chameleon_daemon_last_evaluation_timestamp_seconds", "gauge", "When the daemon last evaluated its rules",
+                   daemon.last_evaluation.map(|last| (String::new(), last.timestamp() as f64)));
+        }
+        out
+    }
+}
+
+/// One metric with its HELP and TYPE lines.
+fn family(out: &mut String, name: &str, kind: &str, help: &str, samples: impl IntoIterator<Item = (String, f64)>) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{}{} {}", name, labels, value);
+    }
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let pairs: Vec<String> = pairs
+        .iter()
+        .map(|(name, value)| {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+/// A `/metrics` response, or a 500 with the reason the logs could not be read.
+pub async fn response() -> Response<Full<Bytes>> {
+    let result = tokio::task::spawn_blocking(|| Stats::collect().map(|stats| stats.render()).map_err(|e| e.to_string()))
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+
+    let (status, body) = match result {
+        Ok(body) => (StatusCode::OK, body),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{}\n", e)),
+    };
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    *response.status_mut() = status;
+    response.headers_mut().insert(CONTENT_TYPE, CONTENT_TYPE_TEXT.parse().expect("valid header value"));
+    response
+}
+
+/// Serve `GET /metrics` on `listen` without authentication, for the
+/// daemon. Binds before returning so a busy port fails the daemon's start.
+pub async fn serve(listen: SocketAddr) -> Result<tokio::task::JoinHandle<()>, Box<dyn Error>> {
+    let listener = TcpListener::bind(listen).await?;
+    println!("[metrics] Serving http://{}/metrics", listener.local_addr()?);
+
+    Ok(tokio::spawn(async move {
+        loop {
+            let Ok((stream, peer)) = listener.accept().await else { continue };
+            tokio::spawn(async move {
+                let service = service_fn(|request: Request<Incoming>| async move {
+                    Ok::<_, Infallible>(match (request.method(), request.uri().path()) {
+                        (&Method::GET, "/metrics") => response().await,
+                        _ => {
+                            let mut response = Response::new(Full::new(Bytes::from("Not found, try /metrics\n")));
+                            *response.status_mut() = StatusCode::NOT_FOUND;
+                            response
+                        }
+                    })
+                });
+                if let Err(e) = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await {
+                    eprintln!("[metrics] {}: {}", peer, e);
+                }
+            });
+        }
+    }))
+}
@@ -0,0 +1,430 @@
+// src/migrate.rs
+use chrono::Utc;
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use crate::config::{get_original_mac, save_original_mac};
+use crate::error::MacError;
+use crate::mac::MacAddress;
+use crate::network::{get_current_mac, get_permanent_mac};
+use crate::profiles::{Profile, ProfileManager, Regenerate};
+
+#[cfg(target_os = "linux")]
+use std::{fs, path::Path, process::Command};
+#[cfg(target_os = "linux")]
+use crate::capabilities::find_tool;
+#[cfg(target_os = "linux")]
+use crate::mac::normalize;
+
+/// Tools chameleon can take over spoofing configuration from.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Source {
+    /// udev rules and systemd units that run macchanger (Linux)
+    Macchanger,
+    /// NetworkAddress overrides left by Technitium MAC Address Changer (Windows)
+    Tmac,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Macchanger => write!(f, "macchanger"),
+            Source::Tmac => write!(f, "tmac"),
+        }
+    }
+}
+
+/// What the old tool does to an interface.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))] // only macchanger randomizes
+pub enum Setting {
+    Fixed(MacAddress),
+    Random,
+    KeepVendor, // macchanger -e
+    Reset,      // macchanger -p
+}
+
+impl fmt::Display for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Setting::Fixed(mac) => write!(f, "fixed MAC {}", mac),
+            Setting::Random => write!(f, "random MAC"),
+            Setting::KeepVendor => write!(f, "random MAC, same vendor"),
+            Setting::Reset => write!(f, "reset to hardware MAC"),
+        }
+    }
+}
+
+/// Where the old tool keeps a setting, so it can be removed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Artifact {
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    UdevRule { path: PathBuf, line: String },
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    SystemdUnit { unit: String, link: PathBuf }, // link is the *.wants entry that enables it
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    Registry { path: String },
+}
+
+impl fmt::Display for Artifact {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Artifact::UdevRule { path, .. } => write!(f, "udev rule in {}", path.display()),
+            Artifact::SystemdUnit { unit, .. } => write!(f, "systemd unit {}", unit),
+            Artifact::Registry { path } => write!(f, "{}\\NetworkAddress", path),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub interface: String,
+    pub setting: Setting,
+    pub artifact: Artifact,
+}
+
+/// Spoofing configuration `source` left on this machine.
+pub fn detect(source: Source) -> Result<Vec<Finding>, Box<dyn Error>> {
+    match source {
+        Source::Macchanger => detect_macchanger(),
+        Source::Tmac => detect_tmac(),
+    }
+}
+
+#[cfg(target_os = "linux")]
+const UDEV_RULES_DIR: &str = "/etc/udev/rules.d";
+#[cfg(target_os = "linux")]
+const SYSTEMD_DIR: &str = "/etc/systemd/system";
+
+#[cfg(target_os = "linux")]
+fn detect_macchanger() -> Result<Vec<Finding>, Box<dyn Error>> {
+    let interfaces: Vec<String> = crate::network::NetworkCard::list()?
+        .into_iter()
+        .map(|card| card.interface)
+        .collect();
+
+    let mut findings = Vec::new();
+
+    for path in files_in(Path::new(UDEV_RULES_DIR), |name| name.ends_with(".rules")) {
+        let Ok(content) = fs::read_to_string(&path) else { continue };
+
+        for line in content.lines().filter(|line| !line.trim_start().starts_with('#')) {
+            let Some(command) = quoted_value(line, "RUN+=\"") else { continue };
+            let Some((setting, target)) = parse_macchanger(command) else { continue };
+
+            // udev substitutes the device name, which the rule matches with KERNEL
+            let pattern = match target.as_deref() {
+                Some("$name") | Some("%k") | Some("$kernel") | None => quoted_value(line, "KERNEL==\"").unwrap_or("*"),
+                Some(interface) => interface,
+            };
+
+            for interface in interfaces.iter().filter(|interface| glob_match(pattern, interface)) {
+                findings.push(Finding {
+                    interface: interface.clone(),
+                    setting: setting.clone(),
+                    artifact: Artifact::UdevRule { path: path.clone(), line: line.to_string() },
+                });
+            }
+        }
+    }
+
+    for unit in files_in(Path::new(SYSTEMD_DIR), |name| name.ends_with(".service")) {
+        let Ok(content) = fs::read_to_string(&unit) else { continue };
+        let Some((setting, target)) = content
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("ExecStart").and_then(|rest| rest.split_once('=')))
+            .find_map(|(_, command)| parse_macchanger(command))
+        else {
+            continue;
+        };
+
+        let unit_name = unit.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let template = unit_name.strip_suffix("@.service");
+
+        // A unit only runs at boot if some target wants it
+        let wants_dirs = fs::read_dir(SYSTEMD_DIR)
+            .map(|entries| entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect::<Vec<_>>())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|dir| dir.is_dir() && dir.to_string_lossy().ends_with(".wants"));
+
+        for link in wants_dirs.flat_map(|dir| files_in(&dir, |_| true)) {
+            let link_name = link.file_name().unwrap_or_default().to_string_lossy().into_owned();
+
+            let interface = match template {
+                Some(template) => match link_name.strip_prefix(&format!("{}@", template)).and_then(|rest| rest.strip_suffix(".service")) {
+                    Some(instance) if matches!(target.as_deref(), Some("%i") | Some("%I") | None) => instance.to_string(),
+                    Some(_) => target.clone().unwrap_or_default(),
+                    None => continue,
+                },
+                None if link_name == unit_name => match &target {
+                    Some(interface) => interface.clone(),
+                    None => continue,
+                },
+                None => continue,
+            };
+
+            findings.push(Finding {
+                interface,
+                setting: setting.clone(),
+                artifact: Artifact::SystemdUnit { unit: link_name, link },
+            });
+        }
+    }
+
+    Ok(findings)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn detect_macchanger() -> Result<Vec<Finding>, Box<dyn Error>> {
+    Err(Box::new(MacError::UnsupportedPlatform("macchanger migration is only available on Linux".into())))
+}
+
+#[cfg(target_os = "windows")]
+fn detect_tmac() -> Result<Vec<Finding>, Box<dyn Error>> {
+    use crate::win32;
+
+    // Chameleon writes the same registry value for --permanent; skip ours
+    let history = crate::logger::MacLogger::new().get_history()?;
+
+    let mut findings = Vec::new();
+    for adapter in win32::list_adapters()? {
+        let Some(mac) = crate::platform::get_permanent_override(&adapter.name) else { continue };
+        let mac = crate::mac::normalize(&mac)?;
+
+        let ours = history.iter().rev()
+            .find(|change| change.interface == adapter.name && change.permanent)
+            .is_some_and(|change| change.new_mac == mac);
+        if ours {
+            continue;
+        }
+
+        let device = win32::NetDevice::open(&adapter.guid)?;
+        findings.push(Finding {
+            interface: adapter.name.clone(),
+            setting: Setting::Fixed(mac),
+            artifact: Artifact::Registry { path: device.registry_path() },
+        });
+    }
+
+    Ok(findings)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn detect_tmac() -> Result<Vec<Finding>, Box<dyn Error>> {
+    Err(Box::new(MacError::UnsupportedPlatform("TMAC migration is only available on Windows".into())))
+}
+
+/// The setting and target interface of a shell command that runs
+/// macchanger, if it does.
+#[cfg(target_os = "linux")]
+fn parse_macchanger(command: &str) -> Option<(Setting, Option<String>)> {
+    let mut tokens = command
+        .split_whitespace()
+        .map(|token| token.trim_matches(|c| c == '"' || c == '\''))
+        .skip_while(|token| !token.rsplit('/').next().unwrap_or_default().eq("macchanger"))
+        .skip(1)
+        .take_while(|token| !matches!(*token, ";" | "&&" | "||" | "|"));
+
+    let mut setting = None;
+    let mut interface = None;
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "-r" | "--random" | "-a" | "--another" | "-A" => setting = Some(Setting::Random),
+            "-e" | "--ending" => setting = Some(Setting::KeepVendor),
+            "-p" | "--permanent" => setting = Some(Setting::Reset),
+            "-m" => setting = tokens.next().and_then(|mac| normalize(mac).ok()).map(Setting::Fixed),
+            _ if token.starts_with("--mac=") => setting = normalize(&token["--mac=".len()..]).ok().map(Setting::Fixed),
+            _ if token.starts_with('-') => {}
+            _ => interface = Some(token.to_string()),
+        }
+    }
+
+    Some((setting?, interface))
+}
+
+/// The text between `prefix` and the next double quote.
+#[cfg(target_os = "linux")]
+fn quoted_value<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    let (_, rest) = line.split_once(prefix)?;
+    rest.split('"').next()
+}
+
+/// Shell-style match supporting `*` and `?`, as udev uses for KERNEL.
+#[cfg(target_os = "linux")]
+fn glob_match(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[u8], name: &[u8]) -> bool {
+        match (pattern.first(), name.first()) {
+            (None, None) => true,
+            (Some(b'*'), _) => matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..])),
+            (Some(b'?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+            (Some(p), Some(c)) if p == c => matches(&pattern[1..], &name[1..]),
+            _ => false,
+        }
+    }
+
+    matches(pattern.as_bytes(), name.as_bytes())
+}
+
+#[cfg(target_os = "linux")]
+fn files_in(dir: &Path, keep: impl Fn(&str) -> bool) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect())
+        .unwrap_or_default();
+    files.retain(|path| !path.is_dir() && path.file_name().is_some_and(|name| keep(&name.to_string_lossy())));
+    files.sort();
+    files
+}
+
+/// Remove a finding's artifact so the old tool no longer changes the MAC at
+/// boot. Returns false if it was already gone.
+#[cfg(target_os = "linux")]
+pub fn remove(finding: &Finding) -> Result<bool, Box<dyn Error>> {
+    match &finding.artifact {
+        Artifact::UdevRule { path, line } => {
+            let Ok(content) = fs::read_to_string(path) else { return Ok(false) };
+            if !content.lines().any(|existing| existing == line) {
+                return Ok(false);
+            }
+
+            let remaining: Vec<&str> = content.lines().filter(|existing| existing != line).collect();
+            if remaining.iter().all(|line| line.trim().is_empty() || line.trim_start().starts_with('#')) {
+                fs::remove_file(path)?;
+            } else {
+                fs::write(path, remaining.join("\n") + "\n")?;
+            }
+
+            if let Some(udevadm) = find_tool("udevadm") {
+                let _ = Command::new(udevadm).args(["control", "--reload-rules"]).output();
+            }
+            Ok(true)
+        }
+        Artifact::SystemdUnit { link, .. } => {
+            if fs::symlink_metadata(link).is_err() {
+                return Ok(false);
+            }
+            fs::remove_file(link)?;
+
+            if let Some(systemctl) = find_tool("systemctl") {
+                let _ = Command::new(systemctl).arg("daemon-reload").output();
+            }
+            Ok(true)
+        }
+        Artifact::Registry { .. } => Ok(false),
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn remove(finding: &Finding) -> Result<bool, Box<dyn Error>> {
+    // Also restarts the adapter so it picks up its own address again
+    Ok(crate::platform::remove_permanent_override(&finding.interface)?.is_some())
+}
+
+#[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+pub fn remove(_finding: &Finding) -> Result<bool, Box<dyn Error>> {
+    Ok(false)
+}
+
+/// The profile that does what the old tool did, if it did more than reset
+/// the address.
+fn to_profile(source: Source, finding: &Finding) -> Option<Profile> {
+    let (mac_address, vendor) = match &finding.setting {
+        Setting::Fixed(mac) => (Some(mac.clone()), None),
+        Setting::Random => (None, None),
+        Setting::KeepVendor => {
+            let mac = get_permanent_mac(&finding.interface).or_else(|| get_current_mac(&finding.interface).ok())?;
+            (None, Some(mac.oui()))
+        }
+        Setting::Reset => return None,
+    };
+
+    Some(Profile {
+        name: format!("{}-{}", source, finding.interface),
+        interface: finding.interface.clone(),
+        mac_address,
+        vendor,
+        hostname: None,
+        regenerate: Regenerate::PerConnect,
+        created: Utc::now(),
+    })
+}
+
+/// Import what `source` configured as profiles and saved original MACs,
+/// optionally removing the old tool's artifacts so only chameleon changes
+/// the address.
+pub fn run(source: Source, remove_old: bool, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let findings = detect(source)?;
+    if findings.is_empty() {
+        println!("No {} configuration found", source);
+        return Ok(());
+    }
+
+    println!("Found {} {} setting(s):", findings.len(), source);
+    for finding in &findings {
+        println!("  {:<12} {} ({})", finding.interface, finding.setting, finding.artifact);
+    }
+
+    if dry_run {
+        let mut imported: Vec<&str> = Vec::new();
+        for finding in &findings {
+            if remove_old {
+                println!("Would remove {}", finding.artifact);
+            }
+            if !imported.contains(&finding.interface.as_str())
+                && let Some(profile) = to_profile(source, finding)
+            {
+                imported.push(&finding.interface);
+                println!("Would import profile {} ({})", profile.name, finding.setting);
+            }
+        }
+        println!("Dry run, nothing was changed");
+        return Ok(());
+    }
+
+    if remove_old {
+        for finding in &findings {
+            match remove(finding) {
+                Ok(true) => println!("Removed {}", finding.artifact),
+                Ok(false) => {}
+                Err(e) => println!("Could not remove {}: {}", finding.artifact, e),
+            }
+        }
+    }
+
+    let mut profiles = ProfileManager::new()?;
+    let mut imported: Vec<&str> = Vec::new();
+
+    for finding in &findings {
+        // Both a udev rule and a unit can target an interface; the first wins
+        if imported.contains(&finding.interface.as_str()) {
+            println!("Skipped {} for {} ({}): already imported", finding.setting, finding.interface, finding.artifact);
+            continue;
+        }
+        imported.push(&finding.interface);
+
+        if get_original_mac(&finding.interface)?.is_none() {
+            match get_permanent_mac(&finding.interface) {
+                Some(mac) => {
+                    save_original_mac(&finding.interface, &mac)?;
+                    println!("Saved original MAC {} for {}", mac, finding.interface);
+                }
+                None => println!("Original MAC of {} is unknown; --restore is unavailable until one is saved",
+                                 finding.interface),
+            }
+        }
+
+        if let Some(profile) = to_profile(source, finding) {
+            let name = profile.name.clone();
+            profiles.save_profile(profile)?;
+            println!("Imported profile {} ({}); apply it with `chameleon profile apply {}`",
+                     name, finding.setting, name);
+        }
+    }
+
+    if !remove_old {
+        println!("The {} configuration is still active; rerun with --remove-old so it does not fight chameleon", source);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+// src/netns.rs
+use std::error::Error;
+use std::sync::OnceLock;
+use crate::error::MacError;
+
+/// The namespace entered with `--netns`, if any.
+static NETNS: OnceLock<String> = OnceLock::new();
+
+pub fn current() -> Option<&'static str> {
+    NETNS.get().map(String::as_str)
+}
+
+/// `interface` as saved and logged: inside a namespace its name says
+/// nothing about which device it is, so the namespace is added, as in
+/// "eth0@web". Keeps a container's eth0 apart from the host's.
+pub fn qualify(interface: &str) -> String {
+    match current() {
+        Some(netns) => format!("{}@{}", interface, netns),
+        None => interface.to_string(),
+    }
+}
+
+/// Move this process into a network namespace: a name from `ip netns add`
+/// or the PID of a process inside it, such as a container's. A private
+/// /sys is mounted from inside, since sysfs shows the namespace of whoever
+/// mounted it. Namespaces belong to threads, so this runs before any other
+/// thread starts; threads created afterwards inherit it.
+#[cfg(target_os = "linux")]
+pub fn enter(target: &str) -> Result<(), Box<dyn Error>> {
+    use nix::mount::{mount, umount2, MntFlags, MsFlags};
+    use nix::sched::{setns, unshare, CloneFlags};
+    use std::path::PathBuf;
+
+    let path = if target.chars().all(|c| c.is_ascii_digit()) {
+        PathBuf::from(format!("/proc/{}/ns/net", target))
+    } else {
+        PathBuf::from("/run/netns").join(target)
+    };
+    let namespace = std::fs::File::open(&path).map_err(|e| MacError::ValidationFailed(
+        format!("No network namespace {} ({}): {}", target, path.display(), e)
+    ))?;
+
+    let denied = |e: nix::Error| MacError::PermissionDenied(format!("Could not enter network namespace {}: {}", target, e));
+    setns(&namespace, CloneFlags::CLONE_NEWNET).map_err(denied)?;
+
+    // The same steps as `ip netns exec`, so the host's mounts are left alone
+    unshare(CloneFlags::CLONE_NEWNS).map_err(denied)?;
+    mount(None::<&str>, "/", None::<&str>, MsFlags::MS_REC | MsFlags::MS_PRIVATE, None::<&str>).map_err(denied)?;
+    umount2("/sys", MntFlags::MNT_DETACH).map_err(denied)?;
+    mount(Some("sysfs"), "/sys", Some("sysfs"), MsFlags::empty(), None::<&str>).map_err(denied)?;
+
+    let _ = NETNS.set(target.to_string());
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn enter(_target: &str) -> Result<(), Box<dyn Error>> {
+    Err(Box::new(MacError::UnsupportedPlatform("Network namespaces only exist on Linux".into())))
+}
@@ -1,55 +1,90 @@
-use std::error::Error;
+use serde::Serialize;
 use std::process::Command;
+use crate::capabilities::find_tool;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use crate::capabilities::require_tool;
+#[cfg(target_os = "linux")]
 use std::fs;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
 use std::path::Path;
 use crate::error::MacError;
+use crate::mac::{MacAddress, MacFormat};
+#[cfg(target_os = "windows")]
+use windows::Win32::NetworkManagement::IpHelper::IF_TYPE_IEEE80211;
+
+/// A per-link address of a Wi-Fi 7 multi-link (MLO) interface.
+#[derive(Debug, Clone, Serialize)]
+pub struct MloLink {
+    pub link_id: u8,
+    pub address: MacAddress,
+}
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct NetworkCard {
     pub interface: String,
     pub vendor: Option<String>,
     pub supports_mac_change: bool,
     pub permanent_change_supported: bool,
     pub driver: String,
+    pub is_up: bool,
+    pub is_wireless: bool,
+    pub mlo_capable: bool,
+    pub mlo_links: Vec<MloLink>,
+    /// Wi-Fi only: the network it is associated with, if any
+    pub connection: Option<WirelessConnection>,
+    /// Whether rfkill has the radio switched off; None where rfkill is unknown
+    pub rfkill: Option<RfkillState>,
+    pub monitor_mode: bool,
 }
 
 impl NetworkCard {
-    #[cfg(not(target_os = "windows"))]
+    /// Every non-loopback interface chameleon could operate on. Interfaces
+    /// whose details cannot be read are skipped.
+    pub fn list() -> Result<Vec<Self>, MacError> {
+        let mut cards: Vec<Self> = list_interfaces()?
+            .iter()
+            .filter_map(|name| Self::new(name).ok())
+            .collect();
+
+        cards.sort_by(|a, b| a.interface.cmp(&b.interface));
+        Ok(cards)
+    }
 
-    pub fn verify_interface(interface: &str) -> Result<Self, Box<dyn Error>> {
+    #[cfg(not(target_os = "windows"))]
+    pub fn verify_interface(interface: &str) -> Result<Self, MacError> {
         let interfaces = pnet::datalink::interfaces();
 
         if !interfaces.iter().any(|iface| iface.name == interface) {
-            return Err(Box::new(MacError::ValidationFailed(
+            return Err(MacError::InterfaceNotFound(
                 format!("Interface {} not found", interface)
-            )));
+            ));
         }
 
         Self::new(interface)
     }
 
     #[cfg(target_os = "linux")]
-    fn new(interface: &str) -> Result<Self, Box<dyn Error>> {
+    fn new(interface: &str) -> Result<Self, MacError> {
         let sys_net_path = Path::new("/sys/class/net").join(interface);
 
         // Check if interface exists in sysfs
         if !sys_net_path.exists() {
-            return Err(Box::new(MacError::ValidationFailed(
+            return Err(MacError::InterfaceNotFound(
                 format!("Interface {} not found in sysfs", interface)
-            )));
+            ));
         }
 
         // Check interface type
         let interface_type = fs::read_to_string(sys_net_path.join("type"))
-            .map_err(|_| MacError::ValidationFailed(format!("Failed to read interface type for {}", interface)))?
+            .map_err(|e| MacError::SystemError(format!("Failed to read interface type for {}: {}", interface, e)))?
             .trim()
             .to_string();
 
         // Check if this is a loopback interface
         if interface_type == "772" {
-            return Err(Box::new(MacError::ValidationFailed(
+            return Err(MacError::ValidationFailed(
                 format!("Cannot change MAC address of loopback interface {}", interface)
-            )));
+            ));
         }
 
         // Get device information if available
@@ -81,88 +116,118 @@ impl NetworkCard {
             interface_type != "772" && // Not loopback
             interface_type != "768";   // Not point to point
 
+        // IFF_UP is bit 0 of the interface flags
+        let is_up = fs::read_to_string(sys_net_path.join("flags"))
+            .ok()
+            .and_then(|flags| u32::from_str_radix(flags.trim().trim_start_matches("0x"), 16).ok())
+            .map(|flags| flags & 0x1 != 0)
+            .unwrap_or(false);
+
+        // Wi-Fi 7 adapters expose the MLD address as the interface address
+        // plus one address per affiliated link
+        let mlo_links = get_mlo_links(interface);
+        let mlo_capable = !mlo_links.is_empty() || is_mlo_capable(interface);
+        let wireless = is_wireless(interface);
+
         Ok(NetworkCard {
             interface: interface.to_string(),
             vendor,
             supports_mac_change,
             permanent_change_supported: supports_mac_change,
             driver,
+            is_up,
+            is_wireless: wireless,
+            mlo_capable,
+            mlo_links,
+            connection: wireless.then(|| get_wireless_connection(interface)).flatten(),
+            rfkill: wireless.then(|| get_rfkill_state(interface)).flatten(),
+            monitor_mode: is_monitor_mode(interface),
         })
     }
 
     #[cfg(target_os = "macos")]
-    fn new(interface: &str) -> Result<Self, Box<dyn Error>> {
-        let output = Command::new("networksetup")
-            .args(&["-listallhardwareports"])
+    fn new(interface: &str) -> Result<Self, MacError> {
+        let output = Command::new(require_tool("networksetup")?)
+            .args(["-listallhardwareports"])
             .output()?;
 
         if !output.status.success() {
-            return Err(Box::new(MacError::SystemError(
+            return Err(MacError::SystemError(
                 String::from_utf8_lossy(&output.stderr).to_string()
-            )));
+            ));
         }
 
+        let is_up = pnet::datalink::interfaces()
+            .iter()
+            .any(|iface| iface.name == interface && iface.is_up());
+
+        let supports_mac_change = !matches!(
+            crate::macos::spoof_capability(interface),
+            crate::macos::SpoofCapability::Unsupported(_)
+        );
+        let wireless = is_wireless(interface);
+
         Ok(NetworkCard {
             interface: interface.to_string(),
             vendor: None,
-            supports_mac_change: true,
+            supports_mac_change,
             permanent_change_supported: false,
             driver: String::new(),
+            is_up,
+            is_wireless: wireless,
+            mlo_capable: false,
+            mlo_links: Vec::new(),
+            connection: wireless.then(|| get_wireless_connection(interface)).flatten(),
+            rfkill: None,
+            monitor_mode: false,
         })
     }
 
-    #[cfg(target_os = "windows")]
-    pub fn verify_interface(interface: &str) -> Result<Self, Box<dyn Error>> {
-        // Use getmac to verify interface exists
-        let output = Command::new("getmac")
-            .args(&["/v", "/fo", "csv"])
-            .output()?;
+    #[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+    fn new(interface: &str) -> Result<Self, MacError> {
+        let output = crate::bsd::ifconfig(interface)
+            .ok_or_else(|| MacError::InterfaceNotFound(format!("Interface {} not found", interface)))?;
+        let wireless = crate::bsd::is_wireless(&output);
 
-        if !output.status.success() {
-            return Err(Box::new(MacError::SystemError(
-                String::from_utf8_lossy(&output.stderr).to_string()
-            )));
-        }
+        Ok(NetworkCard {
+            interface: interface.to_string(),
+            vendor: None,
+            // Tunnels and other interfaces without a link-layer address
+            supports_mac_change: crate::bsd::current_mac(&output).is_some(),
+            permanent_change_supported: true,
+            driver: crate::bsd::driver(interface).to_string(),
+            is_up: crate::bsd::is_up(&output),
+            is_wireless: wireless,
+            mlo_capable: false,
+            mlo_links: Vec::new(),
+            connection: wireless.then(|| crate::bsd::wireless_connection(&output)).flatten(),
+            rfkill: None,
+            monitor_mode: output.contains("<MONITOR") || output.contains(" monitor"),
+        })
+    }
 
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        if !output_str.lines().any(|line| line.contains(interface)) {
-            return Err(Box::new(MacError::ValidationFailed(
-                format!("Interface {} not found", interface)
-            )));
-        }
+    #[cfg(target_os = "windows")]
+    pub fn verify_interface(interface: &str) -> Result<Self, MacError> {
+        // Fails with "Interface ... not found" if no adapter matches
+        crate::win32::find_adapter(interface)?;
 
         Self::new(interface)
     }
 
     #[cfg(target_os = "windows")]
-    fn new(interface: &str) -> Result<Self, Box<dyn Error>> {
-        // Get interface details using wmic
-        let output = Command::new("wmic")
-            .args(&["nic", "where", &format!("NetConnectionID='{}'", interface), "get", "Manufacturer,ServiceName,Name", "/format:csv"])
-            .output()?;
-
-        if !output.status.success() {
-            return Err(Box::new(MacError::SystemError(
-                String::from_utf8_lossy(&output.stderr).to_string()
-            )));
-        }
-
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        let lines: Vec<&str> = output_str.lines().collect();
-
-        // Skip header row and get first data row
-        let vendor = lines.get(1)
-            .and_then(|line| line.split(',').nth(1))
-            .map(|s| s.trim().to_string());
+    fn new(interface: &str) -> Result<Self, MacError> {
+        let adapter = crate::win32::find_adapter(interface)?;
 
-        let driver = lines.get(1)
-            .and_then(|line| line.split(',').nth(2))
-            .map(|s| s.trim().to_string())
-            .unwrap_or_default();
+        // Manufacturer and driver service come from the SetupAPI device
+        let (vendor, driver) = match crate::win32::NetDevice::open(&adapter.guid) {
+            Ok(device) => (device.manufacturer(), device.service().unwrap_or_default()),
+            Err(_) => (None, String::new()),
+        };
 
         // Check if interface supports MAC address changes
-        // Most Windows network interfaces support this, but we can add additional checks here
-        let supports_mac_change = true;
+        // Only adapters backed by a physical address can take a NetworkAddress override
+        let supports_mac_change = adapter.mac.is_some();
+        let wireless = adapter.if_type == IF_TYPE_IEEE80211;
 
         Ok(NetworkCard {
             interface: interface.to_string(),
@@ -170,33 +235,507 @@ impl NetworkCard {
             supports_mac_change,
             permanent_change_supported: true,
             driver,
+            is_up: adapter.is_up,
+            is_wireless: wireless,
+            mlo_capable: false,
+            mlo_links: Vec::new(),
+            connection: wireless.then(|| get_wireless_connection(interface)).flatten(),
+            rfkill: None,
+            monitor_mode: false,
         })
     }
 }
 
-pub fn get_current_mac(interface: &str) -> Result<String, Box<dyn Error>> {
+/// Names of every non-loopback interface.
+#[cfg(not(target_os = "windows"))]
+pub fn list_interfaces() -> Result<Vec<String>, MacError> {
+    Ok(pnet::datalink::interfaces()
+        .into_iter()
+        .filter(|iface| !iface.is_loopback())
+        .map(|iface| iface.name)
+        .collect())
+}
+
+// pnet reports NPF device paths on Windows, use connection names instead
+#[cfg(target_os = "windows")]
+pub fn list_interfaces() -> Result<Vec<String>, MacError> {
+    Ok(crate::win32::list_adapters()?
+        .into_iter()
+        .filter(|adapter| adapter.mac.is_some())
+        .map(|adapter| adapter.name)
+        .collect())
+}
+
+#[cfg(target_os = "linux")]
+pub fn is_wireless(interface: &str) -> bool {
+    let sys_net_path = Path::new("/sys/class/net").join(interface);
+    sys_net_path.join("wireless").exists() || sys_net_path.join("phy80211").exists()
+}
+
+#[cfg(target_os = "macos")]
+pub fn is_wireless(interface: &str) -> bool {
+    hardware_port(interface).is_some_and(|port| port == "Wi-Fi" || port == "AirPort")
+}
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub fn is_wireless(interface: &str) -> bool {
+    crate::bsd::ifconfig(interface).is_some_and(|output| crate::bsd::is_wireless(&output))
+}
+
+/// The networksetup hardware port name of a device, e.g. "Wi-Fi" or
+/// "USB 10/100/1000 LAN".
+#[cfg(target_os = "macos")]
+pub fn hardware_port(interface: &str) -> Option<String> {
+    // networksetup lists each port as a "Hardware Port:" line followed by "Device:"
+    let output = Command::new(find_tool("networksetup")?)
+        .arg("-listallhardwareports")
+        .output()
+        .ok()?;
+
+    let output_str = String::from_utf8_lossy(&output.stdout);
+    let mut current_port = "";
+    for line in output_str.lines() {
+        if let Some(port) = line.strip_prefix("Hardware Port:") {
+            current_port = port.trim();
+        } else if let Some(device) = line.strip_prefix("Device:")
+            && device.trim() == interface
+        {
+            return Some(current_port.to_string());
+        }
+    }
+
+    None
+}
+
+#[cfg(target_os = "windows")]
+pub fn is_wireless(interface: &str) -> bool {
+    crate::win32::find_adapter(interface)
+        .map(|adapter| adapter.if_type == IF_TYPE_IEEE80211)
+        .unwrap_or(false)
+}
+
+/// Administrative and physical state of a link.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkState {
+    pub up: bool,      // Administratively up
+    pub carrier: bool, // Cable plugged in / associated
+}
+
+#[cfg(target_os = "linux")]
+pub fn get_link_state(interface: &str) -> Option<LinkState> {
+    let sys_net_path = Path::new("/sys/class/net").join(interface);
+
+    let flags = fs::read_to_string(sys_net_path.join("flags")).ok()?;
+    let flags = u32::from_str_radix(flags.trim().trim_start_matches("0x"), 16).ok()?;
+
+    // Reading carrier fails with EINVAL while the interface is down
+    let carrier = fs::read_to_string(sys_net_path.join("carrier"))
+        .map(|carrier| carrier.trim() == "1")
+        .unwrap_or(false);
+
+    Some(LinkState { up: flags & 0x1 != 0, carrier })
+}
+
+#[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+pub fn get_link_state(interface: &str) -> Option<LinkState> {
+    let output = Command::new(find_tool("ifconfig")?).arg(interface).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let output_str = String::from_utf8_lossy(&output.stdout);
+    Some(LinkState {
+        up: output_str.lines().next().is_some_and(|flags| flags.contains("<UP")),
+        carrier: output_str.contains("status: active"),
+    })
+}
+
+#[cfg(target_os = "windows")]
+pub fn get_link_state(interface: &str) -> Option<LinkState> {
+    // IP Helper only reports the operational status, which covers both
+    let adapter = crate::win32::find_adapter(interface).ok()?;
+    Some(LinkState { up: adapter.is_up, carrier: adapter.is_up })
+}
+
+/// IPv4 addresses currently assigned to an interface.
+#[cfg(not(target_os = "windows"))]
+pub fn get_ipv4_addresses(interface: &str) -> Vec<String> {
+    pnet::datalink::interfaces()
+        .into_iter()
+        .filter(|iface| iface.name == interface)
+        .flat_map(|iface| iface.ips)
+        .filter(|network| network.is_ipv4())
+        .map(|network| network.ip().to_string())
+        .collect()
+}
+
+#[cfg(target_os = "windows")]
+pub fn get_ipv4_addresses(interface: &str) -> Vec<String> {
+    crate::win32::find_adapter(interface)
+        .and_then(|adapter| crate::win32::ipv4_addresses(&adapter.guid))
+        .unwrap_or_default()
+}
+
+/// The kind of link behind an interface, for rules that target classes of
+/// interfaces instead of names.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InterfaceClass {
+    pub wireless: bool,
+    pub speed_mbps: Option<u64>, // None while there is no link or the driver doesn't say
+    pub docked: bool,            // Attached through USB or Thunderbolt
+}
+
+pub fn get_interface_class(interface: &str) -> InterfaceClass {
+    InterfaceClass {
+        wireless: is_wireless(interface),
+        speed_mbps: get_link_speed(interface),
+        docked: is_docked(interface),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn get_link_speed(interface: &str) -> Option<u64> {
+    // Reads -1 or fails with EINVAL when there is no link
+    let speed = fs::read_to_string(Path::new("/sys/class/net").join(interface).join("speed")).ok()?;
+    speed.trim().parse::<i64>().ok().filter(|&speed| speed > 0).map(|speed| speed as u64)
+}
+
+#[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+fn get_link_speed(interface: &str) -> Option<u64> {
+    // ifconfig reports the negotiated media, e.g. "media: autoselect (1000baseT <full-duplex>)"
+    let output = Command::new(find_tool("ifconfig")?).arg(interface).output().ok()?;
+    let output_str = String::from_utf8_lossy(&output.stdout);
+
+    let media = output_str.lines().find_map(|line| line.trim().strip_prefix("media:"))?;
+    let active = &media[media.find('(')? + 1..];
+    let digits = active.find(|c: char| !c.is_ascii_digit())?;
+    let speed: u64 = active[..digits].parse().ok()?;
+
+    match &active[digits..] {
+        rest if rest.starts_with("Gbase") => Some(speed * 1000),
+        rest if rest.starts_with("base") => Some(speed),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn get_link_speed(interface: &str) -> Option<u64> {
+    crate::win32::find_adapter(interface).ok()?.speed_mbps
+}
+
+#[cfg(target_os = "linux")]
+fn is_docked(interface: &str) -> bool {
+    let Ok(device) = fs::canonicalize(Path::new("/sys/class/net").join(interface).join("device")) else {
+        return false;
+    };
+
+    // USB NICs sit under a USB controller; Thunderbolt PCIe devices are
+    // behind a bridge the kernel marks as removable
+    device.to_string_lossy().contains("/usb")
+        || device.ancestors().take_while(|path| path.starts_with("/sys/devices")).any(|path| {
+            fs::read_to_string(path.join("removable")).is_ok_and(|removable| removable.trim() == "removable")
+        })
+}
+
+#[cfg(target_os = "macos")]
+fn is_docked(interface: &str) -> bool {
+    // "Thunderbolt Bridge" is the virtual IP-over-Thunderbolt port, not a dock NIC
+    hardware_port(interface).is_some_and(|port| {
+        port.contains("USB") || (port.contains("Thunderbolt") && port != "Thunderbolt Bridge")
+    })
+}
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+fn is_docked(interface: &str) -> bool {
+    crate::bsd::is_usb(interface)
+}
+
+#[cfg(target_os = "windows")]
+fn is_docked(interface: &str) -> bool {
+    crate::win32::find_adapter(interface)
+        .and_then(|adapter| crate::win32::NetDevice::open(&adapter.guid))
+        .is_ok_and(|device| device.enumerator().is_some_and(|bus| bus.eq_ignore_ascii_case("USB")))
+}
+
+/// The Wi-Fi network an interface is currently associated with.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct WirelessConnection {
+    pub ssid: Option<String>,
+    pub bssid: Option<MacAddress>,
+}
+
+#[cfg(target_os = "linux")]
+pub fn get_wireless_connection(interface: &str) -> Option<WirelessConnection> {
+    // iw prints "Connected to <bssid> (on wlan0)" followed by "SSID: <name>"
+    if let Some(iw) = find_tool("iw")
+        && let Ok(output) = Command::new(iw).args(["dev", interface, "link"]).output()
+        && output.status.success()
+    {
+        let output_str = String::from_utf8_lossy(&output.stdout);
+        let bssid = output_str
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("Connected to "))
+            .and_then(|rest| rest.split_whitespace().next())
+            .and_then(|bssid| bssid.parse().ok());
+        let ssid = output_str
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("SSID: "))
+            .map(|ssid| ssid.to_string());
+
+        if ssid.is_some() || bssid.is_some() {
+            return Some(WirelessConnection { ssid, bssid });
+        }
+        return None;
+    }
+
+    // Fall back to NetworkManager; terse output escapes the colons in BSSIDs
+    let output = Command::new(find_tool("nmcli")?)
+        .args(["-t", "-f", "ACTIVE,SSID,BSSID", "device", "wifi", "list", "ifname", interface])
+        .output()
+        .ok()?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| {
+            let line = line.strip_prefix("yes:")?.replace("\\:", "\u{0}");
+            let (ssid, bssid) = line.rsplit_once(':')?;
+            Some(WirelessConnection {
+                ssid: Some(ssid.replace('\u{0}', ":")).filter(|s| !s.is_empty()),
+                bssid: bssid.replace('\u{0}', ":").parse().ok(),
+            })
+        })
+}
+
+#[cfg(target_os = "windows")]
+pub fn get_wireless_connection(interface: &str) -> Option<WirelessConnection> {
+    let output = Command::new(find_tool("netsh")?)
+        .args(["wlan", "show", "interfaces"])
+        .output()
+        .ok()?;
+
+    // One "key : value" block per wireless interface, starting with "Name"
+    let output_str = String::from_utf8_lossy(&output.stdout);
+    let mut in_interface = false;
+    let mut connection = WirelessConnection::default();
+
+    for line in output_str.lines() {
+        let Some((key, value)) = line.split_once(':') else { continue };
+        let (key, value) = (key.trim(), value.trim());
+
+        match key {
+            "Name" => in_interface = value.eq_ignore_ascii_case(interface),
+            "SSID" if in_interface => connection.ssid = Some(value.to_string()),
+            "BSSID" if in_interface => connection.bssid = value.parse().ok(),
+            _ => {}
+        }
+    }
+
+    if connection.ssid.is_some() || connection.bssid.is_some() {
+        Some(connection)
+    } else {
+        None
+    }
+}
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub fn get_wireless_connection(interface: &str) -> Option<WirelessConnection> {
+    crate::bsd::wireless_connection(&crate::bsd::ifconfig(interface)?)
+}
+
+#[cfg(target_os = "macos")]
+pub fn get_wireless_connection(interface: &str) -> Option<WirelessConnection> {
+    const AIRPORT_PATH: &str =
+        "/System/Library/PrivateFrameworks/Apple80211.framework/Versions/Current/Resources/airport";
+
+    // airport was removed in macOS 14.4; CoreWLAN answers on newer releases
+    if !Path::new(AIRPORT_PATH).exists() {
+        return crate::macos::wifi_connection(interface);
+    }
+
+    let output = Command::new(AIRPORT_PATH).arg("-I").output().ok()?;
+    let output_str = String::from_utf8_lossy(&output.stdout);
+
+    let field = |name: &str| {
+        output_str
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(name))
+            .map(|value| value.trim().to_string())
+    };
+
+    let connection = WirelessConnection {
+        ssid: field("SSID:"),
+        bssid: field("BSSID:").and_then(|bssid| bssid.parse().ok()),
+    };
+
+    if connection.ssid.is_some() || connection.bssid.is_some() {
+        Some(connection)
+    } else {
+        None
+    }
+}
+
+/// Whether rfkill has switched a radio off. A soft block can be lifted with
+/// `rfkill unblock`; a hard block is a physical switch or firmware setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))] // Only Linux has rfkill
+pub enum RfkillState {
+    Unblocked,
+    SoftBlocked,
+    HardBlocked,
+}
+
+/// The rfkill switch of a Wi-Fi interface's radio, from
+/// `/sys/class/net/<iface>/phy80211/rfkill*`.
+#[cfg(target_os = "linux")]
+pub fn get_rfkill_state(interface: &str) -> Option<RfkillState> {
+    let phy = Path::new("/sys/class/net").join(interface).join("phy80211");
+    let rfkill = fs::read_dir(phy)
+        .ok()?
+        .flatten()
+        .find(|entry| entry.file_name().to_string_lossy().starts_with("rfkill"))?
+        .path();
+    let blocked = |switch: &str| fs::read_to_string(rfkill.join(switch)).is_ok_and(|value| value.trim() == "1");
+
+    Some(if blocked("hard") {
+        RfkillState::HardBlocked
+    } else if blocked("soft") {
+        RfkillState::SoftBlocked
+    } else {
+        RfkillState::Unblocked
+    })
+}
+
+/// Monitor-mode interfaces deliver frames with radiotap headers
+/// (ARPHRD_IEEE80211_RADIOTAP) instead of Ethernet ones.
+#[cfg(target_os = "linux")]
+pub fn is_monitor_mode(interface: &str) -> bool {
+    fs::read_to_string(Path::new("/sys/class/net").join(interface).join("type"))
+        .is_ok_and(|link_type| link_type.trim() == "803")
+}
+
+/// Drop a Wi-Fi association before a MAC change. wpa_supplicant is told to
+/// stay disconnected, so it does not reassociate halfway through; without
+/// it, the kernel is asked directly.
+#[cfg(target_os = "linux")]
+pub fn disconnect_wireless(interface: &str) -> Result<(), MacError> {
+    if run_quietly("wpa_cli", &["-i", interface, "disconnect"]) {
+        return Ok(());
+    }
+    let output = Command::new(require_tool("iw")?).args(["dev", interface, "disconnect"]).output()?;
+    if !output.status.success() {
+        return Err(MacError::SystemError(format!(
+            "Could not disconnect {} from Wi-Fi: {}", interface, String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Reassociate after `disconnect_wireless`, through wpa_supplicant when it
+/// runs. Otherwise only open networks can be rejoined, by SSID.
+#[cfg(target_os = "linux")]
+pub fn reconnect_wireless(interface: &str, ssid: Option<&str>) -> Result<(), MacError> {
+    if run_quietly("wpa_cli", &["-i", interface, "reconnect"]) {
+        return Ok(());
+    }
+    match ssid {
+        Some(ssid) if run_quietly("iw", &["dev", interface, "connect", ssid]) => Ok(()),
+        _ => Err(MacError::SystemError(format!(
+            "Could not reconnect {}: wpa_supplicant is not running for it", interface
+        ))),
+    }
+}
+
+/// Wait up to `timeout` for an interface to associate.
+#[cfg(target_os = "linux")]
+pub fn wait_for_association(interface: &str, timeout: std::time::Duration) -> Option<WirelessConnection> {
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
+        if let Some(connection) = get_wireless_connection(interface) {
+            return Some(connection);
+        }
+        if std::time::Instant::now() >= deadline {
+            return None;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(500));
+    }
+}
+
+/// wpa_cli exits 0 even when the supplicant refuses, so "OK" is checked too.
+#[cfg(target_os = "linux")]
+fn run_quietly(program: &str, args: &[&str]) -> bool {
+    let Some(path) = find_tool(program) else { return false };
+    Command::new(path).args(args).output().is_ok_and(|output| {
+        output.status.success() && (program != "wpa_cli" || String::from_utf8_lossy(&output.stdout).trim() == "OK")
+    })
+}
+
+/// Link addresses of an associated multi-link interface, as reported by
+/// `iw dev <iface> info`. Empty for single-link or non-wireless interfaces.
+#[cfg(target_os = "linux")]
+pub fn get_mlo_links(interface: &str) -> Vec<MloLink> {
+    let Some(iw) = find_tool("iw") else { return Vec::new() };
+    let output = match Command::new(iw)
+        .args(["dev", interface, "info"])
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        _ => return Vec::new(),
+    };
+
+    // Lines look like " - link ID  0 link addr 00:11:22:33:44:56"
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim().strip_prefix("- link ID")?;
+            let (id, addr) = line.split_once("link addr")?;
+            Some(MloLink {
+                link_id: id.trim().parse().ok()?,
+                address: crate::mac::normalize(addr.split_whitespace().next()?).ok()?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn is_mlo_capable(interface: &str) -> bool {
+    let phy = match fs::read_to_string(Path::new("/sys/class/net").join(interface).join("phy80211/name")) {
+        Ok(phy) => phy.trim().to_string(),
+        Err(_) => return false,
+    };
+
+    // Drivers with multi-link support advertise MLD capabilities on the wiphy
+    find_tool("iw")
+        .and_then(|iw| Command::new(iw).args(["phy", &phy, "info"]).output().ok())
+        .is_some_and(|output| String::from_utf8_lossy(&output.stdout).contains("MLD Capabilities"))
+}
+
+/// The MAC the interface uses now, however the platform prints it.
+pub fn get_current_mac(interface: &str) -> Result<MacAddress, MacError> {
+    Ok(MacAddress::parse(&read_current_mac(interface)?)?.with_format(MacFormat::Colon))
+}
+
+fn read_current_mac(interface: &str) -> Result<String, MacError> {
     #[cfg(target_os = "linux")]
     {
         // First try reading from sysfs
         let addr_path = Path::new("/sys/class/net").join(interface).join("address");
-        if addr_path.exists() {
-            if let Ok(mac) = fs::read_to_string(addr_path) {
-                let mac = mac.trim();
-                if !mac.is_empty() {
-                    return Ok(mac.to_string());
-                }
+        if let Ok(mac) = fs::read_to_string(addr_path) {
+            let mac = mac.trim();
+            if !mac.is_empty() {
+                return Ok(mac.to_string());
             }
         }
 
         // Fallback to ip command
-        let output = Command::new("ip")
-            .args(&["link", "show", interface])
+        let output = Command::new(require_tool("ip")?)
+            .args(["link", "show", interface])
             .output()?;
 
         if !output.status.success() {
-            return Err(Box::new(MacError::SystemError(
+            return Err(MacError::SystemError(
                 String::from_utf8_lossy(&output.stderr).to_string()
-            )));
+            ));
         }
 
         let output_str = String::from_utf8_lossy(&output.stdout);
@@ -208,21 +747,21 @@ pub fn get_current_mac(interface: &str) -> Result<String, Box<dyn Error>> {
             return Ok(mac.to_string());
         }
 
-        return Err(Box::new(MacError::ValidationFailed(
+        return Err(MacError::SystemError(
             format!("Could not get current MAC address for interface {}", interface)
-        )));
+        ));
     }
 
     #[cfg(target_os = "macos")]
     {
-        let output = Command::new("ifconfig")
+        let output = Command::new(require_tool("ifconfig")?)
             .arg(interface)
             .output()?;
 
         if !output.status.success() {
-            return Err(Box::new(MacError::SystemError(
+            return Err(MacError::SystemError(
                 String::from_utf8_lossy(&output.stderr).to_string()
-            )));
+            ));
         }
 
         let output_str = String::from_utf8_lossy(&output.stdout);
@@ -234,39 +773,107 @@ pub fn get_current_mac(interface: &str) -> Result<String, Box<dyn Error>> {
             return Ok(mac.to_string());
         }
 
-        return Err(Box::new(MacError::ValidationFailed(
+        return Err(MacError::SystemError(
             format!("Could not get current MAC address for interface {}", interface)
-        )));
+        ));
+    }
+
+    #[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+    {
+        let output = crate::bsd::ifconfig(interface)
+            .ok_or_else(|| MacError::InterfaceNotFound(format!("Interface {} not found", interface)))?;
+        return crate::bsd::current_mac(&output).map(str::to_string).ok_or_else(|| MacError::SystemError(
+            format!("Could not get current MAC address for interface {}", interface)
+        ));
     }
 
     #[cfg(target_os = "windows")]
     {
-        let output = Command::new("getmac")
-            .args(&["/v", "/fo", "csv"])
-            .output()?;
+        return crate::win32::find_adapter(interface)?
+            .mac
+            .ok_or_else(|| MacError::SystemError(
+                format!("Could not get current MAC address for interface {}", interface)
+            ));
+    }
 
-        if !output.status.success() {
-            return Err(Box::new(MacError::SystemError(
-                String::from_utf8_lossy(&output.stderr).to_string()
-            )));
-        }
+    #[allow(unreachable_code)]
+    Err(MacError::UnsupportedPlatform(
+        "Unsupported operating system".into()
+    ))
+}
 
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        if let Some(mac) = output_str
-            .lines()
-            .find(|line| line.contains(interface))
-            .and_then(|line| line.split(',').nth(2))
-        {
-            return Ok(mac.trim_matches('"').to_string());
-        }
+/// The address burned into the hardware, which stays put while the current
+/// MAC is changed. None for virtual interfaces and drivers that don't report
+/// it. Needs no privileges.
+#[cfg(target_os = "linux")]
+pub fn get_permanent_mac(interface: &str) -> Option<MacAddress> {
+    use nix::libc;
+    use nix::sys::socket::{socket, AddressFamily, SockFlag, SockType};
+    use std::os::fd::AsRawFd;
+
+    const ETHTOOL_GPERMADDR: u32 = 0x20;
+
+    #[repr(C)]
+    struct PermAddr {
+        cmd: u32,
+        size: u32,
+        data: [u8; 32],
+    }
 
-        return Err(Box::new(MacError::ValidationFailed(
-            format!("Could not get current MAC address for interface {}", interface)
-        )));
+    if interface.len() >= libc::IFNAMSIZ {
+        return None;
     }
 
-    #[allow(unreachable_code)]
-    Err(Box::new(MacError::UnsupportedPlatform(
-        "Unsupported operating system".into()
-    )))
-}
\ No newline at end of file
+    let fd = socket(AddressFamily::Inet, SockType::Datagram, SockFlag::SOCK_CLOEXEC, None).ok()?;
+    let mut perm_addr = PermAddr { cmd: ETHTOOL_GPERMADDR, size: 32, data: [0; 32] };
+    let mut request: libc::ifreq = unsafe { std::mem::zeroed() };
+    for (dst, &src) in request.ifr_name.iter_mut().zip(interface.as_bytes()) {
+        *dst = src as libc::c_char;
+    }
+    request.ifr_ifru.ifru_data = &mut perm_addr as *mut PermAddr as *mut libc::c_char;
+
+    // The request parameter is c_ulong on glibc and c_int on musl
+    if unsafe { libc::ioctl(fd.as_raw_fd(), libc::SIOCETHTOOL as _, &mut request as *mut libc::ifreq) } < 0 {
+        return None;
+    }
+
+    permanent_from_bytes(perm_addr.data.get(..perm_addr.size as usize)?)
+}
+
+#[cfg(target_os = "macos")]
+pub fn get_permanent_mac(interface: &str) -> Option<MacAddress> {
+    // networksetup reports the factory address after each port's "Device:" line
+    let output = Command::new(find_tool("networksetup")?)
+        .arg("-listallhardwareports")
+        .output()
+        .ok()?;
+
+    let output_str = String::from_utf8_lossy(&output.stdout);
+    let mut lines = output_str.lines();
+    lines.find(|line| line.strip_prefix("Device:").is_some_and(|device| device.trim() == interface))?;
+
+    let address = lines.next()?.strip_prefix("Ethernet Address:")?.trim();
+    let mac = MacAddress::parse(address).ok()?;
+    permanent_from_bytes(mac.get_bytes())
+}
+
+/// FreeBSD reports it as hwaddr; OpenBSD has no way to ask.
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub fn get_permanent_mac(interface: &str) -> Option<MacAddress> {
+    let output = crate::bsd::ifconfig(interface)?;
+    let mac = MacAddress::parse(crate::bsd::hardware_mac(&output)?).ok()?;
+    permanent_from_bytes(mac.get_bytes())
+}
+
+#[cfg(target_os = "windows")]
+pub fn get_permanent_mac(interface: &str) -> Option<MacAddress> {
+    let adapter = crate::win32::find_adapter(interface).ok()?;
+    permanent_from_bytes(&crate::win32::permanent_address(&adapter.guid)?)
+}
+
+/// A 6-byte hardware address, skipping the all-zero placeholder drivers use
+/// when there is none.
+fn permanent_from_bytes(bytes: &[u8]) -> Option<MacAddress> {
+    let mac = MacAddress::new(bytes.try_into().ok()?, MacFormat::Colon);
+    (!mac.is_null()).then_some(mac)
+}
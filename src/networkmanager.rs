@@ -0,0 +1,101 @@
+// src/networkmanager.rs
+use std::error::Error;
+use std::process::Command;
+use crate::capabilities::find_tool;
+use crate::error::MacError;
+
+/// A device as NetworkManager sees it.
+#[derive(Debug, Clone)]
+pub struct Device {
+    pub state: u32,                       // NMDeviceState, e.g. 100 for connected
+    pub kind: String,                     // "ethernet", "wifi", ...
+    pub connection: Option<Connection>,   // The active connection, if any
+}
+
+#[derive(Debug, Clone)]
+pub struct Connection {
+    pub name: String,
+    pub uuid: String,
+}
+
+impl Device {
+    /// NetworkManager will set up the device: it is managed and at least
+    /// preparing a connection. 30 is NM_DEVICE_STATE_DISCONNECTED.
+    pub fn is_active(&self) -> bool {
+        self.state > 30
+    }
+
+    /// The connection setting that holds the MAC for this kind of device.
+    pub fn cloned_mac_setting(&self) -> Option<&'static str> {
+        match self.kind.as_str() {
+            "ethernet" => Some("802-3-ethernet.cloned-mac-address"),
+            "wifi" => Some("802-11-wireless.cloned-mac-address"),
+            _ => None,
+        }
+    }
+}
+
+fn nmcli(args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let nmcli = find_tool("nmcli").ok_or_else(|| MacError::SystemError("nmcli is not installed".into()))?;
+    let output = Command::new(nmcli).args(args).output()?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Box::new(MacError::SystemError(stderr.trim().to_string())));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Terse output escapes colons and backslashes in values.
+fn unescape(value: &str) -> String {
+    value.replace("\\:", ":").replace("\\\\", "\\")
+}
+
+/// How NetworkManager sees `interface`, or None if it isn't running or
+/// doesn't know the interface. Inside `--netns` the host's NetworkManager
+/// would answer for its own interface of the same name, so it is not asked.
+pub fn device(interface: &str) -> Option<Device> {
+    if crate::netns::current().is_some() {
+        return None;
+    }
+    let output = nmcli(&[
+        "-t", "-g", "GENERAL.STATE,GENERAL.TYPE,GENERAL.CONNECTION,GENERAL.CON-UUID",
+        "device", "show", interface,
+    ]).ok()?;
+
+    let mut lines = output.lines().map(unescape);
+    let state = lines.next()?.split_whitespace().next()?.parse().ok()?;
+    let kind = lines.next()?;
+    let name = lines.next().unwrap_or_default();
+    let uuid = lines.next().unwrap_or_default();
+
+    Some(Device {
+        state,
+        kind,
+        connection: (!uuid.is_empty()).then_some(Connection { name, uuid }),
+    })
+}
+
+/// Current value of a connection setting; None when it is unset.
+pub fn get_setting(uuid: &str, setting: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let value = unescape(nmcli(&["-t", "-g", setting, "connection", "show", uuid])?.trim());
+    Ok((!value.is_empty()).then_some(value))
+}
+
+/// Change a connection setting. Temporary changes live only in memory and
+/// are gone after NetworkManager restarts; None clears the setting.
+pub fn set_setting(uuid: &str, setting: &str, value: Option<&str>, temporary: bool) -> Result<(), Box<dyn Error>> {
+    let mut args = vec!["connection", "modify"];
+    if temporary {
+        args.push("--temporary");
+    }
+    args.extend([uuid, setting, value.unwrap_or("")]);
+    nmcli(&args).map(|_| ())
+}
+
+/// Reactivate a connection on `interface` so changed settings take effect.
+/// Only this connection goes down and up; the rest of the host stays online.
+pub fn activate(uuid: &str, interface: &str) -> Result<(), Box<dyn Error>> {
+    nmcli(&["connection", "up", uuid, "ifname", interface]).map(|_| ())
+}
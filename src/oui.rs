@@ -1,153 +1,484 @@
 use std::error::Error;
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+#[cfg(feature = "cli")]
+use std::io::{BufWriter, IsTerminal, Write};
+#[cfg(feature = "cli")]
+use std::fmt;
 use std::path::PathBuf;
+#[cfg(feature = "cli")]
+use std::sync::Arc;
+#[cfg(feature = "cli")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "cli")]
+use std::time::Duration;
+use flate2::read::GzDecoder;
+#[cfg(feature = "cli")]
+use flate2::{Compression, write::GzEncoder};
 use serde::{Deserialize, Serialize};
+use rand::Rng;
+use rand::seq::SliceRandom;
 use crate::error::MacError;
+use crate::mac::{MacAddress, MacFormat};
+
+/// Prefix lengths the IEEE assigns: MA-S (36 bits), MA-M (28) and MA-L (24).
+/// Lookups try the longest first so small blocks inside a shared MA-L win.
+const PREFIX_BITS: [u8; 3] = [36, 28, 24];
+
+/// Bump when the stored layout changes so old files are rebuilt.
+const DB_VERSION: u32 = 1;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VendorInfo {
-    pub prefix: String,
+    pub prefix: String, // Colon-separated, e.g. "00:17:F2" or "70:B3:D5:01:2"
+    #[serde(default = "default_bits")]
+    pub bits: u8,
     pub name: String,
+    #[serde(default)]
+    pub short_name: Option<String>,
     pub country: String,
 }
 
+fn default_bits() -> u8 {
+    24
+}
+
+impl VendorInfo {
+    /// Build an entry from the leading `bits / 4` hex digits of `hex`.
+    fn new(hex: &str, bits: u8, name: String, short_name: Option<String>, country: String) -> Option<Self> {
+        let digits = bits as usize / 4;
+        if hex.len() < digits || !hex[..digits].chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let hex = hex[..digits].to_uppercase();
+        let prefix = hex.as_bytes()
+            .chunks(2)
+            .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join(":");
+
+        Some(Self { prefix, bits, name, short_name, country })
+    }
+
+    fn key(&self) -> Option<(u8, u64)> {
+        let hex: String = self.prefix.chars().filter(|c| c.is_ascii_hexdigit()).collect();
+        Some((self.bits, u64::from_str_radix(&hex, 16).ok()?))
+    }
+
+    /// A random address inside this block: the prefix bits are kept and the
+    /// rest of the 48 are random.
+    pub fn random_mac(&self) -> Option<MacAddress> {
+        let (bits, prefix) = self.key()?;
+        let host_bits = 48 - bits as u32;
+        let host = rand::thread_rng().r#gen::<u64>() & ((1u64 << host_bits) - 1);
+        let value = (prefix << host_bits) | host;
+
+        let mut bytes = [0u8; 6];
+        bytes.copy_from_slice(&value.to_be_bytes()[2..]);
+        Some(MacAddress::new(bytes, MacFormat::Colon))
+    }
+}
+
+/// A registry of OUI assignments that the database can be built from.
+pub trait OuiSource {
+    /// Identifier used to select the source, e.g. "ieee"
+    fn name(&self) -> &'static str;
+
+    /// Files making up the registry; each one is parsed on its own.
+    fn urls(&self) -> &'static [&'static str];
+
+    /// A copy already on disk, preferred over downloading.
+    fn local_file(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// Read the registry a line at a time, so a large file is never held
+    /// in memory whole.
+    fn parse(&self, reader: &mut dyn BufRead) -> io::Result<Vec<VendorInfo>>;
+}
+
+/// The IEEE registration authority's CSV exports of the MA-L, MA-M and MA-S
+/// registries.
+pub struct IeeeOui;
+
+/// Wireshark's `manuf` file: smaller than the IEEE list, carries short
+/// vendor names and ships with Wireshark on most distributions.
+pub struct WiresharkManuf;
+
+/// Where distributions and installers put Wireshark's manuf file.
+const MANUF_PATHS: &[&str] = &[
+    "/usr/share/wireshark/manuf",
+    "/usr/local/share/wireshark/manuf",
+    "/opt/homebrew/share/wireshark/manuf",
+    "/Applications/Wireshark.app/Contents/Resources/share/wireshark/manuf",
+    "C:\\Program Files\\Wireshark\\manuf",
+];
+
+/// Look up a source by the name used on the command line.
+pub fn source_by_name(name: &str) -> Option<Box<dyn OuiSource>> {
+    match name.to_lowercase().as_str() {
+        "ieee" => Some(Box::new(IeeeOui)),
+        "manuf" | "wireshark" => Some(Box::new(WiresharkManuf)),
+        _ => None,
+    }
+}
+
+/// Split one CSV record, honouring quoted fields and doubled quotes.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+
+    fields
+}
+
+impl OuiSource for IeeeOui {
+    fn name(&self) -> &'static str {
+        "ieee"
+    }
+
+    fn urls(&self) -> &'static [&'static str] {
+        &[
+            "https://standards-oui.ieee.org/oui/oui.csv",
+            "https://standards-oui.ieee.org/oui28/mam.csv",
+            "https://standards-oui.ieee.org/oui36/oui36.csv",
+        ]
+    }
+
+    fn parse(&self, reader: &mut dyn BufRead) -> io::Result<Vec<VendorInfo>> {
+        let mut vendors = Vec::new();
+
+        // Registry,Assignment,Organization Name,Organization Address
+        for line in reader.lines().skip(1) {
+            let line = line?;
+            let fields = split_csv_line(&line);
+            let [_, assignment, name, address] = fields.as_slice() else {
+                continue;
+            };
+
+            // Addresses end with the two-letter country code, usually
+            // followed by the postal code
+            let country = address.split_whitespace()
+                .rev()
+                .find(|code| code.len() == 2 && code.chars().all(|c| c.is_ascii_uppercase()))
+                .unwrap_or_default()
+                .to_string();
+
+            let bits = match assignment.len() {
+                6 => 24,
+                7 => 28,
+                9 => 36,
+                _ => continue,
+            };
+
+            vendors.extend(VendorInfo::new(assignment, bits, name.trim().to_string(), None, country));
+        }
+
+        Ok(vendors)
+    }
+}
+
+impl OuiSource for WiresharkManuf {
+    fn name(&self) -> &'static str {
+        "manuf"
+    }
+
+    fn urls(&self) -> &'static [&'static str] {
+        &["https://www.wireshark.org/download/automated/data/manuf"]
+    }
+
+    fn local_file(&self) -> Option<PathBuf> {
+        MANUF_PATHS.iter().map(PathBuf::from).find(|path| path.is_file())
+    }
+
+    fn parse(&self, reader: &mut dyn BufRead) -> io::Result<Vec<VendorInfo>> {
+        let mut vendors = Vec::new();
+
+        // Lines look like "00:00:0C<TAB>Cisco<TAB>Cisco Systems, Inc", where
+        // older files put the long name behind a '#'. MA-M and MA-S blocks
+        // carry a mask, e.g. "70:B3:D5:01:20:00/36"
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split('\t').map(str::trim).filter(|f| !f.is_empty());
+            let (Some(prefix), Some(short_name)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let long_name = fields.next().map(|name| name.trim_start_matches('#').trim());
+
+            let (prefix, mask) = prefix.split_once('/').unwrap_or((prefix, "24"));
+            let bits = match mask.parse::<u8>() {
+                Ok(bits) if PREFIX_BITS.contains(&bits) => bits,
+                _ => continue,
+            };
+
+            let hex: String = prefix.chars().filter(|c| c.is_ascii_hexdigit()).collect();
+            let name = long_name.filter(|name| !name.is_empty()).unwrap_or(short_name).to_string();
+
+            vendors.extend(VendorInfo::new(&hex, bits, name, Some(short_name.to_string()), String::new()));
+        }
+
+        Ok(vendors)
+    }
+}
+
+/// On-disk form of the database: a gzip-compressed bincode list sorted by
+/// prefix, indexed again when loaded.
+#[derive(Serialize, Deserialize)]
+struct StoredDatabase {
+    version: u32,
+    vendors: Vec<VendorInfo>,
+}
+
 pub struct OUIDatabase {
     db_path: PathBuf,
-    vendors: HashMap<String, VendorInfo>,
+    vendors: Vec<VendorInfo>,
+    index: HashMap<(u8, u64), usize>, // (prefix bits, prefix value) -> vendors index
 }
 
 // src/oui.rs (relevant section)
 impl OUIDatabase {
     pub fn new() -> Result<Self, Box<dyn Error>> {
-        let db_path = dirs::config_dir()
+        let config_dir = dirs::config_dir()
             .ok_or_else(|| MacError::DatabaseError("Could not find config directory".into()))?
-            .join("mac_changer")
-            .join("oui.json");
+            .join("mac_changer");
 
         // Create directory if it doesn't exist
-        if let Some(parent) = db_path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
+        std::fs::create_dir_all(&config_dir)?;
+
+        let db_path = config_dir.join("oui.db");
+        let legacy_path = config_dir.join("oui.json");
 
-        let vendors = if db_path.exists() {
-            let content = std::fs::read_to_string(&db_path)?;
-            serde_json::from_str(&content)?
+        let vendors = if let Some(vendors) = Self::load(&db_path) {
+            vendors
+        } else if legacy_path.exists() {
+            // Databases written before the compact format were a JSON map
+            let content = std::fs::read_to_string(&legacy_path)?;
+            let legacy: HashMap<String, VendorInfo> = serde_json::from_str(&content)?;
+            legacy.into_values().collect()
         } else {
             // Initialize with default vendors
-            let mut defaults = HashMap::new();
-
-            // Add some common vendors
-            defaults.insert("00:17:F2".to_string(), VendorInfo {
-                prefix: "00:17:F2".to_string(),
-                name: "Apple, Inc.".to_string(),
-                country: "US".to_string(),
-            });
-
-            defaults.insert("00:1A:11".to_string(), VendorInfo {
-                prefix: "00:1A:11".to_string(),
-                name: "Google, Inc.".to_string(),
-                country: "US".to_string(),
-            });
-
-            defaults
+            vec![
+                VendorInfo {
+                    prefix: "00:17:F2".to_string(),
+                    bits: 24,
+                    name: "Apple, Inc.".to_string(),
+                    short_name: Some("Apple".to_string()),
+                    country: "US".to_string(),
+                },
+                VendorInfo {
+                    prefix: "00:1A:11".to_string(),
+                    bits: 24,
+                    name: "Google, Inc.".to_string(),
+                    short_name: Some("Google".to_string()),
+                    country: "US".to_string(),
+                },
+            ]
         };
 
-        Ok(Self { db_path, vendors })
+        let mut db = Self { db_path, vendors: Vec::new(), index: HashMap::new() };
+        db.set_vendors(vendors);
+        Ok(db)
     }
 
-    pub async fn update(&mut self) -> Result<(), Box<dyn Error>> {
-        println!("Downloading OUI database from IEEE...");
+    fn load(path: &PathBuf) -> Option<Vec<VendorInfo>> {
+        let file = File::open(path).ok()?;
+        let stored: StoredDatabase = bincode::deserialize_from(GzDecoder::new(BufReader::new(file))).ok()?;
+        (stored.version == DB_VERSION).then_some(stored.vendors)
+    }
 
-        // Download the OUI database
-        let response = reqwest::get("http://standards-oui.ieee.org/oui/oui.txt").await?;
-        let content = response.text().await?;
+    #[cfg(feature = "cli")]
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        let stored = StoredDatabase { version: DB_VERSION, vendors: self.vendors.clone() };
 
-        // Parse the text file
-        let mut new_vendors = HashMap::new();
+        // Write next to the database and rename so readers never see half a file
+        let tmp_path = self.db_path.with_extension("db.tmp");
+        let mut encoder = GzEncoder::new(BufWriter::new(File::create(&tmp_path)?), Compression::best());
+        bincode::serialize_into(&mut encoder, &stored)?;
+        encoder.finish()?;
+        std::fs::rename(&tmp_path, &self.db_path)?;
 
-        for line in content.lines() {
-            if line.contains("(hex)") {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() < 4 {
-                    continue;
-                }
+        Ok(())
+    }
 
-                let prefix = parts[0].replace("-", ":");
-
-                // Find company name and address
-                let mut company_name = String::new();
-                let mut found_company = false;
-                let mut country = String::new();
-
-                for part in parts[3..].iter() {
-                    if !found_company {
-                        if !company_name.is_empty() {
-                            company_name.push(' ');
-                        }
-                        company_name.push_str(part);
-                        if company_name.ends_with('.') {
-                            found_company = true;
-                        }
-                    }
-                }
+    fn set_vendors(&mut self, mut vendors: Vec<VendorInfo>) {
+        vendors.sort_by(|a, b| a.prefix.cmp(&b.prefix).then(a.bits.cmp(&b.bits)));
+        vendors.dedup_by(|a, b| a.prefix == b.prefix && a.bits == b.bits);
+
+        self.index = vendors.iter()
+            .enumerate()
+            .filter_map(|(i, vendor)| Some((vendor.key()?, i)))
+            .collect();
+        self.vendors = vendors;
+    }
+
+    /// Replace the database with the registry from `source`, read from a
+    /// local copy when the source has one and downloaded otherwise. Files
+    /// are fetched in parallel, from `mirrors` when the source's own site
+    /// fails, and only when they changed since the last update.
+    #[cfg(feature = "cli")]
+    pub async fn update(&mut self, source: &dyn OuiSource, mirrors: &[String]) -> Result<(), Box<dyn Error>> {
+        if let Some(path) = source.local_file() {
+            println!("Reading {} OUI data from {}...", source.name(), path.display());
+            return self.import(source, &[path]);
+        }
+
+        let dir = cache_dir()?;
+        std::fs::create_dir_all(&dir)?;
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("chameleon/", env!("CARGO_PKG_VERSION")))
+            .connect_timeout(Duration::from_secs(15))
+            .build()?;
 
-                // Try to find country from remaining lines
-                let mut lines = content.lines().skip_while(|&l| l != line).skip(1);
-                while let Some(address_line) = lines.next() {
-                    if address_line.trim().is_empty() {
-                        break;
+        let progress = Arc::new(Progress::default());
+        let mut downloads = tokio::task::JoinSet::new();
+        let mut paths = Vec::new();
+        for url in source.urls() {
+            let (file, urls) = candidates(url, mirrors);
+            println!("Downloading {} OUI data from {}...", source.name(), url);
+            paths.push(dir.join(&file));
+            downloads.spawn(fetch(client.clone(), dir.clone(), file, urls, progress.clone()));
+        }
+
+        // A running total on a terminal; logs only get the result per file
+        let terminal = io::stdout().is_terminal();
+        let mut ticker = tokio::time::interval(Duration::from_millis(500));
+        let mut failures = Vec::new();
+        loop {
+            tokio::select! {
+                joined = downloads.join_next() => {
+                    let Some(joined) = joined else { break };
+                    if terminal {
+                        print!("\r{:<60}\r", "");
+                    }
+                    match joined {
+                        Ok((file, Ok(fetched))) => println!("{}: {}", file, fetched),
+                        Ok((file, Err(e))) => failures.push(format!("{}: {}", file, e)),
+                        Err(e) => failures.push(e.to_string()),
                     }
-                    // Usually the country is on the last line of the address
-                    country = address_line.trim().to_string();
                 }
-
-                // Extract country code (assuming last word is country)
-                let country_code = country.split_whitespace()
-                    .last()
-                    .unwrap_or("US")  // Default to US if we can't determine
-                    .to_string();
-
-                new_vendors.insert(prefix.clone(), VendorInfo {
-                    prefix,
-                    name: company_name,
-                    country: country_code,
-                });
+                _ = ticker.tick(), if terminal => {
+                    print!("\r{}", progress);
+                    io::stdout().flush()?;
+                }
             }
         }
 
-        // Save to file
-        if !new_vendors.is_empty() {
-            let json = serde_json::to_string_pretty(&new_vendors)?;
-            std::fs::write(&self.db_path, json)?;
-            self.vendors = new_vendors;
+        if !failures.is_empty() {
+            return Err(Box::new(MacError::NetworkError(format!(
+                "Could not download the {} OUI data, the database is unchanged: {}",
+                source.name(), failures.join("; ")
+            ))));
+        }
+        self.import(source, &paths)
+    }
+
+    /// Replace the database with `source`'s registry read from files on
+    /// disk, such as copies carried into an air-gapped network.
+    #[cfg(feature = "cli")]
+    pub fn import(&mut self, source: &dyn OuiSource, paths: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+        let mut new_vendors = Vec::new();
+        for path in paths {
+            let file = File::open(path)
+                .map_err(|e| MacError::DatabaseError(format!("Could not read {}: {}", path.display(), e)))?;
+            new_vendors.extend(source.parse(&mut BufReader::new(file))?);
         }
 
+        if new_vendors.is_empty() {
+            return Err(Box::new(MacError::DatabaseError(
+                format!("No OUI entries found in the {} data", source.name())
+            )));
+        }
+
+        self.set_vendors(new_vendors);
+        self.save()?;
+
         println!("OUI database updated successfully. Found {} vendors.", self.vendors.len());
         Ok(())
     }
 
+    /// Whether a registry has been downloaded, rather than only the built-in
+    /// vendors being known. Only then does an unknown prefix mean anything.
+    pub fn is_downloaded(&self) -> bool {
+        self.db_path.exists() || self.db_path.with_file_name("oui.json").exists()
+    }
+
+    /// Vendor owning `mac`, matching the most specific registered block.
     pub fn get_vendor(&self, mac_prefix: &str) -> Option<&VendorInfo> {
-        let prefix = mac_prefix
-            .replace([':', '-', '.'], "")
-            .to_uppercase();
+        let hex: String = mac_prefix.chars().filter(|c| c.is_ascii_hexdigit()).collect();
 
-        if prefix.len() >= 6 {
-            self.vendors.get(&prefix[0..6])
-        } else {
-            None
-        }
+        PREFIX_BITS.iter().find_map(|&bits| {
+            let digits = bits as usize / 4;
+            let value = u64::from_str_radix(hex.get(..digits)?, 16).ok()?;
+            self.index.get(&(bits, value)).map(|&i| &self.vendors[i])
+        })
+    }
+
+    /// Vendors whose name or short name contains `query`, ignoring case.
+    pub fn search(&self, query: &str) -> Vec<&VendorInfo> {
+        let query = query.to_lowercase();
+        self.vendors
+            .iter()
+            .filter(|v| {
+                v.name.to_lowercase().contains(&query)
+                    || v.short_name.as_ref().is_some_and(|short| short.to_lowercase().contains(&query))
+            })
+            .collect()
+    }
+
+    /// A random MAC from a randomly chosen block registered to a vendor
+    /// matching `query`, so repeated calls spread across all of its prefixes.
+    pub fn random_mac_from_vendor(&self, query: &str) -> Result<MacAddress, MacError> {
+        self.search(query)
+            .choose(&mut rand::thread_rng())
+            .and_then(|vendor| vendor.random_mac())
+            .ok_or_else(|| MacError::VendorNotFound(format!("No prefixes registered to a vendor matching '{}'", query)))
+    }
+
+    #[allow(clippy::len_without_is_empty)] // Never empty: it starts from built-in vendors
+    pub fn len(&self) -> usize {
+        self.vendors.len()
+    }
+
+    /// Every 24-bit (MA-L) prefix in the database.
+    pub fn ma_l_prefixes(&self) -> Vec<String> {
+        self.vendors
+            .iter()
+            .filter(|v| v.bits == 24)
+            .map(|v| v.prefix.clone())
+            .collect()
     }
 
     pub fn vendors_by_country(&self, country: &str) -> Vec<&VendorInfo> {
         self.vendors
-            .values()
+            .iter()
             .filter(|v| v.country.to_uppercase() == country.to_uppercase())
             .collect()
     }
 
     pub fn list_countries(&self) -> Vec<String> {
         let mut countries: Vec<String> = self.vendors
-            .values()
+            .iter()
             .map(|v| v.country.clone())
             .collect::<std::collections::HashSet<_>>()
             .into_iter()
@@ -155,4 +486,211 @@ impl OUIDatabase {
         countries.sort();
         countries
     }
-}
\ No newline at end of file
+}
+
+/// Registry files as last downloaded, kept so an unchanged file is not
+/// downloaded again and an interrupted download can pick up where it left.
+#[cfg(feature = "cli")]
+fn cache_dir() -> Result<PathBuf, MacError> {
+    Ok(dirs::cache_dir()
+        .ok_or_else(|| MacError::DatabaseError("Could not find cache directory".into()))?
+        .join("mac_changer")
+        .join("oui"))
+}
+
+/// The file name behind `url`, and every URL it can be fetched from: the
+/// source's own first, then each mirror serving it under the same name.
+#[cfg(feature = "cli")]
+fn candidates(url: &str, mirrors: &[String]) -> (String, Vec<String>) {
+    let file = url.rsplit('/').next().unwrap_or(url).to_string();
+    let mut urls = vec![url.to_string()];
+    urls.extend(mirrors.iter().map(|mirror| format!("{}/{}", mirror.trim_end_matches('/'), file)));
+    (file, urls)
+}
+
+/// What the server said about a cached file, sent back so it can answer
+/// 304 Not Modified or resume a partial download of the same version.
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Validators {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+#[cfg(feature = "cli")]
+impl Validators {
+    fn from_response(url: &str, response: &reqwest::Response) -> Self {
+        let header = |name| response.headers().get(name).and_then(|value| value.to_str().ok()).map(String::from);
+        Self {
+            url: url.to_string(),
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+        }
+    }
+
+    fn load(path: &std::path::Path) -> Option<Self> {
+        serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()
+    }
+
+    fn save(&self, path: &std::path::Path) -> io::Result<()> {
+        std::fs::write(path, serde_json::to_string(self)?)
+    }
+
+    /// If-Range only takes a strong ETag or a date.
+    fn range_validator(&self) -> Option<&str> {
+        self.etag.as_deref().filter(|etag| !etag.starts_with("W/")).or(self.last_modified.as_deref())
+    }
+}
+
+/// Bytes received so far across all files, against what the servers
+/// announced.
+#[cfg(feature = "cli")]
+#[derive(Default)]
+struct Progress {
+    received: AtomicU64,
+    expected: AtomicU64,
+}
+
+#[cfg(feature = "cli")]
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let received = self.received.load(Ordering::Relaxed);
+        let expected = self.expected.load(Ordering::Relaxed);
+        write!(f, "Downloaded {}", megabytes(received))?;
+        if expected > 0 {
+            write!(f, " of {} ({}%)", megabytes(expected), (received * 100 / expected).min(100))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "cli")]
+fn megabytes(bytes: u64) -> String {
+    match bytes {
+        bytes if bytes < 1_000_000 => format!("{} kB", bytes.div_ceil(1000)),
+        bytes => format!("{:.1} MB", bytes as f64 / 1_000_000.0),
+    }
+}
+
+#[cfg(feature = "cli")]
+enum Fetched {
+    Downloaded { url: String, bytes: u64, resumed_from: u64 },
+    NotModified,
+    /// Every URL failed, but the copy from the last update is still there.
+    Stale(String),
+}
+
+#[cfg(feature = "cli")]
+impl fmt::Display for Fetched {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fetched::Downloaded { url, bytes, resumed_from: 0 } => write!(f, "downloaded {} from {}", megabytes(*bytes), url),
+            Fetched::Downloaded { url, bytes, resumed_from } => {
+                write!(f, "downloaded {} from {}, resumed at {}", megabytes(*bytes), url, megabytes(*resumed_from))
+            }
+            Fetched::NotModified => write!(f, "unchanged since the last update"),
+            Fetched::Stale(reason) => write!(f, "could not be downloaded ({}), using the copy from the last update", reason),
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+type FetchError = Box<dyn Error + Send + Sync>;
+
+/// Fetch `file` into the cache from the first of `urls` that answers.
+#[cfg(feature = "cli")]
+async fn fetch(
+    client: reqwest::Client,
+    dir: PathBuf,
+    file: String,
+    urls: Vec<String>,
+    progress: Arc<Progress>,
+) -> (String, Result<Fetched, String>) {
+    let path = dir.join(&file);
+    let cached = Validators::load(&dir.join(format!("{}.json", file))).filter(|_| path.exists());
+
+    let mut errors = Vec::new();
+    for url in &urls {
+        match download(&client, url, &dir, &file, cached.as_ref(), &progress).await {
+            Ok(fetched) => return (file, Ok(fetched)),
+            Err(e) => errors.push(format!("{}: {}", url, e)),
+        }
+    }
+
+    let result = match cached {
+        Some(_) => Ok(Fetched::Stale(errors.join("; "))),
+        None => Err(errors.join("; ")),
+    };
+    (file, result)
+}
+
+/// One attempt at `url`. The body goes to `<file>.part` and replaces the
+/// cached copy once complete; a part left by an earlier attempt at the
+/// same URL is resumed if the server still has that version.
+#[cfg(feature = "cli")]
+async fn download(
+    client: &reqwest::Client,
+    url: &str,
+    dir: &std::path::Path,
+    file: &str,
+    cached: Option<&Validators>,
+    progress: &Progress,
+) -> Result<Fetched, FetchError> {
+    use reqwest::header::{IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, RANGE};
+
+    let part_path = dir.join(format!("{}.part", file));
+    let part_validators_path = dir.join(format!("{}.part.json", file));
+
+    let mut request = client.get(url);
+    if let Some(cached) = cached.filter(|cached| cached.url == url) {
+        if let Some(etag) = &cached.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let partial = Validators::load(&part_validators_path).filter(|partial| partial.url == url);
+    let offset = std::fs::metadata(&part_path).map(|metadata| metadata.len()).unwrap_or(0);
+    if offset > 0
+        && let Some(validator) = partial.as_ref().and_then(Validators::range_validator)
+    {
+        request = request.header(RANGE, format!("bytes={}-", offset)).header(IF_RANGE, validator);
+    }
+
+    let mut response = request.send().await?;
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        let _ = std::fs::remove_file(&part_path);
+        let _ = std::fs::remove_file(&part_validators_path);
+        return Ok(Fetched::NotModified);
+    }
+    response.error_for_status_ref()?;
+
+    // Anything but 206 is the whole file, so start the part over
+    let resumed_from = if response.status() == reqwest::StatusCode::PARTIAL_CONTENT { offset } else { 0 };
+    if resumed_from == 0 {
+        Validators::from_response(url, &response).save(&part_validators_path)?;
+    }
+    progress.expected.fetch_add(resumed_from + response.content_length().unwrap_or(0), Ordering::Relaxed);
+    progress.received.fetch_add(resumed_from, Ordering::Relaxed);
+
+    let mut part = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed_from > 0)
+        .truncate(resumed_from == 0)
+        .open(&part_path)?;
+    let mut bytes = resumed_from;
+    while let Some(chunk) = response.chunk().await? {
+        part.write_all(&chunk)?;
+        bytes += chunk.len() as u64;
+        progress.received.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+    }
+    part.flush()?;
+
+    std::fs::rename(&part_path, dir.join(file))?;
+    std::fs::rename(&part_validators_path, dir.join(format!("{}.json", file)))?;
+    Ok(Fetched::Downloaded { url: url.to_string(), bytes, resumed_from })
+}
@@ -0,0 +1,61 @@
+// src/output.rs
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// How command results are printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// Set once at startup, from `--output` or one of the older `--json` flags.
+pub fn set_format(format: OutputFormat) {
+    let _ = FORMAT.set(format);
+}
+
+pub fn is_json() -> bool {
+    FORMAT.get().copied().unwrap_or_default() == OutputFormat::Json
+}
+
+/// A progress or warning message. In JSON mode these go to stderr so that
+/// stdout carries nothing but the result.
+pub fn print_progress(args: fmt::Arguments) {
+    if is_json() {
+        eprintln!("{}", args);
+    } else {
+        println!("{}", args);
+    }
+}
+
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        $crate::output::print_progress(format_args!($($arg)*))
+    };
+}
+pub(crate) use progress;
+
+/// Ask on the terminal; anything but y or yes is a no. The question goes to
+/// stderr so it never ends up in JSON output.
+pub fn confirm(question: &str) -> Result<bool, Box<dyn Error>> {
+    eprint!("{} [y/N] ", question);
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Print a command's result: as JSON in JSON mode, otherwise with `text`.
+pub fn emit<T: Serialize + ?Sized>(value: &T, text: impl FnOnce(&T)) -> Result<(), Box<dyn Error>> {
+    if is_json() {
+        println!("{}", serde_json::to_string_pretty(value)?);
+    } else {
+        text(value);
+    }
+    Ok(())
+}
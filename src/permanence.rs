@@ -0,0 +1,309 @@
+// src/permanence.rs
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::capabilities::find_tool;
+use crate::error::MacError;
+use crate::mac::MacAddress;
+use crate::networkmanager;
+use crate::output::progress;
+use crate::transaction::{Step, Transaction};
+
+const UDEV_RULES_DIR: &str = "/etc/udev/rules.d";
+/// Older releases put every interface's rule in this one shared file.
+const LEGACY_UDEV_RULES: &str = "/etc/udev/rules.d/70-persistent-net.rules";
+const LINK_FILES_DIR: &str = "/etc/systemd/network";
+const NM_CONNECTIONS_DIR: &str = "/etc/NetworkManager/system-connections";
+
+/// How a MAC is made to survive reboots. Interfaces NetworkManager manages
+/// keep theirs in the connection profile (see `change_with_networkmanager`);
+/// the others get a file that udev applies when the device appears.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PermanenceBackend {
+    Udev,
+    SystemdLink,
+    NetworkManager,
+}
+
+/// A permanent MAC found on the system, and where it lives.
+#[derive(Debug, Clone)]
+pub struct Override {
+    pub backend: PermanenceBackend,
+    pub mac: String,
+    pub location: String,
+}
+
+impl PermanenceBackend {
+    /// systemd-udevd applies .link files and its default policy can replace
+    /// an address set by a udev rule, so hosts that ship it get .link files.
+    pub fn detect() -> Self {
+        let systemd_udevd = ["/usr/lib/systemd/network/99-default.link", "/lib/systemd/network/99-default.link"]
+            .iter()
+            .any(|path| Path::new(path).exists());
+
+        if systemd_udevd { PermanenceBackend::SystemdLink } else { PermanenceBackend::Udev }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            PermanenceBackend::Udev => "udev rule",
+            PermanenceBackend::SystemdLink => "systemd .link file",
+            PermanenceBackend::NetworkManager => "NetworkManager connection",
+        }
+    }
+}
+
+fn udev_rule_path(interface: &str) -> PathBuf {
+    Path::new(UDEV_RULES_DIR).join(format!("70-chameleon-{}.rules", interface))
+}
+
+fn link_file_path(interface: &str) -> PathBuf {
+    Path::new(LINK_FILES_DIR).join(format!("10-chameleon-{}.link", interface))
+}
+
+/// Files with their new content; empty content means the file goes.
+pub type FileChanges = Vec<(PathBuf, String)>;
+
+/// The files `install` would write for `interface` with the host's file
+/// backend.
+pub fn plan(interface: &str, mac: &str) -> Result<(PermanenceBackend, FileChanges), Box<dyn Error>> {
+    let backend = PermanenceBackend::detect();
+    let (path, content) = match backend {
+        PermanenceBackend::SystemdLink => (link_file_path(interface), link_file(interface, mac)),
+        _ => (udev_rule_path(interface), udev_rule(interface, mac)),
+    };
+
+    if backend == PermanenceBackend::Udev && !Path::new(UDEV_RULES_DIR).exists() {
+        return Err(Box::new(MacError::SystemError(format!("Directory {} does not exist", UDEV_RULES_DIR))));
+    }
+
+    let mut files = vec![(path, content)];
+
+    // A rule for the same interface in the shared file would fight ours
+    if let Ok(content) = fs::read_to_string(LEGACY_UDEV_RULES)
+        && let Some(remaining) = without_udev_rule(&content, interface)
+    {
+        files.push((LEGACY_UDEV_RULES.into(), remaining));
+    }
+
+    Ok((backend, files))
+}
+
+/// Install a permanent MAC for `interface` with the host's file backend,
+/// recording every file written so a failed change can put them back.
+pub fn install(transaction: &mut Transaction, interface: &str, mac: &str) -> Result<(), Box<dyn Error>> {
+    let (backend, files) = plan(interface, mac)?;
+    progress!("Making change permanent with a {}...", backend.describe());
+
+    for (path, content) in files {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let previous = fs::read_to_string(&path).ok();
+        write_or_remove(&path, &content)
+            .map_err(|e| MacError::SystemError(format!("Failed to write {}: {}", path.display(), e)))?;
+        transaction.record(Step::FileWritten { path, previous });
+    }
+
+    reload_udev();
+    Ok(())
+}
+
+fn udev_rule(interface: &str, mac: &str) -> String {
+    format!(
+        r#"ACTION=="add", SUBSYSTEM=="net", ATTR{{address}}=="*", ATTR{{dev_id}}=="0x0", ATTR{{type}}=="1", KERNEL=="{}", ATTR{{address}}="{}"
+"#,
+        interface, mac
+    )
+}
+
+/// Matched on the factory address where the driver reports one, which
+/// survives renames. Only the first matching .link file applies, so the
+/// name is pinned too, or the default naming policy would no longer run.
+fn link_file(interface: &str, mac: &str) -> String {
+    let matcher = match crate::network::get_permanent_mac(interface) {
+        Some(permanent) => format!("PermanentMACAddress={}", permanent),
+        None => format!("OriginalName={}", interface),
+    };
+
+    format!(
+        "# Written by chameleon; remove with `chameleon permanent remove -i {}`\n\
+         [Match]\n{}\n\n[Link]\nName={}\nMACAddressPolicy=none\nMACAddress={}\n",
+        interface, matcher, interface, mac
+    )
+}
+
+/// `content` without the rules for `interface`, or None if it has none.
+fn without_udev_rule(content: &str, interface: &str) -> Option<String> {
+    let kernel_match = format!("KERNEL==\"{}\"", interface);
+    let is_rule = |line: &&str| !line.trim_start().starts_with('#') && line.contains(&kernel_match);
+
+    if !content.lines().any(|line| is_rule(&line)) {
+        return None;
+    }
+    let remaining: Vec<&str> = content.lines().filter(|line| !is_rule(line)).collect();
+    Some(remaining.join("\n") + "\n")
+}
+
+fn write_or_remove(path: &Path, content: &str) -> Result<(), Box<dyn Error>> {
+    if content.trim().is_empty() {
+        fs::remove_file(path)?;
+    } else {
+        fs::write(path, content)?;
+    }
+    Ok(())
+}
+
+fn reload_udev() {
+    match find_tool("udevadm") {
+        Some(udevadm) => {
+            let _ = Command::new(udevadm).args(["control", "--reload"]).output();
+        }
+        None => progress!("udevadm not found, the change takes effect from the next boot"),
+    }
+}
+
+/// The address a udev rule for `interface` in `content` assigns.
+fn udev_rule_mac(content: &str, interface: &str) -> Option<String> {
+    let kernel_match = format!("KERNEL==\"{}\"", interface);
+
+    content
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#') && line.contains(&kernel_match))
+        .find_map(|line| {
+            // The assignment is the last ATTR{address}, the first one is a match
+            let (_, value) = line.rsplit_once("ATTR{address}=\"")?;
+            value.split('"').next().map(|mac| mac.to_string())
+        })
+}
+
+fn link_file_mac(content: &str) -> Option<String> {
+    content
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("MACAddress="))
+        .map(|mac| mac.trim().to_string())
+}
+
+/// Saved NetworkManager profiles for `interface` with a fixed cloned MAC,
+/// as (uuid, setting, mac, file). Only profiles on disk count; temporary
+/// changes are gone after a restart anyway.
+fn nm_profiles(interface: &str) -> Vec<(String, &'static str, String, PathBuf)> {
+    let Ok(entries) = fs::read_dir(NM_CONNECTIONS_DIR) else {
+        return Vec::new();
+    };
+    let active = networkmanager::device(interface).and_then(|device| device.connection).map(|c| c.uuid);
+
+    let mut profiles = Vec::new();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let Ok(content) = fs::read_to_string(&path) else { continue };
+
+        let mut section = String::new();
+        let (mut uuid, mut bound_to, mut cloned) = (None, None, None);
+        for line in content.lines().map(str::trim) {
+            if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                section = name.to_string();
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else { continue };
+            match (section.as_str(), key.trim()) {
+                ("connection", "uuid") => uuid = Some(value.trim().to_string()),
+                ("connection", "interface-name") => bound_to = Some(value.trim().to_string()),
+                ("ethernet" | "802-3-ethernet", "cloned-mac-address") => {
+                    cloned = Some(("802-3-ethernet.cloned-mac-address", value.trim().to_string()));
+                }
+                ("wifi" | "802-11-wireless", "cloned-mac-address") => {
+                    cloned = Some(("802-11-wireless.cloned-mac-address", value.trim().to_string()));
+                }
+                _ => {}
+            }
+        }
+
+        // Other values are policies such as "random" or "preserve"
+        let (Some(uuid), Some((setting, mac))) = (uuid, cloned) else { continue };
+        if MacAddress::parse(&mac).is_err() {
+            continue;
+        }
+
+        let applies = match &bound_to {
+            Some(name) => name == interface,
+            None => active.as_deref() == Some(uuid.as_str()),
+        };
+        if applies {
+            profiles.push((uuid, setting, mac, path));
+        }
+    }
+
+    profiles
+}
+
+/// Every permanent MAC installed for `interface`, by any backend.
+pub fn find_overrides(interface: &str) -> Vec<Override> {
+    let mut overrides = Vec::new();
+
+    let udev_files = [udev_rule_path(interface), PathBuf::from(LEGACY_UDEV_RULES)];
+    for path in udev_files {
+        if let Some(mac) = fs::read_to_string(&path).ok().and_then(|content| udev_rule_mac(&content, interface)) {
+            overrides.push(Override {
+                backend: PermanenceBackend::Udev,
+                mac,
+                location: path.display().to_string(),
+            });
+        }
+    }
+
+    let link = link_file_path(interface);
+    if let Some(mac) = fs::read_to_string(&link).ok().and_then(|content| link_file_mac(&content)) {
+        overrides.push(Override {
+            backend: PermanenceBackend::SystemdLink,
+            mac,
+            location: link.display().to_string(),
+        });
+    }
+
+    for (_, _, mac, path) in nm_profiles(interface) {
+        overrides.push(Override {
+            backend: PermanenceBackend::NetworkManager,
+            mac,
+            location: path.display().to_string(),
+        });
+    }
+
+    overrides
+}
+
+/// Remove every permanent MAC installed for `interface` and describe what
+/// was removed. The current address is left as it is.
+pub fn remove_overrides(interface: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut removed = Vec::new();
+
+    let rule = udev_rule_path(interface);
+    if rule.exists() {
+        fs::remove_file(&rule)?;
+        removed.push(format!("udev rule {}", rule.display()));
+    }
+
+    let legacy = Path::new(LEGACY_UDEV_RULES);
+    if let Ok(content) = fs::read_to_string(legacy)
+        && let Some(remaining) = without_udev_rule(&content, interface)
+    {
+        write_or_remove(legacy, &remaining)?;
+        removed.push(format!("udev rule for {} in {}", interface, LEGACY_UDEV_RULES));
+    }
+
+    let link = link_file_path(interface);
+    if link.exists() {
+        fs::remove_file(&link)?;
+        removed.push(format!("systemd .link file {}", link.display()));
+    }
+
+    for (uuid, setting, _, path) in nm_profiles(interface) {
+        networkmanager::set_setting(&uuid, setting, None, false)?;
+        removed.push(format!("{} in NetworkManager connection {}", setting, path.display()));
+    }
+
+    if !removed.is_empty() {
+        reload_udev();
+    }
+    Ok(removed)
+}
@@ -0,0 +1,103 @@
+// src/personas.rs
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use crate::error::MacError;
+use crate::mac::{generate_random_mac, MacAddress, RandomPolicy};
+
+/// A kind of device to blend in as, with the prefixes its network hardware
+/// ships with.
+#[derive(Debug)]
+pub struct Persona {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub prefixes: &'static [&'static str], // MA-L assignments of the device's Wi-Fi/Ethernet vendor
+    pub market_share: u32,                 // Relative weight for `--persona random`
+}
+
+pub const PERSONAS: &[Persona] = &[
+    Persona {
+        id: "iphone-14",
+        name: "iPhone 14",
+        aliases: &["iphone"],
+        prefixes: &["3C:22:FB", "A4:83:E7", "BC:D0:74", "F4:0F:24", "DC:A9:04"],
+        market_share: 28,
+    },
+    Persona {
+        id: "galaxy-s23",
+        name: "Samsung Galaxy S23",
+        aliases: &["galaxy", "samsung"],
+        prefixes: &["84:25:DB", "8C:77:12", "5C:0A:5B", "F0:25:B7"],
+        market_share: 22,
+    },
+    Persona {
+        id: "pixel-7",
+        name: "Google Pixel 7",
+        aliases: &["pixel"],
+        prefixes: &["3C:28:6D", "F4:F5:E8", "00:1A:11"],
+        market_share: 4,
+    },
+    Persona {
+        id: "macbook-air",
+        name: "MacBook Air",
+        aliases: &["macbook", "mac"],
+        prefixes: &["F0:18:98", "AC:BC:32", "8C:85:90", "00:17:F2"],
+        market_share: 9,
+    },
+    Persona {
+        id: "dell-xps",
+        name: "Dell XPS laptop",
+        aliases: &["dell", "xps"],
+        // XPS models ship Intel and Killer (Intel) Wi-Fi cards
+        prefixes: &["3C:A9:F4", "34:13:E8", "00:1B:21"],
+        market_share: 7,
+    },
+    Persona {
+        id: "thinkpad",
+        name: "Lenovo ThinkPad",
+        aliases: &["lenovo"],
+        // LCFC and Wistron build ThinkPads for Lenovo: LCFC's blocks on the
+        // Wi-Fi and onboard Ethernet, Wistron's on older Ethernet ports
+        prefixes: &["8C:16:45", "98:FA:9B", "E8:6A:64", "50:7B:9D", "54:EE:75"],
+        market_share: 6,
+    },
+    Persona {
+        id: "raspberry-pi",
+        name: "Raspberry Pi",
+        aliases: &["rpi", "pi"],
+        prefixes: &["B8:27:EB", "DC:A6:32", "E4:5F:01", "D8:3A:DD"],
+        market_share: 1,
+    },
+];
+
+impl Persona {
+    /// A random MAC under one of this device's prefixes.
+    pub fn generate_mac(&self) -> Result<MacAddress, MacError> {
+        let prefix = self.prefixes
+            .choose(&mut thread_rng())
+            .ok_or_else(|| MacError::VendorNotFound(format!("Persona {} has no prefixes", self.id)))?;
+        generate_random_mac(&RandomPolicy::Vendor(prefix.to_string()))
+    }
+}
+
+/// Look up a persona by id or alias, ignoring case. "random" picks one
+/// weighted by market share.
+pub fn find(name: &str) -> Result<&'static Persona, MacError> {
+    if name.eq_ignore_ascii_case("random") {
+        let weights = WeightedIndex::new(PERSONAS.iter().map(|persona| persona.market_share))
+            .map_err(|e| MacError::SystemError(format!("Invalid persona weights: {}", e)))?;
+        return Ok(&PERSONAS[weights.sample(&mut thread_rng())]);
+    }
+
+    PERSONAS
+        .iter()
+        .find(|persona| {
+            persona.id.eq_ignore_ascii_case(name)
+                || persona.aliases.iter().any(|alias| alias.eq_ignore_ascii_case(name))
+        })
+        .ok_or_else(|| MacError::ValidationFailed(format!(
+            "Unknown persona '{}'. Available: random, {}",
+            name,
+            PERSONAS.iter().map(|persona| persona.id).collect::<Vec<_>>().join(", ")
+        )))
+}
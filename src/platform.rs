@@ -1,123 +1,244 @@
-use std::error::Error;
-use std::{fs, string};
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"))]
+use std::fs;
+#[cfg(not(target_os = "windows"))]
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use chrono::{Local, Utc};
+#[cfg(target_os = "linux")]
+use crate::capabilities::find_tool;
+#[cfg(target_os = "linux")]
+use crate::{networkmanager, permanence};
+use crate::backend::{self, MacBackend};
+use crate::change_window::ChangeWindows;
+use crate::lock;
+use crate::output::progress;
+use crate::hooks::{self, HookEvent, Trigger};
+use crate::config::{get_original_mac, save_original_mac};
 use crate::error::MacError;
-use is_elevated;
-use winreg::{RegKey, RegValue};
-use winreg::enums::*;
+use crate::logger::{MacChange, MacLogger};
+use crate::network::LinkState;
+use crate::mac::{MacAddress, MacFormat};
+use crate::transaction::{Plan, Step, Transaction};
+#[cfg(target_os = "windows")]
+use crate::win32;
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+use crate::bsd;
+
+/// How links are brought down/up and addressed: the `ip` tool when the host
+/// has it, otherwise ioctls on a socket, which needs nothing installed and
+/// keeps static builds working in minimal containers.
+#[cfg(target_os = "linux")]
+enum LinkBackend {
+    Ip(String),
+    Ioctl,
+}
 
 #[cfg(target_os = "linux")]
-fn find_command(cmd: &str) -> Option<String> {
-    let paths = vec![
-        "/sbin",
-        "/usr/sbin",
-        "/bin",
-        "/usr/bin",
-        "/usr/local/sbin",
-        "/usr/local/bin"
-    ];
-
-    for path in paths {
-        let full_path = format!("{}/{}", path, cmd);
-        if std::path::Path::new(&full_path).exists() {
-            return Some(full_path);
+impl LinkBackend {
+    fn detect() -> Self {
+        match find_tool("ip") {
+            Some(path) => LinkBackend::Ip(path.display().to_string()),
+            None => LinkBackend::Ioctl,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            LinkBackend::Ip(path) => format!("command {}", path),
+            LinkBackend::Ioctl => "ioctl backend ('ip' not found)".into(),
+        }
+    }
+
+    fn set_up(&self, interface: &str, up: bool) -> Result<(), MacError> {
+        match self {
+            LinkBackend::Ip(ip) => execute_command(ip, &["link", "set", "dev", interface, if up { "up" } else { "down" }]),
+            LinkBackend::Ioctl => Ok(linux_ioctl::set_up(interface, up)?),
+        }
+    }
+
+    fn set_address(&self, interface: &str, mac: &str) -> Result<(), MacError> {
+        match self {
+            LinkBackend::Ip(ip) => execute_command(ip, &["link", "set", "dev", interface, "address", mac]),
+            LinkBackend::Ioctl => linux_ioctl::set_address(interface, mac),
+        }
+    }
+
+    /// What `set_up` would run, for dry runs.
+    fn set_up_command(&self, interface: &str, up: bool) -> String {
+        match self {
+            LinkBackend::Ip(ip) => format!("{} link set dev {} {}", ip, interface, if up { "up" } else { "down" }),
+            LinkBackend::Ioctl => format!("ioctl SIOCSIFFLAGS {} ({})", interface, if up { "up" } else { "down" }),
+        }
+    }
+
+    fn set_address_command(&self, interface: &str, mac: &str) -> String {
+        match self {
+            LinkBackend::Ip(ip) => format!("{} link set dev {} address {}", ip, interface, mac),
+            LinkBackend::Ioctl => format!("ioctl SIOCSIFHWADDR {} {}", interface, mac),
         }
     }
-    None
 }
 
-#[cfg(target_os = "windows")]
-fn find_command(cmd: &str) -> Option<String> {
-    let paths = vec![
-        "C:\\tools",
-        "C:\\ProgramData",
-        "C:\\Users\\Nathan\\AppData\\Roaming",
-        "C:\\ProgramData\\chocolatey",
-        "C:\\Program Files\\Common Files",
-        "C:\\Program Files (x86)\\Common Files",
-        "C:\\Program Files\\Common Files",
-        "C:\\Windows",
-    ];
-
-    for path in paths {
-        let full_path = format!("{}/{}", path, cmd);
-        if std::path::Path::new(&full_path).exists() {
-            return Some(full_path);
+#[cfg(target_os = "linux")]
+mod linux_ioctl {
+    use nix::libc;
+    use nix::sys::socket::{socket, AddressFamily, SockFlag, SockType};
+    use std::io;
+    use std::os::fd::{AsRawFd, OwnedFd};
+    use crate::error::MacError;
+    use crate::mac::MacAddress;
+
+    fn request(interface: &str) -> Result<(OwnedFd, libc::ifreq), MacError> {
+        if interface.len() >= libc::IFNAMSIZ {
+            return Err(MacError::ValidationFailed(format!("Interface name {} is too long", interface)));
+        }
+
+        let fd = socket(AddressFamily::Inet, SockType::Datagram, SockFlag::SOCK_CLOEXEC, None)?;
+        let mut request: libc::ifreq = unsafe { std::mem::zeroed() };
+        for (dst, &src) in request.ifr_name.iter_mut().zip(interface.as_bytes()) {
+            *dst = src as libc::c_char;
         }
+
+        Ok((fd, request))
+    }
+
+    fn ioctl(fd: &OwnedFd, op: libc::c_ulong, request: &mut libc::ifreq) -> io::Result<()> {
+        // The request parameter is c_ulong on glibc and c_int on musl
+        if unsafe { libc::ioctl(fd.as_raw_fd(), op as _, request as *mut libc::ifreq) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub fn set_up(interface: &str, up: bool) -> Result<(), MacError> {
+        let (fd, mut request) = request(interface)?;
+        ioctl(&fd, libc::SIOCGIFFLAGS, &mut request)?;
+
+        unsafe {
+            if up {
+                request.ifr_ifru.ifru_flags |= libc::IFF_UP as libc::c_short;
+            } else {
+                request.ifr_ifru.ifru_flags &= !(libc::IFF_UP as libc::c_short);
+            }
+        }
+
+        ioctl(&fd, libc::SIOCSIFFLAGS, &mut request)?;
+        Ok(())
+    }
+
+    pub fn set_address(interface: &str, mac: &str) -> Result<(), MacError> {
+        let mac = MacAddress::parse(mac)?;
+        let (fd, mut request) = request(interface)?;
+
+        unsafe {
+            request.ifr_ifru.ifru_hwaddr.sa_family = libc::ARPHRD_ETHER;
+            for (dst, &src) in request.ifr_ifru.ifru_hwaddr.sa_data.iter_mut().zip(mac.get_bytes()) {
+                *dst = src as libc::c_char;
+            }
+        }
+
+        ioctl(&fd, libc::SIOCSIFHWADDR, &mut request)?;
+        Ok(())
     }
-    None
 }
 
 #[cfg(target_os = "linux")]
-fn verify_interface_exists(interface: &str) -> Result<(), Box<dyn Error>> {
+fn verify_interface_exists(interface: &str) -> Result<(), MacError> {
     let sys_path = std::path::Path::new("/sys/class/net").join(interface);
     if !sys_path.exists() {
-        return Err(Box::new(MacError::ValidationFailed(
+        return Err(MacError::InterfaceNotFound(
             format!("Interface {} does not exist", interface)
-        )));
+        ));
     }
 
     // Check if interface is operational
     if let Ok(operstate) = std::fs::read_to_string(sys_path.join("operstate")) {
-        println!("Interface {} current state: {}", interface, operstate.trim());
+        progress!("Interface {} current state: {}", interface, operstate.trim());
     }
 
     Ok(())
 }
 
 #[cfg(target_os = "windows")]
-fn verify_interface_exists(interface: &str) -> Result<(), Box<dyn Error>> {
-    // On Windows, we'll use WMI to verify the interface
-    let output = Command::new("wmic")
-        .args(&["nic", "get", "name,netconnectionid", "/format:csv"])
+fn verify_interface_exists(interface: &str) -> Result<win32::Adapter, MacError> {
+    let adapter = win32::find_adapter(interface)?;
+
+    progress!("Interface {} current state: {}", interface,
+             if adapter.is_up { "up" } else { "down" });
+
+    Ok(adapter)
+}
+
+#[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+fn verify_interface_exists(interface: &str) -> Result<(), MacError> {
+    let output = Command::new("ifconfig")
+        .arg(interface)
         .output()?;
 
-    let output_str = String::from_utf8_lossy(&output.stdout);
-    if !output_str.to_lowercase().contains(&interface.to_lowercase()) {
-        return Err(Box::new(MacError::ValidationFailed(
+    if !output.status.success() {
+        return Err(MacError::InterfaceNotFound(
             format!("Interface {} does not exist", interface)
-        )));
+        ));
     }
 
-    // Check if interface is enabled using netsh
-    let status = Command::new("netsh")
-        .args(&["interface", "show", "interface", interface])
-        .output()?;
-
-    let status_str = String::from_utf8_lossy(&status.stdout);
-    println!("Interface {} current state: {}", interface, status_str.trim());
+    // ifconfig reports "status: active" or "status: inactive" for physical ports
+    let output_str = String::from_utf8_lossy(&output.stdout);
+    if let Some(status) = output_str
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("status:"))
+    {
+        progress!("Interface {} current state: {}", interface, status.trim());
+    }
 
     Ok(())
 }
 
 #[cfg(target_os = "linux")]
-fn check_permissions() -> Result<(), Box<dyn Error>> {
+fn check_permissions() -> Result<(), MacError> {
     if !nix::unistd::Uid::effective().is_root() {
-        return Err(Box::new(MacError::PermissionDenied(
+        return Err(MacError::PermissionDenied(
             "This program must be run with root privileges. Please use sudo.".into()
-        )));
+        ));
     }
 
     Ok(())
 }
 
 #[cfg(target_os = "windows")]
-fn check_permissions() -> Result<(), Box<dyn Error>> {
+fn check_permissions() -> Result<(), MacError> {
     if !is_elevated::is_elevated() {
-        return Err(Box::new(MacError::PermissionDenied(
+        return Err(MacError::PermissionDenied(
             "This program must be run with Administrator privileges. Please use as admin.".into()
-        )));
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+fn check_permissions() -> Result<(), MacError> {
+    if !nix::unistd::Uid::effective().is_root() {
+        return Err(MacError::PermissionDenied(
+            "This program must be run with root privileges. Please use sudo.".into()
+        ));
     }
 
     Ok(())
 }
 
 #[cfg(target_os = "linux")]
-fn execute_command(cmd: &str, args: &[&str]) -> Result<(), Box<dyn Error>> {
-    let output = Command::new("sudo")
-        .arg(cmd)
-        .args(args)
-        .output()?;
+fn execute_command(cmd: &str, args: &[&str]) -> Result<(), MacError> {
+    // Root needs no sudo, and scratch containers don't ship it
+    let sudo = find_tool("sudo").filter(|_| !nix::unistd::Uid::effective().is_root());
+    let mut command = match &sudo {
+        Some(sudo) => {
+            let mut command = Command::new(sudo);
+            command.arg(cmd);
+            command
+        }
+        None => Command::new(cmd),
+    };
+    let output = command.args(args).output()?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -130,14 +251,15 @@ fn execute_command(cmd: &str, args: &[&str]) -> Result<(), Box<dyn Error>> {
             "Unknown error".to_string()
         };
 
-        return Err(Box::new(MacError::SystemError(error_msg)));
+        return Err(MacError::SystemError(error_msg));
     }
 
     Ok(())
 }
 
-#[cfg(target_os = "windows")]
-fn execute_command(cmd: &str, args: &[&str]) -> Result<(), Box<dyn Error>> {
+#[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+fn execute_command(cmd: &str, args: &[&str]) -> Result<(), MacError> {
+    // Already running as root (see check_permissions), so no sudo here
     let output = Command::new(cmd)
         .args(args)
         .output()?;
@@ -153,31 +275,269 @@ fn execute_command(cmd: &str, args: &[&str]) -> Result<(), Box<dyn Error>> {
             "Unknown error".to_string()
         };
 
-        return Err(Box::new(MacError::SystemError(error_msg)));
+        return Err(MacError::SystemError(error_msg));
     }
 
     Ok(())
 }
 
+static RECONNECT: AtomicBool = AtomicBool::new(true);
+
+/// Whether Wi-Fi interfaces disconnected for a change are reassociated
+/// afterwards (`--no-reconnect` turns it off). Set once at startup.
+pub fn set_reconnect(reconnect: bool) {
+    RECONNECT.store(reconnect, Ordering::Relaxed);
+}
+
+/// Change the MAC of `interface`, failing if its change window is closed.
+pub fn change_mac(interface: &str, mac: &MacAddress, permanent: bool, trigger: Trigger) -> Result<Transaction, MacError> {
+    ChangeWindows::new()?.check(interface, Local::now())?;
+    change_mac_anytime(interface, mac, permanent, trigger)
+}
+
+/// Change the MAC of `interface` regardless of its change window, for
+/// putting back addresses in an emergency or after our own temporary
+/// changes. Failure hooks run before returning; a successful change leaves
+/// its hooks to `Transaction::run_hooks`.
+pub fn change_mac_anytime(interface: &str, mac: &MacAddress, permanent: bool, trigger: Trigger) -> Result<Transaction, MacError> {
+    let (result, event) = {
+        let _lock = lock::acquire(interface)?;
+        attempt_change(interface, mac, permanent, trigger)
+    };
+    with_hooks(result, event)
+}
+
+/// One change attempt, made under the caller's lock, and the event its
+/// hooks will be given.
+fn attempt_change(interface: &str, mac: &MacAddress, permanent: bool, trigger: Trigger) -> (Result<Transaction, MacError>, HookEvent) {
+    let old_mac = backend::current().get_mac(interface).map(|mac| mac.to_string()).unwrap_or_default();
+    let result = change_with_rollback(interface, mac, permanent);
+
+    let new_mac = mac.with_format(MacFormat::Colon).to_string();
+    let error = result.as_ref().err().map(|e| e.to_string().trim().to_string());
+    (result, HookEvent::new(interface, &old_mac, &new_mac, trigger, error))
+}
+
+/// Once the lock is released, hand the hooks to the transaction to run
+/// after the network is renewed, or run them now if there is none.
+fn with_hooks(result: Result<Transaction, MacError>, event: HookEvent) -> Result<Transaction, MacError> {
+    match result {
+        Ok(mut transaction) => {
+            transaction.hooks = Some(event);
+            Ok(transaction)
+        }
+        Err(e) => {
+            hooks::fire(&event);
+            Err(e)
+        }
+    }
+}
+
+/// What changing the MAC of `interface` would do, without doing it. Runs
+/// the same checks as a real change, except that missing privileges are
+/// only noted.
+pub fn plan_mac_change(interface: &str, mac: &MacAddress, permanent: bool) -> Result<Plan, MacError> {
+    ChangeWindows::new()?.check(interface, Local::now())?;
+
+    let backend = backend::current();
+    let mac = mac.with_format(MacFormat::Colon);
+    let old_mac = backend.get_mac(interface).map(|mac| mac.to_string()).unwrap_or_default();
+    let mut plan = Plan::new(interface, &old_mac, &mac.to_string(), permanent);
+
+    if let Err(e) = backend.check_permissions() {
+        plan.note(e.to_string());
+    }
+    backend.plan(&mut plan, interface, &mac, permanent)?;
+
+    for hook in hooks::would_run(interface) {
+        plan.note(format!("Hook {} would run: {}", hook.name, hook.describe()));
+    }
+
+    Ok(plan)
+}
+
+/// If any step fails after the system was touched, every recorded step is
+/// undone in reverse so the interface is not left down, disabled or
+/// half-configured.
+fn change_with_rollback(interface: &str, mac: &MacAddress, permanent: bool) -> Result<Transaction, MacError> {
+    let backend = backend::current();
+    let mut transaction = Transaction::new(interface, permanent);
+
+    // ip and ifconfig only take colon-separated addresses
+    let mac = mac.with_format(MacFormat::Colon);
+
+    let result = backend.set_mac(&mut transaction, interface, &mac).and_then(|()| match permanent {
+        true => backend.set_permanent(&mut transaction, interface, &mac),
+        false => Ok(()),
+    });
+    let error = match result {
+        Ok(()) => return Ok(transaction),
+        Err(e) => e,
+    };
+
+    if transaction.steps.is_empty() {
+        return Err(error);
+    }
+
+    progress!("MAC change failed: {}", error);
+    progress!("Rolling back {} step(s)...", transaction.steps.len());
+
+    let failures: Vec<String> = transaction.steps
+        .iter()
+        .rev()
+        .filter_map(|step| backend.undo(step).err().map(|e| format!("undo {}: {}", step, e)))
+        .collect();
+
+    if failures.is_empty() {
+        progress!("Rollback complete, {} is back to its previous state", interface);
+        Err(error)
+    } else {
+        Err(MacError::RollbackFailed(format!(
+            "{}; rollback also failed: {}", error, failures.join("; ")
+        )))
+    }
+}
+
+/// Change the MAC of `interface`, saving the original on first use and
+/// recording the change in the history log, along with who asked for it
+/// when that was a remote client.
+pub fn apply_mac(
+    interface: &str,
+    new_mac: &MacAddress,
+    permanent: bool,
+    logger: &MacLogger,
+    profile: Option<&str>,
+    trigger: Trigger,
+    principal: Option<&str>,
+) -> Result<Transaction, MacError> {
+    ChangeWindows::new()?.check(interface, Local::now())?;
+
+    let (result, event) = {
+        // Held through the history entry, so another process's change cannot
+        // land between reading the old MAC and setting the new one
+        let _lock = lock::acquire(interface)?;
+        let old_mac = backend::current().get_mac(interface)?;
+
+        if get_original_mac(interface)?.is_none() {
+            progress!("Saving original MAC address: {}", old_mac);
+            save_original_mac(interface, &old_mac)?;
+        }
+
+        let (result, event) = attempt_change(interface, new_mac, permanent, trigger);
+        let result = result.and_then(|transaction| {
+            logger.log_change(MacChange {
+                timestamp: Utc::now(),
+                interface: interface.to_string(),
+                old_mac,
+                new_mac: new_mac.with_format(MacFormat::Colon),
+                geo_location: None,
+                permanent,
+                profile: profile.map(|name| name.to_string()),
+                trigger: Some(trigger),
+                hostname: None,
+                dhcp_client_id: None,
+                principal: principal.map(|principal| principal.to_string()),
+            })?;
+            Ok(transaction)
+        });
+        (result, event)
+    };
+    with_hooks(result, event)
+}
+
+/// The host's own interfaces. Each platform supplies its `try_change_mac`,
+/// `make_permanent`, `plan_change_mac` and `undo` below.
+pub struct NativeBackend;
+
+pub fn native() -> Box<dyn MacBackend> {
+    Box::new(NativeBackend)
+}
+
+impl MacBackend for NativeBackend {
+    fn get_mac(&self, interface: &str) -> Result<MacAddress, MacError> {
+        crate::network::get_current_mac(interface)
+    }
+
+    fn set_mac(&self, transaction: &mut Transaction, interface: &str, mac: &MacAddress) -> Result<(), MacError> {
+        try_change_mac(transaction, interface, &mac.to_string())
+    }
+
+    fn set_permanent(&self, transaction: &mut Transaction, interface: &str, mac: &MacAddress) -> Result<(), MacError> {
+        make_permanent(transaction, interface, &mac.to_string())
+    }
+
+    fn list_interfaces(&self) -> Result<Vec<String>, MacError> {
+        crate::network::list_interfaces()
+    }
+
+    fn interface_state(&self, interface: &str) -> Option<LinkState> {
+        crate::network::get_link_state(interface)
+    }
+
+    fn plan(&self, plan: &mut Plan, interface: &str, mac: &MacAddress, permanent: bool) -> Result<(), MacError> {
+        plan_change_mac(plan, interface, &mac.to_string(), permanent)
+    }
+
+    fn undo(&self, step: &Step) -> Result<(), MacError> {
+        undo(step)
+    }
+
+    fn check_permissions(&self) -> Result<(), MacError> {
+        check_permissions()
+    }
+
+    fn verify_interface(&self, interface: &str) -> Result<(), MacError> {
+        let card = crate::network::NetworkCard::verify_interface(interface)?;
+        progress!("Detected network card: {:?}", card);
+        Ok(())
+    }
+}
+
 #[cfg(target_os = "linux")]
-pub fn change_mac(interface: &str, mac: &str, permanent: bool) -> Result<(), Box<dyn Error>> {
+fn try_change_mac(transaction: &mut Transaction, interface: &str, mac: &str) -> Result<(), MacError> {
     // Verify root privileges
     check_permissions()?;
 
     // Verify interface exists
     verify_interface_exists(interface)?;
 
-    // Find ip command path
-    let ip_cmd = find_command("ip").ok_or_else(||
-        MacError::SystemError("'ip' command not found. Please install iproute2".into()))?;
+    let old_mac = crate::network::get_current_mac(interface).map(|mac| mac.to_string()).unwrap_or_default();
+    let wireless = check_wireless(interface)?;
 
-    println!("Using command: {}", ip_cmd);
-    println!("Bringing interface {} down...", interface);
+    // NetworkManager would put its own MAC back on interfaces it manages,
+    // so those are changed through their connection instead; reactivating
+    // the connection also reassociates
+    if let Some(device) = networkmanager::device(interface) {
+        match (device.is_active(), &device.connection, device.cloned_mac_setting()) {
+            (true, Some(connection), Some(setting)) => {
+                return change_with_networkmanager(transaction, interface, mac, old_mac, connection, setting);
+            }
+            (true, _, _) => {
+                progress!("Warning: NetworkManager manages {} but cannot set its MAC; it may revert the change", interface);
+            }
+            _ => {}
+        }
+    }
 
-    // Stop NetworkManager if it's running
-    let _ = Command::new("sudo")
-        .args(&["systemctl", "stop", "NetworkManager"])
-        .output();
+    let backend = LinkBackend::detect();
+
+    // Remember per-link addresses of multi-link Wi-Fi interfaces so we can
+    // tell whether the driver moved them along with the MLD address
+    let old_links = crate::network::get_mlo_links(interface);
+    if !old_links.is_empty() {
+        progress!("Interface {} is a multi-link device with {} active link(s)", interface, old_links.len());
+    }
+
+    progress!("Using {}", backend.describe());
+
+    // Leaving the supplicant associated would have it fight the change
+    if let Some(connection) = &wireless.connection {
+        progress!("Disconnecting {} from {}...", interface, network_name(connection));
+        crate::network::disconnect_wireless(interface)?;
+        transaction.record(Step::Disassociated { interface: interface.to_string() });
+    }
+
+    progress!("Bringing interface {} down...", interface);
 
     // Try to bring interface down with retries
     let max_retries = 3;
@@ -185,13 +545,13 @@ pub fn change_mac(interface: &str, mac: &str, permanent: bool) -> Result<(), Box
     let mut last_error = None;
 
     for attempt in 1..=max_retries {
-        match execute_command(&ip_cmd, &["link", "set", "dev", interface, "down"]) {
+        match backend.set_up(interface, false) {
             Ok(_) => {
                 success = true;
                 break;
             }
             Err(e) => {
-                println!("Attempt {} failed, retrying...", attempt);
+                progress!("Attempt {} failed, retrying...", attempt);
                 std::thread::sleep(std::time::Duration::from_secs(1));
                 last_error = Some(e);
             }
@@ -201,240 +561,766 @@ pub fn change_mac(interface: &str, mac: &str, permanent: bool) -> Result<(), Box
     if !success {
         return Err(last_error.unwrap());
     }
+    transaction.record(Step::InterfaceDown { interface: interface.to_string() });
 
-    println!("Changing MAC address to {}...", mac);
+    progress!("Changing MAC address to {}...", mac);
 
     // Change MAC address
-    execute_command(&ip_cmd, &["link", "set", "dev", interface, "address", mac])?;
+    backend.set_address(interface, mac)?;
+    transaction.record(Step::AddressSet { interface: interface.to_string(), old_mac, new_mac: mac.to_string() });
+
+    // A blocked radio cannot come up; the kernel refuses with ERFKILL
+    if let Some(state) = wireless.rfkill.filter(|state| *state != crate::network::RfkillState::Unblocked) {
+        progress!("Leaving {} down: its radio is {} by rfkill", interface, describe_rfkill(state));
+        transaction.suggest(format!(
+            "{} comes up with the new MAC once its radio is unblocked (`rfkill unblock wifi`)", interface
+        ));
+    } else {
+        progress!("Bringing interface back up...");
+
+        // Bring interface back up
+        backend.set_up(interface, true)?;
+        transaction.record(Step::InterfaceUp { interface: interface.to_string() });
+    }
 
-    println!("Bringing interface back up...");
+    // Verify the change
+    progress!("Verifying MAC address change...");
+    verify_mac_change(interface, mac)?;
 
-    // Bring interface back up
-    execute_command(&ip_cmd, &["link", "set", "dev", interface, "up"])?;
+    if let Some(connection) = &wireless.connection {
+        reassociate(transaction, interface, connection);
+    }
 
-    // Restart NetworkManager if it was running
-    let _ = Command::new("sudo")
-        .args(&["systemctl", "start", "NetworkManager"])
-        .output();
+    if !old_links.is_empty() && !check_mlo_links(interface, &old_links) {
+        transaction.suggest("Reconnect to the network to regenerate per-link addresses");
+    }
 
-    if permanent {
-        println!("Making change permanent...");
-        make_permanent(interface, mac)?;
+    transaction.suggest("Renew the DHCP lease if the network assigned the old IP address");
+    transaction.suggest(restore_hint(interface));
+
+    Ok(())
+}
+
+/// What a change needs to know about a Wi-Fi interface beforehand.
+#[cfg(target_os = "linux")]
+#[derive(Default)]
+struct WirelessState {
+    connection: Option<crate::network::WirelessConnection>,
+    rfkill: Option<crate::network::RfkillState>,
+}
+
+/// Refuse monitor-mode interfaces, whose address identifies them on no
+/// network and which most drivers only let change in managed mode.
+#[cfg(target_os = "linux")]
+fn check_wireless(interface: &str) -> Result<WirelessState, MacError> {
+    if !crate::network::is_wireless(interface) {
+        return Ok(WirelessState::default());
+    }
+    if crate::network::is_monitor_mode(interface) {
+        return Err(MacError::ValidationFailed(format!(
+            "{} is in monitor mode; switch it back with `iw dev {} set type managed` before changing its MAC",
+            interface, interface
+        )));
     }
 
-    // Verify the change
-    println!("Verifying MAC address change...");
+    let rfkill = crate::network::get_rfkill_state(interface);
+    // A blocked radio has no association to drop
+    let connection = match rfkill.is_some_and(|state| state != crate::network::RfkillState::Unblocked) {
+        true => None,
+        false => crate::network::get_wireless_connection(interface),
+    };
+    Ok(WirelessState { connection, rfkill })
+}
+
+#[cfg(target_os = "linux")]
+fn describe_rfkill(state: crate::network::RfkillState) -> &'static str {
+    match state {
+        crate::network::RfkillState::HardBlocked => "hard-blocked (a switch or firmware setting)",
+        _ => "soft-blocked",
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn network_name(connection: &crate::network::WirelessConnection) -> String {
+    match (&connection.ssid, &connection.bssid) {
+        (Some(ssid), _) => ssid.clone(),
+        (None, Some(bssid)) => bssid.to_string(),
+        (None, None) => "its Wi-Fi network".to_string(),
+    }
+}
+
+/// Rejoin the network dropped before the change, unless `--no-reconnect`.
+/// Failing to reassociate does not fail the change; the MAC is already set.
+#[cfg(target_os = "linux")]
+fn reassociate(transaction: &mut Transaction, interface: &str, connection: &crate::network::WirelessConnection) {
+    let network = network_name(connection);
+    if !RECONNECT.load(Ordering::Relaxed) {
+        transaction.suggest(format!("{} was left disconnected from {} (--no-reconnect)", interface, network));
+        return;
+    }
+
+    progress!("Reconnecting {} to {}...", interface, network);
+    if let Err(e) = crate::network::reconnect_wireless(interface, connection.ssid.as_deref()) {
+        progress!("Warning: {}", e);
+        transaction.suggest(format!("Reconnect {} to {}", interface, network));
+        return;
+    }
+    match crate::network::wait_for_association(interface, std::time::Duration::from_secs(15)) {
+        Some(_) => progress!("Reassociated with {}", network),
+        None => transaction.suggest(format!(
+            "{} has not reassociated with {} yet; check the network accepts the new MAC", interface, network
+        )),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn restore_hint(interface: &str) -> String {
+    let netns = crate::netns::current().map(|netns| format!(" --netns {}", netns)).unwrap_or_default();
+    format!("Run `chameleon -i {}{} --restore` to return to the original address", interface, netns)
+}
+
+/// Set the MAC as the connection's cloned address and reactivate it, which
+/// leaves every other connection on the host alone. A temporary change is
+/// only held in memory; a permanent one is saved with the connection.
+#[cfg(target_os = "linux")]
+fn change_with_networkmanager(
+    transaction: &mut Transaction,
+    interface: &str,
+    mac: &str,
+    old_mac: String,
+    connection: &networkmanager::Connection,
+    setting: &str,
+) -> Result<(), MacError> {
+    progress!("{} is managed by NetworkManager (connection {})", interface, connection.name);
+    let permanent = transaction.permanent;
+
+    let previous = networkmanager::get_setting(&connection.uuid, setting)?;
+    progress!("Setting {} to {}...", setting, mac);
+    networkmanager::set_setting(&connection.uuid, setting, Some(mac), !permanent)?;
+    transaction.record(Step::ConnectionModified {
+        connection: connection.name.clone(),
+        uuid: connection.uuid.clone(),
+        interface: interface.to_string(),
+        setting: setting.to_string(),
+        value: mac.to_string(),
+        previous,
+        temporary: !permanent,
+    });
+
+    progress!("Reactivating connection {}...", connection.name);
+    networkmanager::activate(&connection.uuid, interface)?;
+    transaction.record(Step::ConnectionActivated {
+        connection: connection.name.clone(),
+        uuid: connection.uuid.clone(),
+        interface: interface.to_string(),
+    });
+
+    progress!("Verifying MAC address change...");
     verify_mac_change(interface, mac)?;
 
+    // Recorded last: undoing the connection change already puts the old
+    // address back
+    transaction.record(Step::AddressSet { interface: interface.to_string(), old_mac, new_mac: mac.to_string() });
+
+    transaction.suggest(restore_hint(interface));
     Ok(())
 }
 
-#[cfg(target_os = "windows")]
-fn find_network_adapter(interface: &str) -> Result<(RegKey, String), Box<dyn Error>> {
-    let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
-    let net_reg_path = "SYSTEM\\CurrentControlSet\\Control\\Class\\{4D36E972-E325-11CE-BFC1-08002BE10318}";
-    let net_reg_key = hklm.open_subkey_with_flags(net_reg_path, KEY_READ | KEY_WRITE)?;
-
-    // First get the exact adapter name from Windows
-    let output = Command::new("wmic")
-        .args(&["nic", "where", &format!("NetConnectionID='{}'", interface), "get", "Name,NetConnectionID", "/format:csv"])
-        .output()?;
+/// A connection NetworkManager changed was already saved to disk.
+#[cfg(target_os = "linux")]
+fn make_permanent(transaction: &mut Transaction, interface: &str, mac: &str) -> Result<(), MacError> {
+    if transaction.steps.iter().any(|step| matches!(step, Step::ConnectionModified { .. })) {
+        return Ok(());
+    }
+    Ok(permanence::install(transaction, interface, mac)?)
+}
 
-    let output_str = String::from_utf8_lossy(&output.stdout);
-    let mut adapter_name = String::new();
-    let mut found_adapter = false;
-
-    for line in output_str.lines().skip(1) { // Skip header
-        let parts: Vec<&str> = line.split(',').collect();
-        if parts.len() >= 3 && parts[2].trim() == interface {
-            adapter_name = parts[1].trim().to_string();
-            found_adapter = true;
-            break;
+#[cfg(target_os = "linux")]
+fn plan_change_mac(plan: &mut Plan, interface: &str, mac: &str, permanent: bool) -> Result<(), MacError> {
+    verify_interface_exists(interface)?;
+    let old_mac = plan.old_mac.clone();
+    let wireless = check_wireless(interface)?;
+
+    if let Some(device) = networkmanager::device(interface) {
+        match (device.is_active(), &device.connection, device.cloned_mac_setting()) {
+            (true, Some(connection), Some(setting)) => {
+                let temporary = if permanent { "" } else { "--temporary " };
+                plan.add(
+                    Step::ConnectionModified {
+                        connection: connection.name.clone(),
+                        uuid: connection.uuid.clone(),
+                        interface: interface.to_string(),
+                        setting: setting.to_string(),
+                        value: mac.to_string(),
+                        previous: networkmanager::get_setting(&connection.uuid, setting).ok().flatten(),
+                        temporary: !permanent,
+                    },
+                    Some(format!("nmcli connection modify {}{} {} {}", temporary, connection.uuid, setting, mac)),
+                );
+                plan.add(
+                    Step::ConnectionActivated {
+                        connection: connection.name.clone(),
+                        uuid: connection.uuid.clone(),
+                        interface: interface.to_string(),
+                    },
+                    Some(format!("nmcli connection up {} ifname {}", connection.uuid, interface)),
+                );
+                return Ok(());
+            }
+            (true, _, _) => {
+                plan.note(format!("NetworkManager manages {} but cannot set its MAC; it may revert the change", interface));
+            }
+            _ => {}
         }
     }
 
-    if !found_adapter {
-        return Err(Box::new(MacError::ValidationFailed(
-            format!("Could not find adapter with name {}", interface)
-        )));
+    let backend = LinkBackend::detect();
+    if let Some(connection) = &wireless.connection {
+        plan.add(
+            Step::Disassociated { interface: interface.to_string() },
+            Some(format!("wpa_cli -i {} disconnect", interface)),
+        );
+        if RECONNECT.load(Ordering::Relaxed) {
+            plan.note(format!("{} reconnects to {} after the change", interface, network_name(connection)));
+        }
+    }
+    plan.add(
+        Step::InterfaceDown { interface: interface.to_string() },
+        Some(backend.set_up_command(interface, false)),
+    );
+    plan.add(
+        Step::AddressSet { interface: interface.to_string(), old_mac, new_mac: mac.to_string() },
+        Some(backend.set_address_command(interface, mac)),
+    );
+    match wireless.rfkill.filter(|state| *state != crate::network::RfkillState::Unblocked) {
+        Some(state) => plan.note(format!("{} stays down: its radio is {} by rfkill", interface, describe_rfkill(state))),
+        None => plan.add(
+            Step::InterfaceUp { interface: interface.to_string() },
+            Some(backend.set_up_command(interface, true)),
+        ),
     }
 
-    // Now search through registry for this adapter
-    for subkey_name in net_reg_key.enum_keys() {
-        let subkey_name = subkey_name?;
-        if let Ok(subkey) = net_reg_key.open_subkey_with_flags(&subkey_name, KEY_READ | KEY_WRITE) {
-            if let Ok(driver_desc) = subkey.get_value::<String, &str>("DriverDesc") {
-                if driver_desc.trim() == adapter_name {
-                    return Ok((subkey, interface.to_string()));
-                }
+    if permanent {
+        let (backend, files) = permanence::plan(interface, mac)?;
+        plan.note(format!("Permanent with a {}", backend.describe()));
+        for (path, content) in files {
+            plan.add_file(Step::FileWritten { path, previous: None }, content);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn undo(step: &Step) -> Result<(), MacError> {
+    let backend = LinkBackend::detect();
+
+    match step {
+        Step::InterfaceDown { interface } => backend.set_up(interface, true),
+        Step::InterfaceUp { interface } => backend.set_up(interface, false),
+        Step::AddressSet { interface, old_mac, .. } if !old_mac.is_empty() => backend.set_address(interface, old_mac),
+        Step::ServiceStopped { service } => execute_command("systemctl", &["start", service]),
+        Step::ServiceStarted { service } => execute_command("systemctl", &["stop", service]),
+        Step::FileWritten { path, previous } => {
+            match previous {
+                Some(content) => fs::write(path, content)?,
+                None => fs::remove_file(path)?,
             }
+            Ok(())
+        }
+        // Reactivating here also covers undoing ConnectionActivated
+        Step::ConnectionModified { uuid, interface, setting, previous, temporary, .. } => {
+            networkmanager::set_setting(uuid, setting, previous.as_deref(), *temporary)?;
+            Ok(networkmanager::activate(uuid, interface)?)
         }
+        Step::Disassociated { interface } => crate::network::reconnect_wireless(interface, None),
+        _ => Ok(()),
     }
+}
 
-    Err(Box::new(MacError::SystemError(
-        format!("Could not find registry key for interface {}", interface)
-    )))
+/// Link addresses are assigned by the driver when links are set up, so after
+/// the MLD address changes they should be regenerated on reassociation. Warn
+/// when a link still carries one of the old addresses; returns false if so.
+#[cfg(target_os = "linux")]
+fn check_mlo_links(interface: &str, old_links: &[crate::network::MloLink]) -> bool {
+    let new_links = crate::network::get_mlo_links(interface);
+
+    if new_links.is_empty() {
+        progress!("Multi-link addresses for {} will be reassigned when it reassociates", interface);
+        return true;
+    }
+
+    let stale: Vec<_> = new_links
+        .iter()
+        .filter(|link| old_links.iter().any(|old| old.address == link.address))
+        .collect();
+
+    if stale.is_empty() {
+        for link in &new_links {
+            progress!("Link {} address: {}", link.link_id, link.address);
+        }
+        true
+    } else {
+        progress!("Warning: the driver for {} only changed the MLD address.", interface);
+        for link in stale {
+            progress!("Warning: link {} still uses its previous address {}", link.link_id, link.address);
+        }
+        false
+    }
 }
 
 #[cfg(target_os = "windows")]
-pub fn change_mac(interface: &str, mac: &str, permanent: bool) -> Result<(), Box<dyn Error>> {
+fn try_change_mac(transaction: &mut Transaction, interface: &str, mac: &str) -> Result<(), MacError> {
     // Verify admin privileges first
     check_permissions()?;
 
-    // Get the network adapter's registry information
-    let (adapter_key, adapter_name) = find_network_adapter(interface)?;
+    // Resolve the connection name to its adapter and SetupAPI device
+    let adapter = verify_interface_exists(interface)?;
+    let device = win32::NetDevice::open(&adapter.guid)?;
+    let adapter_key = device.registry_key()?;
 
-    println!("Found network adapter: {}", adapter_name);
-    println!("Changing MAC address to {}...", mac);
+    progress!("Found network adapter: {} ({})", adapter.name, adapter.description);
+    progress!("Changing MAC address to {}...", mac);
+
+    let old_mac = adapter.mac.clone().unwrap_or_default();
 
     // Disable the network adapter
-    println!("Disabling network adapter...");
-    execute_command(
-        "netsh",
-        &["interface", "set", "interface", &adapter_name, "admin=disable"]
-    )?;
+    progress!("Disabling network adapter...");
+    device.set_enabled(false)?;
+    transaction.record(Step::AdapterDisabled { adapter: adapter.name.clone(), guid: adapter.guid.clone() });
 
     // Set the MAC address in registry
-    let cleaned_mac = mac.replace(":", "").replace("-", "").replace(".", "");
+    let cleaned_mac = MacAddress::parse(mac)?.with_format(MacFormat::Raw).to_string().to_uppercase();
+    let previous = adapter_key.get_value::<String, _>("NetworkAddress").ok();
     adapter_key.set_value("NetworkAddress", &cleaned_mac)?;
+    transaction.record(Step::RegistryValueSet {
+        key: device.registry_path(),
+        name: "NetworkAddress".into(),
+        value: cleaned_mac,
+        previous,
+    });
+    transaction.record(Step::AddressSet { interface: interface.to_string(), old_mac, new_mac: mac.to_string() });
 
     // Enable the network adapter
-    println!("Enabling network adapter...");
-    execute_command(
-        "netsh",
-        &["interface", "set", "interface", &adapter_name, "admin=enable"]
-    )?;
+    progress!("Enabling network adapter...");
+    device.set_enabled(true)?;
+    transaction.record(Step::AdapterEnabled { adapter: adapter.name.clone(), guid: adapter.guid.clone() });
 
     // Wait for interface to come back up
     std::thread::sleep(std::time::Duration::from_secs(2));
 
+    // The registry value outlives this run whether or not it was asked to
+    if !transaction.permanent {
+        transaction.suggest("The NetworkAddress registry value persists across reboots; use --restore to remove the override");
+    }
+
     // Verify the change
-    println!("Verifying MAC address change...");
-    verify_mac_change(&adapter_name, mac)?;
+    progress!("Verifying MAC address change...");
+    verify_mac_change(&adapter.name, mac)?;
+
+    transaction.suggest("Run `ipconfig /renew` if the network assigned the old IP address");
 
     Ok(())
 }
 
-#[cfg(target_os = "linux")]
-fn verify_mac_change(interface: &str, expected_mac: &str) -> Result<(), Box<dyn Error>> {
-    // Wait a bit for the change to take effect
-    std::thread::sleep(std::time::Duration::from_secs(1));
+#[cfg(target_os = "windows")]
+fn plan_change_mac(plan: &mut Plan, interface: &str, mac: &str, permanent: bool) -> Result<(), MacError> {
+    let adapter = verify_interface_exists(interface)?;
+    let device = win32::NetDevice::open(&adapter.guid)?;
+
+    let cleaned_mac = MacAddress::parse(mac)?.with_format(MacFormat::Raw).to_string().to_uppercase();
+    // Reading works without elevation, writing does not
+    let previous = win32::open_registry_path(&device.registry_path())
+        .ok()
+        .and_then(|key| key.get_value::<String, _>("NetworkAddress").ok());
+
+    plan.add(
+        Step::AdapterDisabled { adapter: adapter.name.clone(), guid: adapter.guid.clone() },
+        Some(format!("SetupAPI: disable {}", adapter.description)),
+    );
+    plan.add(
+        Step::RegistryValueSet {
+            key: device.registry_path(),
+            name: "NetworkAddress".into(),
+            value: cleaned_mac.clone(),
+            previous,
+        },
+        Some(format!("NetworkAddress = {}", cleaned_mac)),
+    );
+    plan.add(
+        Step::AdapterEnabled { adapter: adapter.name.clone(), guid: adapter.guid.clone() },
+        Some(format!("SetupAPI: enable {}", adapter.description)),
+    );
 
-    let current_mac = crate::network::get_current_mac(interface)?;
-    if current_mac.to_lowercase() != expected_mac.to_lowercase() {
-        return Err(Box::new(MacError::ValidationFailed(
-            format!("MAC address change verification failed. Expected {}, got {}",
-                    expected_mac, current_mac)
-        )));
+    if !permanent {
+        plan.note("The NetworkAddress registry value persists across reboots; use --restore to remove the override");
     }
 
     Ok(())
 }
 
 #[cfg(target_os = "windows")]
-fn verify_mac_change(interface: &str, expected_mac: &str) -> Result<(), Box<dyn Error>> {
-    // Wait a bit for the change to take effect
-    std::thread::sleep(std::time::Duration::from_secs(1));
+fn undo(step: &Step) -> Result<(), MacError> {
+    match step {
+        Step::AdapterDisabled { guid, .. } => Ok(win32::NetDevice::open(guid)?.set_enabled(true)?),
+        Step::AdapterEnabled { guid, .. } => Ok(win32::NetDevice::open(guid)?.set_enabled(false)?),
+        Step::RegistryValueSet { key, name, previous, .. } => {
+            let key = win32::open_registry_path(key)?;
+            match previous {
+                Some(value) => key.set_value(name, value)?,
+                None => key.delete_value(name)?,
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
 
-    let current_mac = crate::network::get_current_mac(interface)?;
+#[cfg(target_os = "macos")]
+fn try_change_mac(transaction: &mut Transaction, interface: &str, mac: &str) -> Result<(), MacError> {
+    use crate::macos::{self, SpoofCapability};
 
-    // Convert both MACs to the same format (hyphen-separated) for comparison
-    let expected_mac = expected_mac
-        .replace(":", "-")
-        .replace(".", "-")
-        .to_lowercase();
+    // Verify root privileges
+    check_permissions()?;
 
-    let current_mac = current_mac
-        .replace(":", "-")
-        .replace(".", "-")
-        .to_lowercase();
+    // Refused before the address is touched rather than rolled back after
+    if transaction.permanent {
+        return Err(MacError::UnsupportedPlatform(
+            "Permanent MAC address changes are not supported on macOS".into()
+        ));
+    }
 
-    if current_mac != expected_mac {
-        return Err(Box::new(MacError::ValidationFailed(
-            format!("MAC address change verification failed. Expected {}, got {}",
-                    expected_mac, current_mac)
-        )));
+    // Verify interface exists
+    verify_interface_exists(interface)?;
+
+    let old_mac = crate::network::get_current_mac(interface).map(|mac| mac.to_string()).unwrap_or_default();
+
+    // Decide up front what the hardware allows instead of failing generically
+    let capability = macos::spoof_capability(interface);
+    if let SpoofCapability::Unsupported(reason) = &capability {
+        return Err(MacError::UnsupportedPlatform(reason.clone()));
     }
 
+    // Wi-Fi only accepts a new address while disassociated, and macOS rejoins
+    // a known network within seconds, so change it right after dropping off
+    if capability == SpoofCapability::WhileDisassociated
+        && macos::disassociate(interface)?
+    {
+        progress!("Disassociated {} from the current Wi-Fi network", interface);
+        transaction.record(Step::Disassociated { interface: interface.to_string() });
+        transaction.suggest("Rejoin your Wi-Fi network if macOS does not reconnect automatically");
+    }
+
+    progress!("Changing MAC address to {}...", mac);
+
+    execute_command("ifconfig", &[interface, "ether", mac]).map_err(|e| {
+        let msg = e.to_string();
+        if msg.contains("Operation not permitted") {
+            MacError::PermissionDenied(format!(
+                "macOS refused to change the MAC address of {}. System Integrity Protection \
+                 blocks the change: {}",
+                interface, msg.trim()
+            ))
+        } else if msg.contains("Invalid argument") || msg.contains("Can't assign requested address") {
+            MacError::UnsupportedPlatform(format!(
+                "{}: {}", macos::unsupported_reason(interface, &capability), msg.trim()
+            ))
+        } else {
+            MacError::SystemError(msg)
+        }
+    })?;
+    transaction.record(Step::AddressSet { interface: interface.to_string(), old_mac, new_mac: mac.to_string() });
+
+    // Let the system pick the interface back up and rejoin networks
+    let _ = Command::new("networksetup")
+        .arg("-detectnewhardware")
+        .output();
+
+    // Verify the change
+    progress!("Verifying MAC address change...");
+    verify_mac_change(interface, mac)
+        .map_err(|e| MacError::UnsupportedPlatform(format!(
+            "{}. {}", e, macos::unsupported_reason(interface, &capability)
+        )))?;
+
+    transaction.suggest("The address resets on reboot; run chameleon again after restarting to keep it");
+
     Ok(())
 }
 
-#[cfg(target_os = "linux")]
-fn make_permanent(interface: &str, mac: &str) -> Result<(), Box<dyn Error>> {
-    use std::fs;
-    use std::path::Path;
-
-    // Create udev rule
-    let rule = format!(
-        r#"ACTION=="add", SUBSYSTEM=="net", ATTR{{address}}=="*", ATTR{{dev_id}}=="0x0", ATTR{{type}}=="1", KERNEL=="{}", ATTR{{address}}="{}"
-"#,
-        interface, mac
+#[cfg(target_os = "macos")]
+fn make_permanent(_transaction: &mut Transaction, _interface: &str, _mac: &str) -> Result<(), MacError> {
+    Err(MacError::UnsupportedPlatform(
+        "Permanent MAC address changes are not supported on macOS".into()
+    ))
+}
+
+#[cfg(target_os = "macos")]
+fn plan_change_mac(plan: &mut Plan, interface: &str, mac: &str, permanent: bool) -> Result<(), MacError> {
+    use crate::macos::{self, SpoofCapability};
+
+    if permanent {
+        return Err(MacError::UnsupportedPlatform(
+            "Permanent MAC address changes are not supported on macOS".into()
+        ));
+    }
+
+    verify_interface_exists(interface)?;
+
+    let capability = macos::spoof_capability(interface);
+    if let SpoofCapability::Unsupported(reason) = &capability {
+        return Err(MacError::UnsupportedPlatform(reason.clone()));
+    }
+
+    if capability == SpoofCapability::WhileDisassociated {
+        plan.add(
+            Step::Disassociated { interface: interface.to_string() },
+            Some(format!("CoreWLAN: disassociate {}", interface)),
+        );
+    }
+    plan.add(
+        Step::AddressSet { interface: interface.to_string(), old_mac: plan.old_mac.clone(), new_mac: mac.to_string() },
+        Some(format!("ifconfig {} ether {}", interface, mac)),
     );
+    plan.note("networksetup -detectnewhardware then picks the interface back up");
 
-    let rule_path = Path::new("/etc/udev/rules.d/70-persistent-net.rules");
+    Ok(())
+}
 
-    // Check if we can write to the directory
-    if !Path::new("/etc/udev/rules.d").exists() {
-        return Err(Box::new(MacError::SystemError(
-            "Directory /etc/udev/rules.d does not exist".into()
-        )));
+#[cfg(target_os = "macos")]
+fn undo(step: &Step) -> Result<(), MacError> {
+    match step {
+        // macOS rejoins known networks on its own once the address is back
+        Step::AddressSet { interface, old_mac, .. } if !old_mac.is_empty() => {
+            execute_command("ifconfig", &[interface, "ether", old_mac])
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+fn try_change_mac(transaction: &mut Transaction, interface: &str, mac: &str) -> Result<(), MacError> {
+    check_permissions()?;
+    verify_interface_exists(interface)?;
+
+    let output = bsd::ifconfig(interface).unwrap_or_default();
+    let old_mac = bsd::current_mac(&output).unwrap_or_default().to_string();
+
+    // Most drivers take a new address while up, but wlan(4) and some older
+    // NICs refuse it until the interface is down
+    let was_up = bsd::is_up(&output);
+    if was_up {
+        progress!("Bringing interface {} down...", interface);
+        execute_command("ifconfig", &[interface, "down"])?;
+        transaction.record(Step::InterfaceDown { interface: interface.to_string() });
+    }
+
+    progress!("Changing MAC address to {}...", mac);
+    execute_command("ifconfig", &[interface, bsd::LLADDR, mac])?;
+    transaction.record(Step::AddressSet { interface: interface.to_string(), old_mac, new_mac: mac.to_string() });
+
+    if was_up {
+        progress!("Bringing interface {} up...", interface);
+        execute_command("ifconfig", &[interface, "up"])?;
+        transaction.record(Step::InterfaceUp { interface: interface.to_string() });
     }
 
-    fs::write(rule_path, rule)
-        .map_err(|e| MacError::SystemError(format!("Failed to write udev rule: {}", e)))?;
+    progress!("Verifying MAC address change...");
+    verify_mac_change(interface, mac)?;
 
-    // Reload udev rules
-    Command::new("udevadm")
-        .args(&["control", "--reload-rules"])
-        .output()
-        .map_err(|e| MacError::SystemError(format!("Failed to reload udev rules: {}", e)))?;
+    if !transaction.permanent {
+        transaction.suggest("The address resets on reboot; use --permanent to keep it");
+    }
 
     Ok(())
 }
 
-#[cfg(target_os = "windows")]
-fn make_permanent(_interface: &str, _mac: &str) -> Result<(), Box<dyn Error>> {
-    // On Windows, the registry change made in change_mac() is already permanent
+/// Set the address at boot: in the interface's ifconfig_ line of
+/// /etc/rc.conf on FreeBSD, or as an lladdr line of /etc/hostname.<if> on
+/// OpenBSD.
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+fn make_permanent(transaction: &mut Transaction, interface: &str, mac: &str) -> Result<(), MacError> {
+    let path = bsd::override_path(interface);
+    let previous = fs::read_to_string(&path).ok();
+
+    fs::write(&path, bsd::with_override(previous.as_deref().unwrap_or_default(), interface, mac))?;
+    transaction.record(Step::FileWritten { path, previous });
     Ok(())
 }
 
-pub fn get_running_applications() -> Result<Vec<String>, Box<dyn Error>> {
-    let mut apps = Vec::new();
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+fn plan_change_mac(plan: &mut Plan, interface: &str, mac: &str, permanent: bool) -> Result<(), MacError> {
+    verify_interface_exists(interface)?;
 
-    #[cfg(target_os = "linux")]
-    {
-        // Get running processes from /proc
-        for entry in fs::read_dir("/proc")? {
-            let entry = entry?;
-            if let Ok(name) = fs::read_to_string(entry.path().join("comm")) {
-                apps.push(name.trim().to_string());
+    let output = bsd::ifconfig(interface).unwrap_or_default();
+    let was_up = bsd::is_up(&output);
+    if was_up {
+        plan.add(Step::InterfaceDown { interface: interface.to_string() }, Some(format!("ifconfig {} down", interface)));
+    }
+    plan.add(
+        Step::AddressSet { interface: interface.to_string(), old_mac: plan.old_mac.clone(), new_mac: mac.to_string() },
+        Some(format!("ifconfig {} {} {}", interface, bsd::LLADDR, mac)),
+    );
+    if was_up {
+        plan.add(Step::InterfaceUp { interface: interface.to_string() }, Some(format!("ifconfig {} up", interface)));
+    }
+
+    if permanent {
+        let path = bsd::override_path(interface);
+        let previous = fs::read_to_string(&path).ok();
+        let content = bsd::with_override(previous.as_deref().unwrap_or_default(), interface, mac);
+        plan.add_file(Step::FileWritten { path, previous }, content);
+    }
+
+    Ok(())
+}
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+fn undo(step: &Step) -> Result<(), MacError> {
+    match step {
+        Step::InterfaceDown { interface } => execute_command("ifconfig", &[interface, "up"]),
+        Step::InterfaceUp { interface } => execute_command("ifconfig", &[interface, "down"]),
+        Step::AddressSet { interface, old_mac, .. } if !old_mac.is_empty() => {
+            execute_command("ifconfig", &[interface, bsd::LLADDR, old_mac])
+        }
+        Step::FileWritten { path, previous } => {
+            match previous {
+                Some(content) => fs::write(path, content)?,
+                None => fs::remove_file(path)?,
             }
+            Ok(())
         }
+        _ => Ok(()),
     }
+}
 
-    #[cfg(target_os = "macos")]
-    {
-        let output = std::process::Command::new("ps")
-            .args(&["-e", "-o", "comm="])
-            .output()?;
+fn verify_mac_change(interface: &str, expected_mac: &str) -> Result<(), MacError> {
+    // Wait a bit for the change to take effect
+    std::thread::sleep(std::time::Duration::from_secs(1));
 
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        apps.extend(output_str.lines().map(|s| s.trim().to_string()));
+    // Some tools exit successfully even when the driver ignores the request;
+    // compared as bytes, since each platform prints MACs its own way
+    let expected: MacAddress = expected_mac.parse()?;
+    let current_mac = crate::network::get_current_mac(interface)?;
+    if current_mac != expected {
+        return Err(MacError::VerificationFailed(
+            format!("MAC address change verification failed. Expected {}, got {}",
+                    expected_mac, current_mac)
+        ));
     }
 
-    #[cfg(target_os = "windows")]
-    {
-        let output = std::process::Command::new("tasklist")
-            .args(&["/FO", "CSV", "/NH"])
-            .output()?;
-
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        for line in output_str.lines() {
-            if let Some(name) = line.split(',').next() {
-                apps.push(name.trim_matches('"').to_string());
-            }
-        }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn make_permanent(_transaction: &mut Transaction, _interface: &str, _mac: &str) -> Result<(), MacError> {
+    // On Windows, the registry change made in change_mac() is already permanent
+    Ok(())
+}
+
+/// The MAC address a permanent override would apply to `interface` at boot,
+/// if one is installed.
+#[cfg(target_os = "linux")]
+pub fn get_permanent_override(interface: &str) -> Option<String> {
+    permanence::find_overrides(interface).into_iter().next().map(|found| found.mac)
+}
+
+#[cfg(target_os = "windows")]
+pub fn get_permanent_override(interface: &str) -> Option<String> {
+    let adapter = win32::find_adapter(interface).ok()?;
+    let device = win32::NetDevice::open(&adapter.guid).ok()?;
+    let value: String = device.registry_key().ok()?.get_value("NetworkAddress").ok()?;
+
+    if value.trim().is_empty() { None } else { Some(value) }
+}
+
+#[cfg(target_os = "macos")]
+pub fn get_permanent_override(_interface: &str) -> Option<String> {
+    // Permanent changes are not supported on macOS
+    None
+}
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub fn get_permanent_override(interface: &str) -> Option<String> {
+    let content = fs::read_to_string(bsd::override_path(interface)).ok()?;
+    bsd::find_override(&content, interface)
+}
+
+/// Every permanent override for `interface`, as (where it lives, MAC).
+#[cfg(target_os = "linux")]
+pub fn list_permanent_overrides(interface: &str) -> Vec<(String, String)> {
+    permanence::find_overrides(interface)
+        .into_iter()
+        .map(|found| (format!("{} {}", found.backend.describe(), found.location), found.mac))
+        .collect()
+}
+
+#[cfg(target_os = "windows")]
+pub fn list_permanent_overrides(interface: &str) -> Vec<(String, String)> {
+    let Some(mac) = get_permanent_override(interface) else {
+        return Vec::new();
+    };
+    let location = win32::find_adapter(interface)
+        .and_then(|adapter| win32::NetDevice::open(&adapter.guid))
+        .map(|device| format!("{}\\NetworkAddress", device.registry_path()))
+        .unwrap_or_else(|_| "NetworkAddress registry value".into());
+    vec![(location, mac)]
+}
+
+#[cfg(target_os = "macos")]
+pub fn list_permanent_overrides(_interface: &str) -> Vec<(String, String)> {
+    Vec::new()
+}
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub fn list_permanent_overrides(interface: &str) -> Vec<(String, String)> {
+    get_permanent_override(interface)
+        .map(|mac| vec![(bsd::override_path(interface).display().to_string(), mac)])
+        .unwrap_or_default()
+}
+
+/// Remove the permanent override for `interface`, if there is one, and
+/// describe what was removed.
+#[cfg(target_os = "linux")]
+pub fn remove_permanent_override(interface: &str) -> Result<Option<String>, MacError> {
+    let removed = permanence::remove_overrides(interface)?;
+    Ok((!removed.is_empty()).then(|| removed.join("; ")))
+}
+
+#[cfg(target_os = "windows")]
+pub fn remove_permanent_override(interface: &str) -> Result<Option<String>, MacError> {
+    if get_permanent_override(interface).is_none() {
+        return Ok(None);
+    }
+
+    let adapter = win32::find_adapter(interface)?;
+    let device = win32::NetDevice::open(&adapter.guid)?;
+    device.registry_key()?.delete_value("NetworkAddress")?;
+
+    // The driver only reads NetworkAddress when it starts
+    device.set_enabled(false)?;
+    device.set_enabled(true)?;
+
+    Ok(Some(format!("{}\\NetworkAddress", device.registry_path())))
+}
+
+#[cfg(target_os = "macos")]
+pub fn remove_permanent_override(_interface: &str) -> Result<Option<String>, MacError> {
+    Ok(None)
+}
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub fn remove_permanent_override(interface: &str) -> Result<Option<String>, MacError> {
+    if get_permanent_override(interface).is_none() {
+        return Ok(None);
     }
 
-    Ok(apps)
+    let path = bsd::override_path(interface);
+    let content = bsd::without_override(&fs::read_to_string(&path)?, interface);
+    if content.trim().is_empty() {
+        fs::remove_file(&path)?;
+    } else {
+        fs::write(&path, content)?;
+    }
+    Ok(Some(path.display().to_string()))
 }
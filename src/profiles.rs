@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use chrono::{DateTime, Local, Utc};
+use crate::error::MacError;
+use crate::logger::MacChange;
+use crate::mac::{generate_random_mac, normalize_prefix, MacAddress, RandomPolicy};
+
+/// How often a profile without a fixed MAC mints a new random address.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Regenerate {
+    /// A new MAC every time the profile is applied
+    #[default]
+    PerConnect,
+    /// Reuse the same MAC until the local date changes
+    Daily,
+    /// Generate once and keep reusing it
+    Never,
+}
+
+impl fmt::Display for Regenerate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Regenerate::PerConnect => write!(f, "per-connect"),
+            Regenerate::Daily => write!(f, "daily"),
+            Regenerate::Never => write!(f, "never"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    pub interface: String,
+    pub mac_address: Option<MacAddress>, // Fixed MAC, or None to generate one on apply
+    pub vendor: Option<String>,          // Vendor prefix for generated MACs
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub regenerate: Regenerate,
+    pub created: DateTime<Utc>,
+}
+
+impl Profile {
+    /// The random MAC this profile applied last, if its regeneration cadence
+    /// says it should be used again. Decided from the change history so the
+    /// answer survives restarts and matches what `--history` shows.
+    pub fn reusable_mac<'a>(&self, history: &'a [MacChange]) -> Option<&'a MacAddress> {
+        if self.mac_address.is_some() || self.regenerate == Regenerate::PerConnect {
+            return None;
+        }
+
+        let previous = history.iter().rev().find(|change| {
+            change.profile.as_deref() == Some(self.name.as_str()) && change.interface == self.interface
+        })?;
+
+        // A changed vendor prefix invalidates the old address
+        if let Some(vendor) = &self.vendor
+            && previous.new_mac.oui() != normalize_prefix(vendor)
+        {
+            return None;
+        }
+
+        let still_valid = match self.regenerate {
+            Regenerate::Daily => {
+                previous.timestamp.with_timezone(&Local).date_naive() == Local::now().date_naive()
+            }
+            Regenerate::Never => true,
+            Regenerate::PerConnect => false,
+        };
+
+        still_valid.then_some(&previous.new_mac)
+    }
+
+    /// The MAC this profile applies: the saved address, the previous random
+    /// one if the cadence allows, or a fresh random one (with the profile's
+    /// vendor prefix, if any).
+    pub fn resolve_mac(&self, history: &[MacChange]) -> Result<MacAddress, MacError> {
+        if let Some(mac) = &self.mac_address {
+            return Ok(mac.clone());
+        }
+
+        match self.reusable_mac(history) {
+            Some(mac) => Ok(mac.clone()),
+            None => generate_random_mac(&RandomPolicy::from_vendor(self.vendor.as_deref())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ProfileManager {
+    profiles: HashMap<String, Profile>,
+    config_path: PathBuf,
+}
+
+impl ProfileManager {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        let config_path = dirs::config_dir()
+            .ok_or("Could not find config directory")?
+            .join("mac_changer")
+            .join("profiles.json");
+
+        let mut manager = Self {
+            profiles: HashMap::new(),
+            config_path,
+        };
+
+        manager.load_profiles()?;
+        Ok(manager)
+    }
+
+    fn load_profiles(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.config_path.exists() {
+            let content = fs::read_to_string(&self.config_path)?;
+            self.profiles = serde_json::from_str(&content)?;
+        }
+        Ok(())
+    }
+
+    fn save_profiles(&self) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = self.config_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(&self.profiles)?;
+        fs::write(&self.config_path, content)?;
+        Ok(())
+    }
+
+    pub fn save_profile(&mut self, profile: Profile) -> Result<(), Box<dyn Error>> {
+        self.profiles.insert(profile.name.clone(), profile);
+        self.save_profiles()
+    }
+
+    pub fn remove_profile(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        if self.profiles.remove(name).is_none() {
+            return Err(Box::new(MacError::ValidationFailed(
+                format!("No profile named {}", name)
+            )));
+        }
+        self.save_profiles()
+    }
+
+    pub fn get_profile(&self, name: &str) -> Result<&Profile, MacError> {
+        self.profiles.get(name)
+            .ok_or_else(|| MacError::ValidationFailed(format!("No profile named {}", name)))
+    }
+
+    pub fn list_profiles(&self) -> Vec<&Profile> {
+        let mut profiles: Vec<&Profile> = self.profiles.values().collect();
+        profiles.sort_by(|a, b| a.name.cmp(&b.name));
+        profiles
+    }
+
+    /// Serialize one profile, or all of them, in the same format as the
+    /// profiles file so an export can be dropped into another machine.
+    pub fn export(&self, name: Option<&str>) -> Result<String, Box<dyn Error>> {
+        let selected: HashMap<&String, &Profile> = match name {
+            Some(name) => {
+                let profile = self.get_profile(name)?;
+                HashMap::from([(&profile.name, profile)])
+            }
+            None => self.profiles.iter().collect(),
+        };
+
+        Ok(serde_json::to_string_pretty(&selected)?)
+    }
+}
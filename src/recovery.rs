@@ -0,0 +1,169 @@
+// src/recovery.rs
+use chrono::Utc;
+use std::collections::BTreeSet;
+use std::error::Error;
+use crate::backend;
+use crate::config::{list_original_macs, MacConfig};
+use crate::error::MacError;
+use crate::hooks::Trigger;
+use crate::logger::{MacChange, MacLogger};
+use crate::mac::MacAddress;
+use crate::platform::{change_mac_anytime, list_permanent_overrides, remove_permanent_override};
+use crate::rules::RuleManager;
+
+/// Everything `panic-restore` did, and everything it could not do.
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    pub restored: Vec<(String, MacAddress, MacAddress)>, // interface, from, to
+    pub already_original: Vec<String>,
+    pub artifacts_removed: Vec<String>,
+    pub rules_disabled: usize,
+    pub errors: Vec<String>,
+    pub dry_run: bool, // Lists what would be done; nothing was
+}
+
+impl RestoreReport {
+    pub fn print(&self) {
+        println!();
+        if self.dry_run {
+            println!("Panic restore plan (dry run, nothing was changed)");
+        } else {
+            println!("Panic restore report");
+        }
+        let (restored, removed, disabled) = if self.dry_run {
+            ("Would restore:", "Would remove: ", "would be disabled")
+        } else {
+            ("Restored:     ", "Removed:      ", "disabled")
+        };
+
+        for (interface, from, to) in &self.restored {
+            println!("  {} {} {} -> {}", restored, interface, from, to);
+        }
+        for interface in &self.already_original {
+            println!("  Unchanged:     {} already has its original MAC", interface);
+        }
+        for artifact in &self.artifacts_removed {
+            println!("  {} {}", removed, artifact);
+        }
+        println!("  Rules:         {} {}", self.rules_disabled, disabled);
+        for error in &self.errors {
+            println!("  Error:         {}", error);
+        }
+
+        if self.errors.is_empty() && !self.dry_run {
+            println!("Machine is back to stock. Restart a running daemon so it drops its state.");
+        }
+    }
+}
+
+/// Overrides can exist on interfaces we never saved an original for, so
+/// every interface is checked along with those that have one.
+fn affected_interfaces(originals: &[MacConfig]) -> BTreeSet<String> {
+    backend::current()
+        .list_interfaces()
+        .unwrap_or_default()
+        .into_iter()
+        .chain(originals.iter().map(|config| config.interface.clone()))
+        .collect()
+}
+
+/// What `panic_restore` would do, without changing anything.
+pub fn plan_panic_restore() -> Result<RestoreReport, Box<dyn Error>> {
+    let mut report = RestoreReport { dry_run: true, ..Default::default() };
+
+    match RuleManager::new() {
+        Ok(rules) => report.rules_disabled = rules.list_rules().iter().filter(|rule| rule.enabled).count(),
+        Err(e) => report.errors.push(format!("Could not read rules: {}", e)),
+    }
+
+    let originals = list_original_macs()?;
+
+    for interface in &affected_interfaces(&originals) {
+        for (location, mac) in list_permanent_overrides(interface) {
+            report.artifacts_removed.push(format!("{} ({})", location, mac));
+        }
+    }
+
+    for config in &originals {
+        match backend::current().get_mac(&config.interface) {
+            Ok(current) if current == config.original_mac => {
+                report.already_original.push(config.interface.clone());
+            }
+            Ok(current) => report.restored.push((config.interface.clone(), current, config.original_mac.clone())),
+            Err(e) => report.errors.push(format!("{}: {}", config.interface, e)),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Put every interface back to its original MAC, remove all persistence
+/// artifacts and switch off rules. Keeps going past individual failures so
+/// one broken interface does not leave the rest spoofed.
+pub fn panic_restore(logger: &MacLogger) -> Result<RestoreReport, Box<dyn Error>> {
+    let mut report = RestoreReport::default();
+
+    // Rules first, so a running daemon does not re-spoof what we restore
+    match RuleManager::new().and_then(|mut rules| rules.disable_all()) {
+        Ok(disabled) => report.rules_disabled = disabled,
+        Err(e) => report.errors.push(format!("Could not disable rules: {}", e)),
+    }
+
+    let originals = list_original_macs()?;
+
+    for interface in &affected_interfaces(&originals) {
+        match remove_permanent_override(interface) {
+            Ok(Some(artifact)) => report.artifacts_removed.push(artifact),
+            Ok(None) => {}
+            Err(e) => report.errors.push(format!("Could not remove override for {}: {}", interface, e)),
+        }
+    }
+
+    for config in &originals {
+        let current = match backend::current().get_mac(&config.interface) {
+            Ok(mac) => mac,
+            Err(e) => {
+                report.errors.push(format!("{}: {}", config.interface, e));
+                continue;
+            }
+        };
+
+        if current == config.original_mac {
+            report.already_original.push(config.interface.clone());
+            continue;
+        }
+
+        println!("Restoring {} to {}...", config.interface, config.original_mac);
+        // An emergency; change windows don't apply
+        match change_mac_anytime(&config.interface, &config.original_mac, false, Trigger::Manual).map(|mut transaction| transaction.run_hooks()) {
+            Ok(_) => {
+                logger.log_change(MacChange {
+                    timestamp: Utc::now(),
+                    interface: config.interface.clone(),
+                    old_mac: current.clone(),
+                    new_mac: config.original_mac.clone(),
+                    geo_location: None,
+                    permanent: false,
+                    profile: None,
+                    trigger: Some(Trigger::Manual),
+                    hostname: None,
+                    dhcp_client_id: None,
+                    principal: None,
+                })?;
+                report.restored.push((config.interface.clone(), current, config.original_mac.clone()));
+            }
+            Err(e) => report.errors.push(format!("Could not restore {}: {}", config.interface, e)),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Fail the command when anything could not be undone, so scripts notice.
+pub fn check(report: &RestoreReport) -> Result<(), MacError> {
+    if report.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(MacError::SystemError(format!("Panic restore finished with {} error(s)", report.errors.len())))
+    }
+}
@@ -0,0 +1,239 @@
+// src/renew.rs
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::process::Command;
+use std::time::{Duration, Instant};
+use crate::capabilities::find_tool;
+use crate::error::MacError;
+use crate::network::{get_ipv4_addresses, get_link_state};
+
+/// How long to wait for carrier and an address after renewing.
+const RENEW_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// What was done to get an interface back online after a MAC change.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Renewal {
+    pub dhcp_client: Option<String>, // The client that renewed the lease
+    pub neighbors_flushed: bool,
+    pub carrier: bool,
+    pub addresses: Vec<String>,
+    pub notes: Vec<String>,
+}
+
+/// Release and renew the DHCP lease of `interface`, flush its neighbor
+/// cache, then wait for carrier and an IPv4 address. Nothing here fails the
+/// change; problems end up in `notes`.
+pub fn renew(interface: &str) -> Renewal {
+    let mut renewal = Renewal::default();
+
+    if !get_link_state(interface).is_some_and(|state| state.up) {
+        renewal.notes.push(format!("{} is down, skipped renewing its lease", interface));
+        return renewal;
+    }
+
+    match flush_neighbors(interface) {
+        Ok(()) => renewal.neighbors_flushed = true,
+        Err(e) => renewal.notes.push(format!("Could not flush neighbors: {}", e.to_string().trim())),
+    }
+
+    // Clients on the host would be matched by name, not the one inside
+    match crate::netns::current() {
+        Some(netns) => renewal.notes.push(format!("Left the DHCP lease to the client in network namespace {}", netns)),
+        None => renewal.dhcp_client = renew_lease(interface, &mut renewal.notes),
+    }
+
+    let started = Instant::now();
+    loop {
+        renewal.carrier = get_link_state(interface).is_some_and(|state| state.carrier);
+        renewal.addresses = get_ipv4_addresses(interface);
+        if renewal.carrier && !renewal.addresses.is_empty() {
+            break;
+        }
+        if started.elapsed() > RENEW_TIMEOUT {
+            let missing = if renewal.carrier { "an IPv4 address" } else { "carrier" };
+            renewal.notes.push(format!("No {} on {} after {:?}", missing, interface, RENEW_TIMEOUT));
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+
+    renewal
+}
+
+fn run(tool: &str, args: &[&str]) -> Result<(), Box<dyn Error>> {
+    let path = find_tool(tool).ok_or_else(|| MacError::SystemError(format!("{} is not installed", tool)))?;
+    let output = Command::new(path).args(args).output()?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = if stderr.trim().is_empty() {
+            format!("{} exited with {}", tool, output.status)
+        } else {
+            stderr.trim().to_string()
+        };
+        return Err(Box::new(MacError::SystemError(message)));
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn flush_neighbors(interface: &str) -> Result<(), Box<dyn Error>> {
+    run("ip", &["neigh", "flush", "dev", interface])
+}
+
+/// Renew through whichever client already manages the interface. Hosts
+/// with a static address have none, and are left alone rather than being
+/// switched to DHCP.
+#[cfg(target_os = "linux")]
+fn renew_lease(interface: &str, notes: &mut Vec<String>) -> Option<String> {
+    let mut attempts: Vec<(&str, Vec<Vec<&str>>)> = Vec::new();
+
+    if crate::networkmanager::device(interface).is_some_and(|device| device.is_active()) {
+        attempts.push(("NetworkManager", vec![vec!["nmcli", "device", "connect", interface]]));
+    }
+    if networkd_manages(interface) {
+        attempts.push(("systemd-networkd", vec![vec!["networkctl", "renew", interface]]));
+    }
+    if client_running("dhcpcd", interface) {
+        attempts.push(("dhcpcd", vec![vec!["dhcpcd", "-n", interface]]));
+    }
+    if client_running("dhclient", interface) {
+        attempts.push((
+            "dhclient",
+            vec![vec!["dhclient", "-r", interface], vec!["dhclient", "-nw", interface]],
+        ));
+    }
+
+    if attempts.is_empty() {
+        notes.push(format!("No DHCP client manages {}, kept its addresses as they are", interface));
+        return None;
+    }
+
+    for (client, commands) in attempts {
+        match commands.iter().try_for_each(|command| run(command[0], &command[1..])) {
+            Ok(()) => return Some(client.to_string()),
+            Err(e) => notes.push(format!("{} could not renew: {}", client, e.to_string().trim())),
+        }
+    }
+
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn networkd_manages(interface: &str) -> bool {
+    let Some(networkctl) = find_tool("networkctl") else {
+        return false;
+    };
+
+    Command::new(networkctl)
+        .args(["status", "--no-pager", interface])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .is_some_and(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .any(|line| line.trim().starts_with("State:") && line.contains("(configured"))
+        })
+}
+
+/// A `client` process runs for `interface`, or for every interface when
+/// started without naming any.
+#[cfg(target_os = "linux")]
+fn client_running(client: &str, interface: &str) -> bool {
+    let Ok(processes) = std::fs::read_dir("/proc") else {
+        return false;
+    };
+
+    processes.flatten().any(|process| {
+        let Ok(cmdline) = std::fs::read(process.path().join("cmdline")) else {
+            return false;
+        };
+        let mut args = cmdline
+            .split(|&b| b == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).into_owned());
+
+        let is_client = args
+            .next()
+            .is_some_and(|program| program.rsplit('/').next() == Some(client));
+        let args: Vec<String> = args.collect();
+        is_client && (args.iter().any(|arg| arg == interface) || args.iter().all(|arg| arg.starts_with('-')))
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn flush_neighbors(interface: &str) -> Result<(), Box<dyn Error>> {
+    run("arp", &["-d", "-i", interface, "-a"])
+}
+
+#[cfg(target_os = "macos")]
+fn renew_lease(interface: &str, notes: &mut Vec<String>) -> Option<String> {
+    match run("ipconfig", &["set", interface, "DHCP"]) {
+        Ok(()) => Some("configd".into()),
+        Err(e) => {
+            notes.push(format!("Could not renew the lease: {}", e.to_string().trim()));
+            None
+        }
+    }
+}
+
+#[cfg(target_os = "freebsd")]
+fn flush_neighbors(interface: &str) -> Result<(), Box<dyn Error>> {
+    run("arp", &["-d", "-i", interface, "-a"])
+}
+
+// OpenBSD's arp cannot limit -a to one interface
+#[cfg(target_os = "openbsd")]
+fn flush_neighbors(_interface: &str) -> Result<(), Box<dyn Error>> {
+    run("arp", &["-d", "-a"])
+}
+
+/// rc.d/dhclient only starts a client for interfaces rc.conf configures
+/// with DHCP, so static addresses are left alone.
+#[cfg(target_os = "freebsd")]
+fn renew_lease(interface: &str, notes: &mut Vec<String>) -> Option<String> {
+    match run("service", &["dhclient", "restart", interface]) {
+        Ok(()) => Some("dhclient".into()),
+        Err(e) => {
+            notes.push(format!("Could not renew the lease: {}", e.to_string().trim()));
+            None
+        }
+    }
+}
+
+/// dhcpleased only answers for interfaces with `inet autoconf`.
+#[cfg(target_os = "openbsd")]
+fn renew_lease(interface: &str, notes: &mut Vec<String>) -> Option<String> {
+    match run("dhcpleasectl", &["send", "request", interface]) {
+        Ok(()) => Some("dhcpleased".into()),
+        Err(e) => {
+            notes.push(format!("Could not renew the lease: {}", e.to_string().trim()));
+            None
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn flush_neighbors(interface: &str) -> Result<(), Box<dyn Error>> {
+    let adapter = crate::win32::find_adapter(interface)?;
+    run("netsh", &["interface", "ip", "delete", "arpcache", &adapter.name])
+}
+
+#[cfg(target_os = "windows")]
+fn renew_lease(interface: &str, notes: &mut Vec<String>) -> Option<String> {
+    let renewed = crate::win32::find_adapter(interface).and_then(|adapter| {
+        // Release fails on adapters without a lease; renew says why if it matters
+        let _ = run("ipconfig", &["/release", &adapter.name]);
+        run("ipconfig", &["/renew", &adapter.name])
+    });
+
+    match renewed {
+        Ok(()) => Some("ipconfig".into()),
+        Err(e) => {
+            notes.push(format!("Could not renew the lease: {}", e.to_string().trim()));
+            None
+        }
+    }
+}
@@ -1,34 +1,584 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
 use std::error::Error;
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::fs;
 use std::path::PathBuf;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveTime, Timelike, Utc};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc::UnboundedSender;
+use crate::apps::RunningApp;
+use crate::error::MacError;
+use crate::logger::MacChange;
+use crate::mac::MacAddress;
+use crate::network::{InterfaceClass, WirelessConnection};
+use crate::profiles::ProfileManager;
+
+/// Rule interface that matches every interface, usually narrowed down by a
+/// `class` condition.
+pub const ANY_INTERFACE: &str = "*";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppRule {
     pub app_name: String,
+    #[serde(default)]
+    pub app_field: AppField, // What part of a process app_name is compared with
+    #[serde(default)]
+    pub app_match: AppMatch, // How it is compared
     pub service_name: Option<String>,
-    pub mac_address: String,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub mac_address: Option<MacAddress>, // None for profile and exempt rules
     pub interface: String,
+    #[serde(default)]
+    pub profile: Option<String>, // Apply this profile instead of mac_address
+    #[serde(default)]
+    pub network: Option<NetworkCondition>, // Only match while joined to this network
+    #[serde(default)]
+    pub class: Option<ClassCondition>, // Only match interfaces of this kind
+    #[serde(default)]
+    pub exempt: bool, // Leave matching interfaces alone, overriding other rules
     pub schedule: Option<Schedule>,
     pub last_applied: Option<DateTime<Utc>>,
     pub enabled: bool,
 }
 
+/// Rule files written before MACs were typed hold "" where there is none.
+fn empty_as_none<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<MacAddress>, D::Error> {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(mac) if !mac.trim().is_empty() => crate::mac::normalize(&mac).map(Some).map_err(serde::de::Error::custom),
+        _ => Ok(None),
+    }
+}
+
+impl AppRule {
+    /// Check what a user wrote before the rule is saved: its pattern and
+    /// profile.
+    pub fn validate(&mut self) -> Result<(), Box<dyn Error>> {
+        if !self.app_name.is_empty() {
+            AppMatcher::new(&self.app_name, self.app_match)?;
+        }
+        if let Some(profile) = &self.profile {
+            ProfileManager::new()?.get_profile(profile)?;
+        }
+        Ok(())
+    }
+
+    /// The MAC this rule applies, resolving its profile if it names one.
+    pub fn resolve_mac(&self, history: &[MacChange]) -> Result<MacAddress, Box<dyn Error>> {
+        match (&self.profile, &self.mac_address) {
+            (Some(name), _) => {
+                let profiles = ProfileManager::new()?;
+                Ok(profiles.get_profile(name)?.resolve_mac(history)?)
+            }
+            (None, Some(mac)) => Ok(mac.clone()),
+            (None, None) => Err(Box::new(MacError::ValidationFailed("The rule has neither a MAC nor a profile".into()))),
+        }
+    }
+
+    /// Whether the rule's application is among `apps`. Rules without one
+    /// match regardless; a bad regular expression never matches.
+    pub fn app_running(&self, apps: &[RunningApp]) -> bool {
+        if self.app_name.is_empty() {
+            return true;
+        }
+        let Ok(matcher) = AppMatcher::new(&self.app_name, self.app_match) else {
+            return false;
+        };
+
+        apps.iter().any(|app| match self.app_field {
+            AppField::Name => app.names().any(|name| matcher.matches(name)),
+            AppField::Path => app.path.as_ref().is_some_and(|path| matcher.matches(&path.to_string_lossy())),
+            AppField::Title => app.title.as_deref().is_some_and(|title| matcher.matches(title)),
+        })
+    }
+
+    /// The name a rule is saved under when none is given: "app:interface",
+    /// or just the interface for rules without an application.
+    pub fn default_name(&self) -> String {
+        if self.app_name.is_empty() {
+            self.interface.clone()
+        } else {
+            format!("{}:{}", self.app_name, self.interface)
+        }
+    }
+
+    /// What the rule does when it matches, for listings.
+    pub fn describe_action(&self) -> String {
+        if self.exempt {
+            "leave alone".into()
+        } else if let Some(profile) = &self.profile {
+            format!("profile {}", profile)
+        } else {
+            format!("mac {}", self.mac_address.as_ref().map(|mac| mac.to_string()).unwrap_or_default())
+        }
+    }
+
+    /// When the rule matches, apart from its schedule, for listings.
+    pub fn describe_conditions(&self) -> String {
+        let mut conditions = Vec::new();
+        if !self.app_name.is_empty() {
+            let field = match self.app_field {
+                AppField::Name => "app",
+                AppField::Path => "app path",
+                AppField::Title => "window title",
+            };
+            match self.app_match {
+                AppMatch::Exact => conditions.push(format!("{} {}", field, self.app_name)),
+                AppMatch::Contains => conditions.push(format!("{} containing {}", field, self.app_name)),
+                AppMatch::Regex => conditions.push(format!("{} matching /{}/", field, self.app_name)),
+            }
+        }
+        match &self.network {
+            Some(NetworkCondition::Ssid(ssid)) => conditions.push(format!("ssid {}", ssid)),
+            Some(NetworkCondition::Bssid(bssid)) => conditions.push(format!("bssid {}", bssid)),
+            None => {}
+        }
+        if let Some(class) = &self.class {
+            match class.medium {
+                Some(Medium::Wired) => conditions.push("wired".into()),
+                Some(Medium::Wireless) => conditions.push("wireless".into()),
+                None => {}
+            }
+            if let Some(speed) = class.min_speed_mbps {
+                conditions.push(format!(">= {} Mbps", speed));
+            }
+            match class.dock {
+                Some(true) => conditions.push("docked".into()),
+                Some(false) => conditions.push("not docked".into()),
+                None => {}
+            }
+        }
+
+        if conditions.is_empty() { "always".into() } else { conditions.join(", ") }
+    }
+}
+
+/// The part of a running process a rule's application is looked for in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AppField {
+    /// The process name or the executable's file name
+    #[default]
+    Name,
+    /// The executable's full path
+    Path,
+    /// The title of one of its windows
+    Title,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AppMatch {
+    #[default]
+    Exact,
+    /// Anywhere in it, ignoring case
+    Contains,
+    /// A regular expression, found anywhere unless anchored
+    Regex,
+}
+
+/// An application pattern, compiled once for checking every process.
+pub enum AppMatcher<'a> {
+    Exact(&'a str),
+    Contains(String),
+    Regex(regex::Regex),
+}
+
+impl<'a> AppMatcher<'a> {
+    pub fn new(pattern: &'a str, how: AppMatch) -> Result<Self, MacError> {
+        Ok(match how {
+            AppMatch::Exact => AppMatcher::Exact(pattern),
+            AppMatch::Contains => AppMatcher::Contains(pattern.to_lowercase()),
+            AppMatch::Regex => AppMatcher::Regex(regex::Regex::new(pattern).map_err(|e| {
+                MacError::ValidationFailed(format!("Invalid regular expression '{}': {}", pattern, e))
+            })?),
+        })
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        match self {
+            AppMatcher::Exact(pattern) => text == *pattern,
+            AppMatcher::Contains(pattern) => text.to_lowercase().contains(pattern.as_str()),
+            AppMatcher::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+/// Wi-Fi network a rule is tied to. SSIDs match exactly, BSSIDs by address.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkCondition {
+    Ssid(String),
+    Bssid(MacAddress),
+}
+
+impl NetworkCondition {
+    pub fn matches(&self, connection: Option<&WirelessConnection>) -> bool {
+        let Some(connection) = connection else {
+            return false;
+        };
+
+        match self {
+            NetworkCondition::Ssid(ssid) => connection.ssid.as_deref() == Some(ssid.as_str()),
+            NetworkCondition::Bssid(bssid) => connection.bssid.as_ref() == Some(bssid),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Medium {
+    Wired,
+    Wireless,
+}
+
+/// The kind of interface a rule applies to. Unset fields match anything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClassCondition {
+    #[serde(default)]
+    pub medium: Option<Medium>,
+    #[serde(default)]
+    pub min_speed_mbps: Option<u64>, // Interfaces with an unknown speed never qualify
+    #[serde(default)]
+    pub dock: Option<bool>,
+}
+
+impl ClassCondition {
+    pub fn matches(&self, class: &InterfaceClass) -> bool {
+        let medium = if class.wireless { Medium::Wireless } else { Medium::Wired };
+
+        self.medium.is_none_or(|wanted| wanted == medium)
+            && self.min_speed_mbps.is_none_or(|min| class.speed_mbps.is_some_and(|speed| speed >= min))
+            && self.dock.is_none_or(|dock| dock == class.docked)
+    }
+}
+
+/// When a rule may match: time windows on selected days, a cron expression,
+/// or both. The schedule is active while any of them is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "RawSchedule")]
 pub struct Schedule {
-    pub days: Vec<String>,  // "monday", "tuesday", etc.
-    pub start_time: String, // "HH:MM"
-    pub end_time: String,   // "HH:MM"
+    pub days: Vec<String>, // "monday", "tuesday", etc.
+    #[serde(default)]
+    pub windows: Vec<TimeWindow>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cron: Option<String>, // Five fields: minute hour day-of-month month day-of-week
+}
+
+/// A daily window. One that ends before it starts runs overnight into the
+/// next day, e.g. 22:00-06:00 on "friday" lasts until Saturday morning.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeWindow {
+    pub start: String, // "HH:MM"
+    pub end: String,   // "HH:MM", exclusive
+}
+
+/// Schedules as written by older versions, with a single start/end pair.
+#[derive(Deserialize)]
+struct RawSchedule {
+    #[serde(default)]
+    days: Vec<String>,
+    #[serde(default)]
+    windows: Vec<TimeWindow>,
+    #[serde(default)]
+    cron: Option<String>,
+    start_time: Option<String>,
+    end_time: Option<String>,
+}
+
+impl From<RawSchedule> for Schedule {
+    fn from(raw: RawSchedule) -> Self {
+        let mut windows = raw.windows;
+        if let (Some(start), Some(end)) = (raw.start_time, raw.end_time) {
+            windows.insert(0, TimeWindow { start, end });
+        }
+        Self { days: raw.days, windows, cron: raw.cron }
+    }
+}
+
+const DAYS: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+
+/// Index into DAYS of a full or three-letter day name.
+fn parse_day(name: &str) -> Result<usize, MacError> {
+    let name = name.trim().to_lowercase();
+    DAYS.iter()
+        .position(|day| *day == name || (name.len() == 3 && day.starts_with(&name)))
+        .ok_or_else(|| MacError::ValidationFailed(format!("Unknown day '{}' in schedule", name)))
 }
 
+fn parse_time(time: &str) -> Result<NaiveTime, MacError> {
+    NaiveTime::parse_from_str(time.trim(), "%H:%M")
+        .map_err(|_| MacError::ValidationFailed(format!("Invalid time '{}' in schedule, use HH:MM", time.trim())))
+}
+
+/// How schedule transitions are shown, e.g. "Mon Oct 19 09:00".
+pub const TIME_FORMAT: &str = "%a %b %-d %H:%M";
+
+/// How far ahead transitions are searched. Windows repeat weekly; cron
+/// expressions can name a single day of the year.
+const WINDOW_HORIZON_DAYS: i64 = 8;
+const CRON_HORIZON_DAYS: i64 = 366;
+
+impl Schedule {
+    /// Parse "DAYS:WINDOWS", where DAYS is a comma-separated list of day
+    /// names or ranges and WINDOWS a comma-separated list of HH:MM-HH:MM,
+    /// e.g. "mon-fri:09:00-12:00,13:00-17:00" or "fri,sat:22:00-06:00".
+    pub fn parse(spec: &str) -> Result<Self, MacError> {
+        let invalid = || MacError::ValidationFailed(format!("Invalid schedule '{}', use DAYS:HH:MM-HH:MM", spec));
+        let (days, times) = spec.split_once(':').ok_or_else(invalid)?;
+
+        let mut selected = [false; 7];
+        for part in days.split(',') {
+            match part.split_once('-') {
+                Some((first, last)) => {
+                    let (first, last) = (parse_day(first)?, parse_day(last)?);
+                    // Ranges may wrap around the weekend, e.g. "fri-mon"
+                    let length = (last + 7 - first) % 7;
+                    for offset in 0..=length {
+                        selected[(first + offset) % 7] = true;
+                    }
+                }
+                None => selected[parse_day(part)?] = true,
+            }
+        }
+
+        let mut windows = Vec::new();
+        for window in times.split(',') {
+            let (start, end) = window.split_once('-').ok_or_else(invalid)?;
+            let (start_time, end_time) = (parse_time(start)?, parse_time(end)?);
+            if start_time == end_time {
+                return Err(MacError::ValidationFailed(format!(
+                    "Schedule window {} starts and ends at the same time", window.trim()
+                )));
+            }
+            windows.push(TimeWindow {
+                start: start_time.format("%H:%M").to_string(),
+                end: end_time.format("%H:%M").to_string(),
+            });
+        }
+
+        Ok(Self {
+            days: DAYS.iter().zip(selected).filter(|(_, on)| *on).map(|(day, _)| day.to_string()).collect(),
+            windows,
+            cron: None,
+        })
+    }
+
+    /// A schedule that is active during every minute matching a standard
+    /// five-field cron expression, e.g. "*/15 9-17 * * mon-fri".
+    pub fn from_cron(expr: &str) -> Result<Self, MacError> {
+        CronExpr::parse(expr)?;
+        Ok(Self {
+            days: Vec::new(),
+            windows: Vec::new(),
+            cron: Some(expr.split_whitespace().collect::<Vec<_>>().join(" ")),
+        })
+    }
+
+    pub fn is_active_at(&self, at: DateTime<Local>) -> bool {
+        self.matcher().is_active_at(&at)
+    }
+
+    /// The next time after `after` at which the schedule switches on, or
+    /// None if it never does within the search horizon.
+    pub fn next_activation(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        self.matcher().next_change(after, Some(true))
+    }
+
+    /// The next time after `after` at which the schedule switches on or off.
+    pub fn next_transition(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        self.matcher().next_change(after, None)
+    }
+
+    /// Parse the stored strings once. Hand-edited rules can hold bad times
+    /// or cron expressions; those parts never match.
+    fn matcher(&self) -> Matcher {
+        let mut days = [false; 7];
+        for day in &self.days {
+            if let Ok(index) = parse_day(day) {
+                days[index] = true;
+            }
+        }
+
+        Matcher {
+            days,
+            windows: self.windows
+                .iter()
+                .filter_map(|window| Some((parse_time(&window.start).ok()?, parse_time(&window.end).ok()?)))
+                .collect(),
+            cron: self.cron.as_deref().and_then(|expr| CronExpr::parse(expr).ok()),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        if !self.windows.is_empty() {
+            let days: Vec<&str> = self.days.iter().map(|day| day.get(..3).unwrap_or(day)).collect();
+            let windows: Vec<String> = self.windows.iter().map(|window| format!("{}-{}", window.start, window.end)).collect();
+            parts.push(format!("{} {}", days.join(","), windows.join(",")));
+        }
+        if let Some(cron) = &self.cron {
+            parts.push(format!("cron {}", cron));
+        }
+        write!(f, "{}", parts.join(" or "))
+    }
+}
+
+struct Matcher {
+    days: [bool; 7], // Indexed like DAYS
+    windows: Vec<(NaiveTime, NaiveTime)>,
+    cron: Option<CronExpr>,
+}
+
+impl Matcher {
+    fn is_active_at(&self, at: &DateTime<Local>) -> bool {
+        let today = at.weekday().num_days_from_monday() as usize;
+        let yesterday = (today + 6) % 7;
+        let time = at.time();
+
+        let in_window = self.windows.iter().any(|&(start, end)| {
+            if start < end {
+                self.days[today] && time >= start && time < end
+            } else {
+                // Overnight: the evening belongs to today, the morning to yesterday's window
+                (self.days[today] && time >= start) || (self.days[yesterday] && time < end)
+            }
+        });
+
+        in_window || self.cron.as_ref().is_some_and(|cron| cron.matches(at))
+    }
+
+    /// Walk forward minute by minute, since every window edge and cron
+    /// match falls on a whole minute. `to` picks the direction; None finds
+    /// either.
+    fn next_change(&self, after: DateTime<Local>, to: Option<bool>) -> Option<DateTime<Local>> {
+        let horizon = if self.cron.is_some() { CRON_HORIZON_DAYS } else { WINDOW_HORIZON_DAYS };
+        let mut active = self.is_active_at(&after);
+        let mut minute = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+
+        for _ in 0..horizon * 24 * 60 {
+            let now_active = self.is_active_at(&minute);
+            if now_active != active && to.is_none_or(|to| to == now_active) {
+                return Some(minute);
+            }
+            active = now_active;
+            minute += Duration::minutes(1);
+        }
+        None
+    }
+}
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A parsed cron expression, one bit per allowed value in each field.
+struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64, // Bit 0 is Sunday
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl CronExpr {
+    fn parse(expr: &str) -> Result<Self, MacError> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(MacError::ValidationFailed(format!(
+                "Invalid cron expression '{}', use five fields: minute hour day-of-month month day-of-week", expr.trim()
+            )));
+        };
+
+        let mut days_of_week = cron_field(day_of_week, 0, 7, &WEEKDAYS)?;
+        // Both 0 and 7 mean Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week |= 1;
+        }
+
+        Ok(Self {
+            minutes: cron_field(minute, 0, 59, &[])?,
+            hours: cron_field(hour, 0, 23, &[])?,
+            days_of_month: cron_field(day_of_month, 1, 31, &[])?,
+            months: cron_field(month, 1, 12, &MONTHS)?,
+            days_of_week,
+            any_day_of_month: day_of_month == "*",
+            any_day_of_week: day_of_week == "*",
+        })
+    }
+
+    fn matches(&self, at: &DateTime<Local>) -> bool {
+        let set = |mask: u64, value: u32| mask & (1 << value) != 0;
+        let day_of_month = set(self.days_of_month, at.day());
+        let day_of_week = set(self.days_of_week, at.weekday().num_days_from_sunday());
+
+        // As in cron, restricting both day fields matches days that satisfy either
+        let day = match (self.any_day_of_month, self.any_day_of_week) {
+            (true, true) => true,
+            (false, true) => day_of_month,
+            (true, false) => day_of_week,
+            (false, false) => day_of_month || day_of_week,
+        };
+
+        day && set(self.minutes, at.minute()) && set(self.hours, at.hour()) && set(self.months, at.month())
+    }
+}
+
+/// Parse one cron field: "*", values, "a-b" ranges and "/n" steps, joined
+/// by commas. `names` are accepted in place of numbers, starting at `min`.
+fn cron_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, MacError> {
+    let invalid = || MacError::ValidationFailed(format!("Invalid cron field '{}'", field));
+    let value = |text: &str| -> Result<u32, MacError> {
+        let text = text.to_lowercase();
+        let value = match names.iter().position(|name| *name == text) {
+            Some(index) => index as u32 + min,
+            None => text.parse().map_err(|_| invalid())?,
+        };
+        if (min..=max).contains(&value) { Ok(value) } else { Err(invalid()) }
+    };
+
+    let mut mask = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<usize>().ok().filter(|step| *step > 0).ok_or_else(invalid)?),
+            None => (item, 1),
+        };
+
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (value(first)?, value(last)?),
+            // "5/10" runs from 5 to the end of the field
+            None if step > 1 => (value(range)?, max),
+            None => (value(range)?, value(range)?),
+        };
+        if first > last {
+            return Err(invalid());
+        }
+
+        for value in (first..=last).step_by(step) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+/// Rules keyed by "app_name:interface".
+type Rules = HashMap<String, AppRule>;
+
 #[derive(Debug)]
 pub struct RuleManager {
-    rules: HashMap<String, AppRule>,
+    rules: Rules,
     config_path: PathBuf,
+    version: Option<u64>, // Hash of the file as last read or written; None if it didn't exist
 }
 
+/// How often a save is retried when another writer keeps changing the file.
+const SAVE_ATTEMPTS: usize = 5;
+
 impl RuleManager {
     pub fn new() -> Result<Self, Box<dyn Error>> {
         let config_path = dirs::config_dir()
@@ -39,6 +589,7 @@ impl RuleManager {
         let mut manager = Self {
             rules: HashMap::new(),
             config_path,
+            version: None,
         };
 
         manager.load_rules()?;
@@ -46,71 +597,233 @@ impl RuleManager {
     }
 
     fn load_rules(&mut self) -> Result<(), Box<dyn Error>> {
-        if self.config_path.exists() {
-            let content = fs::read_to_string(&self.config_path)?;
-            self.rules = serde_json::from_str(&content)?;
-        }
+        (self.rules, self.version) = self.read_rules()?;
         Ok(())
     }
 
-    fn save_rules(&self) -> Result<(), Box<dyn Error>> {
+    fn read_rules(&self) -> Result<(Rules, Option<u64>), Box<dyn Error>> {
+        if !self.config_path.exists() {
+            return Ok((HashMap::new(), None));
+        }
+
+        let content = fs::read_to_string(&self.config_path)?;
+        Ok((serde_json::from_str(&content)?, Some(content_version(&content))))
+    }
+
+    fn disk_version(&self) -> Result<Option<u64>, Box<dyn Error>> {
+        match fs::read_to_string(&self.config_path) {
+            Ok(content) => Ok(Some(content_version(&content))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Pick up changes made by hand or by another process. Returns whether
+    /// the rules changed.
+    pub fn reload(&mut self) -> Result<bool, Box<dyn Error>> {
+        let (rules, version) = self.read_rules()?;
+        if version == self.version {
+            return Ok(false);
+        }
+
+        self.rules = rules;
+        self.version = version;
+        Ok(true)
+    }
+
+    /// Apply `change` on top of the latest rules on disk and save them. If
+    /// the file changes underneath us, the change is replayed on the new
+    /// contents instead of overwriting them.
+    fn update<T>(&mut self, mut change: impl FnMut(&mut Rules) -> T) -> Result<T, Box<dyn Error>> {
+        for _ in 0..SAVE_ATTEMPTS {
+            self.reload()?;
+            let result = change(&mut self.rules);
+
+            if self.save_rules()? {
+                return Ok(result);
+            }
+        }
+
+        Err(Box::new(MacError::SystemError(
+            format!("{} keeps changing, rules not saved", self.config_path.display())
+        )))
+    }
+
+    /// Write through a temporary file and rename, so readers never see a
+    /// half-written file. Returns false without writing if the file changed
+    /// since it was last read. The check and the rename happen under an
+    /// exclusive lock on `app_rules.json.lock`, so another saver cannot
+    /// slip in between them.
+    fn save_rules(&mut self) -> Result<bool, Box<dyn Error>> {
         if let Some(parent) = self.config_path.parent() {
             fs::create_dir_all(parent)?;
         }
 
+        let lock = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(self.config_path.with_extension("json.lock"))?;
+        lock.lock()?;
+
+        if self.disk_version()? != self.version {
+            return Ok(false);
+        }
+
+        // Named for this process, so a saver that crashed mid-write never
+        // leaves a file another one picks up
         let content = serde_json::to_string_pretty(&self.rules)?;
-        fs::write(&self.config_path, content)?;
-        Ok(())
+        let temp_path = self.config_path.with_extension(format!("json.{}.tmp", std::process::id()));
+        fs::write(&temp_path, &content)?;
+        if let Err(e) = fs::rename(&temp_path, &self.config_path) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e.into());
+        }
+        self.version = Some(content_version(&content));
+        Ok(true)
     }
 
-    pub fn add_rule(&mut self, rule: AppRule) -> Result<(), Box<dyn Error>> {
-        let key = format!("{}:{}", rule.app_name, rule.interface);
-        self.rules.insert(key, rule);
-        self.save_rules()?;
-        Ok(())
+    /// Send a notification whenever the rules file is written, replaced or
+    /// removed. Events stop when the returned watcher is dropped.
+    pub fn watch(&self, tx: UnboundedSender<()>) -> Result<RecommendedWatcher, Box<dyn Error>> {
+        let directory = self.config_path.parent().ok_or("Rules file has no parent directory")?;
+        fs::create_dir_all(directory)?;
+
+        // Watch the directory: saves replace the file, which would end a
+        // watch on the file itself
+        let file_name = self.config_path.file_name().map(|name| name.to_os_string());
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            // Reading the file produces access events too, including our own reloads
+            if let Ok(event) = event
+                && (event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove())
+                && event.paths.iter().any(|path| path.file_name() == file_name.as_deref())
+            {
+                let _ = tx.send(());
+            }
+        })?;
+
+        watcher.watch(directory, RecursiveMode::NonRecursive)?;
+        Ok(watcher)
     }
 
-    pub fn remove_rule(&mut self, app_name: &str, interface: &str) -> Result<(), Box<dyn Error>> {
-        let key = format!("{}:{}", app_name, interface);
-        self.rules.remove(&key);
-        self.save_rules()?;
-        Ok(())
+    /// Save a rule under `name`, replacing any rule with that name.
+    pub fn add_rule(&mut self, name: &str, rule: AppRule) -> Result<(), Box<dyn Error>> {
+        self.update(|rules| {
+            rules.insert(name.to_string(), rule.clone());
+        })
     }
 
-    pub fn get_rule(&self, app_name: &str, interface: &str) -> Option<&AppRule> {
-        let key = format!("{}:{}", app_name, interface);
-        self.rules.get(&key)
+    /// Delete a rule. Returns false if there was none with that name.
+    pub fn remove_rule(&mut self, name: &str) -> Result<bool, Box<dyn Error>> {
+        self.update(|rules| rules.remove(name).is_some())
+    }
+
+    /// Switch a rule on or off. Returns false if there was none with that name.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<bool, Box<dyn Error>> {
+        self.update(|rules| match rules.get_mut(name) {
+            Some(rule) => {
+                rule.enabled = enabled;
+                true
+            }
+            None => false,
+        })
+    }
+
+    pub fn get_rule(&self, name: &str) -> Option<&AppRule> {
+        self.rules.get(name)
+    }
+
+    /// Turn off every rule, returning how many were enabled.
+    pub fn disable_all(&mut self) -> Result<usize, Box<dyn Error>> {
+        self.update(|rules| {
+            let mut disabled = 0;
+            for rule in rules.values_mut().filter(|rule| rule.enabled) {
+                rule.enabled = false;
+                disabled += 1;
+            }
+            disabled
+        })
     }
 
     pub fn list_rules(&self) -> Vec<&AppRule> {
         self.rules.values().collect()
     }
 
-    pub fn is_rule_active(&self, rule: &AppRule) -> bool {
-        if !rule.enabled {
-            return false;
-        }
+    /// Rules with the names they are saved under, sorted by name.
+    pub fn named_rules(&self) -> Vec<(&str, &AppRule)> {
+        let mut rules: Vec<(&str, &AppRule)> = self.rules.iter().map(|(name, rule)| (name.as_str(), rule)).collect();
+        rules.sort_by_key(|(name, _)| *name);
+        rules
+    }
 
-        if let Some(schedule) = &rule.schedule {
-            let now = chrono::Local::now();
-            let current_day = now.format("%A").to_string().to_lowercase();
+    /// Names of the enabled rules whose application is among `apps`. The
+    /// daemon re-evaluates rules when this changes.
+    pub fn running_app_rules(&self, apps: &[RunningApp]) -> Vec<&str> {
+        let mut names: Vec<&str> = self.rules
+            .iter()
+            .filter(|(_, rule)| rule.enabled && !rule.app_name.is_empty() && rule.app_running(apps))
+            .map(|(name, _)| name.as_str())
+            .collect();
+        names.sort();
+        names
+    }
 
-            // Check if current day is in schedule
-            if !schedule.days.iter().any(|day| day.to_lowercase() == current_day) {
-                return false;
-            }
+    pub fn has_app_rules(&self) -> bool {
+        self.rules.values().any(|rule| rule.enabled && !rule.app_name.is_empty())
+    }
+
+    /// Whether any rule targets every interface rather than a named one.
+    pub fn has_wildcard_rules(&self) -> bool {
+        self.rules.values().any(|rule| rule.interface == ANY_INTERFACE)
+    }
 
-            // Parse schedule times
-            let start_time = chrono::NaiveTime::parse_from_str(&schedule.start_time, "%H:%M").unwrap();
-            let end_time = chrono::NaiveTime::parse_from_str(&schedule.end_time, "%H:%M").unwrap();
-            let current_time = now.time();
+    /// The first enabled, in-schedule rule for `interface` whose application
+    /// is running and whose network and class conditions (if any) match.
+    /// Rules without an application name only depend on their conditions.
+    /// Nothing matches while a matching rule exempts the interface. The
+    /// rule comes with the name it is saved under.
+    pub fn find_matching_rule(
+        &self,
+        interface: &str,
+        running_apps: &[RunningApp],
+        connection: Option<&WirelessConnection>,
+        class: &InterfaceClass,
+    ) -> Option<(&str, &AppRule)> {
+        let mut matching = self.named_rules().into_iter().filter(|(_, rule)| {
+            (rule.interface == interface || rule.interface == ANY_INTERFACE)
+                && rule.app_running(running_apps)
+                && rule.network.as_ref().is_none_or(|network| network.matches(connection))
+                && rule.class.as_ref().is_none_or(|condition| condition.matches(class))
+                && self.is_rule_active(rule)
+        });
 
-            // Check if current time is within schedule
-            if current_time < start_time || current_time > end_time {
-                return false;
-            }
+        if matching.clone().any(|(_, rule)| rule.exempt) {
+            return None;
         }
 
-        true
+        // Rules naming the interface win over wildcard ones
+        matching.clone()
+            .find(|(_, rule)| rule.interface == interface)
+            .or_else(|| matching.next())
+    }
+
+    pub fn is_rule_active(&self, rule: &AppRule) -> bool {
+        rule.enabled && rule.schedule.as_ref().is_none_or(|schedule| schedule.is_active_at(Local::now()))
+    }
+
+    /// The next time any enabled rule's schedule switches on or off, so the
+    /// daemon can re-evaluate rules then instead of waiting for its next tick.
+    pub fn next_transition(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        self.rules
+            .values()
+            .filter(|rule| rule.enabled)
+            .filter_map(|rule| rule.schedule.as_ref()?.next_transition(after))
+            .min()
     }
 }
+
+fn content_version(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
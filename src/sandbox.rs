@@ -0,0 +1,67 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+/// Interfaces explicitly marked as lab/sandbox interfaces. Destructive modes
+/// such as `chaos` refuse to touch anything not listed here.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Sandbox {
+    interfaces: BTreeSet<String>,
+    #[serde(skip)]
+    config_path: PathBuf,
+}
+
+impl Sandbox {
+    pub fn new() -> Self {
+        let config_path = dirs::config_dir()
+            .unwrap_or_default()
+            .join("mac_changer")
+            .join("sandbox.json");
+
+        let mut sandbox = Self {
+            interfaces: BTreeSet::new(),
+            config_path,
+        };
+
+        sandbox.load();
+        sandbox
+    }
+
+    fn load(&mut self) {
+        if let Ok(content) = fs::read_to_string(&self.config_path)
+            && let Ok(sandbox) = serde_json::from_str::<Sandbox>(&content)
+        {
+            self.interfaces = sandbox.interfaces;
+        }
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = self.config_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(&self.config_path, content)?;
+        Ok(())
+    }
+
+    pub fn add(&mut self, interface: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.interfaces.insert(interface.to_string());
+        self.save()
+    }
+
+    pub fn remove(&mut self, interface: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let removed = self.interfaces.remove(interface);
+        self.save()?;
+        Ok(removed)
+    }
+
+    pub fn contains(&self, interface: &str) -> bool {
+        self.interfaces.contains(interface)
+    }
+
+    pub fn list(&self) -> Vec<&String> {
+        self.interfaces.iter().collect()
+    }
+}
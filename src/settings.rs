@@ -0,0 +1,237 @@
+// src/settings.rs
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use crate::error::MacError;
+use crate::filter::FilterMode;
+use crate::hooks::{Hook, HookAction};
+
+pub const SYSTEM_PATH: &str = "/etc/chameleon/config.toml";
+
+/// Written by `config edit` when there is no file yet.
+pub const TEMPLATE: &str = r#"# chameleon settings. Flags on the command line override everything here,
+# and this file overrides /etc/chameleon/config.toml.
+
+# Interface to change when -i is not given
+# interface = "wlan0"
+
+# How -r builds a MAC: "locally-administered", "burned-in-like" or "preserve-oui"
+# random_policy = "locally-administered"
+
+# Vendor prefix for -r, like -v
+# vendor = "00:11:22"
+
+# Keep changes across reboots, like -p (turn off for one run with --no-permanent)
+# permanent = false
+
+# What happens to MACs the whitelist or blacklist rejects: "enforce", "warn" or "off"
+# filter_mode = "enforce"
+
+# [daemon]
+# interval = "30s"
+
+# Where `oui update` looks when the registry's own site fails, in order.
+# Each mirror serves the files under their usual names (oui.csv, manuf)
+# [oui]
+# mirrors = ["https://mirror.example.com/oui/"]
+
+# Run alongside the hooks from `chameleon hooks add`
+# [[hooks]]
+# name = "notify"
+# on = "failure"
+# webhook = "https://example.com/chameleon"
+
+# Per-interface overrides of random_policy, vendor and permanent
+# [interfaces.eth0]
+# random_policy = "burned-in-like"
+# permanent = true
+"#;
+
+/// How `-r` builds an address when no flag picks one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum RandomPolicyName {
+    LocallyAdministered,
+    BurnedInLike,
+    PreserveOui,
+}
+
+/// The settings an interface can override.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InterfaceSettings {
+    pub random_policy: Option<RandomPolicyName>,
+    pub vendor: Option<String>,
+    pub permanent: Option<bool>,
+}
+
+impl InterfaceSettings {
+    fn merge(self, over: InterfaceSettings) -> Self {
+        Self {
+            random_policy: over.random_policy.or(self.random_policy),
+            vendor: over.vendor.or(self.vendor),
+            permanent: over.permanent.or(self.permanent),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonSettings {
+    pub interval: Option<String>, // How often rules are evaluated, like --interval
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OuiSettings {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<String>, // Base URLs tried after the registry's own
+}
+
+/// `config.toml`: defaults for whatever the command line leaves out.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub interface: Option<String>,
+    pub random_policy: Option<RandomPolicyName>,
+    pub vendor: Option<String>,
+    pub permanent: Option<bool>,
+    pub filter_mode: Option<FilterMode>,
+    pub daemon: DaemonSettings,
+    pub oui: OuiSettings,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<Hook>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub interfaces: BTreeMap<String, InterfaceSettings>,
+}
+
+impl Settings {
+    pub fn user_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_default()
+            .join("mac_changer")
+            .join("config.toml")
+    }
+
+    /// The files settings come from, the one that wins last.
+    pub fn paths() -> [PathBuf; 2] {
+        [PathBuf::from(SYSTEM_PATH), Self::user_path()]
+    }
+
+    /// The system file with the user's on top; neither has to exist.
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let mut settings = Settings::default();
+        for path in Self::paths() {
+            if let Some(file) = Self::read(&path)? {
+                settings = settings.merge(file);
+            }
+        }
+        Ok(settings)
+    }
+
+    /// One file, or None if it does not exist.
+    pub fn read(path: &Path) -> Result<Option<Self>, Box<dyn Error>> {
+        match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content)
+                .map(Some)
+                .map_err(|e| MacError::ParseError(format!("{}: {}", path.display(), e.to_string().trim())).into()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    fn merge(self, over: Settings) -> Self {
+        let mut interfaces = self.interfaces;
+        for (name, settings) in over.interfaces {
+            let merged = interfaces.remove(&name).unwrap_or_default().merge(settings);
+            interfaces.insert(name, merged);
+        }
+
+        let mut hooks = self.hooks;
+        hooks.retain(|hook| !over.hooks.iter().any(|other| other.name == hook.name));
+        hooks.extend(over.hooks);
+
+        Self {
+            interface: over.interface.or(self.interface),
+            random_policy: over.random_policy.or(self.random_policy),
+            vendor: over.vendor.or(self.vendor),
+            permanent: over.permanent.or(self.permanent),
+            filter_mode: over.filter_mode.or(self.filter_mode),
+            daemon: DaemonSettings { interval: over.daemon.interval.or(self.daemon.interval) },
+            oui: if over.oui.mirrors.is_empty() { self.oui } else { over.oui },
+            hooks,
+            interfaces,
+        }
+    }
+
+    /// The defaults for `interface`, with its overrides applied.
+    pub fn for_interface(&self, interface: &str) -> InterfaceSettings {
+        let defaults = InterfaceSettings {
+            random_policy: self.random_policy,
+            vendor: self.vendor.clone(),
+            permanent: self.permanent,
+        };
+        match self.interfaces.get(interface) {
+            Some(overrides) => defaults.merge(overrides.clone()),
+            None => defaults,
+        }
+    }
+
+    pub fn daemon_interval(&self) -> Result<Option<Duration>, MacError> {
+        self.daemon
+            .interval
+            .as_deref()
+            .map(|interval| crate::parse_duration(interval).map_err(|e| MacError::ParseError(format!("daemon.interval: {}", e))))
+            .transpose()
+    }
+
+    /// Everything wrong with these settings that parsing let through.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vendor_problems("", &self.for_interface(""));
+        for (name, overrides) in &self.interfaces {
+            problems.extend(vendor_problems(&format!("interfaces.{}: ", name), overrides));
+            if overrides.vendor.is_none() && overrides.random_policy.is_none() && overrides.permanent.is_none() {
+                problems.push(format!("interfaces.{}: sets nothing", name));
+            }
+        }
+
+        if let Err(e) = self.daemon_interval() {
+            problems.push(e.to_string());
+        }
+
+        for mirror in &self.oui.mirrors {
+            if let Err(e) = reqwest::Url::parse(mirror) {
+                problems.push(format!("oui.mirrors: invalid URL {}: {}", mirror, e));
+            }
+        }
+
+        for (i, hook) in self.hooks.iter().enumerate() {
+            if self.hooks[..i].iter().any(|other| other.name == hook.name) {
+                problems.push(format!("hooks: {} is defined twice", hook.name));
+            }
+            if let HookAction::Webhook(url) = &hook.action
+                && let Err(e) = reqwest::Url::parse(url)
+            {
+                problems.push(format!("hooks: {} has an invalid URL {}: {}", hook.name, url, e));
+            }
+        }
+
+        problems
+    }
+}
+
+fn vendor_problems(scope: &str, settings: &InterfaceSettings) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(vendor) = &settings.vendor {
+        if !crate::is_valid_vendor_format(vendor) {
+            problems.push(format!("{}vendor {} is not a prefix like XX:XX:XX", scope, vendor));
+        }
+        if settings.random_policy.is_some_and(|policy| policy != RandomPolicyName::LocallyAdministered) {
+            problems.push(format!("{}vendor only applies with random_policy = \"locally-administered\"", scope));
+        }
+    }
+    problems
+}
@@ -0,0 +1,83 @@
+// src/status.rs
+use serde::Serialize;
+use std::error::Error;
+use crate::config::get_original_mac;
+use crate::error::MacError;
+use crate::inspect::{self, Inspection, SpoofSignal};
+use crate::mac::MacAddress;
+use crate::oui::OUIDatabase;
+use crate::platform::get_permanent_override;
+
+#[derive(Debug, Serialize)]
+pub struct InterfaceStatus {
+    pub interface: String,
+    pub current_mac: MacAddress,
+    pub original_mac: Option<MacAddress>,
+    pub permanent_override: Option<String>,
+    pub hardware_mac: Option<MacAddress>,
+    pub vendor: Option<String>,
+    pub locally_administered: bool,
+    pub spoofed: bool,
+    pub boot_randomization: Option<String>,
+}
+
+impl InterfaceStatus {
+    pub fn collect(interface: &str, oui_db: &OUIDatabase) -> Result<Self, Box<dyn Error>> {
+        Self::from_inspection(inspect::inspect(interface, Some(oui_db))?)
+    }
+
+    fn from_inspection(inspection: Inspection) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            original_mac: get_original_mac(&inspection.interface)?,
+            permanent_override: get_permanent_override(&inspection.interface),
+            locally_administered: inspection.signals.contains(&SpoofSignal::LocallyAdministered),
+            spoofed: inspection.is_spoofed(),
+            boot_randomization: crate::boot::installed(&inspection.interface),
+            hardware_mac: inspection.permanent_mac,
+            vendor: inspection.vendor,
+            current_mac: inspection.current_mac,
+            interface: inspection.interface,
+        })
+    }
+
+    pub fn print(&self) {
+        println!("{}", self.interface);
+        println!("  Current MAC:   {}", self.current_mac);
+        println!("  Original MAC:  {}", self.original_mac.as_ref().map(|mac| mac.to_string()).unwrap_or_else(|| "(not saved)".into()));
+        println!("  Hardware MAC:  {}", self.hardware_mac.as_ref().map_or("(not reported)".into(), MacAddress::to_string));
+        match &self.permanent_override {
+            Some(mac) => println!("  Permanent:     yes ({})", mac),
+            None => println!("  Permanent:     no"),
+        }
+        println!("  Vendor:        {}", self.vendor.as_deref().unwrap_or("Unknown"));
+        println!("  Address type:  {}",
+                 if self.locally_administered { "locally administered" } else { "universally administered" });
+        println!("  Spoofed:       {}", if self.spoofed { "likely" } else { "no" });
+        match &self.boot_randomization {
+            Some(installed) => println!("  Boot random:   yes ({})", installed),
+            None => println!("  Boot random:   no"),
+        }
+    }
+}
+
+/// Status for one interface, or for every interface with a hardware address.
+pub fn collect_status(interface: Option<&str>, oui_db: &OUIDatabase) -> Result<Vec<InterfaceStatus>, Box<dyn Error>> {
+    match interface {
+        Some(interface) => Ok(vec![InterfaceStatus::collect(interface, oui_db)?]),
+        None => {
+            let mut statuses: Vec<InterfaceStatus> = inspect::inspect_all(Some(oui_db))?
+                .into_iter()
+                .map(InterfaceStatus::from_inspection)
+                .collect::<Result<_, _>>()?;
+
+            if statuses.is_empty() {
+                return Err(Box::new(MacError::ValidationFailed(
+                    "No network interfaces with a MAC address found".into()
+                )));
+            }
+
+            statuses.sort_by(|a, b| a.interface.cmp(&b.interface));
+            Ok(statuses)
+        }
+    }
+}
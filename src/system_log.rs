@@ -0,0 +1,186 @@
+// src/system_log.rs
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use crate::logger::MacChange;
+
+const IDENTIFIER: &str = "chameleon";
+
+/// Syslog facility for security and authorization messages, where audit
+/// tooling usually looks.
+#[cfg(unix)]
+const LOG_AUTH: u8 = 4;
+
+#[cfg(unix)]
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Linux, macOS and the BSDs respectively.
+#[cfg(unix)]
+const SYSLOG_SOCKETS: [&str; 3] = ["/dev/log", "/var/run/syslog", "/var/run/log"];
+
+/// Severity MAC changes are logged at.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Debug,
+    Info,
+    #[default]
+    Notice,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// Syslog level, also journald's PRIORITY.
+    #[cfg(unix)]
+    fn level(self) -> u8 {
+        match self {
+            Severity::Error => 3,
+            Severity::Warning => 4,
+            Severity::Notice => 5,
+            Severity::Info => 6,
+            Severity::Debug => 7,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Severity::Debug => "debug",
+            Severity::Info => "info",
+            Severity::Notice => "notice",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Whether MAC changes also go to the system log, next to the file log.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SystemLogConfig {
+    pub enabled: bool,
+    #[serde(default)]
+    pub severity: Severity,
+}
+
+impl SystemLogConfig {
+    fn path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_default()
+            .join("mac_changer")
+            .join("system_log.json")
+    }
+
+    pub fn load() -> Self {
+        fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+fn describe(change: &MacChange) -> String {
+    format!("MAC of {} changed from {} to {}{}{}{}{}",
+            change.interface,
+            change.old_mac,
+            change.new_mac,
+            change.trigger.map(|trigger| format!(" (trigger {})", trigger)).unwrap_or_default(),
+            change.principal.as_ref().map(|principal| format!(" by {}", principal)).unwrap_or_default(),
+            change.profile.as_ref().map(|profile| format!(" from profile {}", profile)).unwrap_or_default(),
+            if change.permanent { ", permanent" } else { "" })
+}
+
+/// Record a change in journald or syslog. journald gets it through its
+/// native protocol so each field can be searched on its own, e.g.
+/// `journalctl CHAMELEON_INTERFACE=wlan0`.
+#[cfg(unix)]
+pub fn send(change: &MacChange, severity: Severity) -> Result<(), Box<dyn Error>> {
+    use std::os::unix::net::UnixDatagram;
+
+    let socket = UnixDatagram::unbound()?;
+    let message = describe(change);
+
+    if socket.send_to(&journal_entry(change, severity, &message), JOURNALD_SOCKET).is_ok() {
+        return Ok(());
+    }
+
+    let datagram = format!("<{}>{}[{}]: {}", LOG_AUTH * 8 + severity.level(), IDENTIFIER, std::process::id(), message);
+    for path in SYSLOG_SOCKETS {
+        if socket.send_to(datagram.as_bytes(), path).is_ok() {
+            return Ok(());
+        }
+    }
+
+    Err(Box::new(crate::error::MacError::SystemError("Neither journald nor syslog is listening".into())))
+}
+
+#[cfg(unix)]
+fn journal_entry(change: &MacChange, severity: Severity, message: &str) -> Vec<u8> {
+    let mut fields = vec![
+        ("MESSAGE", message.to_string()),
+        ("PRIORITY", severity.level().to_string()),
+        ("SYSLOG_FACILITY", LOG_AUTH.to_string()),
+        ("SYSLOG_IDENTIFIER", IDENTIFIER.to_string()),
+        ("CHAMELEON_INTERFACE", change.interface.clone()),
+        ("CHAMELEON_OLD_MAC", change.old_mac.to_string()),
+        ("CHAMELEON_NEW_MAC", change.new_mac.to_string()),
+        ("CHAMELEON_PERMANENT", change.permanent.to_string()),
+    ];
+    if let Some(trigger) = change.trigger {
+        fields.push(("CHAMELEON_TRIGGER", trigger.to_string()));
+    }
+    if let Some(profile) = &change.profile {
+        fields.push(("CHAMELEON_PROFILE", profile.clone()));
+    }
+    if let Some(hostname) = &change.hostname {
+        fields.push(("CHAMELEON_HOSTNAME", hostname.clone()));
+    }
+    if let Some(client_id) = &change.dhcp_client_id {
+        fields.push(("CHAMELEON_DHCP_CLIENT_ID", client_id.clone()));
+    }
+    if let Some(principal) = &change.principal {
+        fields.push(("CHAMELEON_PRINCIPAL", principal.clone()));
+    }
+
+    let mut entry = Vec::new();
+    for (key, value) in fields {
+        if value.contains('\n') {
+            // Values with line breaks are sent with an explicit length
+            entry.extend_from_slice(key.as_bytes());
+            entry.push(b'\n');
+            entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+            entry.extend_from_slice(value.as_bytes());
+        } else {
+            entry.extend_from_slice(format!("{}={}", key, value).as_bytes());
+        }
+        entry.push(b'\n');
+    }
+    entry
+}
+
+/// Record a change in the Application event log under the source
+/// "chameleon".
+#[cfg(windows)]
+pub fn send(change: &MacChange, severity: Severity) -> Result<(), Box<dyn Error>> {
+    use crate::win32::EventLevel;
+
+    let level = match severity {
+        Severity::Error => EventLevel::Error,
+        Severity::Warning => EventLevel::Warning,
+        _ => EventLevel::Information,
+    };
+    crate::win32::report_event(IDENTIFIER, level, &describe(change))
+}
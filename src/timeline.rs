@@ -0,0 +1,73 @@
+// src/timeline.rs
+use chrono::{DateTime, Local, Utc};
+use std::collections::HashMap;
+use crate::logger::{LinkEvent, MacChange};
+
+enum Entry<'a> {
+    Change(&'a MacChange),
+    Link(&'a LinkEvent),
+}
+
+impl Entry<'_> {
+    fn timestamp(&self) -> DateTime<Utc> {
+        match self {
+            Entry::Change(change) => change.timestamp,
+            Entry::Link(event) => event.timestamp,
+        }
+    }
+
+    fn interface(&self) -> &str {
+        match self {
+            Entry::Change(change) => &change.interface,
+            Entry::Link(event) => &event.interface,
+        }
+    }
+}
+
+/// Print MAC changes and link transitions in time order, noting how long
+/// each interface was without a usable link.
+pub fn print(changes: &[MacChange], events: &[LinkEvent], interface: Option<&str>) {
+    let mut entries: Vec<Entry> = changes.iter().map(Entry::Change)
+        .chain(events.iter().map(Entry::Link))
+        .filter(|entry| interface.is_none_or(|interface| entry.interface() == interface))
+        .collect();
+    entries.sort_by_key(|entry| entry.timestamp());
+
+    if entries.is_empty() {
+        println!("No history recorded");
+        return;
+    }
+
+    // When each interface last lost its link, to report downtime on recovery
+    let mut down_since: HashMap<&str, DateTime<Utc>> = HashMap::new();
+
+    for entry in &entries {
+        let time = entry.timestamp().with_timezone(&Local).format("%Y-%m-%d %H:%M:%S");
+
+        match entry {
+            Entry::Change(change) => {
+                println!("{}  {:<12} MAC   {} -> {}{}", time, change.interface, change.old_mac, change.new_mac,
+                         if change.permanent { " (permanent)" } else { "" });
+            }
+            Entry::Link(event) => {
+                let state = match (event.up, event.carrier) {
+                    (true, true) => "up",
+                    (true, false) => "up, no carrier",
+                    (false, _) => "down",
+                };
+
+                let usable = event.up && event.carrier;
+                let downtime = if usable {
+                    down_since.remove(event.interface.as_str())
+                        .map(|since| format!(" (down for {}s)", (event.timestamp - since).num_seconds()))
+                        .unwrap_or_default()
+                } else {
+                    down_since.entry(&event.interface).or_insert(event.timestamp);
+                    String::new()
+                };
+
+                println!("{}  {:<12} LINK  {}{}", time, event.interface, state, downtime);
+            }
+        }
+    }
+}
@@ -0,0 +1,336 @@
+// src/transaction.rs
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Instant;
+use crate::hooks::{self, HookEvent};
+use crate::renew::Renewal;
+
+/// One side effect of a MAC change, recorded in the order it happened.
+/// Each platform only records the steps it actually performs.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum Step {
+    InterfaceDown { interface: String },
+    InterfaceUp { interface: String },
+    AddressSet { interface: String, old_mac: String, new_mac: String },
+    ServiceStopped { service: String },
+    ServiceStarted { service: String },
+    AdapterDisabled { adapter: String, guid: String },
+    AdapterEnabled { adapter: String, guid: String },
+    // `previous` is what rollback puts back; None means the value or file
+    // did not exist before
+    RegistryValueSet { key: String, name: String, value: String, previous: Option<String> },
+    FileWritten {
+        path: PathBuf,
+        #[serde(skip)]
+        previous: Option<String>,
+    },
+    Disassociated { interface: String },
+    // NetworkManager connection settings; temporary ones are not saved to disk
+    ConnectionModified {
+        connection: String,
+        uuid: String,
+        interface: String,
+        setting: String,
+        value: String,
+        previous: Option<String>,
+        temporary: bool,
+    },
+    ConnectionActivated { connection: String, uuid: String, interface: String },
+    HostnameSet { hostname: String, previous: String },
+    DhcpClientIdSet { interface: String, client_id: String, previous: Option<String> },
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Step::InterfaceDown { interface } => write!(f, "bring {} down", interface),
+            Step::InterfaceUp { interface } => write!(f, "bring {} up", interface),
+            Step::AddressSet { interface, new_mac, .. } => write!(f, "set {} to {}", interface, new_mac),
+            Step::ServiceStopped { service } => write!(f, "stop {}", service),
+            Step::ServiceStarted { service } => write!(f, "start {}", service),
+            Step::AdapterDisabled { adapter, .. } => write!(f, "disable adapter {}", adapter),
+            Step::AdapterEnabled { adapter, .. } => write!(f, "enable adapter {}", adapter),
+            Step::RegistryValueSet { key, name, .. } => write!(f, "write {}\\{}", key, name),
+            Step::FileWritten { path, .. } => write!(f, "write {}", path.display()),
+            Step::Disassociated { interface } => write!(f, "disassociate {}", interface),
+            Step::ConnectionModified { connection, setting, .. } => write!(f, "set {} on connection {}", setting, connection),
+            Step::ConnectionActivated { connection, .. } => write!(f, "reactivate connection {}", connection),
+            Step::HostnameSet { hostname, .. } => write!(f, "set the hostname to {}", hostname),
+            Step::DhcpClientIdSet { interface, client_id, .. } => {
+                write!(f, "set the DHCP client ID of {} to {}", interface, client_id)
+            }
+        }
+    }
+}
+
+/// Log of everything a single MAC change did to the system.
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    pub interface: String,
+    pub permanent: bool,
+    pub steps: Vec<Step>,
+    pub suggestions: Vec<String>,
+    pub renewal: Option<Renewal>, // Set once the network has been renewed
+    pub started: Instant,
+    pub hooks: Option<HookEvent>, // Waiting for `run_hooks`
+}
+
+impl Transaction {
+    pub fn new(interface: &str, permanent: bool) -> Self {
+        Self {
+            interface: interface.to_string(),
+            permanent,
+            steps: Vec::new(),
+            suggestions: Vec::new(),
+            renewal: None,
+            started: Instant::now(),
+            hooks: None,
+        }
+    }
+
+    /// Run the hooks for this change. Callers do so once the interface
+    /// lock is released and the network renewed, so hooks see the finished
+    /// change; later calls do nothing.
+    pub fn run_hooks(&mut self) {
+        if let Some(event) = self.hooks.take() {
+            hooks::fire(&event);
+        }
+    }
+
+    pub fn record(&mut self, step: Step) {
+        self.steps.push(step);
+    }
+
+    /// Something the user should do once the change is complete.
+    pub fn suggest(&mut self, suggestion: impl Into<String>) {
+        let suggestion = suggestion.into();
+        if !self.suggestions.contains(&suggestion) {
+            self.suggestions.push(suggestion);
+        }
+    }
+
+    pub fn summary(&self) -> ChangeSummary {
+        ChangeSummary::from_transaction(self)
+    }
+}
+
+/// What a run changed, for printing at the end instead of piecing it
+/// together from progress messages.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangeSummary {
+    pub interface: String,
+    pub old_mac: Option<String>,
+    pub new_mac: Option<String>,
+    pub vendor: Option<String>, // Of the new MAC
+    pub permanent: bool,
+    pub duration_ms: u64,
+    pub errors: Vec<String>,
+    /// The `code` of the error that stopped the change, see `MacError::code`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    pub artifacts: Vec<String>,
+    pub services: Vec<String>,
+    pub suggestions: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub renewal: Option<Renewal>,
+}
+
+impl ChangeSummary {
+    pub fn from_transaction(transaction: &Transaction) -> Self {
+        let mut old_mac = None;
+        let mut new_mac = None;
+        let mut artifacts = Vec::new();
+        let mut services = Vec::new();
+
+        let mut touch = |service: String| {
+            if !services.contains(&service) {
+                services.push(service);
+            }
+        };
+
+        for step in &transaction.steps {
+            match step {
+                Step::AddressSet { old_mac: old, new_mac: new, .. } => {
+                    // Keep the first old address in case the change was retried
+                    old_mac.get_or_insert_with(|| old.clone());
+                    new_mac = Some(new.clone());
+                }
+                Step::FileWritten { path, .. } => artifacts.push(path.display().to_string()),
+                Step::RegistryValueSet { key, name, value, .. } => {
+                    artifacts.push(format!("{}\\{} = {}", key, name, value));
+                }
+                Step::ServiceStopped { service } | Step::ServiceStarted { service } => {
+                    touch(format!("{} restarted", service));
+                }
+                Step::InterfaceDown { interface } | Step::InterfaceUp { interface } => {
+                    touch(format!("interface {} bounced", interface));
+                }
+                Step::AdapterDisabled { adapter, .. } | Step::AdapterEnabled { adapter, .. } => {
+                    touch(format!("adapter {} bounced", adapter));
+                }
+                Step::Disassociated { interface } => {
+                    touch(format!("{} disassociated from Wi-Fi", interface));
+                }
+                Step::ConnectionModified { connection, setting, value, temporary, .. } => {
+                    let lifetime = if *temporary { " (until NetworkManager restarts)" } else { "" };
+                    artifacts.push(format!("connection {}: {} = {}{}", connection, setting, value, lifetime));
+                }
+                Step::ConnectionActivated { connection, .. } => {
+                    touch(format!("connection {} reactivated", connection));
+                }
+                Step::HostnameSet { hostname, .. } => artifacts.push(format!("hostname = {}", hostname)),
+                Step::DhcpClientIdSet { interface, client_id, .. } => {
+                    artifacts.push(format!("DHCP client ID of {} = {}", interface, client_id));
+                }
+            }
+        }
+
+        Self {
+            interface: transaction.interface.clone(),
+            old_mac,
+            new_mac,
+            vendor: None,
+            permanent: transaction.permanent,
+            duration_ms: transaction.started.elapsed().as_millis() as u64,
+            errors: Vec::new(),
+            error_code: None,
+            artifacts,
+            services,
+            suggestions: transaction.suggestions.clone(),
+            renewal: transaction.renewal.clone(),
+        }
+    }
+
+    /// A change that did not go through.
+    pub fn failed(interface: &str, old_mac: Option<String>, new_mac: &str, permanent: bool, started: Instant, error: String) -> Self {
+        Self {
+            interface: interface.to_string(),
+            old_mac,
+            new_mac: Some(new_mac.to_string()),
+            vendor: None,
+            permanent,
+            duration_ms: started.elapsed().as_millis() as u64,
+            errors: vec![error],
+            error_code: None,
+            artifacts: Vec::new(),
+            services: Vec::new(),
+            suggestions: Vec::new(),
+            renewal: None,
+        }
+    }
+
+    pub fn print(&self) {
+        println!();
+        println!("Summary for {}", self.interface);
+        match (&self.old_mac, &self.new_mac) {
+            (Some(old), Some(new)) => println!("  MAC:          {} -> {}", old, new),
+            (None, Some(new)) => println!("  MAC:          {}", new),
+            _ => println!("  MAC:          unchanged"),
+        }
+        if let Some(vendor) = &self.vendor {
+            println!("  Vendor:       {}", vendor);
+        }
+        println!("  Persistent:   {}", if self.permanent { "yes" } else { "no" });
+        for artifact in &self.artifacts {
+            println!("  Wrote:        {}", artifact);
+        }
+        for service in &self.services {
+            println!("  Touched:      {}", service);
+        }
+        if let Some(renewal) = &self.renewal {
+            if let Some(client) = &renewal.dhcp_client {
+                println!("  DHCP:         lease renewed by {}", client);
+            }
+            if renewal.neighbors_flushed {
+                println!("  Neighbors:    flushed");
+            }
+            match renewal.addresses.as_slice() {
+                [] => println!("  IP:           none"),
+                addresses => println!("  IP:           {}", addresses.join(", ")),
+            }
+            for note in &renewal.notes {
+                println!("  Note:         {}", note);
+            }
+        }
+        for suggestion in &self.suggestions {
+            println!("  Next:         {}", suggestion);
+        }
+        for error in &self.errors {
+            println!("  Error:        {}", error);
+        }
+    }
+}
+
+/// One step a change would take, with the command or API that performs it.
+#[derive(Debug, Serialize)]
+pub struct PlannedAction {
+    #[serde(flatten)]
+    pub step: Step,
+    pub command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>, // What a file would contain afterwards
+}
+
+/// What a change would do, from `--dry-run`. Built by the same checks as a
+/// real change, but nothing on the system is touched.
+#[derive(Debug, Serialize)]
+pub struct Plan {
+    pub interface: String,
+    pub old_mac: String,
+    pub new_mac: String,
+    pub permanent: bool,
+    pub actions: Vec<PlannedAction>,
+    pub notes: Vec<String>,
+}
+
+impl Plan {
+    pub fn new(interface: &str, old_mac: &str, new_mac: &str, permanent: bool) -> Self {
+        Self {
+            interface: interface.to_string(),
+            old_mac: old_mac.to_string(),
+            new_mac: new_mac.to_string(),
+            permanent,
+            actions: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, step: Step, command: Option<String>) {
+        self.actions.push(PlannedAction { step, command, content: None });
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"))]
+    pub fn add_file(&mut self, step: Step, content: String) {
+        self.actions.push(PlannedAction { step, command: None, content: Some(content) });
+    }
+
+    pub fn note(&mut self, note: impl Into<String>) {
+        self.notes.push(note.into());
+    }
+
+    pub fn print(&self) {
+        println!();
+        println!("Dry run for {}, nothing was changed", self.interface);
+        if self.old_mac.is_empty() {
+            println!("  MAC:          {}", self.new_mac);
+        } else {
+            println!("  MAC:          {} -> {}", self.old_mac, self.new_mac);
+        }
+        println!("  Persistent:   {}", if self.permanent { "yes" } else { "no" });
+        for (number, action) in self.actions.iter().enumerate() {
+            println!("  {:>2}. {}", number + 1, action.step);
+            if let Some(command) = &action.command {
+                println!("        $ {}", command);
+            }
+            for line in action.content.iter().flat_map(|content| content.lines()) {
+                println!("        | {}", line);
+            }
+        }
+        for note in &self.notes {
+            println!("  Note:         {}", note);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+// src/watch.rs
+use chrono::{Local, Utc};
+use std::error::Error;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use crate::backend;
+use crate::change_window::ChangeWindows;
+use crate::hooks::Trigger;
+use crate::logger::{MacChange, MacLogger};
+use crate::mac::MacAddress;
+use crate::output::progress;
+use crate::platform::change_mac;
+use crate::rules::TIME_FORMAT;
+
+/// Drivers often report the link before the address settles, so give them a
+/// moment after an event before comparing.
+const SETTLE_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+pub struct WatchConfig {
+    pub interface: String,
+    pub mac: MacAddress,
+    pub permanent: bool,
+    /// How often to check when no link event arrives.
+    pub poll_interval: Duration,
+}
+
+/// Keep `config.mac` on the interface until interrupted, re-applying it
+/// whenever a driver reset, resume or reconnect puts another address back.
+pub async fn run(config: WatchConfig, logger: &MacLogger) -> Result<(), Box<dyn Error>> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let _events = match subscribe(&config.interface, tx) {
+        Ok(events) => Some(events),
+        Err(e) => {
+            progress!("Link events unavailable ({}), polling every {:?}", e, config.poll_interval);
+            None
+        }
+    };
+
+    progress!("Watching {} for changes away from {}. Press Ctrl-C to stop.", config.interface, config.mac);
+
+    let mut deferred = false;
+
+    loop {
+        tokio::select! {
+            Some(()) = rx.recv() => tokio::time::sleep(SETTLE_DELAY).await,
+            _ = tokio::time::sleep(config.poll_interval) => {}
+            _ = tokio::signal::ctrl_c() => {
+                progress!("[watch] Stopped");
+                return Ok(());
+            }
+        }
+
+        let current = match backend::current().get_mac(&config.interface) {
+            Ok(mac) => mac,
+            // The interface may be gone for a moment while the driver resets
+            Err(_) => continue,
+        };
+
+        if current == config.mac {
+            deferred = false;
+            continue;
+        }
+
+        // Outside the change window, keep polling until it opens
+        let windows = match ChangeWindows::new() {
+            Ok(windows) => windows,
+            Err(e) => {
+                progress!("[watch] Not re-applying {}: {}", config.mac, e);
+                continue;
+            }
+        };
+        if !windows.is_open(&config.interface, Local::now()) {
+            if !deferred {
+                let opens = windows.next_opening(&config.interface, Local::now())
+                    .map(|opens| format!(" at {}", opens.format(TIME_FORMAT)))
+                    .unwrap_or_default();
+                progress!("[watch] {} reverted to {}, re-applying {} when its change window opens{}",
+                         config.interface, current, config.mac, opens);
+                deferred = true;
+            }
+            continue;
+        }
+        deferred = false;
+
+        progress!("[watch] {} reverted to {}, re-applying {}", config.interface, current, config.mac);
+
+        match change_mac(&config.interface, &config.mac, config.permanent, Trigger::Watch).map(|mut transaction| transaction.run_hooks()) {
+            Ok(_) => logger.log_change(MacChange {
+                timestamp: Utc::now(),
+                interface: config.interface.clone(),
+                old_mac: current,
+                new_mac: config.mac.clone(),
+                geo_location: None,
+                permanent: config.permanent,
+                profile: None,
+                trigger: Some(Trigger::Watch),
+                hostname: None,
+                dhcp_client_id: None,
+                principal: None,
+            })?,
+            Err(e) => progress!("[watch] Re-applying failed: {}", e),
+        }
+
+        // Our own down/up generated events; don't react to them
+        drain(&mut rx);
+    }
+}
+
+fn drain(rx: &mut UnboundedReceiver<()>) {
+    while rx.try_recv().is_ok() {}
+}
+
+/// Link change notifications from the kernel's routing netlink socket.
+#[cfg(target_os = "linux")]
+pub fn subscribe(_interface: &str, tx: UnboundedSender<()>) -> Result<std::thread::JoinHandle<()>, Box<dyn Error>> {
+    use nix::sys::socket::{bind, recv, socket, AddressFamily, MsgFlags, NetlinkAddr, SockFlag, SockProtocol, SockType};
+    use std::os::fd::AsRawFd;
+
+    const RTMGRP_LINK: u32 = 1;
+
+    let fd = socket(AddressFamily::Netlink, SockType::Raw, SockFlag::empty(), SockProtocol::NetlinkRoute)?;
+    bind(fd.as_raw_fd(), &NetlinkAddr::new(0, RTMGRP_LINK))?;
+
+    // Any RTM_NEWLINK/RTM_DELLINK is worth a look; the address check after
+    // the event filters out other interfaces
+    Ok(std::thread::spawn(move || {
+        let mut buffer = [0u8; 8192];
+        while recv(fd.as_raw_fd(), &mut buffer, MsgFlags::empty()).is_ok() {
+            if tx.send(()).is_err() {
+                break;
+            }
+        }
+    }))
+}
+
+/// IP Helper interface change notifications, which fire when an adapter is
+/// reset, re-enabled or resumes.
+#[cfg(target_os = "windows")]
+pub fn subscribe(_interface: &str, tx: UnboundedSender<()>) -> Result<InterfaceNotifier, Box<dyn Error>> {
+    InterfaceNotifier::new(tx)
+}
+
+#[cfg(target_os = "windows")]
+pub struct InterfaceNotifier {
+    handle: windows::Win32::Foundation::HANDLE,
+    sender: *mut UnboundedSender<()>,
+}
+
+#[cfg(target_os = "windows")]
+impl InterfaceNotifier {
+    fn new(tx: UnboundedSender<()>) -> Result<Self, Box<dyn Error>> {
+        use windows::Win32::Foundation::{HANDLE, NO_ERROR};
+        use windows::Win32::NetworkManagement::IpHelper::NotifyIpInterfaceChange;
+        use windows::Win32::Networking::WinSock::AF_UNSPEC;
+
+        let sender = Box::into_raw(Box::new(tx));
+        let mut handle = HANDLE::default();
+
+        let result = unsafe {
+            NotifyIpInterfaceChange(AF_UNSPEC, Some(on_interface_change), Some(sender as *const _), false, &mut handle)
+        };
+        if result != NO_ERROR {
+            drop(unsafe { Box::from_raw(sender) });
+            return Err(Box::new(crate::error::MacError::SystemError(
+                format!("NotifyIpInterfaceChange failed with error {}", result.0)
+            )));
+        }
+
+        Ok(Self { handle, sender })
+    }
+}
+
+#[cfg(target_os = "windows")]
+unsafe extern "system" fn on_interface_change(
+    context: *const core::ffi::c_void,
+    _row: *const windows::Win32::NetworkManagement::IpHelper::MIB_IPINTERFACE_ROW,
+    _notification_type: windows::Win32::NetworkManagement::IpHelper::MIB_NOTIFICATION_TYPE,
+) {
+    let sender = unsafe { &*(context as *const UnboundedSender<()>) };
+    let _ = sender.send(());
+}
+
+#[cfg(target_os = "windows")]
+impl Drop for InterfaceNotifier {
+    fn drop(&mut self) {
+        // Cancelling waits for running callbacks, so the sender is unused after
+        unsafe {
+            let _ = windows::Win32::NetworkManagement::IpHelper::CancelMibChangeNotify2(self.handle);
+            drop(Box::from_raw(self.sender));
+        }
+    }
+}
+
+/// No event source here; the caller falls back to polling.
+#[cfg(target_os = "macos")]
+pub fn subscribe(_interface: &str, _tx: UnboundedSender<()>) -> Result<(), Box<dyn Error>> {
+    Err("no link event source on macOS".into())
+}
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd"))]
+pub fn subscribe(_interface: &str, _tx: UnboundedSender<()>) -> Result<(), Box<dyn Error>> {
+    Err("no link event source on BSD".into())
+}
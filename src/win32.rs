@@ -0,0 +1,481 @@
+// src/win32.rs
+use std::error::Error;
+use std::mem::size_of;
+use winreg::RegKey;
+use winreg::enums::*;
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{CloseHandle, ERROR_BUFFER_OVERFLOW, NO_ERROR};
+use windows::Win32::Devices::DeviceAndDriverInstallation::*;
+use windows::Win32::NetworkManagement::IpHelper::*;
+use windows::Win32::NetworkManagement::Ndis::{IfOperStatusUp, OID_802_3_PERMANENT_ADDRESS};
+use windows::Win32::Networking::WinSock::{AF_INET, AF_UNSPEC, SOCKADDR_IN};
+use windows::Win32::Storage::FileSystem::*;
+use windows::Win32::System::Diagnostics::ToolHelp::*;
+use windows::Win32::System::EventLog::{
+    DeregisterEventSource, RegisterEventSourceW, ReportEventW,
+    EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE, EVENTLOG_WARNING_TYPE,
+};
+use windows::Win32::System::IO::DeviceIoControl;
+use crate::error::MacError;
+use crate::mac::{MacAddress, MacFormat};
+
+const CLASS_KEY: &str = "SYSTEM\\CurrentControlSet\\Control\\Class";
+
+/// A network adapter as reported by the IP Helper API.
+#[derive(Debug, Clone)]
+pub struct Adapter {
+    pub name: String,        // Connection name, e.g. "Ethernet" or "Wi-Fi"
+    pub description: String, // Adapter model, e.g. "Intel(R) Ethernet Connection"
+    pub guid: String,        // NetCfgInstanceId, e.g. "{5F3C...}"
+    pub mac: Option<String>,
+    pub is_up: bool,
+    pub if_type: u32,
+    pub speed_mbps: Option<u64>,
+}
+
+/// Open a key from a path as returned by `NetDevice::registry_path`.
+pub fn open_registry_path(path: &str) -> Result<RegKey, Box<dyn Error>> {
+    let subkey = path.strip_prefix("HKLM\\").unwrap_or(path);
+    let key = RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey_with_flags(subkey, KEY_READ | KEY_WRITE)?;
+    Ok(key)
+}
+
+/// Raw GetAdaptersAddresses output. It tells us how much space it needs on
+/// overflow, so retry with the requested size until the list fits.
+fn adapter_addresses(family: u32, flags: GET_ADAPTERS_ADDRESSES_FLAGS) -> Result<Vec<u64>, Box<dyn Error>> {
+    let mut size: u32 = 16 * 1024;
+    loop {
+        let mut buffer = vec![0u64; (size as usize).div_ceil(size_of::<u64>())];
+        let ret = unsafe {
+            GetAdaptersAddresses(
+                family,
+                flags,
+                None,
+                Some(buffer.as_mut_ptr() as *mut IP_ADAPTER_ADDRESSES_LH),
+                &mut size,
+            )
+        };
+
+        if ret == ERROR_BUFFER_OVERFLOW.0 {
+            continue;
+        }
+        if ret != NO_ERROR.0 {
+            return Err(Box::new(MacError::SystemError(
+                format!("GetAdaptersAddresses failed with error code {}", ret)
+            )));
+        }
+        return Ok(buffer);
+    }
+}
+
+pub fn list_adapters() -> Result<Vec<Adapter>, Box<dyn Error>> {
+    let flags = GAA_FLAG_SKIP_UNICAST
+        | GAA_FLAG_SKIP_ANYCAST
+        | GAA_FLAG_SKIP_MULTICAST
+        | GAA_FLAG_SKIP_DNS_SERVER
+        | GAA_FLAG_INCLUDE_ALL_INTERFACES;
+    let buffer = adapter_addresses(AF_UNSPEC.0 as u32, flags)?;
+
+    let mut adapters = Vec::new();
+    let mut current = buffer.as_ptr() as *const IP_ADAPTER_ADDRESSES_LH;
+
+    while !current.is_null() {
+        let entry = unsafe { &*current };
+
+        let name = unsafe { entry.FriendlyName.to_string() }.unwrap_or_default();
+        let description = unsafe { entry.Description.to_string() }.unwrap_or_default();
+        let guid = unsafe { entry.AdapterName.to_string() }.unwrap_or_default();
+
+        let mac = if entry.PhysicalAddressLength == 6 {
+            let mut bytes = [0u8; 6];
+            bytes.copy_from_slice(&entry.PhysicalAddress[..6]);
+            Some(MacAddress::new(bytes, MacFormat::Colon).to_string())
+        } else {
+            None
+        };
+
+        adapters.push(Adapter {
+            name,
+            description,
+            guid,
+            mac,
+            is_up: entry.OperStatus == IfOperStatusUp,
+            if_type: entry.IfType,
+            // Unknown speeds are reported as all ones
+            speed_mbps: Some(entry.TransmitLinkSpeed)
+                .filter(|&speed| speed != 0 && speed != u64::MAX)
+                .map(|speed| speed / 1_000_000),
+        });
+
+        current = entry.Next;
+    }
+
+    Ok(adapters)
+}
+
+/// IPv4 addresses assigned to the adapter with this GUID.
+pub fn ipv4_addresses(guid: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let flags = GAA_FLAG_SKIP_ANYCAST | GAA_FLAG_SKIP_MULTICAST | GAA_FLAG_SKIP_DNS_SERVER;
+    let buffer = adapter_addresses(AF_INET.0 as u32, flags)?;
+
+    let mut addresses = Vec::new();
+    let mut current = buffer.as_ptr() as *const IP_ADAPTER_ADDRESSES_LH;
+
+    while !current.is_null() {
+        let entry = unsafe { &*current };
+        let adapter_guid = unsafe { entry.AdapterName.to_string() }.unwrap_or_default();
+
+        if adapter_guid.eq_ignore_ascii_case(guid) {
+            let mut unicast = entry.FirstUnicastAddress;
+            while !unicast.is_null() {
+                let address = unsafe { &*unicast };
+                let sockaddr = address.Address.lpSockaddr as *const SOCKADDR_IN;
+                if !sockaddr.is_null() {
+                    let octets = unsafe { (*sockaddr).sin_addr.S_un.S_addr }.to_ne_bytes();
+                    addresses.push(std::net::Ipv4Addr::from(octets).to_string());
+                }
+                unicast = address.Next;
+            }
+        }
+
+        current = entry.Next;
+    }
+
+    Ok(addresses)
+}
+
+/// Find an adapter by connection name, description or GUID.
+pub fn find_adapter(interface: &str) -> Result<Adapter, Box<dyn Error>> {
+    list_adapters()?
+        .into_iter()
+        .find(|a| {
+            a.name.eq_ignore_ascii_case(interface)
+                || a.description.eq_ignore_ascii_case(interface)
+                || a.guid.eq_ignore_ascii_case(interface)
+        })
+        .ok_or_else(|| {
+            Box::new(MacError::InterfaceNotFound(
+                format!("Interface {} not found", interface)
+            )) as Box<dyn Error>
+        })
+}
+
+/// The factory address of an adapter, read from its NDIS driver. Works
+/// without elevation and ignores any NetworkAddress override.
+pub fn permanent_address(guid: &str) -> Option<[u8; 6]> {
+    const IOCTL_NDIS_QUERY_GLOBAL_STATS: u32 = 0x0017_0002;
+
+    let path: Vec<u16> = format!("\\\\.\\{}", guid).encode_utf16().chain(Some(0)).collect();
+    let device = unsafe {
+        CreateFileW(
+            PCWSTR(path.as_ptr()),
+            0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            None,
+            OPEN_EXISTING,
+            FILE_FLAGS_AND_ATTRIBUTES(0),
+            None,
+        )
+    }
+    .ok()?;
+
+    let oid = OID_802_3_PERMANENT_ADDRESS;
+    let mut address = [0u8; 6];
+    let mut returned = 0u32;
+    let result = unsafe {
+        DeviceIoControl(
+            device,
+            IOCTL_NDIS_QUERY_GLOBAL_STATS,
+            Some(&oid as *const u32 as *const _),
+            size_of::<u32>() as u32,
+            Some(address.as_mut_ptr() as *mut _),
+            address.len() as u32,
+            Some(&mut returned),
+            None,
+        )
+    };
+
+    unsafe {
+        let _ = CloseHandle(device);
+    }
+
+    (result.is_ok() && returned == 6).then_some(address)
+}
+
+/// Process ids and executable names of all running processes, e.g.
+/// (4242, "chrome.exe").
+pub fn processes() -> Result<Vec<(u32, String)>, Box<dyn Error>> {
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0)? };
+
+    let mut entry = PROCESSENTRY32W {
+        dwSize: size_of::<PROCESSENTRY32W>() as u32,
+        ..Default::default()
+    };
+
+    let mut processes = Vec::new();
+    let mut more = unsafe { Process32FirstW(snapshot, &mut entry) }.is_ok();
+    while more {
+        let length = entry.szExeFile.iter().position(|&c| c == 0).unwrap_or(entry.szExeFile.len());
+        processes.push((entry.th32ProcessID, String::from_utf16_lossy(&entry.szExeFile[..length])));
+        more = unsafe { Process32NextW(snapshot, &mut entry) }.is_ok();
+    }
+
+    unsafe {
+        let _ = CloseHandle(snapshot);
+    }
+
+    Ok(processes)
+}
+
+/// Full path of a process's executable. None for protected processes and,
+/// without Administrator, for other users' processes.
+pub fn process_path(pid: u32) -> Option<std::path::PathBuf> {
+    use windows::core::PWSTR;
+    use windows::Win32::System::Threading::{OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION};
+
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) }.ok()?;
+    let mut buffer = [0u16; 1024];
+    let mut length = buffer.len() as u32;
+    let result = unsafe { QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, PWSTR(buffer.as_mut_ptr()), &mut length) };
+    unsafe {
+        let _ = CloseHandle(process);
+    }
+
+    result.ok()?;
+    Some(String::from_utf16_lossy(&buffer[..length as usize]).into())
+}
+
+/// Title of the first visible top-level window of each process on the
+/// current desktop. A service in session 0 sees none of the user's.
+pub fn window_titles() -> std::collections::HashMap<u32, String> {
+    use std::collections::HashMap;
+    use windows::core::BOOL;
+    use windows::Win32::Foundation::{HWND, LPARAM};
+    use windows::Win32::UI::WindowsAndMessaging::{EnumWindows, GetWindowTextW, GetWindowThreadProcessId, IsWindowVisible};
+
+    unsafe extern "system" fn collect(window: HWND, titles: LPARAM) -> BOOL {
+        let titles = unsafe { &mut *(titles.0 as *mut HashMap<u32, String>) };
+        if unsafe { IsWindowVisible(window) }.as_bool() {
+            let mut buffer = [0u16; 512];
+            let length = unsafe { GetWindowTextW(window, &mut buffer) };
+            let mut pid = 0;
+            unsafe { GetWindowThreadProcessId(window, Some(&mut pid)) };
+            if length > 0 {
+                titles.entry(pid).or_insert_with(|| String::from_utf16_lossy(&buffer[..length as usize]));
+            }
+        }
+        true.into() // Keep enumerating
+    }
+
+    let mut titles = HashMap::new();
+    let _ = unsafe { EnumWindows(Some(collect), LPARAM(&mut titles as *mut HashMap<u32, String> as isize)) };
+    titles
+}
+
+/// A WMI subscription to `Win32_ProcessTrace`, the start and stop events of
+/// every process. Lives on the thread that created it.
+pub struct ProcessTraces {
+    events: windows::Win32::System::Wmi::IEnumWbemClassObject,
+}
+
+impl ProcessTraces {
+    /// Block until the next process starts or stops. False once WMI fails.
+    pub fn next(&self) -> bool {
+        use windows::Win32::System::Wmi::WBEM_INFINITE;
+
+        let mut objects = [None];
+        let mut returned = 0;
+        let result = unsafe { self.events.Next(WBEM_INFINITE, &mut objects, &mut returned) };
+        result.is_ok() && returned > 0
+    }
+}
+
+pub fn process_traces() -> Result<ProcessTraces, Box<dyn Error>> {
+    use windows::core::BSTR;
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CoInitializeSecurity, CLSCTX_INPROC_SERVER, COINIT_MULTITHREADED,
+        EOAC_NONE, RPC_C_AUTHN_LEVEL_DEFAULT, RPC_C_IMP_LEVEL_IMPERSONATE,
+    };
+    use windows::Win32::System::Wmi::{IWbemLocator, WbemLocator, WBEM_FLAG_FORWARD_ONLY, WBEM_FLAG_RETURN_IMMEDIATELY};
+
+    unsafe {
+        CoInitializeEx(None, COINIT_MULTITHREADED).ok()?;
+        // Fails harmlessly if the process already set it
+        let _ = CoInitializeSecurity(
+            None, -1, None, None, RPC_C_AUTHN_LEVEL_DEFAULT, RPC_C_IMP_LEVEL_IMPERSONATE, None, EOAC_NONE, None,
+        );
+
+        let locator: IWbemLocator = CoCreateInstance(&WbemLocator, None, CLSCTX_INPROC_SERVER)?;
+        let services = locator.ConnectServer(
+            &BSTR::from("ROOT\\CIMV2"), &BSTR::new(), &BSTR::new(), &BSTR::new(), 0, &BSTR::new(), None,
+        )?;
+        let events = services.ExecNotificationQuery(
+            &BSTR::from("WQL"),
+            &BSTR::from("SELECT * FROM Win32_ProcessTrace"),
+            WBEM_FLAG_FORWARD_ONLY | WBEM_FLAG_RETURN_IMMEDIATELY,
+            None,
+        )?;
+        Ok(ProcessTraces { events })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum EventLevel {
+    Information,
+    Warning,
+    Error,
+}
+
+/// Write a message to the Application event log. Without a registered
+/// message file Event Viewer prefixes it with a note that the description
+/// is missing, but the message itself is stored and shown.
+pub fn report_event(source: &str, level: EventLevel, message: &str) -> Result<(), Box<dyn Error>> {
+    let source: Vec<u16> = source.encode_utf16().chain(Some(0)).collect();
+    let message: Vec<u16> = message.encode_utf16().chain(Some(0)).collect();
+    let event_type = match level {
+        EventLevel::Information => EVENTLOG_INFORMATION_TYPE,
+        EventLevel::Warning => EVENTLOG_WARNING_TYPE,
+        EventLevel::Error => EVENTLOG_ERROR_TYPE,
+    };
+
+    unsafe {
+        let handle = RegisterEventSourceW(PCWSTR::null(), PCWSTR(source.as_ptr()))?;
+        let result = ReportEventW(handle, event_type, 0, 1, None, 0, Some(&[PCWSTR(message.as_ptr())]), None);
+        let _ = DeregisterEventSource(handle);
+        result?;
+    }
+
+    Ok(())
+}
+
+/// Handle to the SetupAPI device backing a network adapter.
+pub struct NetDevice {
+    set: HDEVINFO,
+    data: SP_DEVINFO_DATA,
+    driver_key: String,
+}
+
+impl NetDevice {
+    /// Locate the device in the network class whose driver key carries the
+    /// adapter's NetCfgInstanceId.
+    pub fn open(guid: &str) -> Result<Self, Box<dyn Error>> {
+        let set = unsafe {
+            SetupDiGetClassDevsW(Some(&GUID_DEVCLASS_NET), PCWSTR::null(), None, DIGCF_PRESENT)?
+        };
+
+        let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
+        let mut index = 0;
+
+        loop {
+            let mut data = SP_DEVINFO_DATA {
+                cbSize: size_of::<SP_DEVINFO_DATA>() as u32,
+                ..Default::default()
+            };
+
+            if unsafe { SetupDiEnumDeviceInfo(set, index, &mut data) }.is_err() {
+                break;
+            }
+            index += 1;
+
+            let Some(driver_key) = device_property(set, &data, SPDRP_DRIVER) else {
+                continue;
+            };
+
+            let instance_id = hklm
+                .open_subkey(format!("{}\\{}", CLASS_KEY, driver_key))
+                .and_then(|key| key.get_value::<String, _>("NetCfgInstanceId"));
+
+            if let Ok(instance_id) = instance_id {
+                if instance_id.eq_ignore_ascii_case(guid) {
+                    return Ok(Self { set, data, driver_key });
+                }
+            }
+        }
+
+        unsafe {
+            let _ = SetupDiDestroyDeviceInfoList(set);
+        }
+
+        Err(Box::new(MacError::SystemError(
+            format!("Could not find device for adapter {}", guid)
+        )))
+    }
+
+    /// Open the adapter's driver key, where `NetworkAddress` lives.
+    pub fn registry_key(&self) -> Result<RegKey, Box<dyn Error>> {
+        let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
+        let key = hklm.open_subkey_with_flags(
+            format!("{}\\{}", CLASS_KEY, self.driver_key),
+            KEY_READ | KEY_WRITE,
+        )?;
+        Ok(key)
+    }
+
+    /// Full path of the adapter's driver key, for reporting.
+    pub fn registry_path(&self) -> String {
+        format!("HKLM\\{}\\{}", CLASS_KEY, self.driver_key)
+    }
+
+    pub fn manufacturer(&self) -> Option<String> {
+        device_property(self.set, &self.data, SPDRP_MFG)
+    }
+
+    pub fn service(&self) -> Option<String> {
+        device_property(self.set, &self.data, SPDRP_SERVICE)
+    }
+
+    /// Bus the device was enumerated on, e.g. "PCI" or "USB".
+    pub fn enumerator(&self) -> Option<String> {
+        device_property(self.set, &self.data, SPDRP_ENUMERATOR_NAME)
+    }
+
+    /// Enable or disable the device through the class installer, the same
+    /// thing Device Manager does.
+    pub fn set_enabled(&self, enabled: bool) -> Result<(), Box<dyn Error>> {
+        let params = SP_PROPCHANGE_PARAMS {
+            ClassInstallHeader: SP_CLASSINSTALL_HEADER {
+                cbSize: size_of::<SP_CLASSINSTALL_HEADER>() as u32,
+                InstallFunction: DIF_PROPERTYCHANGE,
+            },
+            StateChange: if enabled { DICS_ENABLE } else { DICS_DISABLE },
+            Scope: DICS_FLAG_GLOBAL,
+            HwProfile: 0,
+        };
+
+        unsafe {
+            SetupDiSetClassInstallParamsW(
+                self.set,
+                Some(&self.data),
+                Some(&params.ClassInstallHeader),
+                size_of::<SP_PROPCHANGE_PARAMS>() as u32,
+            )?;
+            SetupDiCallClassInstaller(DIF_PROPERTYCHANGE, self.set, Some(&self.data))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for NetDevice {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = SetupDiDestroyDeviceInfoList(self.set);
+        }
+    }
+}
+
+fn device_property(set: HDEVINFO, data: &SP_DEVINFO_DATA, property: SETUP_DI_REGISTRY_PROPERTY) -> Option<String> {
+    let mut buffer = [0u8; 1024];
+    unsafe {
+        SetupDiGetDeviceRegistryPropertyW(set, data, property, None, Some(&mut buffer), None).ok()?;
+    }
+
+    let wide: Vec<u16> = buffer
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|&c| c != 0)
+        .collect();
+
+    let value = String::from_utf16_lossy(&wide);
+    if value.is_empty() { None } else { Some(value) }
+}
@@ -0,0 +1,348 @@
+// tests/change_path.rs
+// Runs the binary against the mock backend, with the config and data
+// directories in a scratch directory, so no test needs root or a NIC.
+// dirs only takes those directories from the environment on Unix, and
+// release builds have no mock.
+#![cfg(all(unix, debug_assertions))]
+
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+struct Sandbox {
+    dir: PathBuf,
+}
+
+impl Sandbox {
+    /// mock0 and mock1 with the given MACs.
+    fn new(mock0: &str, mock1: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!(
+            "chameleon-test-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let sandbox = Sandbox { dir };
+        sandbox.write_state(json!({
+            "interfaces": {
+                "mock0": { "mac": mock0 },
+                "mock1": { "mac": mock1 },
+            }
+        }));
+        sandbox
+    }
+
+    fn state_path(&self) -> PathBuf {
+        self.dir.join("state.json")
+    }
+
+    fn write_state(&self, state: Value) {
+        fs::write(self.state_path(), state.to_string()).unwrap();
+    }
+
+    fn state(&self) -> Value {
+        serde_json::from_str(&fs::read_to_string(self.state_path()).unwrap()).unwrap()
+    }
+
+    fn mac(&self, interface: &str) -> String {
+        self.state()["interfaces"][interface]["mac"].as_str().unwrap().to_string()
+    }
+
+    fn set(&self, interface: &str, key: &str, value: Value) {
+        let mut state = self.state();
+        state["interfaces"][interface][key] = value;
+        self.write_state(state);
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_Chameleon"))
+            .args(args)
+            .env("CHAMELEON_MOCK", self.state_path())
+            .env("HOME", self.dir.join("home"))
+            .env("XDG_CONFIG_HOME", self.dir.join("config"))
+            .env("XDG_DATA_HOME", self.dir.join("data"))
+            .output()
+            .unwrap()
+    }
+
+    /// Run a change that should succeed, without touching the real network.
+    fn change(&self, args: &[&str]) -> Output {
+        let mut args = args.to_vec();
+        args.extend(["--no-renew", "--no-collision-check"]);
+        let output = self.run(&args);
+        assert!(output.status.success(), "{:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+        output
+    }
+
+    fn history(&self) -> Vec<Value> {
+        let output = self.run(&["history", "--output", "json"]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        serde_json::from_slice(&output.stdout).unwrap()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn change_saves_original_and_logs() {
+    let sandbox = Sandbox::new("00:11:22:33:44:55", "00:11:22:33:44:66");
+
+    sandbox.change(&["-i", "mock0", "-m", "02:AA:BB:CC:DD:EE"]);
+    assert_eq!(sandbox.mac("mock0"), "02:aa:bb:cc:dd:ee");
+
+    let original = fs::read_to_string(sandbox.dir.join("config/mac_changer/mock0.json")).unwrap();
+    assert!(original.contains("00:11:22:33:44:55"));
+
+    let history = sandbox.history();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0]["interface"], "mock0");
+    assert_eq!(history[0]["old_mac"], "00:11:22:33:44:55");
+    assert_eq!(history[0]["trigger"], "manual");
+}
+
+#[test]
+fn restore_puts_back_the_first_original() {
+    let sandbox = Sandbox::new("00:11:22:33:44:55", "00:11:22:33:44:66");
+
+    sandbox.change(&["-i", "mock0", "-r"]);
+    sandbox.change(&["-i", "mock0", "-r"]);
+    assert_ne!(sandbox.mac("mock0"), "00:11:22:33:44:55");
+
+    let output = sandbox.run(&["-i", "mock0", "--restore", "--no-renew"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(sandbox.mac("mock0"), "00:11:22:33:44:55");
+}
+
+#[test]
+fn dry_run_changes_nothing() {
+    let sandbox = Sandbox::new("00:11:22:33:44:55", "00:11:22:33:44:66");
+
+    let output = sandbox.change(&["-i", "mock0", "-m", "02:aa:bb:cc:dd:ee", "--dry-run"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("mock: set mock0 to 02:aa:bb:cc:dd:ee"));
+    assert_eq!(sandbox.mac("mock0"), "00:11:22:33:44:55");
+    assert!(!sandbox.dir.join("config/mac_changer/mock0.json").exists());
+}
+
+#[test]
+fn blacklisted_mac_is_refused() {
+    let sandbox = Sandbox::new("00:11:22:33:44:55", "00:11:22:33:44:66");
+    assert!(sandbox.run(&["filter", "add", "02:aa:bb", "--list", "blacklist"]).status.success());
+
+    let output = sandbox.run(&["-i", "mock0", "-m", "02:aa:bb:cc:dd:ee", "--no-renew", "--no-collision-check"]);
+    assert!(!output.status.success());
+    assert_eq!(sandbox.mac("mock0"), "00:11:22:33:44:55");
+
+    // --force skips the filter
+    sandbox.change(&["-i", "mock0", "-m", "02:aa:bb:cc:dd:ee", "--force"]);
+    assert_eq!(sandbox.mac("mock0"), "02:aa:bb:cc:dd:ee");
+}
+
+#[test]
+fn random_mac_stays_inside_the_whitelist() {
+    let sandbox = Sandbox::new("00:11:22:33:44:55", "00:11:22:33:44:66");
+    assert!(sandbox.run(&["filter", "add", "00:11:22", "--list", "whitelist"]).status.success());
+
+    let output = sandbox.run(&["-i", "mock0", "-m", "02:cc:dd:00:00:01", "--no-renew", "--no-collision-check"]);
+    assert!(!output.status.success(), "a MAC outside the whitelist was applied");
+
+    sandbox.change(&["-i", "mock0", "-r", "--preserve-oui"]);
+    assert!(sandbox.mac("mock0").starts_with("00:11:22:"));
+}
+
+#[test]
+fn matching_rule_wins_over_the_requested_mac() {
+    let sandbox = Sandbox::new("00:11:22:33:44:55", "00:11:22:33:44:66");
+    let output = sandbox.run(&["rules", "add", "-i", "mock0", "-m", "02:00:00:00:00:42"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // Only the MAC that is applied goes through the filter
+    assert!(sandbox.run(&["filter", "add", "02:aa:bb", "--list", "blacklist"]).status.success());
+    sandbox.change(&["-i", "mock0", "-m", "02:aa:bb:cc:dd:ee"]);
+    assert_eq!(sandbox.mac("mock0"), "02:00:00:00:00:42");
+    assert_eq!(sandbox.history()[0]["trigger"], "rule");
+}
+
+#[test]
+fn collision_with_another_interface_is_refused() {
+    let sandbox = Sandbox::new("00:11:22:33:44:55", "00:11:22:33:44:66");
+
+    let output = sandbox.run(&["-i", "mock0", "-m", "00:11:22:33:44:66", "--no-renew"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("local interface mock1"), "{}", stderr(&output));
+    assert_eq!(sandbox.mac("mock0"), "00:11:22:33:44:55");
+}
+
+#[test]
+fn permanent_change_is_recorded() {
+    let sandbox = Sandbox::new("00:11:22:33:44:55", "00:11:22:33:44:66");
+
+    sandbox.change(&["-i", "mock0", "-m", "02:aa:bb:cc:dd:ee", "-p"]);
+    assert_eq!(sandbox.state()["interfaces"]["mock0"]["permanent_mac"], "02:aa:bb:cc:dd:ee");
+    assert_eq!(sandbox.history()[0]["permanent"], true);
+}
+
+#[test]
+fn failed_permanent_change_is_rolled_back() {
+    let sandbox = Sandbox::new("00:11:22:33:44:55", "00:11:22:33:44:66");
+    sandbox.set("mock0", "permanent_supported", json!(false));
+
+    let output = sandbox.run(&["-i", "mock0", "-m", "02:aa:bb:cc:dd:ee", "-p", "--no-renew", "--no-collision-check"]);
+    assert!(!output.status.success());
+    assert_eq!(sandbox.mac("mock0"), "00:11:22:33:44:55");
+    assert!(sandbox.history().is_empty());
+}
+
+#[test]
+fn ignored_change_fails_verification() {
+    let sandbox = Sandbox::new("00:11:22:33:44:55", "00:11:22:33:44:66");
+    sandbox.set("mock0", "ignores_changes", json!(true));
+
+    let output = sandbox.run(&["-i", "mock0", "-m", "02:aa:bb:cc:dd:ee", "--no-renew", "--no-collision-check"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("verification failed"), "{}", stderr(&output));
+    assert_eq!(output.status.code(), Some(5));
+    assert!(sandbox.history().is_empty());
+}
+
+#[test]
+fn unknown_interface_is_refused() {
+    let sandbox = Sandbox::new("00:11:22:33:44:55", "00:11:22:33:44:66");
+
+    let output = sandbox.run(&["-i", "mock9", "-r", "--no-renew", "--no-collision-check"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("mock9 not found"), "{}", stderr(&output));
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn errors_are_reported_as_json() {
+    let sandbox = Sandbox::new("00:11:22:33:44:55", "00:11:22:33:44:66");
+
+    let output = sandbox.run(&["-i", "mock9", "-r", "--no-renew", "--no-collision-check", "--output", "json"]);
+    assert_eq!(output.status.code(), Some(4));
+    let last = stderr(&output).lines().last().unwrap_or_default().to_string();
+    let report: Value = serde_json::from_str(&last).unwrap();
+    assert_eq!(report["error"]["code"], "interface_not_found");
+    assert_eq!(report["error"]["exit_code"], 4);
+}
+
+#[test]
+fn config_fills_in_what_flags_leave_out() {
+    let sandbox = Sandbox::new("00:11:22:33:44:55", "00:11:22:33:44:66");
+    fs::create_dir_all(sandbox.dir.join("config/mac_changer")).unwrap();
+    fs::write(sandbox.dir.join("config/mac_changer/config.toml"), r#"
+interface = "mock0"
+random_policy = "preserve-oui"
+
+[interfaces.mock1]
+permanent = true
+"#).unwrap();
+
+    sandbox.change(&["-r"]);
+    assert!(sandbox.mac("mock0").starts_with("00:11:22:"));
+    assert_ne!(sandbox.mac("mock0"), "00:11:22:33:44:55");
+
+    // mock1 changes are permanent unless --no-permanent says otherwise
+    sandbox.change(&["-i", "mock1", "-m", "02:aa:bb:cc:dd:ee"]);
+    assert_eq!(sandbox.state()["interfaces"]["mock1"]["permanent_mac"], "02:aa:bb:cc:dd:ee");
+    sandbox.change(&["-i", "mock1", "-m", "02:aa:bb:cc:dd:ff", "--no-permanent"]);
+    assert_eq!(sandbox.state()["interfaces"]["mock1"]["permanent_mac"], "02:aa:bb:cc:dd:ee");
+}
+
+#[test]
+fn locked_interface_is_refused_with_no_wait() {
+    let sandbox = Sandbox::new("00:11:22:33:44:55", "00:11:22:33:44:66");
+    let locks = sandbox.dir.join("config/mac_changer/locks");
+    fs::create_dir_all(&locks).unwrap();
+
+    // Stand in for another chameleon process part way through a change
+    let held = fs::File::create(locks.join("mock0.lock")).unwrap();
+    held.lock().unwrap();
+
+    let output = sandbox.run(&["-i", "mock0", "-m", "02:aa:bb:cc:dd:ee", "--no-wait", "--no-renew", "--no-collision-check"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("being changed by another chameleon process"), "{}", stderr(&output));
+    assert_eq!(sandbox.mac("mock0"), "00:11:22:33:44:55");
+
+    // Other interfaces are not held up
+    sandbox.change(&["-i", "mock1", "-m", "02:aa:bb:cc:dd:ff", "--no-wait"]);
+
+    drop(held);
+    sandbox.change(&["-i", "mock0", "-m", "02:aa:bb:cc:dd:ee", "--no-wait"]);
+    assert_eq!(sandbox.mac("mock0"), "02:aa:bb:cc:dd:ee");
+}
+
+#[test]
+fn factory_restore_uses_the_hardware_address() {
+    // The backup was lost while mock0 had a spoofed MAC
+    let sandbox = Sandbox::new("02:11:22:33:44:55", "00:11:22:33:44:66");
+    sandbox.set("mock0", "hardware_mac", json!("00:11:22:33:44:55"));
+
+    let output = sandbox.change(&["-i", "mock0", "-r"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("locally administered"));
+    let original = fs::read_to_string(sandbox.dir.join("config/mac_changer/mock0.json")).unwrap();
+    assert!(original.contains("\"hardware_mac\": \"00:11:22:33:44:55\""));
+
+    let output = sandbox.run(&["-i", "mock0", "--restore", "--factory", "--no-renew"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(sandbox.mac("mock0"), "00:11:22:33:44:55");
+
+    // mock1's driver reports no hardware address
+    let output = sandbox.run(&["-i", "mock1", "--restore", "--factory", "--no-renew"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn stats_count_changes_per_interface() {
+    let sandbox = Sandbox::new("00:11:22:33:44:55", "00:11:22:33:44:66");
+    sandbox.change(&["-i", "mock0", "-r"]);
+    sandbox.change(&["-i", "mock0", "-r"]);
+    sandbox.change(&["-i", "mock1", "-r"]);
+
+    let output = sandbox.run(&["stats", "--output", "json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stats: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stats["interfaces"]["mock0"]["changes"], 2);
+    assert_eq!(stats["interfaces"]["mock0"]["by_trigger"]["manual"], 2);
+    assert_eq!(stats["interfaces"]["mock1"]["changes"], 1);
+
+    let output = sandbox.run(&["stats", "--prometheus"]);
+    let metrics = String::from_utf8_lossy(&output.stdout);
+    assert!(metrics.contains("chameleon_mac_changes_total{interface=\"mock0\",trigger=\"manual\"} 2"));
+    assert!(metrics.contains("chameleon_daemon_up 0"));
+}
+
+#[test]
+fn bundle_with_a_path_for_an_interface_is_refused() {
+    let sandbox = Sandbox::new("00:11:22:33:44:55", "00:11:22:33:44:66");
+    let bundle = sandbox.dir.join("bundle.json");
+    fs::write(&bundle, json!({
+        "format": "chameleon-bundle",
+        "version": 1,
+        "exported": "2026-01-01T00:00:00Z",
+        "originals": [{
+            "original_mac": "00:11:22:33:44:55",
+            "interface": "../../evil",
+            "vendor": null,
+            "last_modified": "2026-01-01T00:00:00Z",
+        }],
+    }).to_string()).unwrap();
+
+    let output = sandbox.run(&["import", bundle.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("not an interface name"), "{}", stderr(&output));
+    assert!(!sandbox.dir.join("evil.json").exists());
+    assert!(!sandbox.dir.join("config/evil.json").exists());
+}